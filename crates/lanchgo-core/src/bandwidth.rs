@@ -0,0 +1,112 @@
+// What the app put on / took off the wire this session, in total and per peer.
+// "Chat" is every UDP datagram (messages, offers, announcements), "files" the TCP transfers.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use chrono::Local;
+
+use crate::file_transfer_protocol::human_size;
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Chat,
+    File,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Counters {
+    chat_sent: u64,
+    chat_received: u64,
+    file_sent: u64,
+    file_received: u64,
+}
+
+impl Counters {
+    fn add(&mut self, kind: Kind, sent: bool, bytes: u64) {
+        let slot = match (kind, sent) {
+            (Kind::Chat, true) => &mut self.chat_sent,
+            (Kind::Chat, false) => &mut self.chat_received,
+            (Kind::File, true) => &mut self.file_sent,
+            (Kind::File, false) => &mut self.file_received,
+        };
+        *slot += bytes;
+    }
+}
+
+struct Session {
+    started: Instant,
+    started_at: String, // "14:05" for the summary
+    total: Counters,
+    // broadcasts have no single receiver, so sent chat bytes only show up in the total
+    per_peer: BTreeMap<IpAddr, Counters>,
+}
+
+static SESSION: OnceLock<Mutex<Session>> = OnceLock::new();
+
+fn session() -> &'static Mutex<Session> {
+    SESSION.get_or_init(|| {
+        Mutex::new(Session {
+            started: Instant::now(),
+            started_at: Local::now().format("%H:%M").to_string(),
+            total: Counters::default(),
+            per_peer: BTreeMap::new(),
+        })
+    })
+}
+
+fn record(kind: Kind, sent: bool, peer: Option<IpAddr>, bytes: usize) {
+    let mut s = session().lock().unwrap();
+    s.total.add(kind, sent, bytes as u64);
+    if let Some(peer) = peer {
+        s.per_peer.entry(peer).or_default().add(kind, sent, bytes as u64);
+    }
+}
+
+pub fn record_sent(kind: Kind, peer: Option<IpAddr>, bytes: usize) {
+    record(kind, true, peer, bytes);
+}
+
+pub fn record_received(kind: Kind, peer: IpAddr, bytes: usize) {
+    record(kind, false, Some(peer), bytes);
+}
+
+/// Text for /stats and the statistics popup
+pub fn summary() -> String {
+    let s = session().lock().unwrap();
+    let mins = s.started.elapsed().as_secs() / 60;
+
+    let mut out = format!("Session statistics (since {}, {}h {:02}m)\n\n", s.started_at, mins / 60, mins % 60);
+    out.push_str(&format!(
+        "        Chat     ↑ {:<10} ↓ {}\n        Files    ↑ {:<10} ↓ {}\n",
+        human_size(s.total.chat_sent),
+        human_size(s.total.chat_received),
+        human_size(s.total.file_sent),
+        human_size(s.total.file_received),
+    ));
+
+    if !s.per_peer.is_empty() {
+        out.push_str("\n        Per peer:\n");
+        for (peer, c) in &s.per_peer {
+            out.push_str(&format!(
+                "        {:<16} chat ↓ {:<10} files ↑ {:<10} ↓ {}\n",
+                peer.to_string(),
+                human_size(c.chat_received),
+                human_size(c.file_sent),
+                human_size(c.file_received),
+            ));
+        }
+    }
+    out
+}
+
+/// (peer, bytes sent to it, bytes received from it) this session, for /export
+pub fn per_peer() -> Vec<(IpAddr, u64, u64)> {
+    let s = session().lock().unwrap();
+    s.per_peer
+        .iter()
+        .map(|(ip, c)| (*ip, c.chat_sent + c.file_sent, c.chat_received + c.file_received))
+        .collect()
+}
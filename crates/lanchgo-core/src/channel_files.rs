@@ -0,0 +1,113 @@
+// "Send to everyone in the channel": the offer travels sealed with the channel key in an
+// EFOF packet and the file itself over the encrypted TCP path (FOFE, see tcp_file_server.rs).
+//
+// EFOF + bincode(SecureMessage of the offer as JSON). Older builds sent the offer as an ENCM
+// chat line starting with OFFER_PREFIX, those are still understood.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+
+use crate::file_transfer_protocol::{offer_id_to_hex, FileOffer, FILE_PROTOCOL_VERSION};
+use crate::secure_channel_code::{self, SecureMessage};
+
+pub const EFOF_MAGIC: &[u8; 4] = b"EFOF";
+/// ENCM plaintext carrying a channel file offer instead of a chat line (older builds)
+pub const OFFER_PREFIX: &str = "\u{1}SFOFT";
+
+// our offers: offer id -> members that finished downloading it
+static SENT: Mutex<BTreeMap<[u8; 16], BTreeSet<IpAddr>>> = Mutex::new(BTreeMap::new());
+// offers from other members: offer id hex -> (sender, offer)
+static RECEIVED: Mutex<BTreeMap<String, (SocketAddr, FileOffer)>> = Mutex::new(BTreeMap::new());
+
+/// EFOF packet announcing `offer` to the channel, None outside secure mode
+pub fn build_offer_packet(offer: &FileOffer) -> Option<Vec<u8>> {
+    let channel = secure_channel_code::get_active_channel()?;
+    let json = serde_json::to_string(offer).ok()?;
+    let encrypted = secure_channel_code::encrypt_message(&channel.key, &json);
+    let payload = bincode::serde::encode_to_vec(&encrypted, bincode::config::standard()).ok()?;
+
+    let mut packet = Vec::from(EFOF_MAGIC as &[u8]);
+    packet.extend_from_slice(&payload);
+    Some(packet)
+}
+
+/// The offer in an EFOF packet, None when it isn't one or wasn't sealed with our channel's key
+pub fn parse_offer_packet(bytes: &[u8]) -> Option<FileOffer> {
+    let (plain, _) = secure_channel_code::decrypt_message_with_nonce(bytes.strip_prefix(EFOF_MAGIC)?)?;
+    let offer: FileOffer = serde_json::from_str(&plain).ok()?;
+    (offer.protocol_version == FILE_PROTOCOL_VERSION).then_some(offer)
+}
+
+/// The offer inside a decrypted ENCM message from an older build, None for ordinary chat text
+pub fn parse_offer(plain: &str) -> Option<FileOffer> {
+    let offer: FileOffer = serde_json::from_str(plain.strip_prefix(OFFER_PREFIX)?).ok()?;
+    (offer.protocol_version == FILE_PROTOCOL_VERSION).then_some(offer)
+}
+
+// ===================== sender side =====================
+
+pub fn track_sent(offer_id: [u8; 16]) {
+    SENT.lock().unwrap().insert(offer_id, BTreeSet::new());
+}
+
+/// Only offers made to the channel may be pulled over FOFE
+pub fn is_channel_offer(offer_id: &[u8; 16]) -> bool {
+    SENT.lock().unwrap().contains_key(offer_id)
+}
+
+/// Record a member that has the whole file, returns how many members have it now
+pub fn mark_delivered(offer_id: &[u8; 16], member: IpAddr) -> Option<usize> {
+    let mut sent = SENT.lock().unwrap();
+    let members = sent.get_mut(offer_id)?;
+    members.insert(member);
+    Some(members.len())
+}
+
+// ===================== member side =====================
+
+/// Remember an offer from another member, false if we already have it
+pub fn register_remote(sender: SocketAddr, offer: FileOffer) -> bool {
+    let id_hex = offer_id_to_hex(&offer.offer_id);
+    let mut received = RECEIVED.lock().unwrap();
+    if received.contains_key(&id_hex) {
+        return false;
+    }
+    received.insert(id_hex, (sender, offer));
+    true
+}
+
+pub fn remote(id_hex: &str) -> Option<(SocketAddr, FileOffer)> {
+    RECEIVED.lock().unwrap().get(id_hex).cloned()
+}
+
+/// The member that offered `id_hex` withdrew it, false when it came from someone else
+pub fn withdraw(id_hex: &str, from: IpAddr) -> bool {
+    let mut received = RECEIVED.lock().unwrap();
+    if received.get(id_hex).is_none_or(|(addr, _)| addr.ip() != from) {
+        return false;
+    }
+    received.remove(id_hex).is_some()
+}
+
+/// Leaving the channel forgets its offers on both sides
+pub fn clear() {
+    SENT.lock().unwrap().clear();
+    RECEIVED.lock().unwrap().clear();
+}
+
+// ===================== membership proof =====================
+// a FOFE request carries the offer id encrypted with the channel key,
+// so only someone holding the key can pull the file
+
+pub fn request_proof(key: &[u8; 32], offer_id: &[u8; 16]) -> Vec<u8> {
+    let sealed = secure_channel_code::encrypt_bytes(key, offer_id);
+    bincode::serde::encode_to_vec(&sealed, bincode::config::standard()).unwrap_or_default()
+}
+
+pub fn check_proof(key: &[u8; 32], offer_id: &[u8; 16], proof: &[u8]) -> bool {
+    let Ok((sealed, _)) = bincode::serde::decode_from_slice::<SecureMessage, _>(proof, bincode::config::standard()) else {
+        return false;
+    };
+    secure_channel_code::decrypt_bytes(key, &sealed).is_some_and(|plain| plain == offer_id)
+}
@@ -0,0 +1,91 @@
+// The AEAD a secure channel runs on. AES-256-GCM stays the default, hosts on CPUs without
+// AES instructions pick ChaCha20-Poly1305 instead. The host's choice travels as a SUIT
+// trailer on ANCH (see secure_channel_code), builds that don't send one mean AES-256-GCM.
+// Phones keep talking AES-256-GCM over MENCM either way.
+
+use aes_gcm::Aes256Gcm;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+
+pub trait ChannelCipher: Sync {
+    /// `aad` is authenticated but not encrypted, open only succeeds with the same bytes
+    fn seal_aad(&self, key: &[u8], nonce: &[u8; 12], plain: &[u8], aad: &[u8]) -> Option<Vec<u8>>;
+    fn open_aad(&self, key: &[u8], nonce: &[u8; 12], ciphertext: &[u8], aad: &[u8]) -> Option<Vec<u8>>;
+
+    fn seal(&self, key: &[u8], nonce: &[u8; 12], plain: &[u8]) -> Option<Vec<u8>> {
+        self.seal_aad(key, nonce, plain, &[])
+    }
+    fn open(&self, key: &[u8], nonce: &[u8; 12], ciphertext: &[u8]) -> Option<Vec<u8>> {
+        self.open_aad(key, nonce, ciphertext, &[])
+    }
+}
+
+struct AesGcm;
+struct ChaCha;
+
+impl ChannelCipher for AesGcm {
+    fn seal_aad(&self, key: &[u8], nonce: &[u8; 12], plain: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        Aes256Gcm::new_from_slice(key).ok()?.encrypt(Nonce::from_slice(nonce), Payload { msg: plain, aad }).ok()
+    }
+    fn open_aad(&self, key: &[u8], nonce: &[u8; 12], ciphertext: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        Aes256Gcm::new_from_slice(key).ok()?.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad }).ok()
+    }
+}
+
+impl ChannelCipher for ChaCha {
+    fn seal_aad(&self, key: &[u8], nonce: &[u8; 12], plain: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        ChaCha20Poly1305::new_from_slice(key).ok()?.encrypt(Nonce::from_slice(nonce), Payload { msg: plain, aad }).ok()
+    }
+    fn open_aad(&self, key: &[u8], nonce: &[u8; 12], ciphertext: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        ChaCha20Poly1305::new_from_slice(key).ok()?.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad }).ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CipherSuite {
+    #[default]
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    /// Id sent in the SUIT trailer
+    pub fn id(self) -> u8 {
+        match self {
+            CipherSuite::Aes256Gcm => 1,
+            CipherSuite::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(CipherSuite::Aes256Gcm),
+            2 => Some(CipherSuite::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    pub fn cipher(self) -> &'static dyn ChannelCipher {
+        match self {
+            CipherSuite::Aes256Gcm => &AesGcm,
+            CipherSuite::ChaCha20Poly1305 => &ChaCha,
+        }
+    }
+
+    /// What a host on this machine offers: AES where the CPU speeds it up, ChaCha otherwise
+    pub fn preferred() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if !std::arch::is_x86_feature_detected!("aes") {
+                return CipherSuite::ChaCha20Poly1305;
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if !std::arch::is_aarch64_feature_detected!("aes") {
+                return CipherSuite::ChaCha20Poly1305;
+            }
+        }
+        CipherSuite::Aes256Gcm
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::watch;
+
+/// The UDP sockets everyone sends/receives on, swappable so they can be re-bound at runtime.
+/// v4 is the primary (broadcast) path, v6 link-local multicast is best effort.
+/// The sockets are tokio ones (the receiver awaits on them), sync code sends through `send_to`.
+#[derive(Debug)]
+pub struct SharedSocket {
+    inner: RwLock<Arc<UdpSocket>>,
+    inner_v6: RwLock<Option<Arc<UdpSocket>>>,
+    // bumped on every replace so the receiver drops the old socket right away
+    generation: watch::Sender<u64>,
+}
+
+// a full send buffer is very rare for UDP, give it a moment before failing
+const SEND_RETRIES: u32 = 20;
+
+fn send_now(sock: &UdpSocket, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+    let mut tries = 0;
+    loop {
+        match sock.try_send_to(buf, target) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock && tries < SEND_RETRIES => {
+                tries += 1;
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            other => return other,
+        }
+    }
+}
+
+impl SharedSocket {
+    pub fn new(sock: Arc<UdpSocket>, sock_v6: Option<Arc<UdpSocket>>) -> Self {
+        Self { inner: RwLock::new(sock), inner_v6: RwLock::new(sock_v6), generation: watch::channel(0).0 }
+    }
+    pub fn get(&self) -> Arc<UdpSocket> {
+        self.inner.read().unwrap().clone()
+    }
+    pub fn get_v6(&self) -> Option<Arc<UdpSocket>> {
+        self.inner_v6.read().unwrap().clone()
+    }
+    /// Blocking-free send for non-async callers (UI thread, watcher threads)
+    pub fn send_to(&self, buf: &[u8], target: impl Into<SocketAddr>) -> io::Result<usize> {
+        send_now(&self.get(), buf, target.into())
+    }
+    /// Same over the v6 socket, None when there is no v6 socket
+    pub fn send_to_v6(&self, buf: &[u8], target: impl Into<SocketAddr>) -> Option<io::Result<usize>> {
+        self.get_v6().map(|v6| send_now(&v6, buf, target.into()))
+    }
+    /// Resolves whenever the sockets are replaced
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.generation.subscribe()
+    }
+    /// Take over the sockets of a freshly bound SharedSocket
+    pub fn replace(&self, other: SharedSocket) {
+        *self.inner.write().unwrap() = other.get();
+        *self.inner_v6.write().unwrap() = other.get_v6();
+        self.generation.send_modify(|g| *g += 1);
+    }
+}
+
+/// A phone paired with /pair, its pushes skip the accept prompt and are sealed with `key`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PairedDevice {
+    pub name: String,
+    pub key: String, // hex, 32 bytes
+    pub last_ip: String,
+}
+
+/// File transfer timeouts. Raise them for links that stall (busy Wi-Fi, powerline),
+/// lower them to notice a dead peer sooner on a good wired network.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TransferTimeouts {
+    pub server_read_secs: u64,  // sender waiting for the request
+    pub server_write_secs: u64, // sender waiting for the receiver to take a chunk
+    pub client_read_secs: u64,  // receiver waiting for the next chunk
+    pub client_write_secs: u64, // receiver sending its request
+    pub connect_retries: u32,
+    pub connect_backoff_ms: u64, // pause between connect attempts
+    pub stall_secs: u64,         // a download that made no progress this long reconnects once, 0 = never
+}
+
+impl TransferTimeouts {
+    pub const DEFAULT: Self = Self {
+        server_read_secs: 20,
+        server_write_secs: 120,
+        client_read_secs: 60,
+        client_write_secs: 20,
+        connect_retries: 20,
+        connect_backoff_ms: 100,
+        stall_secs: 30,
+    };
+}
+
+impl Default for TransferTimeouts {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Caps on file transfer speed in KB/s, 0 = no cap. Chat never counts against them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct RateLimit {
+    pub upload_kib_per_sec: u64,
+    pub download_kib_per_sec: u64,
+}
@@ -0,0 +1,585 @@
+// Our side of file offers: what we offer and where it lives on disk, zip bundles, hashes,
+// transfer timeouts. The packets themselves are lanchgo_proto::file_offer.
+
+use std::{ collections::{HashMap, HashSet}, fs::File, io::{self, BufReader, Read, Write}, net::SocketAddr, path::{Path, PathBuf}, sync::{atomic::{AtomicU16, AtomicUsize, Ordering}, mpsc, Mutex}, thread, time::{Duration, Instant, SystemTime, UNIX_EPOCH}, };
+use uuid::Uuid;
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::classes::TransferTimeouts;
+
+pub use lanchgo_proto::file_offer::*;
+
+// the port our file server really listens on, every offer carries it (see main: bind fallback)
+static TCP_PORT: AtomicU16 = AtomicU16::new(DEFAULT_TCP_PORT);
+
+pub fn tcp_port() -> u16 {
+    TCP_PORT.load(Ordering::Relaxed)
+}
+
+pub fn set_tcp_port(port: u16) {
+    TCP_PORT.store(port, Ordering::Relaxed);
+}
+
+// ===================== Timeouts =====================
+
+static TIMEOUTS: Mutex<TransferTimeouts> = Mutex::new(TransferTimeouts::DEFAULT);
+
+/// Config.transfer, call again after every change. Zeros would make every transfer fail, so they're raised.
+pub fn set_timeouts(t: TransferTimeouts) {
+    *TIMEOUTS.lock().unwrap() = TransferTimeouts {
+        server_read_secs: t.server_read_secs.max(1),
+        server_write_secs: t.server_write_secs.max(1),
+        client_read_secs: t.client_read_secs.max(1),
+        client_write_secs: t.client_write_secs.max(1),
+        connect_retries: t.connect_retries.max(1),
+        connect_backoff_ms: t.connect_backoff_ms,
+        stall_secs: t.stall_secs,
+    };
+}
+
+pub fn server_timeouts() -> (Duration, Duration) {
+    let t = *TIMEOUTS.lock().unwrap();
+    (Duration::from_secs(t.server_read_secs), Duration::from_secs(t.server_write_secs))
+}
+
+pub fn client_timeouts() -> (Duration, Duration) {
+    let t = *TIMEOUTS.lock().unwrap();
+    (Duration::from_secs(t.client_read_secs), Duration::from_secs(t.client_write_secs))
+}
+
+/// How long a download may go without progress before the watchdog steps in, None = no watchdog
+pub fn stall_timeout() -> Option<Duration> {
+    let secs = TIMEOUTS.lock().unwrap().stall_secs;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// (attempts, pause between them)
+pub fn connect_retry() -> (u32, Duration) {
+    let t = *TIMEOUTS.lock().unwrap();
+    (t.connect_retries, Duration::from_millis(t.connect_backoff_ms))
+}
+
+/// ✅ Local-only (DO NOT serialize). This is what the sender will actually stream later over TCP.
+#[derive(Debug, Clone)]
+pub struct LocalFileOffer {
+    pub path: PathBuf, // real file path (single) OR temp zip path (bundle)
+    pub kind: OfferKind,
+    pub size: u64,
+    pub name: String, // handy for logs/debug
+}
+
+#[derive(Debug)]
+pub enum BundleEvent {
+    Progress {
+        offer_id: [u8; 16],
+        done: u64,
+        total: u64,
+        current: PathBuf,
+    },
+    Finished {
+        offer_id: [u8; 16],
+        packet: Vec<u8>,
+        local: LocalFileOffer,
+    },
+    Error {
+        offer_id: [u8; 16],
+        message: String,
+    },
+}
+
+pub enum BuildResult {
+    Ready(Vec<u8>), // single file -> packet now
+    Bundling {
+        offer_id: [u8; 16],
+        rx: mpsc::Receiver<BundleEvent>,
+        handle: thread::JoinHandle<()>,
+    },
+}
+
+pub type OfferRegistry = HashMap<[u8; 16], LocalFileOffer>;
+// sender address is where the offer came from (UDP port), only the IP + scope are used for TCP
+pub type RemoteWindowsOfferRegistry = HashMap<String, (SocketAddr, FileOffer)>; // for the FOFT
+pub type RemoteMobileOfferRegistry = HashMap<String, (SocketAddr, FileOffer)>; // for MFOFT
+static ACTIVE_BUNDLES: AtomicUsize = AtomicUsize::new(0);
+const MAX_BUNDLES: usize = 2;
+
+/// Build bytes ready to broadcast: "FOFT" + bincode(FileOffer)
+/// - 1 file  -> returns Ready(packet) immediately
+/// - >1 file -> returns Bundling{rx,...} and the zip happens in a background thread
+pub fn build_foft_packet_async(paths: Vec<PathBuf>, registry: &mut OfferRegistry) -> io::Result<BuildResult> {
+    if paths.is_empty() {
+        return Err(io::Error::other("No file selected"));
+    }
+
+    let offer_id: [u8; 16] = *Uuid::new_v4().as_bytes();
+
+    if paths.len() == 1 {
+        let packet = build_foft_packet_single(&paths[0], offer_id, registry)?;
+        Ok(BuildResult::Ready(packet))
+    } else {
+        // Try to reserve a bundling slot
+        let prev = ACTIVE_BUNDLES.fetch_add(1, Ordering::SeqCst);
+
+        if prev >= MAX_BUNDLES {
+            // undo reservation
+            ACTIVE_BUNDLES.fetch_sub(1, Ordering::SeqCst);
+            return Err(io::Error::other(
+                format!("Too many bundles running (max {})", MAX_BUNDLES),
+            ));
+        }
+
+        let (rx, handle) = spawn_zip_bundle_thread(paths, offer_id);
+        Ok(BuildResult::Bundling { offer_id, rx, handle })
+    }
+}
+
+// -------------------- Builders --------------------
+
+/// FOFT packet for a whole folder, its tree is rebuilt on the receiver (see FolderManifest)
+pub fn build_foft_packet_for_folder(path: &Path, registry: &mut OfferRegistry) -> io::Result<Vec<u8>> {
+    let (manifest, _) = build_folder_manifest(path)?;
+    if manifest.entries.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "The folder has no files"));
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "folder".to_string());
+    let size = manifest.entries.iter().map(|e| e.size).sum();
+    let offer_id: [u8; 16] = *Uuid::new_v4().as_bytes();
+
+    registry.insert(
+        offer_id,
+        LocalFileOffer {
+            path: path.to_path_buf(),
+            kind: OfferKind::Folder,
+            size,
+            name: name.clone(),
+        },
+    );
+    encode_offer_packet(&FileOffer {
+        offer_id,
+        name,
+        size,
+        kind: OfferKind::Folder,
+        protocol_version: FILE_PROTOCOL_VERSION,
+        tcp_port: tcp_port(),
+        expires_at: None,
+    })
+}
+
+/// Every file under `root` (symlinks skipped) with its manifest entry, in sending order.
+/// Built again when a peer downloads, so files changed since the offer are sent as they are now.
+pub fn build_folder_manifest(root: &Path) -> io::Result<(FolderManifest, Vec<PathBuf>)> {
+    let mut entries = Vec::new();
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(root).follow_links(false).sort_by_file_name() {
+        let entry = entry.map_err(|e| io::Error::other(e.to_string()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(root).map_err(|e| io::Error::other(e.to_string()))?;
+        let parts: Option<Vec<&str>> = rel.components().map(|c| c.as_os_str().to_str()).collect();
+        let Some(parts) = parts else { continue; }; // not valid UTF-8, can't be named on the wire
+        entries.push(ManifestEntry { path: parts.join("/"), size: entry.metadata().map_err(|e| io::Error::other(e.to_string()))?.len() });
+        files.push(entry.path().to_path_buf());
+    }
+    Ok((FolderManifest { entries }, files))
+}
+
+/// FOFT packet for one file we already have a path for, no dialog
+pub fn build_foft_packet_for_file(path: &Path, registry: &mut OfferRegistry) -> io::Result<Vec<u8>> {
+    let offer_id: [u8; 16] = *Uuid::new_v4().as_bytes();
+    build_foft_packet_single(path, offer_id, registry)
+}
+
+fn build_foft_packet_single( path: &Path, offer_id: [u8; 16], registry: &mut OfferRegistry, ) -> io::Result<Vec<u8>> {
+    let offer = register_single_offer(path, offer_id, registry)?;
+    encode_offer_packet(&offer)
+}
+
+/// Register one file for later TCP transfer and describe it, without building any packet
+/// (the secure channel wraps the offer itself, see channel_files.rs)
+pub fn register_single_offer( path: &Path, offer_id: [u8; 16], registry: &mut OfferRegistry, ) -> io::Result<FileOffer> {
+    let meta = std::fs::metadata(path)?;
+
+    if meta.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Folder sending not supported yet",
+        ));
+    }
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let size = meta.len();
+
+    // store locally for later TCP transfer
+    registry.insert(
+        offer_id,
+        LocalFileOffer {
+            path: path.to_path_buf(),
+            kind: OfferKind::SingleFile,
+            size,
+            name: name.clone(),
+        },
+    );
+
+    Ok(FileOffer {
+        offer_id,
+        name,
+        size,
+        kind: OfferKind::SingleFile,
+        protocol_version: FILE_PROTOCOL_VERSION,
+        tcp_port: tcp_port(),
+        expires_at: None,
+    })
+}
+
+// NOTE: You can keep this blocking builder if you want,
+// but the async flow does NOT call it.
+#[allow(dead_code)]
+fn build_foft_packet_zip_bundle_with_progress<F: FnMut(u64, u64, &Path)>( paths: &[PathBuf], offer_id: [u8; 16], registry: &mut OfferRegistry, mut on_progress: F, ) -> io::Result<Vec<u8>> {
+    let (packet, local) = build_zip_bundle_packet_no_registry(paths, offer_id, &mut on_progress)?;
+    registry.insert(offer_id, local);
+    Ok(packet)
+}
+
+// our nickname, sent after our offers so the panel can group them by sender
+static NICKNAME: Mutex<String> = Mutex::new(String::new());
+
+/// Config.nickname, call again after every change
+pub fn set_nickname(nickname: &str) {
+    *NICKNAME.lock().unwrap() = nickname.to_string();
+}
+
+fn encode_offer_packet(offer: &FileOffer) -> io::Result<Vec<u8>> {
+    let nickname = NICKNAME.lock().unwrap().clone();
+    let packet = encode_foft(offer, &nickname, offer_hash(&offer.offer_id).as_ref(), OFFER_TTL)?;
+    ANNOUNCED.lock().unwrap().get_or_insert_with(HashSet::new).insert(offer.offer_id);
+    Ok(packet)
+}
+
+// ===================== Expiry =====================
+// Our offers go out again every REANNOUNCE_INTERVAL, peers drop the row of one they haven't
+// heard about within its TTL (sender gone, app closed without /clearfiles).
+
+pub const REANNOUNCE_INTERVAL: Duration = Duration::from_secs(3 * 60);
+const OFFER_TTL: Duration = Duration::from_secs(10 * 60);
+
+// offers of ours that went out in an FOFT, channel offers never do and aren't re-announced
+static ANNOUNCED: Mutex<Option<HashSet<[u8; 16]>>> = Mutex::new(None);
+
+/// When a received FOFT stops counting, None from builds without the TTL trailer
+pub fn foft_expiry(bytes: &[u8]) -> Option<Instant> {
+    Some(Instant::now() + foft_ttl(bytes)?)
+}
+
+/// FOFT packets for every public offer we still have
+pub fn reannounce_packets(registry: &OfferRegistry) -> Vec<Vec<u8>> {
+    let announced = ANNOUNCED.lock().unwrap().clone().unwrap_or_default();
+    registry
+        .iter()
+        .filter(|(id, _)| announced.contains(*id))
+        .filter_map(|(id, local)| reoffer_packet(*id, local).ok())
+        .collect()
+}
+
+/// Drop offers whose sender stopped announcing them, returns their ids. One that's downloading
+/// stays until the download ends.
+pub fn sweep_expired(registry: &mut RemoteWindowsOfferRegistry) -> Vec<String> {
+    let now = Instant::now();
+    let expired: Vec<String> = registry
+        .iter()
+        .filter(|(id, (_, offer))| offer.expires_at.is_some_and(|at| at <= now) && !crate::transfers::is_running(id))
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in &expired {
+        registry.remove(id);
+    }
+    expired
+}
+
+/// FOFT for an offer we still have, the answer to a peer's FOFQ (see later.rs)
+pub fn reoffer_packet(offer_id: [u8; 16], local: &LocalFileOffer) -> io::Result<Vec<u8>> {
+    encode_offer_packet(&FileOffer {
+        offer_id,
+        name: local.name.clone(),
+        size: local.size,
+        kind: local.kind.clone(),
+        protocol_version: FILE_PROTOCOL_VERSION,
+        tcp_port: tcp_port(),
+        expires_at: None,
+    })
+}
+
+/// Zip bundles and pasted images live here until the offers are cleared
+pub fn temp_offer_dir() -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    dir.push("LanChGo");
+    dir.push("offers");
+
+    // ensure folder exists
+    std::fs::create_dir_all(&dir).ok();
+    dir
+}
+
+fn make_temp_zip_path(offer_id: &[u8; 16]) -> PathBuf {
+    let hex: String = offer_id.iter().map(|b| format!("{:02x}", b)).collect();
+    temp_offer_dir().join(format!("offer_{hex}.zip"))
+}
+
+/// A long paste the user chose to send as a file instead (see main_helpers::paste_preview)
+pub fn save_pasted_text(text: &str) -> io::Result<PathBuf> {
+    let name = format!("pasted_{}.txt", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"));
+    let path = temp_offer_dir().join(name);
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+pub fn cleanup_temp_offers(registry: &mut OfferRegistry) {
+    let mut to_remove: Vec<[u8; 16]> = Vec::new();
+    let temp_dir = temp_offer_dir();
+
+    for (id, local) in registry.iter() {
+        if matches!(local.kind, OfferKind::ZipBundle) || local.path.starts_with(&temp_dir) {
+            if let Err(e) = std::fs::remove_file(&local.path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    // println!(
+                    //     "[FOFT][CLEANUP] failed to delete {}: {}",
+                    //     local.path.display(),
+                    //     e
+                    // );
+                }
+            } else {
+                //println!("[FOFT][CLEANUP] deleted {}", local.path.display());
+            }
+            to_remove.push(*id);
+        }
+    }
+
+    for id in to_remove {
+        registry.remove(&id);
+    }
+}
+
+/// Where a download of `offer_id` collects its bytes, the same for every attempt at that offer
+/// so a new try can pick up where the last one broke off
+pub fn resume_part_path(save_path: &Path, offer_id: &[u8; 16]) -> PathBuf {
+    save_path.with_file_name(format!("{}.part", offer_id_to_hex(offer_id)))
+}
+
+/// The connection broke rather than the data being wrong: worth keeping the .part for a resume
+pub fn is_resumable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::NotConnected
+    )
+}
+
+// -------------------- File hashes --------------------
+//
+// Hashing a big file takes a while, so an offer goes out right away and its SHA-256 follows
+// in an FOFH once a background thread has it. Downloads of offers with a known hash are
+// checked before the .part is renamed.
+
+// offer id -> SHA-256, ours and the ones peers announced
+static OFFER_HASHES: Mutex<Option<HashMap<[u8; 16], [u8; 32]>>> = Mutex::new(None);
+
+pub fn remember_hash(offer_id: [u8; 16], digest: [u8; 32]) {
+    OFFER_HASHES.lock().unwrap().get_or_insert_with(HashMap::new).insert(offer_id, digest);
+}
+
+pub fn offer_hash(offer_id: &[u8; 16]) -> Option<[u8; 32]> {
+    OFFER_HASHES.lock().unwrap().as_ref()?.get(offer_id).copied()
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<[u8; 32]> {
+    use sha2::{Digest, Sha256};
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+pub const CHECKSUM_MISMATCH: &str = "checksum mismatch, file discarded";
+
+/// The file arrived but doesn't match the sender's hash
+pub fn is_corrupt(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::InvalidData && e.to_string() == CHECKSUM_MISMATCH
+}
+
+pub fn build_unique_download_path(dir: &Path, filename: &str, offer_id_hex: &str) -> PathBuf {
+    let mut ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    ts += 1;
+
+    let short_id: String = offer_id_hex.chars().take(6).collect();
+
+    let out = match filename.rsplit_once('.') {
+        Some((stem, ext)) => format!("{stem}_{ts}_{short_id}.{ext}"),
+        None => format!("{filename}_{ts}_{short_id}"),
+    };
+    dir.join(out)
+}
+
+/// Spawns a background thread that does the zip bundling.
+pub fn spawn_zip_bundle_thread( paths: Vec<PathBuf>, offer_id: [u8; 16], ) -> (mpsc::Receiver<BundleEvent>, thread::JoinHandle<()>) {
+    let (tx, rx) = mpsc::channel::<BundleEvent>();
+    let handle = thread::spawn(move || {
+        let result: io::Result<(Vec<u8>, LocalFileOffer)> =
+            build_zip_bundle_packet_no_registry(&paths, offer_id, |done, total, path| {
+                let _ = tx.send(BundleEvent::Progress {
+                    offer_id,
+                    done,
+                    total,
+                    current: path.to_path_buf(),
+                });
+            });
+
+        match result {
+            Ok((packet, local)) => {
+                let _ = tx.send(BundleEvent::Finished {
+                    offer_id,
+                    packet,
+                    local,
+                });
+            }
+            Err(e) => {
+                let _ = tx.send(BundleEvent::Error {
+                    offer_id,
+                    message: e.to_string(),
+                });
+            }
+        }
+    });
+    (rx, handle)
+}
+
+fn build_zip_bundle_packet_no_registry<F: FnMut(u64, u64, &Path)>( paths: &[PathBuf], offer_id: [u8; 16], mut on_progress: F, ) -> io::Result<(Vec<u8>, LocalFileOffer)> {
+    let mut total_bytes: u64 = 0;
+    let mut infos: Vec<(PathBuf, u64)> = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let meta = std::fs::metadata(path)?;
+        if meta.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Folder inside multi-select not supported yet",
+            ));
+        }
+        let sz = meta.len();
+        total_bytes = total_bytes.saturating_add(sz);
+        infos.push((path.clone(), sz));
+    }
+
+    // ✅ required function
+    let zip_path = make_temp_zip_path(&offer_id);
+
+    let file = File::create(&zip_path)?;
+    let mut zip = ZipWriter::new(file);
+    let options: FileOptions<'_, ()> = FileOptions::default();
+
+    let mut done_bytes: u64 = 0;
+    let mut buf = vec![0u8; 256 * 1024];
+
+    for (path, _file_total) in infos {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "unknown".to_string());
+        zip.start_file(name, options).map_err(|e| io::Error::other(e.to_string()))?;
+        let f = File::open(&path)?;
+        let mut r = BufReader::new(f);
+        loop {
+            let n = r.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            zip.write_all(&buf[..n])?;
+            done_bytes += n as u64;
+            on_progress(done_bytes, total_bytes, &path);
+        }
+    }
+
+    zip.finish()
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+    let zip_size = std::fs::metadata(&zip_path)?.len();
+    let name = format!("bundle_{}.zip", offer_id_to_hex(&offer_id));
+
+    let local = LocalFileOffer {
+        path: zip_path,
+        kind: OfferKind::ZipBundle,
+        size: zip_size,
+        name: name.clone(),
+    };
+
+    let offer = FileOffer {
+        offer_id,
+        name,
+        size: zip_size,
+        kind: OfferKind::ZipBundle,
+        protocol_version: FILE_PROTOCOL_VERSION,
+        tcp_port: tcp_port(),
+        expires_at: None,
+    };
+
+    let packet = encode_offer_packet(&offer)?;
+    Ok((packet, local))
+}
+
+pub fn bundle_slot_release() {
+    ACTIVE_BUNDLES.fetch_sub(1, Ordering::SeqCst);
+}
+
+// ─────────────────────────────────────────────────────────────
+// Mobile (Flutter) file-offer decoder (MFOFT)
+// ─────────────────────────────────────────────────────────────
+
+// MFOFT offers whose sender can send a checksum, by offer id hex
+static CHECKSUM_OFFERS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn mobile_offer_has_checksum(offer_id_hex: &str) -> bool {
+    CHECKSUM_OFFERS.lock().unwrap().iter().any(|id| id == offer_id_hex)
+}
+
+pub fn decode_mfoft(payload: &[u8]) -> Option<(FileOffer, String)> {
+    let (offer, offer_id_hex, checksum) = parse_mfoft(payload)?;
+    if checksum {
+        let mut ids = CHECKSUM_OFFERS.lock().unwrap();
+        if !ids.contains(&offer_id_hex) {
+            ids.push(offer_id_hex.clone());
+        }
+    }
+    Some((offer, offer_id_hex))
+}
+
+pub fn register_remote_offer(
+    remote_offers: &std::sync::Arc<std::sync::Mutex<RemoteMobileOfferRegistry>>,
+    sender_addr: SocketAddr,
+    id_hex: String,
+    offer: FileOffer,
+) -> bool {
+    let mut reg = remote_offers.lock().unwrap();
+    if let std::collections::hash_map::Entry::Vacant(e) = reg.entry(id_hex) {
+        e.insert((sender_addr, offer));
+        true // new
+    } else {
+        false // duplicate
+    }
+}
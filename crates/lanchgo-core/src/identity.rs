@@ -0,0 +1,144 @@
+// This install's long-lived ed25519 key, kept in identity_key.bin next to the config.
+// The public half goes out in our mDNS TXT record so peers can recognise us by key rather
+// than by nickname (see contacts), the secret half signs transcripts when we host.
+//
+// Anyone can put a copied key in their TXT record, so a key only counts for an address once
+// the peer there proved it holds the secret half, through our file server:
+// FIDP + nonce(32)          fresh from the asker
+// signature(64)             over PROOF_CONTEXT || nonce || the address the asker connected to
+// The address is in there so a proof can't be relayed from the real owner's machine.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::{rngs::OsRng, TryRngCore};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::file_transfer_protocol::digest_to_hex;
+
+pub const FIDP_MAGIC: &[u8; 4] = b"FIDP";
+const PROOF_CONTEXT: &[u8] = b"LanChGo identity proof v1";
+// a proven address is believed this long, mDNS re-resolving the peer proves it again
+const PROOF_TTL: Duration = Duration::from_secs(60 * 60);
+const PROOF_TIMEOUT: Duration = Duration::from_secs(5);
+
+// the folder the config lives in, set by the app before the key is first needed
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+static KEY: OnceLock<Option<SigningKey>> = OnceLock::new();
+// address -> the key its owner proved to hold, and when
+static PROVEN: Mutex<Option<HashMap<IpAddr, (String, Instant)>>> = Mutex::new(None);
+
+/// Where identity_key.bin is kept, call once at startup
+pub fn init(dir: PathBuf) {
+    let _ = DIR.set(dir);
+}
+
+fn key_path() -> Option<PathBuf> {
+    Some(DIR.get()?.join("identity_key.bin"))
+}
+
+// older builds kept the same kind of key for transcript signing only
+fn old_key_path() -> Option<PathBuf> {
+    Some(DIR.get()?.join("transcript_key.bin"))
+}
+
+fn load_or_create() -> Option<SigningKey> {
+    let (key_path, old_key_path) = (key_path()?, old_key_path()?);
+    if !key_path.exists() && old_key_path.exists() {
+        // keep the fingerprint members already know us by
+        let _ = std::fs::rename(&old_key_path, &key_path);
+    }
+    if let Ok(bytes) = std::fs::read(&key_path) {
+        if let Ok(secret) = <[u8; 32]>::try_from(bytes.as_slice()) {
+            return Some(SigningKey::from_bytes(&secret));
+        }
+    }
+    let mut secret = [0u8; 32];
+    OsRng.try_fill_bytes(&mut secret).ok()?;
+    std::fs::write(&key_path, secret).ok()?;
+    Some(SigningKey::from_bytes(&secret))
+}
+
+/// None only when the key file can't be read or written (or init wasn't called)
+pub fn signing_key() -> Option<SigningKey> {
+    KEY.get_or_init(load_or_create).clone()
+}
+
+/// Our public key as hex, "" without a key
+pub fn public_hex() -> String {
+    signing_key().map(|k| digest_to_hex(k.verifying_key().as_bytes())).unwrap_or_default()
+}
+
+/// First 8 bytes of a key, enough to tell installs apart
+pub fn fingerprint(key_hex: &str) -> &str {
+    &key_hex[..key_hex.len().min(16)]
+}
+
+fn proof_bytes(nonce: &[u8; 32], addr: IpAddr) -> Vec<u8> {
+    [PROOF_CONTEXT, &nonce[..], addr.to_canonical().to_string().as_bytes()].concat()
+}
+
+/// Server side of FIDP, sign the asker's nonce with our key
+pub async fn serve_proof(mut stream: TcpStream, read_limit: Duration, write_limit: Duration) -> io::Result<()> {
+    let mut request = [0u8; 36];
+    tokio::time::timeout(read_limit, stream.read_exact(&mut request))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "FIDP timed out"))??;
+    let nonce: [u8; 32] = request[4..].try_into().unwrap();
+    let key = signing_key().ok_or_else(|| io::Error::other("no identity key"))?;
+    let signature = key.sign(&proof_bytes(&nonce, stream.local_addr()?.ip()));
+    tokio::time::timeout(write_limit, stream.write_all(&signature.to_bytes()))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "FIDP timed out"))?
+}
+
+async fn ask_proof(addr: SocketAddr, key: &VerifyingKey) -> io::Result<bool> {
+    let mut nonce = [0u8; 32];
+    OsRng.try_fill_bytes(&mut nonce).map_err(|e| io::Error::other(e.to_string()))?;
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(&[&FIDP_MAGIC[..], &nonce[..]].concat()).await?;
+    let mut signature = [0u8; 64];
+    stream.read_exact(&mut signature).await?;
+    Ok(key.verify(&proof_bytes(&nonce, addr.ip()), &Signature::from_bytes(&signature)).is_ok())
+}
+
+/// Does the peer at `ip` hold the secret half of `key_hex`. Asks its file server on `tcp_port`
+/// unless it proved it within PROOF_TTL. False for builds that don't answer FIDP.
+pub async fn prove(ip: IpAddr, tcp_port: u16, key_hex: &str) -> bool {
+    if proven_key(ip).as_deref() == Some(key_hex) {
+        return true;
+    }
+    let Some(key) = from_hex_key(key_hex) else { return false; };
+    let proven = tokio::time::timeout(PROOF_TIMEOUT, ask_proof(SocketAddr::new(ip, tcp_port), &key))
+        .await
+        .is_ok_and(|r| r.unwrap_or(false));
+    if proven {
+        PROVEN.lock().unwrap().get_or_insert_with(HashMap::new).insert(ip, (key_hex.to_string(), Instant::now()));
+    }
+    proven
+}
+
+/// The key the peer at `ip` proved to hold within PROOF_TTL
+pub fn proven_key(ip: IpAddr) -> Option<String> {
+    let mut proven = PROVEN.lock().unwrap();
+    let map = proven.get_or_insert_with(HashMap::new);
+    map.retain(|_, (_, at)| at.elapsed() < PROOF_TTL);
+    map.get(&ip).map(|(key, _)| key.clone())
+}
+
+fn from_hex_key(hex: &str) -> Option<VerifyingKey> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    VerifyingKey::from_bytes(&bytes).ok()
+}
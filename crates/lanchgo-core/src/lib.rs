@@ -0,0 +1,20 @@
+// Networking, crypto and transfer pieces of LanChGo that don't touch the UI
+
+pub mod bandwidth;              // per-direction speed caps shared by all transfers
+pub mod channel_files;          // files shared into the private channel
+pub mod cipher_suite;           // AES-256-GCM / ChaCha20-Poly1305 behind one trait
+pub mod classes;                // types the app's Config embeds
+pub mod file_transfer_protocol; // our offers, zip bundles, hashes, transfer timeouts
+pub mod identity;               // long-term ed25519 key, fingerprints
+pub mod net_runtime;            // shared tokio runtime for UDP, the file server and downloads
+pub mod own_packets;            // recognises our own broadcasts when they loop back
+pub mod pairing;                // /pair: phones that push without a prompt
+pub mod phone_protocol;         // MPUSH / DPUSH framing
+pub mod rate_limit;             // Config.rate_limit mirror
+pub mod sealed_stream;          // file bytes sealed with the channel key
+pub mod secure_channel_code;    // the private channel: PIN, keys, nonces
+pub mod session_bundle;         // LCSB: passphrase-sealed export of the session
+pub mod tcp_file_client;        // downloads and pushes
+pub mod tcp_file_server;        // serves our offers over TCP
+pub mod transfers;              // running downloads / uploads, cancelled by /clearfiles
+pub mod udp_receiver;           // the UDP loop: sockets, dedupe, fragments
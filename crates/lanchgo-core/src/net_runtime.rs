@@ -0,0 +1,28 @@
+use std::future::Future;
+use std::sync::OnceLock;
+
+use tokio::runtime::{Builder, Runtime};
+use tokio::task::JoinHandle;
+
+// one runtime for UDP receive, the TCP file server and downloads
+// (the web companion keeps its own, it is started and stopped on demand)
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+pub fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("lanchgo-net")
+            .enable_all()
+            .build()
+            .expect("Failed to start network runtime")
+    })
+}
+
+pub fn spawn<F>(fut: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    runtime().spawn(fut)
+}
@@ -0,0 +1,30 @@
+// Self-detection for every packet type: what this instance broadcast lately. A packet that
+// comes back byte for byte is our own loopback, whatever its type and whichever address the
+// OS reports for it (second adapter, IPv6, a changed DHCP lease). Another install on the same
+// machine sends different bytes, so it still counts as a peer.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+const MEMORY: usize = 128;
+// shorter packets ("REQA") carry nothing unique, a peer's could look exactly like ours
+const MIN_LEN: usize = 16;
+
+static RECENT: Mutex<VecDeque<Vec<u8>>> = Mutex::new(VecDeque::new());
+
+/// Call before the packet goes out, its loopback copy can arrive before send_to returns
+pub fn remember(packet: &[u8]) {
+    if packet.len() < MIN_LEN {
+        return;
+    }
+    let mut recent = RECENT.lock().unwrap();
+    if recent.len() >= MEMORY {
+        recent.pop_front();
+    }
+    recent.push_back(packet.to_vec());
+}
+
+/// We sent exactly this a moment ago
+pub fn is_own(packet: &[u8]) -> bool {
+    packet.len() >= MIN_LEN && RECENT.lock().unwrap().iter().any(|p| p == packet)
+}
@@ -0,0 +1,182 @@
+// Pairing our own phone with this desktop (/pair). The QR code carries a one-time token that
+// never goes over the network: the phone sends an X25519 public key through the file server
+// (MPAIR) with an HMAC of it under the token, we answer with ours and an HMAC the other way.
+// The key comes from the X25519 secret, so someone sniffing the exchange can't work it out and
+// someone without the QR code can't sit in the middle. Paired phones are stored in the config
+// and count as trusted peers, their pushes skip the accept prompt and arrive sealed with that
+// key (MPUSHE, see phone_push).
+//
+// QR     lanchgo-pair://<ip>:<tcp port>/<token hex>
+// MPAIR <device id> <phone public hex> <HMAC(token, REQUEST_LABEL || device id || phone public) hex> <device name>\n
+// OK <our public hex> <HMAC(token, REPLY_LABEL || our public || phone public) hex> <our nickname>\n | ERR\n
+// key = SHA-256(KEY_CONTEXT || X25519 secret || token || device id || phone public || our public)
+
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, TryRngCore};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::classes::PairedDevice;
+use crate::file_transfer_protocol::digest_to_hex;
+
+pub const MPAIR_MAGIC: &[u8; 5] = b"MPAIR";
+
+// the QR code is only good for this long, and only once
+const TOKEN_TTL: Duration = Duration::from_secs(120);
+const KEY_CONTEXT: &[u8] = b"LanChGo pair v2";
+const REQUEST_LABEL: &[u8] = b"LanChGo pair v2 request";
+const REPLY_LABEL: &[u8] = b"LanChGo pair v2 reply";
+const MAX_LINE: u64 = 256;
+
+static TOKEN: Mutex<Option<([u8; 16], Instant)>> = Mutex::new(None);
+// mirror of Config.paired_devices: (device id, key)
+static DEVICES: Mutex<Vec<(String, [u8; 32])>> = Mutex::new(Vec::new());
+static NICKNAME: Mutex<String> = Mutex::new(String::new());
+// paired on the network thread, the app saves them on tcp_file_server::ServerEvent::Paired
+static COMPLETED: Mutex<Vec<(String, PairedDevice)>> = Mutex::new(Vec::new());
+
+/// Config.paired_devices and the nickname we answer pairings with, call again after every change
+pub fn set_devices(devices: &BTreeMap<String, PairedDevice>, nickname: &str) {
+    *DEVICES.lock().unwrap() = devices
+        .iter()
+        .filter_map(|(id, d)| Some((id.clone(), parse_key(&d.key)?)))
+        .collect();
+    *NICKNAME.lock().unwrap() = nickname.to_string();
+}
+
+/// New one-time token, returns what goes in the QR code
+pub fn start(ip: Ipv4Addr, tcp_port: u16) -> Option<String> {
+    let mut token = [0u8; 16];
+    OsRng.try_fill_bytes(&mut token).ok()?;
+    *TOKEN.lock().unwrap() = Some((token, Instant::now()));
+    Some(format!("lanchgo-pair://{}:{}/{}", ip, tcp_port, digest_to_hex(&token)))
+}
+
+/// Forget the token, the popup was closed
+pub fn cancel() {
+    *TOKEN.lock().unwrap() = None;
+}
+
+/// Pairings finished since the last call, for the app to save
+pub fn take_completed() -> Vec<(String, PairedDevice)> {
+    std::mem::take(&mut *COMPLETED.lock().unwrap())
+}
+
+pub fn device_key(device_id: &str) -> Option<[u8; 32]> {
+    DEVICES.lock().unwrap().iter().find(|(id, _)| id == device_id).map(|(_, key)| *key)
+}
+
+fn token_mac(token: &[u8; 16], parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(token).expect("HMAC takes any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+fn derive_key(shared: &[u8; 32], token: &[u8; 16], device_id: &str, phone: &[u8; 32], ours: &[u8; 32]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(KEY_CONTEXT);
+    h.update(shared);
+    h.update(token);
+    h.update(device_id.as_bytes());
+    h.update(phone);
+    h.update(ours);
+    h.finalize().into()
+}
+
+fn parse_key(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(key)
+}
+
+// takes the token if the request was made with it and it hasn't expired, so a replayed QR code is useless
+fn consume_token(made_with: impl Fn(&[u8; 16]) -> bool) -> Option<[u8; 16]> {
+    let mut current = TOKEN.lock().unwrap();
+    let (token, at) = (*current)?;
+    if at.elapsed() > TOKEN_TTL || !made_with(&token) {
+        return None;
+    }
+    *current = None;
+    Some(token)
+}
+
+/// One MPAIR connection, called by the file server once it saw the magic. Ok means a new
+/// device waits in take_completed.
+pub async fn handle_pair(stream: TcpStream, read_limit: Duration, write_limit: Duration) -> std::io::Result<()> {
+    let phone = stream.peer_addr()?.ip();
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    tokio::time::timeout(read_limit, (&mut reader).take(MAX_LINE).read_line(&mut line))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "MPAIR timed out"))??;
+
+    let paired = parse_request(&line).and_then(|request| {
+        let token = consume_token(|token| token_mac(token, &[REQUEST_LABEL, request.device_id.as_bytes(), &request.public]).verify_slice(&request.proof).is_ok())?;
+        let secret = StaticSecret::from(crate::secure_channel_code::generate_key());
+        let ours = PublicKey::from(&secret);
+        let shared = secret.diffie_hellman(&PublicKey::from(request.public));
+        if !shared.was_contributory() {
+            return None;
+        }
+        let key = derive_key(shared.as_bytes(), &token, request.device_id, &request.public, ours.as_bytes());
+        let confirm = token_mac(&token, &[REPLY_LABEL, ours.as_bytes(), &request.public]).finalize().into_bytes();
+        let device = PairedDevice { name: request.name.to_string(), key: digest_to_hex(&key), last_ip: phone.to_string() };
+        Some((request.device_id.to_string(), device, digest_to_hex(ours.as_bytes()), digest_to_hex(&confirm)))
+    });
+
+    let Some((device_id, device, ours, confirm)) = paired else {
+        let _ = tokio::time::timeout(write_limit, reader.get_mut().write_all(b"ERR\n")).await;
+        return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "pairing token wrong or expired"));
+    };
+
+    let reply = format!("OK {} {} {}\n", ours, confirm, NICKNAME.lock().unwrap());
+    tokio::time::timeout(write_limit, reader.get_mut().write_all(reply.as_bytes()))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "MPAIR write timed out"))??;
+
+    COMPLETED.lock().unwrap().push((device_id, device));
+    Ok(())
+}
+
+struct PairRequest<'a> {
+    device_id: &'a str,
+    public: [u8; 32], // the phone's X25519 key
+    proof: [u8; 32],  // HMAC under the token
+    name: &'a str,
+}
+
+// "MPAIR <device id> <public> <proof> <name>"
+fn parse_request(line: &str) -> Option<PairRequest<'_>> {
+    let mut parts = line.trim().splitn(5, ' ');
+    if parts.next()? != "MPAIR" {
+        return None;
+    }
+    let device_id = parts.next()?;
+    let public = parse_key(parts.next()?)?;
+    let proof = parse_key(parts.next()?)?;
+    let name = parts.next().unwrap_or("Phone").trim();
+    let id_ok = !device_id.is_empty() && device_id.len() <= 64 && device_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !id_ok {
+        return None;
+    }
+    let name = match name.char_indices().nth(40) {
+        Some((end, _)) => &name[..end],
+        None => name,
+    };
+    Some(PairRequest { device_id, public, proof, name })
+}
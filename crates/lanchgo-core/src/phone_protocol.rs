@@ -1,11 +1,10 @@
 use std::sync::{OnceLock, Mutex};
 use crate::secure_channel_code::{ChannelAnnounce, SecureMessage, Channel};
+use crate::cipher_suite::CipherSuite;
 use serde_json::Value;
 use base64::engine::general_purpose::STANDARD as b64;
 use base64::Engine;
 use aes_gcm::{Aes256Gcm, KeyInit, aead::{Aead, Key}};
-use rand::rngs::OsRng;
-use rand::TryRngCore;
 //use std::time::{Instant, Duration};
 
 static ANNOUNCE_STORE_PHONE: OnceLock<Mutex<Vec<ChannelAnnounce>>> = OnceLock::new();
@@ -47,6 +46,9 @@ pub fn store_announcement_phone(bytes: &[u8]) -> bool {
                 let incoming = ChannelAnnounce {
                     salt,
                     validation: SecureMessage { nonce, ciphertext },
+                    suite: CipherSuite::Aes256Gcm,
+                    key_exchange: false, // phones join with the PIN key
+                    host: None,
                 };
 
                 // --- Store without duplicates ---
@@ -106,8 +108,9 @@ pub fn encrypt_message_phone(key: &[u8; 32], msg_content: &str) -> Vec<u8> {
 
     let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
 
-    let mut nonce_bytes = [0u8; 12];
-    OsRng.try_fill_bytes(&mut nonce_bytes).expect("RNG failed");
+    // shares the channel counter with ENCM, see secure_channel_code::next_nonce. Fully random
+    // when the channel has a session key, the PIN key then isn't the channel's
+    let nonce_bytes = crate::secure_channel_code::next_nonce(key);
 
     let nonce = GenericArray::from_slice(&nonce_bytes); // ✅ fixed
 
@@ -128,21 +131,23 @@ pub fn decrypt_message_phone(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) ->
     let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
     let nonce_arr = GenericArray::from_slice(nonce);
     match cipher.decrypt(nonce_arr, ciphertext) {
-        Ok(plain) => String::from_utf8(plain).ok(),
-        Err(_) => None,
+        Ok(plain) if crate::secure_channel_code::accept_random_nonce(nonce) => String::from_utf8(plain).ok(),
+        _ => None,
     }
 }
 
+/// Phones can't do the key exchange, MANCH and their MENCM traffic stay on the PIN key
 #[allow(non_snake_case)]
-pub fn build_MANCH(channel: &Channel) -> Result<String, serde_json::Error> {
-    let validation = encrypt_message_phone(&channel.key, "SECURE_OK");
+pub fn build_MANCH(channel: &Channel, port: u16) -> Result<String, serde_json::Error> {
+    let validation = encrypt_message_phone(&channel.auth_key, "SECURE_OK");
 
     let json = serde_json::json!({
-        "salt": b64.encode(&channel.salt),
+        "salt": b64.encode(channel.salt),
         "validation": {
             "nonce": b64.encode(&validation[5..17]),
             "ciphertext": b64.encode(&validation[17..]),
-        }
+        },
+        "port": port
     });
 
     let json_str = serde_json::to_string(&json)?;
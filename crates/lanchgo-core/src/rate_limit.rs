@@ -0,0 +1,91 @@
+// Upload / download caps for file transfers (/limit), so one big transfer doesn't fill the
+// office Wi-Fi and starve the chat. One token bucket per direction, shared by every transfer
+// running that way; the loops that move file bytes wait here before passing a buffer on.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::classes::RateLimit;
+
+struct Bucket {
+    rate: u64, // bytes per second, 0 = no cap
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new() -> Self {
+        Self { rate: 0, tokens: 0.0, last: Instant::now() }
+    }
+
+    fn set_rate(&mut self, rate: u64) {
+        if self.rate != rate {
+            self.rate = rate;
+            self.tokens = 0.0;
+            self.last = Instant::now();
+        }
+    }
+
+    // take `bytes` out of the bucket, how long to wait before they may move
+    fn reserve(&mut self, bytes: usize) -> Duration {
+        if self.rate == 0 {
+            return Duration::ZERO;
+        }
+        let now = Instant::now();
+        let refill = now.duration_since(self.last).as_secs_f64() * self.rate as f64;
+        // at most a second's worth saved up, a burst after a pause stays short
+        self.tokens = (self.tokens + refill).min(self.rate as f64);
+        self.last = now;
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate as f64)
+        }
+    }
+}
+
+static UPLOAD: Mutex<Option<Bucket>> = Mutex::new(None);
+static DOWNLOAD: Mutex<Option<Bucket>> = Mutex::new(None);
+
+/// Config.rate_limit, call again after every change. Running transfers slow down right away.
+pub fn set_limits(limits: &RateLimit) {
+    UPLOAD.lock().unwrap().get_or_insert_with(Bucket::new).set_rate(limits.upload_kib_per_sec * 1024);
+    DOWNLOAD.lock().unwrap().get_or_insert_with(Bucket::new).set_rate(limits.download_kib_per_sec * 1024);
+}
+
+async fn wait(slot: &Mutex<Option<Bucket>>, bytes: usize) {
+    let wait = slot.lock().unwrap().get_or_insert_with(Bucket::new).reserve(bytes);
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Wait until `bytes` more may go out
+pub async fn upload(bytes: usize) {
+    wait(&UPLOAD, bytes).await
+}
+
+/// Wait until `bytes` more may come in (TCP slows the sender down meanwhile)
+pub async fn download(bytes: usize) {
+    wait(&DOWNLOAD, bytes).await
+}
+
+fn kib(rate: u64) -> String {
+    if rate == 0 {
+        "no limit".into()
+    } else if rate >= 1024 {
+        format!("{:.1} MB/s", rate as f64 / 1024.0)
+    } else {
+        format!("{} KB/s", rate)
+    }
+}
+
+/// "/limit" status line
+pub fn summary(limits: &RateLimit) -> String {
+    format!(
+        "🚦 Uploads: {}, downloads: {}",
+        kib(limits.upload_kib_per_sec),
+        kib(limits.download_kib_per_sec)
+    )
+}
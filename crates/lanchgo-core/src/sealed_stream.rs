@@ -0,0 +1,202 @@
+// Encrypted file streams for secure channel mode. What would go over TCP raw is cut into
+// CHUNK pieces, each sealed with the channel key, framed the same way as MPUSHE (see phone_push):
+//   len(u32) + bincode(SecureMessage)
+// Every chunk is sealed under offer_id(16) || index(u64 LE) || final(u8) as AAD, from index 0.
+// Chunk 0 carries total(u64) + start(u64), the FOFS header repeated where it can't be altered.
+// The last chunk is empty with the final flag set, a stream that stops before it (a bare len 0
+// included) was cut short. A chunk can't be dropped, reordered or moved to another offer without
+// failing to open.
+// FOFE channel offers always use it, FOFR downloads ask for it while a channel is active
+// (FOFR_SEALED_VERSION, see tcp_file_server).
+
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::secure_channel_code::{self, SecureMessage};
+
+pub const CHUNK: usize = 64 * 1024; // plaintext per sealed chunk
+// nonce + tag + bincode framing on top of a full chunk
+const MAX_SEALED: usize = CHUNK + 64;
+
+async fn timed<T>(limit: Duration, fut: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    tokio::time::timeout(limit, fut)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Peer stalled"))?
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Send one len(u32) + bincode(SecureMessage) frame, returns how many bytes went on the wire
+pub async fn write_frame<W: AsyncWrite + Unpin>(w: &mut W, sealed: &SecureMessage, limit: Duration) -> io::Result<usize> {
+    let frame = bincode::serde::encode_to_vec(sealed, bincode::config::standard())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    timed(limit, w.write_all(&(frame.len() as u32).to_le_bytes())).await?;
+    timed(limit, w.write_all(&frame)).await?;
+    Ok(frame.len() + 4)
+}
+
+/// The next frame, still sealed, and the bytes read off the wire. None at a 0 length.
+pub async fn read_frame<R: AsyncRead + Unpin>(r: &mut R, max_len: usize, limit: Duration) -> io::Result<Option<(SecureMessage, usize)>> {
+    let mut len = [0u8; 4];
+    timed(limit, r.read_exact(&mut len)).await?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len == 0 {
+        return Ok(None);
+    }
+    if len > max_len {
+        return Err(invalid("Chunk too large"));
+    }
+    let mut frame = vec![0u8; len];
+    timed(limit, r.read_exact(&mut frame)).await?;
+    let (sealed, _) = bincode::serde::decode_from_slice::<SecureMessage, _>(&frame, bincode::config::standard())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    Ok(Some((sealed, len + 4)))
+}
+
+fn chunk_aad(offer_id: &[u8; 16], index: u64, last: bool) -> [u8; 25] {
+    let mut aad = [0u8; 25];
+    aad[..16].copy_from_slice(offer_id);
+    aad[16..24].copy_from_slice(&index.to_le_bytes());
+    aad[24] = last as u8;
+    aad
+}
+
+/// Sending side of a transfer: bytes go out raw, or sealed when there's a key
+pub struct Sink {
+    key: Option<[u8; 32]>,
+    offer_id: [u8; 16],
+    index: u64,
+    limit: Duration,
+}
+
+impl Sink {
+    pub fn new(key: Option<[u8; 32]>, offer_id: [u8; 16], limit: Duration) -> Self {
+        Self { key, offer_id, index: 0, limit }
+    }
+
+    async fn write_chunk<W: AsyncWrite + Unpin>(&mut self, w: &mut W, key: &[u8; 32], plain: &[u8], last: bool) -> io::Result<usize> {
+        let sealed = secure_channel_code::encrypt_bytes_aad(key, plain, &chunk_aad(&self.offer_id, self.index, last));
+        self.index += 1;
+        write_frame(w, &sealed, self.limit).await
+    }
+
+    /// Chunk 0 with the size and start from the FOFS header, right after it. Nothing when raw.
+    pub async fn begin<W: AsyncWrite + Unpin>(&mut self, w: &mut W, total: u64, start: u64) -> io::Result<usize> {
+        let Some(key) = self.key else { return Ok(0); };
+        let mut head = [0u8; 16];
+        head[..8].copy_from_slice(&total.to_le_bytes());
+        head[8..].copy_from_slice(&start.to_le_bytes());
+        self.write_chunk(w, &key, &head, false).await
+    }
+
+    /// Returns how many bytes went on the wire
+    pub async fn write_all<W: AsyncWrite + Unpin>(&mut self, w: &mut W, data: &[u8]) -> io::Result<usize> {
+        let Some(key) = self.key else {
+            timed(self.limit, w.write_all(data)).await?;
+            return Ok(data.len());
+        };
+        let mut sent = 0;
+        for piece in data.chunks(CHUNK) {
+            sent += self.write_chunk(w, &key, piece, false).await?;
+        }
+        Ok(sent)
+    }
+
+    pub async fn finish<W: AsyncWrite + Unpin>(&mut self, w: &mut W) -> io::Result<()> {
+        if let Some(key) = self.key {
+            self.write_chunk(w, &key, &[], true).await?;
+        }
+        timed(self.limit, w.flush()).await
+    }
+}
+
+/// Receiving side: hands out the plaintext whichever way it was sent
+pub struct Source {
+    key: Option<[u8; 32]>,
+    offer_id: [u8; 16],
+    index: u64,
+    pending: Vec<u8>,
+    pos: usize,
+    ended: bool,
+}
+
+impl Source {
+    pub fn new(key: Option<[u8; 32]>, offer_id: [u8; 16]) -> Self {
+        Self { key, offer_id, index: 0, pending: Vec::new(), pos: 0, ended: false }
+    }
+
+    // the next chunk opened and whether it was the final one
+    async fn read_chunk<R: AsyncRead + Unpin>(&mut self, r: &mut R, key: &[u8; 32], limit: Duration) -> io::Result<(Vec<u8>, bool)> {
+        let (sealed, _) = read_frame(r, MAX_SEALED, limit)
+            .await?
+            .ok_or_else(|| invalid("Sealed stream ended without its final chunk"))?;
+        let opened = [false, true].into_iter().find_map(|last| {
+            secure_channel_code::decrypt_bytes_aad(key, &sealed, &chunk_aad(&self.offer_id, self.index, last)).map(|plain| (plain, last))
+        });
+        self.index += 1;
+        opened.ok_or_else(|| invalid("Chunk failed to decrypt"))
+    }
+
+    /// The (size, start) sealed in chunk 0, None when raw. Read it right after the FOFS header
+    /// and check it against what the header said.
+    pub async fn begin<R: AsyncRead + Unpin>(&mut self, r: &mut R, limit: Duration) -> io::Result<Option<(u64, u64)>> {
+        let Some(key) = self.key else { return Ok(None); };
+        match self.read_chunk(r, &key, limit).await? {
+            (head, false) if head.len() == 16 => Ok(Some((
+                u64::from_le_bytes(head[..8].try_into().unwrap()),
+                u64::from_le_bytes(head[8..].try_into().unwrap()),
+            ))),
+            _ => Err(invalid("Bad sealed stream header")),
+        }
+    }
+
+    /// Like AsyncRead::read, 0 at the end of the stream
+    pub async fn read<R: AsyncRead + Unpin>(&mut self, r: &mut R, buf: &mut [u8], limit: Duration) -> io::Result<usize> {
+        let Some(key) = self.key else {
+            return timed(limit, r.read(buf)).await;
+        };
+        while self.pos == self.pending.len() {
+            if self.ended {
+                return Ok(0);
+            }
+            let (plain, last) = self.read_chunk(r, &key, limit).await?;
+            self.pending = plain;
+            self.pos = 0;
+            self.ended = last;
+        }
+        let n = buf.len().min(self.pending.len() - self.pos);
+        buf[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+
+    pub async fn read_exact<R: AsyncRead + Unpin>(&mut self, r: &mut R, buf: &mut [u8], limit: Duration) -> io::Result<()> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let n = self.read(r, &mut buf[filled..], limit).await?;
+            if n == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed early"));
+            }
+            filled += n;
+        }
+        Ok(())
+    }
+
+    /// After the last byte we wanted: a sealed stream must close with its final chunk and
+    /// nothing more, so a cut-off file isn't taken for a whole one
+    pub async fn finish<R: AsyncRead + Unpin>(&mut self, r: &mut R, limit: Duration) -> io::Result<()> {
+        if self.key.is_none() {
+            return Ok(());
+        }
+        let mut extra = [0u8; 1];
+        if self.read(r, &mut extra, limit).await? != 0 {
+            return Err(invalid("Sealed stream longer than announced"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,804 @@
+#![allow(nonstandard_style)]
+
+use rand::{Rng, rngs::OsRng, TryRngCore};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use pbkdf2::pbkdf2_hmac;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroize;
+use crate::cipher_suite::CipherSuite;
+use serde::{Serialize, Deserialize};
+use std::time::{Instant, Duration};
+
+static HOST_PIN: OnceLock<Mutex<Option<i32>>> = OnceLock::new();
+static ACTIVE_CHANNEL: OnceLock<Mutex<Option<Channel>>> = OnceLock::new();
+static BRUTE_FORCE_STATE: OnceLock<Mutex<BruteForceTracker>> = OnceLock::new();
+const VALIDATION_TEXT: &str = "SECURE_OK";
+/// Global store for channel announcements (for joiners)
+static ANNOUNCE_STORE: OnceLock<Mutex<Vec<ChannelAnnounce>>> = OnceLock::new();
+/// Channels we host get a random session key handed out over X25519 (Config.forward_secrecy)
+static FORWARD_SECRECY: AtomicBool = AtomicBool::new(false);
+// where the ANCH of the channel we joined came from, None while hosting or in public
+static JOINED_HOST: Mutex<Option<IpAddr>> = Mutex::new(None);
+
+/// Channel struct
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub salt: [u8; 16],
+    pub key: [u8; 32],      // seals the traffic
+    pub auth_key: [u8; 32], // from the PIN: ANCH / MANCH validation, the key exchange and phone traffic (MENCM)
+    pub counter: u64,
+    pub sender: [u8; 4],    // nonce prefix, fresh whenever the counter starts over
+    pub suite: CipherSuite,
+}
+
+impl Channel {
+    pub fn new(PIN: i32) -> Self {
+        let salt = generate_salt();
+        let auth_key = derive_key(PIN, &salt);
+        // a session key nobody can work out from the PIN, joiners get it in the key exchange
+        let key = if FORWARD_SECRECY.load(Ordering::Relaxed) { generate_key() } else { auth_key };
+        Self { salt, counter: 0, sender: new_sender_id(), key, auth_key, suite: CipherSuite::preferred() }
+    }
+
+    pub fn new_join_channel(salt: &[u8; 16], key: &[u8; 32], suite: CipherSuite) -> Self {
+        Self { salt: *salt, counter: 0, sender: new_sender_id(), key: *key, auth_key: *key, suite }
+    }
+
+    /// The traffic key isn't the PIN's
+    pub fn forward_secret(&self) -> bool {
+        self.key != self.auth_key
+    }
+
+    pub fn clear(&mut self) {
+        self.key.zeroize();
+        self.auth_key.zeroize();
+        self.salt.zeroize();
+        self.counter = 0;
+    }
+}
+
+/// Config.forward_secrecy, takes effect for the next channel we host
+pub fn set_forward_secrecy(on: bool) {
+    FORWARD_SECRECY.store(on, Ordering::Relaxed);
+}
+
+/// Message struct
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SecureMessage {
+    pub nonce: [u8; 12],
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChannelAnnounce {
+    pub salt: [u8; 16],            // random salt for key derivation
+    pub validation: SecureMessage, // encrypted "SECURE_OK"
+    #[serde(skip)]
+    pub suite: CipherSuite,        // from the SUIT trailer, not part of the bincode payload
+    #[serde(skip)]
+    pub key_exchange: bool,        // KEXV trailer: the traffic key comes from the host over X25519
+    #[serde(skip)]
+    pub host: Option<IpAddr>,      // where it came from, None for our own
+}
+
+struct BruteForceTracker {
+    failed_attempts: u32,
+    last_attempt: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl BruteForceTracker {
+    fn new() -> Self {
+        Self {
+            failed_attempts: 0,
+            last_attempt: Instant::now(),
+            locked_until: None,
+        }
+    }
+}
+
+/// Encrypt and Decrypt
+pub fn encrypt_message(key: &[u8; 32], msg_content: &str) -> SecureMessage {
+    encrypt_bytes(key, msg_content.as_bytes())
+}
+
+pub fn decrypt_message(key: &[u8], secure_msg: &SecureMessage) -> Option<String> {
+    String::from_utf8(decrypt_bytes(key, secure_msg)?).ok()
+}
+
+/// Same as encrypt_message for raw bytes (file chunks)
+pub fn encrypt_bytes(key: &[u8; 32], plain: &[u8]) -> SecureMessage {
+    seal(suite_for(key), key, plain)
+}
+
+pub fn decrypt_bytes(key: &[u8], secure_msg: &SecureMessage) -> Option<Vec<u8>> {
+    open(suite_for(key), key, secure_msg)
+}
+
+/// encrypt_bytes with `aad` bound in, a chunk only opens where the same `aad` is expected
+pub fn encrypt_bytes_aad(key: &[u8; 32], plain: &[u8], aad: &[u8]) -> SecureMessage {
+    let nonce = next_nonce(key);
+    let ciphertext = suite_for(key).cipher().seal_aad(key, &nonce, plain, aad)
+        .expect("encryption failed");
+    SecureMessage { nonce, ciphertext }
+}
+
+pub fn decrypt_bytes_aad(key: &[u8], secure_msg: &SecureMessage, aad: &[u8]) -> Option<Vec<u8>> {
+    suite_for(key).cipher().open_aad(key, &secure_msg.nonce, &secure_msg.ciphertext, aad)
+}
+
+// the active channel's suite for its key, AES-256-GCM for anything else (phones, PIN checks)
+fn suite_for(key: &[u8]) -> CipherSuite {
+    get_active_channel()
+        .filter(|ch| ch.key.as_slice() == key)
+        .map(|ch| ch.suite)
+        .unwrap_or_default()
+}
+
+fn seal(suite: CipherSuite, key: &[u8; 32], plain: &[u8]) -> SecureMessage {
+    let nonce = next_nonce(key);
+    let ciphertext = suite.cipher().seal(key, &nonce, plain)
+        .expect("encryption failed");
+    SecureMessage { nonce, ciphertext }
+}
+
+fn open(suite: CipherSuite, key: &[u8], secure_msg: &SecureMessage) -> Option<Vec<u8>> {
+    suite.cipher().open(key, &secure_msg.nonce, &secure_msg.ciphertext)
+}
+
+pub fn decrypt_message_from_bytes(bytes: &[u8]) -> Option<String> {
+    decrypt_message_with_nonce(bytes).map(|(plain, _)| plain)
+}
+
+/// decrypt_message_from_bytes that also hands back the nonce (transcripts key lines by it)
+pub fn decrypt_message_with_nonce(bytes: &[u8]) -> Option<(String, [u8; 12])> {
+    let channel = get_active_channel()?;
+
+    let decoded = bincode::serde::decode_from_slice::<SecureMessage, _>(
+        bytes,
+        bincode::config::standard(),
+    );
+
+    match decoded {
+        Ok((secure_msg, _)) => {
+            let plain = decrypt_message(&channel.key, &secure_msg)?;
+            accept_nonce(&secure_msg.nonce).then_some((plain, secure_msg.nonce))
+        }
+        Err(_e) => {
+            //eprintln!("❌ Failed to decode SecureMessage: {:?}", e);
+            None
+        }
+    }
+}
+
+// ===================== nonces =====================
+// nonce = sender id (4 random bytes) || counter (8 bytes, big endian). Both are the active
+// Channel's: the counter only goes up while we are in it, and every time it starts over (join,
+// rejoin, a new session key) the sender id is drawn again, so a key never sees a nonce twice.
+// Receivers keep the highest counter per sender id and a bitmap of the REORDER_WINDOW below
+// it, an older or repeated counter is a replay. Older builds send fully random nonces, each
+// reads as a sender id of its own and opens a new window (MAX_SENDERS bounds them), so a repeat
+// still lands in the window it opened. Only an all-zero id, which we never draw, goes to the
+// seen list.
+
+fn new_sender_id() -> [u8; 4] {
+    loop {
+        let mut id = [0u8; 4];
+        OsRng.try_fill_bytes(&mut id).expect("RNG failed");
+        if id != [0; 4] {
+            return id;
+        }
+    }
+}
+
+/// Nonce for the next message sealed with `key`
+pub fn next_nonce(key: &[u8; 32]) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    OsRng.try_fill_bytes(&mut nonce).expect("RNG failed");
+
+    let counter = ACTIVE_CHANNEL.get().and_then(|lock| {
+        let mut guard = lock.lock().unwrap();
+        let channel = guard.as_mut().filter(|ch| &ch.key == key)?;
+        channel.counter += 1;
+        Some((channel.sender, channel.counter))
+    });
+    // a key outside the active channel (nothing sent with it yet) keeps the fully random nonce
+    if let Some((sender, counter)) = counter {
+        nonce[..4].copy_from_slice(&sender);
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    }
+    nonce
+}
+
+const REORDER_WINDOW: u64 = 64; // UDP can swap messages, this far behind the highest is fine
+const MAX_SENDERS: usize = 1024;
+
+struct ReplayWindow {
+    high: u64,
+    seen: u64, // bit n: high - n arrived
+    used: Instant,
+}
+
+// (channel salt, sender id) -> its window. Kept across leaving and rejoining the same
+// channel, so what was said before can't be replayed into the next visit.
+type Windows = HashMap<([u8; 16], [u8; 4]), ReplayWindow>;
+static WINDOWS: Mutex<Option<Windows>> = Mutex::new(None);
+
+/// Record the nonce of a channel message that decrypted fine, false if it's a replay
+pub fn accept_nonce(nonce: &[u8]) -> bool {
+    let Ok(nonce) = <[u8; 12]>::try_from(nonce) else { return false; };
+    let sender: [u8; 4] = nonce[..4].try_into().unwrap();
+    let counter = u64::from_be_bytes(nonce[4..].try_into().unwrap());
+    let Some(channel) = get_active_channel() else { return false; };
+    if sender == [0; 4] {
+        return accept_random_nonce(&nonce);
+    }
+
+    let mut windows = WINDOWS.lock().unwrap();
+    let windows = windows.get_or_insert_with(HashMap::new);
+    let Some(window) = windows.get_mut(&(channel.salt, sender)) else {
+        if windows.len() >= MAX_SENDERS {
+            // only senders holding the key get here, the longest quiet one makes room
+            if let Some(oldest) = windows.iter().min_by_key(|(_, w)| w.used).map(|(k, _)| *k) {
+                windows.remove(&oldest);
+            }
+        }
+        windows.insert((channel.salt, sender), ReplayWindow { high: counter, seen: 1, used: Instant::now() });
+        return true;
+    };
+    window.used = Instant::now();
+    if counter > window.high {
+        let shift = counter - window.high;
+        window.seen = if shift >= REORDER_WINDOW { 0 } else { window.seen << shift };
+        window.seen |= 1;
+        window.high = counter;
+        return true;
+    }
+    let behind = window.high - counter;
+    if behind >= REORDER_WINDOW || window.seen & (1 << behind) != 0 {
+        return false;
+    }
+    window.seen |= 1 << behind;
+    true
+}
+
+// nonces without a counter (phones, older builds), the same one again inside the window is a
+// replay. Full means refusing, not forgetting one that could then be replayed.
+const REPLAY_WINDOW: Duration = Duration::from_secs(10 * 60);
+const MAX_SEEN_NONCES: usize = 4096;
+static SEEN_NONCES: Mutex<VecDeque<([u8; 12], Instant)>> = Mutex::new(VecDeque::new());
+
+/// Record a random nonce of a message that decrypted fine, false if we've had it already
+pub fn accept_random_nonce(nonce: &[u8]) -> bool {
+    let Ok(nonce) = <[u8; 12]>::try_from(nonce) else { return false; };
+    let mut seen = SEEN_NONCES.lock().unwrap();
+    while seen.front().is_some_and(|(_, at)| at.elapsed() > REPLAY_WINDOW) {
+        seen.pop_front();
+    }
+    if seen.len() >= MAX_SEEN_NONCES || seen.iter().any(|(n, _)| *n == nonce) {
+        return false;
+    }
+    seen.push_back((nonce, Instant::now()));
+    true
+}
+
+/// Generate PIN
+pub fn generate_PIN() -> i32 {
+    let PIN = rand::rng().random_range(10_000_000..100_000_000);
+    let lock = HOST_PIN.get_or_init(|| Mutex::new(None));
+    *lock.lock().unwrap() = Some(PIN);
+    //println!("Generated PIN: {PIN}");
+    PIN
+}
+
+/// Getting the PIN
+pub fn get_host_PIN() -> Option<i32> {
+    HOST_PIN.get().and_then(|lock| *lock.lock().unwrap())
+}
+
+pub fn get_host_PIN_string() -> String {
+    get_host_PIN().map(|p| p.to_string()).unwrap_or_else(|| "N/A".to_string())
+}
+
+pub fn get_masked_host_PIN() -> Option<String> {
+    get_host_PIN().map(|p| {
+        let s = p.to_string();
+        format!("****{}", &s[s.len()-4..])
+    })
+}
+
+/// New PIN and channel
+pub fn regenerate_PIN() -> Channel {
+    let mut guard = ACTIVE_CHANNEL.get_or_init(|| Mutex::new(None)).lock().unwrap();
+    if let Some(mut old) = guard.take() {
+        old.clear();
+        //println!("Old channel cleared!");
+    }
+
+    let PIN = generate_PIN();
+    let new_channel = Channel::new(PIN);
+    *guard = Some(new_channel.clone());
+    //println!("New channel created with PIN {PIN}");
+    new_channel
+}
+
+/// Helpers
+pub fn generate_salt() -> [u8; 16] {
+    let mut salt = [0u8; 16];
+    OsRng.try_fill_bytes(&mut salt).expect("RNG failed");
+    salt
+}
+
+pub fn generate_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    OsRng.try_fill_bytes(&mut key).expect("RNG failed");
+    key
+}
+
+pub fn derive_key(PIN: i32, salt: &[u8; 16]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(PIN.to_string().as_bytes(), salt, 100_000, &mut key);
+    key
+}
+
+/// Create a channel (host side)
+pub fn create_new_channel() -> Channel {
+    let PIN = generate_PIN();
+    let channel = Channel::new(PIN);
+
+    let mut guard = ACTIVE_CHANNEL.get_or_init(|| Mutex::new(None)).lock().unwrap();
+    *guard = Some(channel.clone());
+
+    //println!("✅ Channel created: PIN {PIN}");
+    channel
+}
+
+pub fn get_active_channel() -> Option<Channel> {
+    let val = ACTIVE_CHANNEL.get().and_then(|lock| lock.lock().unwrap().clone());
+    //println!("📦 get_active_channel: {:?}", val.is_some());
+    val
+}
+
+/// The host of the channel we joined, None while hosting, in public or joined from a bundle
+pub fn channel_host() -> Option<IpAddr> {
+    *JOINED_HOST.lock().unwrap()
+}
+
+/// Join with a key we already hold (session bundle), no PIN or announcement needed
+pub fn adopt_channel(channel: Channel) {
+    *JOINED_HOST.lock().unwrap() = None;
+    *ACTIVE_CHANNEL.get_or_init(|| Mutex::new(None)).lock().unwrap() = Some(channel);
+}
+
+/// Drop the active channel and clear the PIN. The app forgets its own per-channel state on top.
+pub fn destroy_channel() {
+    if let Some(lock) = ACTIVE_CHANNEL.get() {
+        let mut guard = lock.lock().unwrap();
+        if let Some(ch) = guard.as_mut() {
+            ch.clear();
+        }
+        *guard = None;
+    }
+
+    if let Some(lock) = HOST_PIN.get() {
+        *lock.lock().unwrap() = None;
+    }
+
+    crate::channel_files::clear();
+    *PENDING_KEX.lock().unwrap() = None;
+    *JOINED_HOST.lock().unwrap() = None;
+    SEEN_NONCES.lock().unwrap().clear();
+
+    //println!("🔓 Switched to Public: channel + PIN destroyed");
+}
+
+// ECLS + bincode(SecureMessage): the host closed the channel. The sealed text names the channel
+// by its salt, so only a key holder can send it and it can't close a later channel.
+pub const ECLS_MAGIC: &[u8; 4] = b"ECLS";
+const CLOSE_PREFIX: &[u8] = b"LANCHGO_CLOSE:";
+
+/// Host side, broadcast before the channel is destroyed
+pub fn build_close_packet(channel: &Channel) -> Option<Vec<u8>> {
+    let sealed = encrypt_bytes(&channel.key, &[CLOSE_PREFIX, &channel.salt[..]].concat());
+    let payload = bincode::serde::encode_to_vec(&sealed, bincode::config::standard()).ok()?;
+    let mut packet = Vec::from(ECLS_MAGIC as &[u8]);
+    packet.extend_from_slice(&payload);
+    Some(packet)
+}
+
+/// Joiner side: true when `bytes` (after ECLS) closes the channel we're in
+pub fn is_close_packet(bytes: &[u8]) -> bool {
+    let Some(channel) = get_active_channel() else { return false; };
+    let Ok((sealed, _)) = bincode::serde::decode_from_slice::<SecureMessage, _>(bytes, bincode::config::standard()) else { return false; };
+    decrypt_bytes(&channel.key, &sealed)
+        .is_some_and(|plain| plain.strip_prefix(CLOSE_PREFIX) == Some(&channel.salt[..]) && accept_nonce(&sealed.nonce))
+}
+
+/// Build announcement (host side)
+pub fn build_announcement(channel: &Channel) -> ChannelAnnounce {
+    let validation = seal(channel.suite, &channel.auth_key, VALIDATION_TEXT.as_bytes());
+    ChannelAnnounce {
+        salt: channel.salt,
+        validation,
+        suite: channel.suite,
+        key_exchange: channel.forward_secret(),
+        host: None,
+    }
+}
+
+// ANCH trailers right after the bincode payload: "PORT" + u16 LE, "SUIT" + suite id, then
+// "KEXV" + key exchange version when the channel has a session key.
+// decode_from_slice stops at the end of ChannelAnnounce so older builds just ignore them.
+const PORT_TRAILER: &[u8; 4] = b"PORT";
+const SUITE_TRAILER: &[u8; 4] = b"SUIT";
+const KEX_TRAILER: &[u8; 4] = b"KEXV";
+const KEX_VERSION: u8 = 1;
+
+/// Tell joiners which UDP port the host listens on, which cipher suite the channel uses and
+/// whether they have to ask for the session key
+pub fn append_announce_trailers(packet: &mut Vec<u8>, port: u16, channel: &Channel) {
+    packet.extend_from_slice(PORT_TRAILER);
+    packet.extend_from_slice(&port.to_le_bytes());
+    packet.extend_from_slice(SUITE_TRAILER);
+    packet.push(channel.suite.id());
+    if channel.forward_secret() {
+        packet.extend_from_slice(KEX_TRAILER);
+        packet.push(KEX_VERSION);
+    }
+}
+
+// the value after `tag`, trailers are walked in the order they're written
+fn announce_trailer<'a>(bytes: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let (_, used) = bincode::serde::decode_from_slice::<ChannelAnnounce, _>( bytes, bincode::config::standard(), ).ok()?;
+    let mut rest = &bytes[used..];
+    for (known, len) in [(PORT_TRAILER, 2), (SUITE_TRAILER, 1), (KEX_TRAILER, 1)] {
+        if rest.len() < 4 + len || &rest[..4] != known {
+            return None;
+        }
+        if known == tag {
+            return Some(&rest[4..4 + len]);
+        }
+        rest = &rest[4 + len..];
+    }
+    None
+}
+
+/// UDP port the announcing host is on, None for builds that don't send it
+pub fn announcement_port(bytes: &[u8]) -> Option<u16> {
+    announce_trailer(bytes, PORT_TRAILER).map(|v| u16::from_le_bytes([v[0], v[1]]))
+}
+
+/// Cipher suite of the announced channel, AES-256-GCM for builds that don't say
+pub fn announcement_suite(bytes: &[u8]) -> Option<CipherSuite> {
+    match announce_trailer(bytes, SUITE_TRAILER) {
+        Some(v) => CipherSuite::from_id(v[0]), // unknown suite: a newer build, we can't join it
+        None => Some(CipherSuite::Aes256Gcm),
+    }
+}
+
+/// The announced channel hands out a session key, None for one we can't do the exchange for
+pub fn announcement_key_exchange(bytes: &[u8]) -> Option<bool> {
+    match announce_trailer(bytes, KEX_TRAILER) {
+        Some(v) => (v[0] == KEX_VERSION).then_some(true), // a newer exchange, we can't join it
+        None => Some(false),
+    }
+}
+
+/// Decode & store full ChannelAnnounce only if it’s not already in the store
+pub fn store_announcement(bytes: &[u8], from: IpAddr) -> bool {
+    match bincode::serde::decode_from_slice::<ChannelAnnounce, _>( bytes, bincode::config::standard(), ) {
+        Ok((mut incoming, _)) => {
+            let Some(suite) = announcement_suite(bytes) else { return true; };
+            let Some(key_exchange) = announcement_key_exchange(bytes) else { return true; };
+            incoming.suite = suite;
+            incoming.key_exchange = key_exchange;
+            incoming.host = Some(from);
+            let store = ANNOUNCE_STORE.get_or_init(|| Mutex::new(Vec::new()));
+            let mut vec = store.lock().unwrap();
+
+            // 🔍 Check if an announcement with the same salt already exists
+            let already_exists = vec.iter().any(|existing| existing.salt == incoming.salt);
+
+            if !already_exists {
+                vec.push(incoming);
+                //println!("✅ Stored a new ChannelAnnounce, total stored = {}", vec.len());
+            } else {
+                //println!("⚠️ Skipped duplicate ChannelAnnounce");
+            }
+
+            true
+        }
+        Err(_e) => {
+            //eprintln!("⚠️ Failed to decode ChannelAnnounce: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Try to validate PIN against stored ChannelAnnounce list
+pub fn join_with_PIN(str_PIN: &str) -> bool {
+    let now = Instant::now();
+    //println!("{} this is in the secure channel a function called join_with_PIN", str_PIN);
+    let tracker = BRUTE_FORCE_STATE.get_or_init(|| Mutex::new(BruteForceTracker::new()));
+    let mut guard = tracker.lock().unwrap();
+
+    // 🚫 Check if locked
+    if let Some(until) = guard.locked_until {
+        if now < until {
+            return false;
+        } else {
+            guard.locked_until = None;
+            guard.failed_attempts = 0;
+        }
+    }
+
+    guard.last_attempt = now;
+
+    let Ok(in_PIN) = str_PIN.trim().parse::<i32>() else {
+        guard.failed_attempts += 1;
+        return false;
+    };
+    //println!("{} this is in the secure channel a function called join_with_PIN after triming it and doing stuff", str_PIN);
+    // 1) Check desktop ANNOUNCE_STORE first (existing behavior)
+    {
+        let store = ANNOUNCE_STORE.get_or_init(|| Mutex::new(Vec::new()));
+        let announcements = store.lock().unwrap();
+
+        if !announcements.is_empty() {
+            for ann in announcements.iter().rev() {
+                let key = derive_key(in_PIN, &ann.salt);
+                if key_is_good(&key, ann) {
+                    let channel = Channel::new_join_channel(&ann.salt, &key, ann.suite);
+                    // the PIN only got us as far as asking the host for the session key
+                    *PENDING_KEX.lock().unwrap() = ann.key_exchange.then(|| StaticSecret::from(generate_key()));
+                    *JOINED_HOST.lock().unwrap() = ann.host;
+                    let mut active = ACTIVE_CHANNEL
+                        .get_or_init(|| Mutex::new(None))
+                        .lock()
+                        .unwrap();
+                    *active = Some(channel);
+
+                    // reset brute-force tracker
+                    guard.failed_attempts = 0;
+                    guard.locked_until = None;
+                    return true;
+                }
+            }
+        }
+    }
+
+    // 2) If desktop announcement check failed, try phone announcements
+    //    (calls into phone_protocol which returns salt+key if matched)
+    if let Some((salt_arr, key_arr)) = crate::phone_protocol::try_find_matching_announce(in_PIN) {
+        // phones announce AES-256-GCM only
+        let channel = Channel::new_join_channel(&salt_arr, &key_arr, CipherSuite::Aes256Gcm);
+        *JOINED_HOST.lock().unwrap() = None;
+        let mut active = ACTIVE_CHANNEL
+            .get_or_init(|| Mutex::new(None))
+            .lock()
+            .unwrap();
+        *active = Some(channel);
+        //println!("{} this is in the secure channel a function called join_with_PIN this is when it tries the phone announcments", str_PIN);
+        // reset brute-force tracker
+        guard.failed_attempts = 0;
+        guard.locked_until = None;
+        return true;
+    }
+
+    // ❌ Failed PIN
+    guard.failed_attempts += 1;
+
+    if guard.failed_attempts >= 3 {
+        guard.locked_until = Some(Instant::now() + Duration::from_secs(10));
+    }
+    false
+}
+
+// Key exchange, once the PIN checked out against a KEXV announcement. The joiner broadcasts
+// EKXQ + bincode(SecureMessage) sealed with the PIN key: KEX_REQUEST_PREFIX || salt || its
+// ephemeral X25519 public key. The host answers it alone, EKXR + bincode(SecureMessage) sealed
+// with the PIN key: KEX_REPLY_PREFIX || joiner public || host public (fresh per request) ||
+// nonce || the session key sealed with SHA-256(KEX_LABEL || shared secret || salt || publics).
+// The ephemeral secrets are gone once it's done, so a PIN learned later opens the exchange but
+// not the session key, and not the traffic recorded with it.
+pub const EKXQ_MAGIC: &[u8; 4] = b"EKXQ";
+pub const EKXR_MAGIC: &[u8; 4] = b"EKXR";
+const KEX_REQUEST_PREFIX: &[u8] = b"LANCHGO_KEXQ:";
+const KEX_REPLY_PREFIX: &[u8] = b"LANCHGO_KEXR:";
+const KEX_LABEL: &[u8] = b"LANCHGO_KEX1";
+
+// joiner side, the secret half of the key we asked with until the host answers
+static PENDING_KEX: Mutex<Option<StaticSecret>> = Mutex::new(None);
+
+/// Joined but still waiting for the session key, nothing we send or get makes sense yet
+pub fn key_exchange_pending() -> bool {
+    PENDING_KEX.lock().unwrap().is_some()
+}
+
+fn kex_packet(magic: &[u8; 4], sealed: &SecureMessage) -> Option<Vec<u8>> {
+    let payload = bincode::serde::encode_to_vec(sealed, bincode::config::standard()).ok()?;
+    let mut packet = Vec::from(magic as &[u8]);
+    packet.extend_from_slice(&payload);
+    Some(packet)
+}
+
+fn kex_open(channel: &Channel, bytes: &[u8], prefix: &[u8]) -> Option<Vec<u8>> {
+    let (sealed, _) = bincode::serde::decode_from_slice::<SecureMessage, _>(bytes, bincode::config::standard()).ok()?;
+    let plain = open(channel.suite, &channel.auth_key, &sealed)?;
+    plain.strip_prefix(prefix).map(<[u8]>::to_vec)
+}
+
+fn wrap_key(shared: &[u8; 32], salt: &[u8; 16], joiner: &[u8; 32], host: &[u8; 32]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(KEX_LABEL)
+        .chain_update(shared)
+        .chain_update(salt)
+        .chain_update(joiner)
+        .chain_update(host)
+        .finalize()
+        .into()
+}
+
+/// Joiner side: the EKXQ to broadcast, None when there's no exchange waiting
+pub fn key_request() -> Option<Vec<u8>> {
+    let channel = get_active_channel()?;
+    let public = PublicKey::from(PENDING_KEX.lock().unwrap().as_ref()?);
+    let sealed = seal(channel.suite, &channel.auth_key, &[KEX_REQUEST_PREFIX, &channel.salt[..], public.as_bytes()].concat());
+    kex_packet(EKXQ_MAGIC, &sealed)
+}
+
+/// Host side: the EKXR for `bytes` (after EKXQ), None unless it's for our channel
+pub fn answer_key_request(bytes: &[u8]) -> Option<Vec<u8>> {
+    let channel = get_active_channel().filter(Channel::forward_secret)?;
+    let plain = kex_open(&channel, bytes, KEX_REQUEST_PREFIX)?;
+    let (salt, joiner) = plain.split_at_checked(16)?;
+    let joiner: [u8; 32] = joiner.try_into().ok()?;
+    if salt != channel.salt {
+        return None;
+    }
+    let secret = StaticSecret::from(generate_key());
+    let host = PublicKey::from(&secret);
+    let shared = secret.diffie_hellman(&PublicKey::from(joiner));
+    if !shared.was_contributory() {
+        return None;
+    }
+    let mut wrap = wrap_key(shared.as_bytes(), &channel.salt, &joiner, host.as_bytes());
+    let wrapped = seal(channel.suite, &wrap, &channel.key);
+    wrap.zeroize();
+    let reply = [KEX_REPLY_PREFIX, &joiner[..], host.as_bytes(), &wrapped.nonce[..], &wrapped.ciphertext].concat();
+    kex_packet(EKXR_MAGIC, &seal(channel.suite, &channel.auth_key, &reply))
+}
+
+/// Joiner side: take the session key from `bytes` (after EKXR), true once we have it
+pub fn finish_key_exchange(bytes: &[u8]) -> bool {
+    let Some(channel) = get_active_channel() else { return false; };
+    let mut pending = PENDING_KEX.lock().unwrap();
+    let Some(secret) = pending.as_ref() else { return false; };
+    let Some(plain) = kex_open(&channel, bytes, KEX_REPLY_PREFIX) else { return false; };
+    if plain.len() < 32 + 32 + 12 {
+        return false;
+    }
+    let (joiner, rest) = plain.split_at(32);
+    let (host, rest) = rest.split_at(32);
+    let (nonce, ciphertext) = rest.split_at(12);
+    let ours = PublicKey::from(secret);
+    // another joiner's answer
+    if joiner != ours.as_bytes() {
+        return false;
+    }
+    let host: [u8; 32] = host.try_into().unwrap();
+    let shared = secret.diffie_hellman(&PublicKey::from(host));
+    if !shared.was_contributory() {
+        return false;
+    }
+    let mut wrap = wrap_key(shared.as_bytes(), &channel.salt, ours.as_bytes(), &host);
+    let wrapped = SecureMessage { nonce: nonce.try_into().unwrap(), ciphertext: ciphertext.to_vec() };
+    let session = open(channel.suite, &wrap, &wrapped).and_then(|k| <[u8; 32]>::try_from(k.as_slice()).ok());
+    wrap.zeroize();
+    let Some(session) = session else { return false; };
+
+    let mut active = ACTIVE_CHANNEL.get_or_init(|| Mutex::new(None)).lock().unwrap();
+    match active.as_mut().filter(|ch| ch.salt == channel.salt) {
+        Some(ch) => {
+            ch.key = session;
+            ch.counter = 0;
+            ch.sender = new_sender_id();
+        }
+        None => return false,
+    }
+    *pending = None;
+    true
+}
+
+/// Validate derived key by decrypting ChannelAnnounce.validation
+fn key_is_good(key: &[u8; 32], announce: &ChannelAnnounce) -> bool {
+    if let Some(plaintext) = open(announce.suite, key, &announce.validation) {
+        if plaintext == VALIDATION_TEXT.as_bytes() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the active channel and the windows are global, one test at a time
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn fresh_channel() -> Channel {
+        let channel = Channel::new_join_channel(&generate_salt(), &generate_key(), CipherSuite::preferred());
+        adopt_channel(channel.clone());
+        *WINDOWS.lock().unwrap() = None;
+        channel
+    }
+
+    fn nonce(sender: u32, counter: u64) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[..4].copy_from_slice(&sender.to_be_bytes());
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+        nonce
+    }
+
+    #[test]
+    fn reorder_inside_the_window() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        fresh_channel();
+        assert!(accept_nonce(&nonce(1, 100)));
+        assert!(accept_nonce(&nonce(1, 102)));
+        assert!(accept_nonce(&nonce(1, 101)));
+        assert!(accept_nonce(&nonce(1, 102 - (REORDER_WINDOW - 1))));
+    }
+
+    #[test]
+    fn repeat_is_a_replay() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        fresh_channel();
+        assert!(accept_nonce(&nonce(1, 5)));
+        assert!(!accept_nonce(&nonce(1, 5)));
+        assert!(accept_nonce(&nonce(1, 7)));
+        assert!(!accept_nonce(&nonce(1, 5)));
+        assert!(!accept_nonce(&nonce(1, 7)));
+        // the same counter from another sender is its own message
+        assert!(accept_nonce(&nonce(2, 5)));
+    }
+
+    #[test]
+    fn jump_past_the_window() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        fresh_channel();
+        assert!(accept_nonce(&nonce(1, 100)));
+        assert!(accept_nonce(&nonce(1, 100 + REORDER_WINDOW)));
+        // now too far behind, whether it was seen or not
+        assert!(!accept_nonce(&nonce(1, 100)));
+        assert!(!accept_nonce(&nonce(1, 99)));
+        // the bitmap started over, the slot right behind the new high is free
+        assert!(accept_nonce(&nonce(1, 101)));
+        assert!(!accept_nonce(&nonce(1, 101)));
+        assert!(accept_nonce(&nonce(1, 1000)));
+        assert!(!accept_nonce(&nonce(1, 100 + REORDER_WINDOW)));
+    }
+
+    #[test]
+    fn full_table_drops_the_quietest_sender() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let channel = fresh_channel();
+        for sender in 1..=MAX_SENDERS as u32 {
+            assert!(accept_nonce(&nonce(sender, 1)));
+        }
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(accept_nonce(&nonce(1, 2))); // sender 1 is the most recent again
+        assert!(accept_nonce(&nonce(MAX_SENDERS as u32 + 1, 1)));
+
+        let windows = WINDOWS.lock().unwrap();
+        let windows = windows.as_ref().unwrap();
+        assert_eq!(windows.len(), MAX_SENDERS);
+        assert!(windows.contains_key(&(channel.salt, 1u32.to_be_bytes())));
+        assert!(windows.contains_key(&(channel.salt, (MAX_SENDERS as u32 + 1).to_be_bytes())));
+    }
+}
@@ -0,0 +1,96 @@
+// Session handoff to a machine that has no network yet (/exportsession, /importsession): the
+// secure channel key, the contact book and the settings in one passphrase-protected file,
+// carried over on a USB stick. LCSB + version(1) + salt(16) + nonce(12) + AES-256-GCM(JSON),
+// keyed with PBKDF2-SHA256 of the passphrase, so the file is only as strong as the passphrase.
+// The JSON itself is the app's, this is the envelope and the channel inside it.
+
+use std::io;
+
+use pbkdf2::pbkdf2_hmac;
+use rand::{rngs::OsRng, TryRngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+use crate::cipher_suite::CipherSuite;
+use crate::secure_channel_code::{self, Channel};
+
+const MAGIC: &[u8; 4] = b"LCSB";
+const VERSION: u8 = 1;
+const ROUNDS: u32 = 600_000;
+const HEADER_LEN: usize = 4 + 1 + 16 + 12;
+pub const MIN_PASSPHRASE: usize = 8;
+pub const EXTENSION: &str = "lcsb";
+
+#[derive(Serialize, Deserialize)]
+pub struct BundledChannel {
+    salt: [u8; 16],
+    key: [u8; 32],
+    suite: u8, // CipherSuite::id
+    #[serde(default)]
+    auth_key: Option<[u8; 32]>, // the PIN key when `key` is a session key, phones talk with it
+}
+
+impl BundledChannel {
+    /// The channel we're in, None outside secure mode
+    pub fn active() -> Option<Self> {
+        secure_channel_code::get_active_channel().map(|ch| BundledChannel { salt: ch.salt, key: ch.key, suite: ch.suite.id(), auth_key: ch.forward_secret().then_some(ch.auth_key) })
+    }
+
+    /// A channel to join, fails for a suite this build doesn't know
+    pub fn into_channel(self) -> io::Result<Channel> {
+        let suite = CipherSuite::from_id(self.suite).ok_or_else(|| invalid("unknown cipher suite"))?;
+        let mut channel = Channel::new_join_channel(&self.salt, &self.key, suite);
+        if let Some(auth_key) = self.auth_key {
+            channel.auth_key = auth_key;
+        }
+        Ok(channel)
+    }
+}
+
+fn derive(passphrase: &str, salt: &[u8; 16]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, ROUNDS, &mut key);
+    key
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// The file bytes for `plain` under `passphrase`, `plain` is wiped
+pub fn seal(passphrase: &str, mut plain: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    OsRng.try_fill_bytes(&mut salt).expect("RNG failed");
+    OsRng.try_fill_bytes(&mut nonce).expect("RNG failed");
+    let mut key = derive(passphrase, &salt);
+    let sealed = CipherSuite::Aes256Gcm.cipher().seal(&key, &nonce, &plain);
+    key.zeroize();
+    plain.zeroize();
+    let sealed = sealed.ok_or_else(|| io::Error::other("encryption failed"))?;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + sealed.len());
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// The plaintext of a bundle file, the caller wipes it once parsed
+pub fn open(bytes: &[u8], passphrase: &str) -> io::Result<Vec<u8>> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err(invalid("not a session bundle"));
+    }
+    if bytes[4] != VERSION {
+        return Err(invalid("bundle from a newer LanChGo"));
+    }
+    let salt: [u8; 16] = bytes[5..21].try_into().unwrap();
+    let nonce: [u8; 12] = bytes[21..HEADER_LEN].try_into().unwrap();
+    let mut key = derive(passphrase, &salt);
+    let plain = CipherSuite::Aes256Gcm.cipher().open(&key, &nonce, &bytes[HEADER_LEN..]);
+    key.zeroize();
+    plain.ok_or_else(|| invalid("wrong passphrase or damaged file"))
+}
@@ -0,0 +1,595 @@
+use std::{ future::Future, io::{self, SeekFrom}, net::SocketAddr, path::PathBuf, time::{Duration, Instant}, };
+use tokio::{ fs::{self, OpenOptions}, io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter}, net::TcpStream, };
+
+use crate::bandwidth::{self, Kind};
+use crate::file_transfer_protocol;
+use crate::rate_limit;
+use crate::sealed_stream::{self, Source};
+use sha2::{Digest, Sha256};
+
+// "OK " + 64 hex chars, anything much longer isn't a status line
+const MAX_STATUS_LINE: usize = 80;
+
+async fn with_timeout<T>(limit: Duration, fut: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    tokio::time::timeout(limit, fut)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Peer stalled"))?
+}
+
+// connect (small retry helps on Wi-Fi), attempts and pause from Config.transfer
+async fn connect_with_retry(addr: SocketAddr) -> io::Result<TcpStream> {
+    let (attempts, backoff) = file_transfer_protocol::connect_retry();
+    let mut last_err: Option<io::Error> = None;
+    for _ in 0..attempts {
+        match TcpStream::connect(addr).await {
+            Ok(s) => return Ok(s),
+            Err(e) => {
+                last_err = Some(e);
+                tokio::time::sleep(backoff).await;
+            }
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::other("connect failed")))
+}
+
+// FOFR out, FOFS header back: (size it announces, where the bytes start). With `resume_from`
+// the request asks to continue a .part of that length (see tcp_file_server, Resume), with
+// `key` it asks for the bytes sealed with that channel key (Sealed)
+async fn request_offer(stream: &mut TcpStream, offer_id: [u8; 16], resume_from: Option<u64>, key: Option<&[u8; 32]>, read_limit: Duration, write_limit: Duration) -> io::Result<(u64, u64)> {
+    use crate::file_transfer_protocol::{FILE_PROTOCOL_VERSION, FOFR_RESUME_VERSION, FOFR_SEALED_VERSION};
+    let ver = match (key, resume_from) {
+        (Some(_), _) => FOFR_SEALED_VERSION,
+        (None, Some(_)) => FOFR_RESUME_VERSION,
+        (None, None) => FILE_PROTOCOL_VERSION,
+    };
+
+    // ---- request
+    let mut request = Vec::with_capacity(29);
+    request.extend_from_slice(b"FOFR");
+    request.push(ver);
+    request.extend_from_slice(&offer_id);
+    if ver != FILE_PROTOCOL_VERSION {
+        request.extend_from_slice(&resume_from.unwrap_or(0).to_le_bytes());
+    }
+    if let Some(key) = key {
+        let proof = crate::channel_files::request_proof(key, &offer_id);
+        request.extend_from_slice(&(proof.len() as u16).to_le_bytes());
+        request.extend_from_slice(&proof);
+    }
+    with_timeout(write_limit, stream.write_all(&request)).await?;
+    // No need to flush here; TCP will send. (Flushing can add stalls on some stacks.)
+
+    // ---- response header
+    let mut magic = [0u8; 4];
+    with_timeout(read_limit, stream.read_exact(&mut magic)).await?;
+    if &magic != b"FOFS" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Bad FOFS magic"));
+    }
+
+    let mut ver_back = [0u8; 1];
+    with_timeout(read_limit, stream.read_exact(&mut ver_back)).await?;
+    if ver_back[0] != ver {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Protocol version mismatch",
+        ));
+    }
+
+    let mut size_bytes = [0u8; 8];
+    with_timeout(read_limit, stream.read_exact(&mut size_bytes)).await?;
+    let mut start = 0u64;
+    if ver != FILE_PROTOCOL_VERSION {
+        let mut start_bytes = [0u8; 8];
+        with_timeout(read_limit, stream.read_exact(&mut start_bytes)).await?;
+        start = u64::from_le_bytes(start_bytes);
+    }
+    Ok((u64::from_le_bytes(size_bytes), start))
+}
+
+// a sealed stream repeats the FOFS size and start in its first chunk, where they can't be altered
+async fn check_sealed_header(source: &mut Source, stream: &mut TcpStream, header: (u64, u64), read_limit: Duration) -> io::Result<()> {
+    match source.begin(stream, read_limit).await? {
+        Some(sealed) if sealed != header => Err(io::Error::new(io::ErrorKind::InvalidData, "FOFS header doesn't match the sealed one")),
+        _ => Ok(()),
+    }
+}
+
+// while a secure channel is active, FOFR downloads ask for the file sealed with its key
+fn channel_key() -> Option<[u8; 32]> {
+    crate::secure_channel_code::get_active_channel().map(|ch| ch.key)
+}
+
+// a sender outside our channel (or too old to seal) just hangs up on a sealed request
+fn sealed_refused(e: io::Error, key: &Option<[u8; 32]>) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted if key.is_some() => io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the sender can't encrypt it for your channel, leave the channel to download it in the clear",
+        ),
+        _ => e,
+    }
+}
+
+/// `sender` is the address the offer came from, only its IP (and IPv6 scope id) is used.
+/// A .part left by an earlier attempt at the same offer is continued, not started over.
+/// Ok(true) when the file was checked against the hash the sender announced.
+pub async fn download_offer( sender: SocketAddr, tcp_port: u16, offer_id: [u8; 16], save_path: PathBuf, mut on_progress: impl FnMut(u64, u64) + Send, ) -> io::Result<bool> {
+    let mut addr = sender;
+    addr.set_port(tcp_port);
+    let (read_limit, write_limit) = file_transfer_protocol::client_timeouts();
+
+    // ---- download into .part file (atomic publish), named by offer so a retry finds it
+    let part_path = file_transfer_protocol::resume_part_path(&save_path, &offer_id);
+    let have = fs::metadata(&part_path).await.map(|m| m.len()).unwrap_or(0);
+    let key = channel_key();
+
+    let mut stream = connect_with_retry(addr).await?;
+    let _ = stream.set_nodelay(true); // header request benefits a bit
+
+    let (total, start) = match request_offer(&mut stream, offer_id, (have > 0).then_some(have), key.as_ref(), read_limit, write_limit).await {
+        Ok(answer) => answer,
+        // an older sender hangs up on the resume request, start over the old way
+        Err(_) if have > 0 => {
+            stream = connect_with_retry(addr).await?;
+            let _ = stream.set_nodelay(true);
+            request_offer(&mut stream, offer_id, None, key.as_ref(), read_limit, write_limit)
+                .await
+                .map_err(|e| sealed_refused(e, &key))?
+        }
+        Err(e) => return Err(sealed_refused(e, &key)),
+    };
+    let mut source = Source::new(key, offer_id);
+    check_sealed_header(&mut source, &mut stream, (total, start), read_limit).await?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(start == 0)
+        .write(true)
+        .open(&part_path)
+        .await?;
+    if start > 0 {
+        file.set_len(start).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+    }
+    // no pre-allocation: the length of the .part is where a resume picks up
+
+    // Big buffered writer for fewer syscalls
+    let mut out = BufWriter::with_capacity(1024 * 1024, file);
+
+    // Bigger read buffer (1MB)
+    let mut buf = vec![0u8; 1024 * 1024];
+
+    let mut got = start;
+    on_progress(got, total);
+
+    // Throttle progress updates (UI can be the bottleneck)
+    let mut last_ui = Instant::now();
+    const UI_INTERVAL: Duration = Duration::from_millis(150);
+
+    let res: io::Result<bool> = async {
+        while got < total {
+            let want = (total - got).min(buf.len() as u64) as usize;
+            let n = source.read(&mut stream, &mut buf[..want], read_limit).await?;
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Connection closed early",
+                ));
+            }
+
+            out.write_all(&buf[..n]).await?;
+            bandwidth::record_received(Kind::File, sender.ip(), n);
+            rate_limit::download(n).await;
+            got += n as u64;
+
+            if last_ui.elapsed() >= UI_INTERVAL || got == total {
+                last_ui = Instant::now();
+                on_progress(got, total);
+            }
+        }
+
+        // Guard: must match exactly
+        if got != total {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("incomplete file: got {} of {}", got, total),
+            ));
+        }
+        source.finish(&mut stream, read_limit).await?;
+
+        let needs_sync = matches!(
+            save_path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase().as_str(),"iso" | "img" | "bin" | "dmg" | "vhd" | "vhdx" | "vmdk"
+        );
+
+        out.flush().await?; // ensure buffered bytes hit the OS
+        if needs_sync { out.get_ref().sync_all().await?; }
+        // ⚠️ sync_all is very slow on Windows; only enable if you *need* durability guarantees.
+        // If you want it as an option:
+        // out.get_ref().sync_all()?;
+
+        // hashed from disk, a resumed .part has bytes from an earlier run too
+        let verified = match file_transfer_protocol::offer_hash(&offer_id) {
+            Some(expected) => {
+                let path = part_path.clone();
+                let digest = tokio::task::spawn_blocking(move || file_transfer_protocol::sha256_file(&path))
+                    .await
+                    .map_err(|e| io::Error::other(e.to_string()))??;
+                if digest != expected {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, file_transfer_protocol::CHECKSUM_MISMATCH));
+                }
+                true
+            }
+            None => false,
+        };
+
+        // Atomic “publish”
+        fs::rename(&part_path, &save_path).await?;
+        Ok(verified)
+    }
+    .await;
+
+    // a dropped connection keeps what arrived for the next try, bad data starts over
+    if let Err(e) = &res {
+        if file_transfer_protocol::is_resumable(e) {
+            let _ = out.flush().await;
+        } else {
+            let _ = fs::remove_file(&part_path).await;
+        }
+    }
+    res
+}
+
+/// Folder offer: the manifest comes first, then every file back to back. Everything lands in
+/// "<save_dir>.part" and is renamed to `save_dir` once the last file is complete.
+pub async fn download_folder_offer( sender: SocketAddr, tcp_port: u16, offer_id: [u8; 16], save_dir: PathBuf, mut on_progress: impl FnMut(u64, u64) + Send, ) -> io::Result<()> {
+    let mut addr = sender;
+    addr.set_port(tcp_port);
+    let mut stream = connect_with_retry(addr).await?;
+    let (read_limit, write_limit) = file_transfer_protocol::client_timeouts();
+    let _ = stream.set_nodelay(true);
+
+    let key = channel_key();
+    let (total, start) = request_offer(&mut stream, offer_id, None, key.as_ref(), read_limit, write_limit)
+        .await
+        .map_err(|e| sealed_refused(e, &key))?;
+    let mut source = Source::new(key, offer_id);
+    check_sealed_header(&mut source, &mut stream, (total, start), read_limit).await?;
+
+    // ---- manifest
+    let mut len = [0u8; 4];
+    source.read_exact(&mut stream, &mut len, read_limit).await?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > file_transfer_protocol::MAX_MANIFEST_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Folder manifest too large"));
+    }
+    let mut encoded = vec![0u8; len];
+    source.read_exact(&mut stream, &mut encoded, read_limit).await?;
+    let (manifest, _): (file_transfer_protocol::FolderManifest, usize) = bincode::serde::decode_from_slice(&encoded, bincode::config::standard())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    if manifest.entries.iter().map(|e| e.size).sum::<u64>() != total {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Folder manifest doesn't add up"));
+    }
+
+    let part_dir = PathBuf::from(format!("{}.part", save_dir.display()));
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut got = 0u64;
+    let mut last_ui = Instant::now();
+    const UI_INTERVAL: Duration = Duration::from_millis(150);
+
+    let res: io::Result<()> = async {
+        fs::create_dir_all(&part_dir).await?;
+        for entry in &manifest.entries {
+            // a path like "../x" must not leave the download folder
+            let target = file_transfer_protocol::manifest_target(&part_dir, &entry.path)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Unsafe path in folder: {}", entry.path)))?;
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            let file = OpenOptions::new().create(true).truncate(true).write(true).open(&target).await?;
+            let mut out = BufWriter::with_capacity(1024 * 1024, file);
+
+            let mut left = entry.size;
+            while left > 0 {
+                let want = left.min(buf.len() as u64) as usize;
+                let n = source.read(&mut stream, &mut buf[..want], read_limit).await?;
+                if n == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed early"));
+                }
+                out.write_all(&buf[..n]).await?;
+                bandwidth::record_received(Kind::File, sender.ip(), n);
+                rate_limit::download(n).await;
+                left -= n as u64;
+                got += n as u64;
+
+                if last_ui.elapsed() >= UI_INTERVAL || got == total {
+                    last_ui = Instant::now();
+                    on_progress(got, total);
+                }
+            }
+            out.flush().await?;
+        }
+        source.finish(&mut stream, read_limit).await?;
+
+        fs::rename(&part_dir, &save_dir).await?;
+        Ok(())
+    }
+    .await;
+
+    if res.is_err() { let _ = fs::remove_dir_all(&part_dir).await; }
+    res
+}
+
+/// Mobile (Flutter) TCP download:
+/// - connect to sender ip:tcp_port
+/// - send "{offer_id_hex}\n", or "{offer_id_hex} sha256\n" when the offer said the phone can (`checksum`)
+/// - expect "OK\n" / "OK {sha256_hex}\n" (or "ERR\n")
+/// - then stream raw bytes until EOF, checked against the hash when there is one
+///
+/// `on_progress(done, total)` is caller-defined. Since the mobile stream has no size header,
+/// pass the expected total from the offer at the call site (e.g. offer.size).
+pub async fn download_offer_mobile( sender: SocketAddr, tcp_port: u16, offer_id_hex: &str, checksum: bool, save_path: PathBuf, mut on_progress: impl FnMut(u64, u64) + Send, ) -> io::Result<()> {
+    let mut addr = sender;
+    addr.set_port(tcp_port);
+    let mut stream = connect_with_retry(addr).await?;
+    let (read_limit, write_limit) = file_transfer_protocol::client_timeouts();
+    let _ = stream.set_nodelay(true);
+
+    // ---- request: "<offer_id_hex>[ sha256]\n"
+    if offer_id_hex.len() != 32 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "offer_id_hex must be 32 hex chars",
+        ));
+    }
+
+    let request = if checksum {
+        format!("{} {}\n", offer_id_hex, file_transfer_protocol::MOBILE_CHECKSUM_TOKEN)
+    } else {
+        format!("{}\n", offer_id_hex)
+    };
+    with_timeout(write_limit, stream.write_all(request.as_bytes())).await?;
+
+    // ---- response: "OK\n", "OK <sha256 hex>\n" or "ERR\n", read a byte at a time so no file data is eaten
+    let mut status = Vec::new();
+    loop {
+        let mut byte = [0u8; 1];
+        with_timeout(read_limit, stream.read_exact(&mut byte)).await?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        status.push(byte[0]);
+        if status.len() > MAX_STATUS_LINE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "mobile server: status line too long"));
+        }
+    }
+    let status = String::from_utf8_lossy(&status).into_owned();
+
+    let expected = match status.split_once(' ') {
+        None if status == "OK" => None,
+        Some(("OK", hash)) if hash.len() == 64 => Some(hash.to_ascii_lowercase()),
+        _ if status == "ERR" => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "mobile server: offer not found (ERR)",
+            ));
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("mobile server: bad status header: {:?}", status),
+            ));
+        }
+    };
+    let mut hasher = Sha256::new();
+
+    // ---- download into .part (atomic publish)
+    let part_path = save_path.with_extension("part");
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&part_path)
+        .await?;
+
+    let mut out = BufWriter::with_capacity(1024 * 1024, file);
+    let mut buf = vec![0u8; 1024 * 1024];
+
+    let mut got: u64 = 0;
+
+    let mut last_ui = Instant::now();
+    const UI_INTERVAL: Duration = Duration::from_millis(150);
+
+    let res: io::Result<()> = async {
+        loop {
+            let n = with_timeout(read_limit, stream.read(&mut buf)).await?;
+            if n == 0 {
+                break; // EOF
+            }
+
+            out.write_all(&buf[..n]).await?;
+            hasher.update(&buf[..n]);
+            bandwidth::record_received(Kind::File, sender.ip(), n);
+            rate_limit::download(n).await;
+            got += n as u64;
+
+            if last_ui.elapsed() >= UI_INTERVAL {
+                last_ui = Instant::now();
+                on_progress(got, 0); // caller can substitute total (offer.size)
+            }
+        }
+
+        out.flush().await?;
+        if let Some(expected) = &expected {
+            if file_transfer_protocol::digest_to_hex(&hasher.finalize()) != *expected {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, file_transfer_protocol::CHECKSUM_MISMATCH));
+            }
+        }
+        fs::rename(&part_path, &save_path).await?;
+        Ok(())
+    }
+    .await;
+
+    if res.is_err() {
+        let _ = fs::remove_file(&part_path).await;
+    }
+
+    on_progress(got, got);
+    res
+}
+
+/// Secure channel download (FOFE): chunks arrive sealed with the channel key,
+/// and the sender gets a FOFK once the file is published so it can tick us off
+pub async fn download_channel_offer( sender: SocketAddr, tcp_port: u16, offer_id: [u8; 16], key: [u8; 32], save_path: PathBuf, mut on_progress: impl FnMut(u64, u64) + Send, ) -> io::Result<()> {
+    use crate::tcp_file_server::{FOFE_MAGIC, FOFK_MAGIC};
+
+    let mut addr = sender;
+    addr.set_port(tcp_port);
+    let mut stream = connect_with_retry(addr).await?;
+    let (read_limit, write_limit) = file_transfer_protocol::client_timeouts();
+    let _ = stream.set_nodelay(true);
+
+    // ---- request
+    let proof = crate::channel_files::request_proof(&key, &offer_id);
+    let mut request = Vec::with_capacity(23 + proof.len());
+    request.extend_from_slice(FOFE_MAGIC);
+    request.push(crate::file_transfer_protocol::FILE_PROTOCOL_VERSION);
+    request.extend_from_slice(&offer_id);
+    request.extend_from_slice(&(proof.len() as u16).to_le_bytes());
+    request.extend_from_slice(&proof);
+    with_timeout(write_limit, stream.write_all(&request)).await?;
+
+    // ---- response header (the sender just closes on a bad proof)
+    let mut header = [0u8; 13];
+    with_timeout(read_limit, stream.read_exact(&mut header)).await?;
+    if &header[..4] != b"FOFS" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Bad FOFS magic"));
+    }
+    if header[4] != crate::file_transfer_protocol::FILE_PROTOCOL_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Protocol version mismatch"));
+    }
+    let total = u64::from_le_bytes(header[5..13].try_into().unwrap());
+    let mut source = Source::new(Some(key), offer_id);
+    check_sealed_header(&mut source, &mut stream, (total, 0), read_limit).await?;
+
+    let part_path = save_path.with_extension("part");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&part_path)
+        .await?;
+    let mut out = BufWriter::with_capacity(1024 * 1024, file);
+
+    let mut got = 0u64;
+    let mut last_ui = Instant::now();
+    const UI_INTERVAL: Duration = Duration::from_millis(150);
+
+    let res: io::Result<()> = async {
+        let mut buf = vec![0u8; sealed_stream::CHUNK];
+        while got < total {
+            let want = (total - got).min(buf.len() as u64) as usize;
+            let n = source.read(&mut stream, &mut buf[..want], read_limit).await?;
+            if n == 0 {
+                break;
+            }
+            bandwidth::record_received(Kind::File, sender.ip(), n);
+            rate_limit::download(n).await;
+            out.write_all(&buf[..n]).await?;
+            got += n as u64;
+
+            if last_ui.elapsed() >= UI_INTERVAL || got == total {
+                last_ui = Instant::now();
+                on_progress(got, total);
+            }
+        }
+
+        if got != total {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("incomplete file: got {} of {}", got, total),
+            ));
+        }
+        source.finish(&mut stream, read_limit).await?;
+
+        out.flush().await?;
+        fs::rename(&part_path, &save_path).await?;
+
+        // best effort, the file is ours either way
+        let _ = with_timeout(write_limit, stream.write_all(FOFK_MAGIC)).await;
+        Ok(())
+    }
+    .await;
+
+    if res.is_err() { let _ = fs::remove_file(&part_path).await; }
+    res
+}
+
+// "OK", "NO", "DONE"... one line, read a byte at a time so nothing after it is eaten
+async fn read_status_line(stream: &mut TcpStream, limit: Duration) -> io::Result<String> {
+    let mut status = Vec::new();
+    loop {
+        let mut byte = [0u8; 1];
+        with_timeout(limit, stream.read_exact(&mut byte)).await?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        status.push(byte[0]);
+        if status.len() > MAX_STATUS_LINE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "status line too long"));
+        }
+    }
+    Ok(String::from_utf8_lossy(&status).into_owned())
+}
+
+/// How long a push waits for someone to click on the other end
+pub const PUSH_ACCEPT_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Push one of our files straight to a peer (DPUSH, see phone_push.rs), the other way round
+/// from an offer. Waits for them to accept, Ok(false) when they declined or didn't answer.
+pub async fn push_file(target: SocketAddr, path: PathBuf) -> io::Result<bool> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "file name isn't valid UTF-8"))?
+        .to_string();
+    let size = fs::metadata(&path).await?.len();
+    let hash_path = path.clone();
+    let digest = tokio::task::spawn_blocking(move || file_transfer_protocol::sha256_file(&hash_path))
+        .await
+        .map_err(io::Error::other)??;
+    let (read_limit, write_limit) = file_transfer_protocol::client_timeouts();
+
+    let mut stream = connect_with_retry(target).await?;
+    let header = format!("DPUSH {} {} {}\n", size, file_transfer_protocol::digest_to_hex(&digest), name);
+    with_timeout(write_limit, stream.write_all(header.as_bytes())).await?;
+
+    // someone has to click on the other end, give them the whole prompt time
+    match read_status_line(&mut stream, PUSH_ACCEPT_TIMEOUT + read_limit).await?.as_str() {
+        "OK" => {}
+        "NO" => return Ok(false),
+        status => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad push answer: {:?}", status))),
+    }
+
+    let mut file = fs::File::open(&path).await?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut sent = 0u64;
+    while sent < size {
+        let want = buf.len().min((size - sent) as usize);
+        let n = file.read(&mut buf[..want]).await?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file shrank while sending"));
+        }
+        with_timeout(write_limit, stream.write_all(&buf[..n])).await?;
+        bandwidth::record_sent(Kind::File, Some(target.ip()), n);
+        rate_limit::upload(n).await;
+        sent += n as u64;
+    }
+
+    // DONE once the file is saved and its checksum matched
+    let saved = read_status_line(&mut stream, read_limit).await? == "DONE";
+    if !saved {
+        return Err(io::Error::other("the peer couldn't save the file"));
+    }
+    Ok(true)
+}
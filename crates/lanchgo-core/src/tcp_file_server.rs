@@ -0,0 +1,513 @@
+use std::{ future::Future, io::{self, SeekFrom}, net::{IpAddr, Ipv6Addr}, pin::Pin, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}, time::Duration, };
+use tokio::{ fs::File, io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader}, net::{TcpListener, TcpStream}, task::JoinHandle, };
+use crate::file_transfer_protocol::{ self, hex_to_offer_id, LocalFileOffer, OfferKind, OfferRegistry, FILE_PROTOCOL_VERSION, FOFR_RESUME_VERSION, FOFR_SEALED_VERSION, };
+use crate::bandwidth::{self, Kind};
+use crate::sealed_stream::{self, Sink};
+use crate::{channel_files, identity, net_runtime, pairing, rate_limit, secure_channel_code, transfers};
+
+const FOFR_MAGIC: &[u8; 4] = b"FOFR"; // Windows request
+const FOFS_MAGIC: &[u8; 4] = b"FOFS"; // Windows stream response
+pub const FOFE_MAGIC: &[u8; 4] = b"FOFE"; // secure channel request
+pub const FOFK_MAGIC: &[u8; 4] = b"FOFK"; // secure channel "got it all" from the member
+
+// Tunables
+const FILE_BUF_SIZE: usize = 1024 * 1024; // 1 MB
+const MAX_PROOF_LEN: usize = 256;
+
+// connections being served right now, /exit waits for them (bounded)
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+pub fn active_transfers() -> usize {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+// socket timeouts are gone with tokio, every read / write goes through one of these.
+// Limits come from Config.transfer, see file_transfer_protocol::set_timeouts
+async fn read_timeout<T>(fut: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    tokio::time::timeout(file_transfer_protocol::server_timeouts().0, fut)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Read timed out"))?
+}
+
+async fn write_timeout<T>(fut: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    tokio::time::timeout(file_transfer_protocol::server_timeouts().1, fut)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Write timed out"))?
+}
+
+// ===================== Server =====================
+
+/// What the server tells the app, from its own tasks
+#[derive(Debug)]
+pub enum ServerEvent {
+    /// Something failed, for the diagnostics log
+    Log(String),
+    /// A whole offer went out to `peer`
+    Sent { peer: Option<IpAddr>, name: String, size: u64 },
+    /// A channel member confirmed one of our channel offers, `count` members have it now
+    Delivered { offer_id: [u8; 16], member: IpAddr, count: usize },
+    /// A phone finished /pair, it waits in pairing::take_completed
+    Paired,
+}
+
+/// Handler for a protocol the app serves itself: the stream (nothing read yet), our offers,
+/// read and write timeouts
+pub type ServeFn = fn(TcpStream, Arc<Mutex<OfferRegistry>>, Duration, Duration) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send>>;
+
+/// A connection starting with `magic` goes to `serve`
+pub struct Protocol {
+    pub magic: &'static [u8],
+    pub serve: ServeFn,
+}
+
+pub struct FileServer {
+    pub registry: Arc<Mutex<OfferRegistry>>,
+    /// Checked after pairing and before the offer protocols, anything unknown is a phone download
+    pub protocols: Vec<Protocol>,
+    pub on_event: Box<dyn Fn(ServerEvent) + Send + Sync>,
+}
+
+impl FileServer {
+    fn emit(&self, event: ServerEvent) {
+        (self.on_event)(event);
+    }
+
+    fn sent(&self, peer: Option<IpAddr>, local: &LocalFileOffer) {
+        self.emit(ServerEvent::Sent { peer, name: local.name.clone(), size: local.size });
+    }
+}
+
+/// Listen on `port` (0 = any free one), returns the port we really got
+pub fn start_file_server(server: Arc<FileServer>, port: u16) -> io::Result<(JoinHandle<()>, u16)> {
+    // bind synchronously so a busy port is reported to the caller right away
+    let listener = std::net::TcpListener::bind(("0.0.0.0", port))?;
+    listener.set_nonblocking(true)?;
+    let port = listener.local_addr()?.port();
+
+    let _guard = net_runtime::runtime().enter();
+
+    // IPv6 peers, best effort
+    if let Ok(listener_v6) = bind_v6_listener(port) {
+        if listener_v6.set_nonblocking(true).is_ok() {
+            if let Ok(listener_v6) = TcpListener::from_std(listener_v6) {
+                let _v6_handle = spawn_accept_loop(listener_v6, Arc::clone(&server));
+            }
+        }
+    }
+
+    Ok((spawn_accept_loop(TcpListener::from_std(listener)?, server), port))
+}
+
+fn spawn_accept_loop(listener: TcpListener, server: Arc<FileServer>) -> JoinHandle<()> {
+    net_runtime::spawn(async move {
+        //println!("[TCP] File server listening on {:?}", listener.local_addr());
+
+        // accept errors repeat every 100 ms while they last, log the first of a streak only
+        let mut accept_failing = false;
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    accept_failing = false;
+                    //println!("[TCP] accepted from {:?}", peer);
+                    let server = Arc::clone(&server);
+
+                    // the inner task is awaited so a panic in a handler is logged, not lost
+                    ACTIVE.fetch_add(1, Ordering::Relaxed);
+                    tokio::spawn(async move {
+                        match tokio::spawn(handle_client(stream, Arc::clone(&server))).await {
+                            Ok(Ok(())) => {}
+                            Ok(Err(e)) if transfers::is_cancelled(&e) => {}
+                            Ok(Err(e)) => server.emit(ServerEvent::Log(format!("{}: {}", peer, e))),
+                            Err(e) if e.is_panic() => server.emit(ServerEvent::Log(format!("{}: handler panicked", peer))),
+                            Err(_) => {}
+                        }
+                        ACTIVE.fetch_sub(1, Ordering::Relaxed);
+                    });
+                }
+                Err(e) => {
+                    if !std::mem::replace(&mut accept_failing, true) {
+                        server.emit(ServerEvent::Log(format!("accept failed: {}", e)));
+                    }
+                    // e.g. out of file descriptors, don't spin on it
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    })
+}
+
+// [::] only for IPv6: dual-stack it would clash with the v4 listener on Linux or take v4
+// connections as mapped addresses
+fn bind_v6_listener(port: u16) -> io::Result<std::net::TcpListener> {
+    let sock = socket2::Socket::new(socket2::Domain::IPV6, socket2::Type::STREAM, Some(socket2::Protocol::TCP))?;
+    sock.set_only_v6(true)?;
+    // what std's TcpListener::bind does too
+    #[cfg(not(windows))]
+    sock.set_reuse_address(true)?;
+    sock.bind(&std::net::SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    sock.listen(128)?;
+    Ok(sock.into())
+}
+
+// ===================== Dispatcher =====================
+
+async fn handle_client(stream: TcpStream, server: Arc<FileServer>) -> io::Result<()> {
+    //println!("[TCP] client connected {:?}", stream.peer_addr().ok());
+
+    let _ = stream.set_nodelay(true);
+    let registry = Arc::clone(&server.registry);
+
+    // Peek first 5 bytes to determine protocol
+    let mut head = [0u8; 5];
+    let n = read_timeout(stream.peek(&mut head)).await?;
+    let first4 = &head[..4];
+    let (read_limit, write_limit) = file_transfer_protocol::server_timeouts();
+
+    if n >= 5 && &head == pairing::MPAIR_MAGIC {
+        //println!("[TCP] protocol = PHONE PAIRING (MPAIR)");
+        pairing::handle_pair(stream, read_limit, write_limit).await?;
+        server.emit(ServerEvent::Paired);
+        Ok(())
+    } else if let Some(protocol) = server.protocols.iter().find(|p| n >= p.magic.len() && head.starts_with(p.magic)) {
+        (protocol.serve)(stream, registry, read_limit, write_limit).await
+    } else if n >= 4 && first4 == FOFR_MAGIC {
+        //println!("[TCP] protocol = WINDOWS (FOFR)");
+        transfers::cancellable(None, handle_client_windows(stream, &server)).await
+    } else if n >= 4 && first4 == FOFE_MAGIC {
+        //println!("[TCP] protocol = SECURE CHANNEL (FOFE)");
+        transfers::cancellable(None, handle_client_channel(stream, &server)).await
+    } else if n >= 4 && first4 == identity::FIDP_MAGIC {
+        identity::serve_proof(stream, read_limit, write_limit).await
+    } else {
+        //println!("[TCP] protocol = MOBILE");
+        transfers::cancellable(None, handle_client_mobile(stream, &server)).await
+    }
+}
+
+// ===================== Windows protocol =====================
+// FOFR + ver + offer_id(16)
+// FOFS + ver + size(u64)
+// raw bytes
+//
+// Resume (ver = FOFR_RESUME_VERSION): FOFR + ver + offer_id(16) + offset(u64)
+// FOFS + ver + size(u64) + start(u64), raw bytes from `start` on. `start` is 0 when the
+// offset doesn't fit the file. Older senders close on the unknown version.
+//
+// Sealed (ver = FOFR_SEALED_VERSION, secure channel mode): FOFR + ver + offer_id(16) +
+// offset(u64) + proof_len(u16) + proof (offer id sealed with the channel key)
+// FOFS + ver + size(u64) + start(u64), then everything that follows sealed in chunks, size and
+// start again in the first one (see sealed_stream). Closed without an answer when we aren't in
+// the requester's channel.
+//
+// Folder offers: FOFS + ver + total size(u64) [+ start(u64), always 0, when sealed] +
+// manifest_len(u32) + bincode(FolderManifest), then every file's raw bytes back to back in
+// manifest order
+
+async fn handle_client_windows(mut stream: TcpStream, server: &FileServer) -> io::Result<()> {
+    let peer = stream.peer_addr().ok().map(|a| a.ip());
+    let mut magic = [0u8; 4];
+    read_timeout(stream.read_exact(&mut magic)).await?;
+    if &magic != FOFR_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Bad FOFR magic"));
+    }
+
+    let mut ver = [0u8; 1];
+    read_timeout(stream.read_exact(&mut ver)).await?;
+    if ver[0] != FILE_PROTOCOL_VERSION && ver[0] != FOFR_RESUME_VERSION && ver[0] != FOFR_SEALED_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Protocol version mismatch"));
+    }
+
+    let mut offer_id = [0u8; 16];
+    read_timeout(stream.read_exact(&mut offer_id)).await?;
+
+    let resume_from = if ver[0] != FILE_PROTOCOL_VERSION {
+        let mut offset = [0u8; 8];
+        read_timeout(stream.read_exact(&mut offset)).await?;
+        Some(u64::from_le_bytes(offset))
+    } else {
+        None
+    };
+
+    // sealed: only for someone who holds the key of the channel we're in
+    let key = if ver[0] == FOFR_SEALED_VERSION {
+        let proof = read_proof(&mut stream).await?;
+        let channel = secure_channel_code::get_active_channel()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "No secure channel"))?;
+        if !channel_files::check_proof(&channel.key, &offer_id, &proof) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Not a channel member"));
+        }
+        Some(channel.key)
+    } else {
+        None
+    };
+    // a channel offer never leaves unsealed, the offer id alone is visible to anyone sniffing a member's request
+    if key.is_none() && channel_files::is_channel_offer(&offer_id) {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Channel offers are sealed only"));
+    }
+
+    let local: LocalFileOffer = {
+        let reg = server.registry.lock().unwrap();
+        reg.get(&offer_id)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Offer not found"))?
+    };
+
+    //println!( "[TCP][WIN] serving {} ({} bytes)", local.path.display(), local.size );
+    if matches!(local.kind, OfferKind::Folder) {
+        if resume_from.is_some_and(|offset| offset > 0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Folder offers can't resume"));
+        }
+        return serve_folder(stream, server, offer_id, &local, peer, key).await;
+    }
+
+    let start = resume_from.filter(|&offset| offset <= local.size).unwrap_or(0);
+    let mut header = Vec::with_capacity(21);
+    header.extend_from_slice(FOFS_MAGIC);
+    header.push(ver[0]);
+    header.extend_from_slice(&local.size.to_le_bytes());
+    if resume_from.is_some() {
+        header.extend_from_slice(&start.to_le_bytes());
+    }
+    write_timeout(stream.write_all(&header)).await?;
+
+    let mut file = File::open(&local.path).await?;
+    if start > 0 {
+        file.seek(SeekFrom::Start(start)).await?;
+    }
+    // no more than announced, a sealed stream that runs over is refused
+    let mut reader = BufReader::with_capacity(FILE_BUF_SIZE, file).take(local.size - start);
+    let mut buf = vec![0u8; FILE_BUF_SIZE];
+    let mut sink = Sink::new(key, offer_id, file_transfer_protocol::server_timeouts().1);
+    sink.begin(&mut stream, local.size, start).await?;
+
+    let mut _sent: u64 = 0;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        let wire = sink.write_all(&mut stream, &buf[..n]).await?;
+        bandwidth::record_sent(Kind::File, peer, wire);
+        rate_limit::upload(wire).await;
+        _sent += n as u64;
+    }
+
+    sink.finish(&mut stream).await?;
+    server.sent(peer, &local);
+    //println!("[TCP][WIN] done sent={sent}");
+
+    Ok(())
+}
+
+async fn serve_folder(mut stream: TcpStream, server: &FileServer, offer_id: [u8; 16], local: &LocalFileOffer, peer: Option<IpAddr>, key: Option<[u8; 32]>) -> io::Result<()> {
+    let root = local.path.clone();
+    let (manifest, files) = tokio::task::spawn_blocking(move || file_transfer_protocol::build_folder_manifest(&root))
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))??;
+    let encoded = bincode::serde::encode_to_vec(&manifest, bincode::config::standard())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    if encoded.len() > file_transfer_protocol::MAX_MANIFEST_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Folder has too many files"));
+    }
+    let total: u64 = manifest.entries.iter().map(|e| e.size).sum();
+
+    let mut header = Vec::with_capacity(21);
+    header.extend_from_slice(FOFS_MAGIC);
+    header.push(if key.is_some() { FOFR_SEALED_VERSION } else { FILE_PROTOCOL_VERSION });
+    header.extend_from_slice(&total.to_le_bytes());
+    if key.is_some() {
+        header.extend_from_slice(&0u64.to_le_bytes());
+    }
+    write_timeout(stream.write_all(&header)).await?;
+
+    // the manifest names every file, it goes through the sink like the contents
+    let mut sink = Sink::new(key, offer_id, file_transfer_protocol::server_timeouts().1);
+    sink.begin(&mut stream, total, 0).await?;
+    let mut listing = Vec::with_capacity(4 + encoded.len());
+    listing.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+    listing.extend_from_slice(&encoded);
+    sink.write_all(&mut stream, &listing).await?;
+
+    let mut buf = vec![0u8; FILE_BUF_SIZE];
+    for (entry, path) in manifest.entries.iter().zip(files) {
+        // exactly the size in the manifest, the receiver splits the stream by it
+        let file = File::open(&path).await?;
+        let mut reader = BufReader::with_capacity(FILE_BUF_SIZE, file).take(entry.size);
+        let mut left = entry.size;
+        while left > 0 {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} shrank while sending", entry.path)));
+            }
+            let wire = sink.write_all(&mut stream, &buf[..n]).await?;
+            bandwidth::record_sent(Kind::File, peer, wire);
+            rate_limit::upload(wire).await;
+            left -= n as u64;
+        }
+    }
+
+    sink.finish(&mut stream).await?;
+    server.sent(peer, local);
+    Ok(())
+}
+
+// proof_len(u16) + proof, the offer id sealed with the channel key
+async fn read_proof(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut proof_len = [0u8; 2];
+    read_timeout(stream.read_exact(&mut proof_len)).await?;
+    let proof_len = u16::from_le_bytes(proof_len) as usize;
+    if proof_len > MAX_PROOF_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Proof too long"));
+    }
+    let mut proof = vec![0u8; proof_len];
+    read_timeout(stream.read_exact(&mut proof)).await?;
+    Ok(proof)
+}
+
+// ===================== Secure channel protocol =====================
+// FOFE + ver + offer_id(16) + proof_len(u16) + proof (offer id sealed with the channel key)
+// FOFS + ver + size(u64)
+// the file sealed in chunks, see sealed_stream
+// FOFK from the member once everything is on disk
+
+async fn handle_client_channel(mut stream: TcpStream, server: &FileServer) -> io::Result<()> {
+    let member = stream.peer_addr()?.ip();
+
+    let mut magic = [0u8; 4];
+    read_timeout(stream.read_exact(&mut magic)).await?;
+    let mut ver = [0u8; 1];
+    read_timeout(stream.read_exact(&mut ver)).await?;
+    if ver[0] != FILE_PROTOCOL_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Protocol version mismatch"));
+    }
+
+    let mut offer_id = [0u8; 16];
+    read_timeout(stream.read_exact(&mut offer_id)).await?;
+
+    let proof = read_proof(&mut stream).await?;
+
+    // only channel offers, and only to someone who holds the key
+    let channel = secure_channel_code::get_active_channel()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "No secure channel"))?;
+    if !channel_files::is_channel_offer(&offer_id) || !channel_files::check_proof(&channel.key, &offer_id, &proof) {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Not a channel member"));
+    }
+
+    let local: LocalFileOffer = {
+        let reg = server.registry.lock().unwrap();
+        reg.get(&offer_id)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Offer not found"))?
+    };
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(FOFS_MAGIC);
+    header.push(FILE_PROTOCOL_VERSION);
+    header.extend_from_slice(&local.size.to_le_bytes());
+    write_timeout(stream.write_all(&header)).await?;
+
+    let file = File::open(&local.path).await?;
+    let mut reader = BufReader::with_capacity(FILE_BUF_SIZE, file).take(local.size);
+    let mut buf = vec![0u8; sealed_stream::CHUNK];
+    let mut sink = Sink::new(Some(channel.key), offer_id, file_transfer_protocol::server_timeouts().1);
+    sink.begin(&mut stream, local.size, 0).await?;
+
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        let wire = sink.write_all(&mut stream, &buf[..n]).await?;
+        bandwidth::record_sent(Kind::File, Some(member), wire);
+        rate_limit::upload(wire).await;
+    }
+    sink.finish(&mut stream).await?;
+    server.sent(Some(member), &local);
+
+    // the tick only counts once the member says the file is complete
+    let mut ack = [0u8; 4];
+    read_timeout(stream.read_exact(&mut ack)).await?;
+    if &ack == FOFK_MAGIC {
+        if let Some(count) = channel_files::mark_delivered(&offer_id, member) {
+            server.emit(ServerEvent::Delivered { offer_id, member, count });
+        }
+    }
+
+    Ok(())
+}
+
+// ===================== Mobile protocol =====================
+// "<offer_id_hex>\n"            v1
+// "<offer_id_hex> sha256\n"     v2, phones that check what they got
+// "OK\n" / "OK <sha256 hex>\n"  (v2)
+// raw bytes until EOF
+
+async fn handle_client_mobile(stream: TcpStream, server: &FileServer) -> io::Result<()> {
+    let peer = stream.peer_addr().ok().map(|a| a.ip());
+    let mut reader = BufReader::new(stream);
+
+    // Read offer_id_hex line
+    let mut line = String::new();
+    read_timeout(reader.read_line(&mut line)).await?;
+    let mut words = line.split_whitespace();
+    let offer_id_hex = words.next().unwrap_or("");
+    let wants_checksum = words.next() == Some(file_transfer_protocol::MOBILE_CHECKSUM_TOKEN);
+
+    //println!("[TCP][MOBILE] request id={offer_id_hex}");
+
+    if offer_id_hex.len() != 32 {
+        write_timeout(reader.get_mut().write_all(b"ERR\n")).await?;
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid offer_id_hex"));
+    }
+
+    let offer_id = hex_to_offer_id(offer_id_hex)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Bad hex offer id"))?;
+    // phones have no sealed download, channel offers are for members on FOFE / sealed FOFR
+    if channel_files::is_channel_offer(&offer_id) {
+        write_timeout(reader.get_mut().write_all(b"ERR\n")).await?;
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Channel offers are sealed only"));
+    }
+
+    let local: LocalFileOffer = {
+        let reg = server.registry.lock().unwrap();
+        reg.get(&offer_id)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Offer not found"))?
+    };
+
+    //println!( "[TCP][MOBILE] serving {} ({} bytes)", local.path.display(), local.size );
+
+    // Mobile ACK, v2 gets the checksum first so the phone can verify as it writes
+    if wants_checksum {
+        let path = local.path.clone();
+        let digest = tokio::task::spawn_blocking(move || file_transfer_protocol::sha256_file(&path))
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))??;
+        let ack = format!("OK {}\n", file_transfer_protocol::digest_to_hex(&digest));
+        write_timeout(reader.get_mut().write_all(ack.as_bytes())).await?;
+    } else {
+        write_timeout(reader.get_mut().write_all(b"OK\n")).await?;
+    }
+
+    let file = File::open(&local.path).await?;
+    let mut file_reader = BufReader::with_capacity(FILE_BUF_SIZE, file);
+    let mut buf = vec![0u8; FILE_BUF_SIZE];
+
+    let mut _sent: u64 = 0;
+    loop {
+        let n = file_reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        write_timeout(reader.get_mut().write_all(&buf[..n])).await?;
+        bandwidth::record_sent(Kind::File, peer, n);
+        rate_limit::upload(n).await;
+        _sent += n as u64;
+    }
+
+    write_timeout(reader.get_mut().flush()).await?;
+    server.sent(peer, &local);
+    //println!("[TCP][MOBILE] done sent={sent}");
+
+    Ok(())
+}
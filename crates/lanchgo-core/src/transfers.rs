@@ -0,0 +1,153 @@
+// Transfer manager: every download and every upload of one of our offers runs through
+// `cancellable`, so /clearfiles and /clearall stop them all at once instead of only emptying
+// the files panel. Downloads also get a stop signal of their own (`download`), that's the ✕
+// on a downloading row. A stopped download leaves no .part behind. `watchdog` gives up on a
+// download whose progress stopped moving, so the caller can reconnect instead of waiting forever.
+//
+// Withdrawing our own offers: FOFX + offer_id(16), broadcast once per offer. Peers drop the row
+// when it comes from the address that made the offer.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use tokio::sync::{watch, Notify};
+
+pub const FOFX_MAGIC: &[u8; 4] = b"FOFX";
+
+// bumped by cancel_all, every running transfer watches it
+static CANCEL: OnceLock<watch::Sender<u64>> = OnceLock::new();
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+// offer id hex -> stop signal of the download running for it
+static DOWNLOADS: Mutex<Option<HashMap<String, Arc<Notify>>>> = Mutex::new(None);
+
+fn cancel_signal() -> &'static watch::Sender<u64> {
+    CANCEL.get_or_init(|| watch::channel(0).0)
+}
+
+/// Run `fut` until it's done or cancel_all is called. `partial` is the download's .part file
+/// (or folder), removed when the transfer is cancelled.
+pub async fn cancellable<T>(partial: Option<PathBuf>, fut: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    let mut cancelled = cancel_signal().subscribe();
+    RUNNING.fetch_add(1, Ordering::Relaxed);
+    let res = tokio::select! {
+        res = fut => res,
+        _ = cancelled.changed() => Err(cancelled_error()),
+    };
+    RUNNING.fetch_sub(1, Ordering::Relaxed);
+
+    // the transfer future is gone by now, so its file handles are closed
+    if let (Err(e), Some(partial)) = (&res, partial) {
+        if is_cancelled(e) {
+            if partial.is_dir() {
+                let _ = tokio::fs::remove_dir_all(&partial).await;
+            } else {
+                let _ = tokio::fs::remove_file(&partial).await;
+            }
+        }
+    }
+    res
+}
+
+/// `cancellable` for the download of one offer, which `cancel` can also stop on its own
+pub async fn download<T>(offer_id_hex: &str, partial: PathBuf, fut: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    let stop = Arc::new(Notify::new());
+    DOWNLOADS.lock().unwrap().get_or_insert_with(HashMap::new).insert(offer_id_hex.to_string(), Arc::clone(&stop));
+    let res = cancellable(Some(partial), async {
+        tokio::select! {
+            res = fut => res,
+            _ = stop.notified() => Err(cancelled_error()),
+        }
+    })
+    .await;
+    if let Some(downloads) = DOWNLOADS.lock().unwrap().as_mut() {
+        // a newer download of the same offer may have taken the slot
+        if downloads.get(offer_id_hex).is_some_and(|s| Arc::ptr_eq(s, &stop)) {
+            downloads.remove(offer_id_hex);
+        }
+    }
+    res
+}
+
+/// Stop the download of this offer, false when none is running
+pub fn cancel(offer_id_hex: &str) -> bool {
+    let stop = DOWNLOADS.lock().unwrap().as_mut().and_then(|d| d.remove(offer_id_hex));
+    match stop {
+        Some(stop) => {
+            stop.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+/// A download of this offer is running right now
+pub fn is_running(offer_id_hex: &str) -> bool {
+    DOWNLOADS.lock().unwrap().as_ref().is_some_and(|d| d.contains_key(offer_id_hex))
+}
+
+/// Stop every running transfer, returns how many there were
+pub fn cancel_all() -> usize {
+    let running = RUNNING.load(Ordering::Relaxed);
+    cancel_signal().send_modify(|generation| *generation += 1);
+    running
+}
+
+/// Run `fut` while `progress` (bytes done so far) keeps moving. When it stands still for `stall_after`
+/// the future is dropped and a stalled error comes back, what arrived of a file stays in its .part.
+pub async fn watchdog<T>(progress: &AtomicU64, stall_after: Option<Duration>, fut: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    let Some(stall_after) = stall_after else { return fut.await; };
+    tokio::pin!(fut);
+    let mut seen = progress.load(Ordering::Relaxed);
+    loop {
+        tokio::select! {
+            res = &mut fut => return res,
+            _ = tokio::time::sleep(stall_after) => {
+                let now = progress.load(Ordering::Relaxed);
+                if now == seen {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, STALLED));
+                }
+                seen = now;
+            }
+        }
+    }
+}
+
+const STALLED: &str = "stalled";
+
+/// The watchdog gave up on it. Counts as resumable, it's a TimedOut.
+pub fn is_stalled(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::TimedOut && e.to_string() == STALLED
+}
+
+fn cancelled_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
+
+/// The transfer failed because it was stopped on purpose, not worth an error toast
+pub fn is_cancelled(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::Interrupted && e.to_string() == "cancelled"
+}
+
+/// Where a download of `save_path` keeps its data until it's complete
+pub fn partial_path(save_path: &std::path::Path, is_folder: bool) -> PathBuf {
+    if is_folder {
+        PathBuf::from(format!("{}.part", save_path.display()))
+    } else {
+        save_path.with_extension("part")
+    }
+}
+
+pub fn withdraw_packet(offer_id: &[u8; 16]) -> Vec<u8> {
+    let mut packet = Vec::from(FOFX_MAGIC as &[u8]);
+    packet.extend_from_slice(offer_id);
+    packet
+}
+
+pub fn parse_withdraw(bytes: &[u8]) -> Option<[u8; 16]> {
+    bytes.strip_prefix(FOFX_MAGIC)?.get(..16)?.try_into().ok()
+}
@@ -0,0 +1,310 @@
+// The UDP side of the app: both receive loops, re-binding when they fail, FRAG reassembly and
+// the v4 / v6 duplicates. What a packet means is up to the caller's on_packet.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{ Arc, Mutex, atomic::{AtomicBool, Ordering}, };
+use std::time::{Duration, Instant};
+use lanchgo_proto::fragmentation;
+use tokio::net::UdpSocket;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use crate::classes::SharedSocket;
+use crate::{bandwidth, net_runtime, own_packets, phone_protocol, secure_channel_code};
+
+// a message sent on both v4 and v6 arrives twice within this window
+const DUAL_STACK_WINDOW: Duration = Duration::from_secs(2);
+
+/// Drops the second copy of a packet that already arrived over the other IP family
+struct DualStackDedupe {
+    recent: VecDeque<(u64, bool, Instant)>, // (hash, came over v6, when)
+}
+
+impl DualStackDedupe {
+    fn new() -> Self {
+        Self { recent: VecDeque::new() }
+    }
+
+    fn accept(&mut self, is_v6: bool, bytes: &[u8]) -> bool {
+        while self.recent.front().is_some_and(|(_, _, t)| t.elapsed() > DUAL_STACK_WINDOW) {
+            self.recent.pop_front();
+        }
+
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // matched a copy from the other family -> consume it and drop this one
+        if let Some(pos) = self.recent.iter().position(|(h, v6, _)| *h == hash && *v6 != is_v6) {
+            self.recent.remove(pos);
+            return false;
+        }
+
+        if self.recent.len() >= 256 {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((hash, is_v6, Instant::now()));
+        true
+    }
+}
+
+// one REQA answer per source this often, a looping client gets nothing in between
+const REQA_MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+// salt, port, ANCH, MANCH
+type CachedAnnounce = ([u8; 16], u16, Vec<u8>, Option<Vec<u8>>);
+
+/// Host side of REQA: the ANCH / MANCH answer is built once per channel + port and
+/// reused, and each source is rate limited
+pub struct AnnounceReplies {
+    cached: Option<CachedAnnounce>,
+    last_reply: HashMap<IpAddr, Instant>,
+}
+
+impl Default for AnnounceReplies {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnnounceReplies {
+    pub fn new() -> Self {
+        Self { cached: None, last_reply: HashMap::new() }
+    }
+
+    /// false if this source got an answer less than REQA_MIN_INTERVAL ago
+    pub fn allow(&mut self, from: IpAddr) -> bool {
+        self.last_reply.retain(|_, at| at.elapsed() < REQA_MIN_INTERVAL);
+        if self.last_reply.contains_key(&from) {
+            return false;
+        }
+        self.last_reply.insert(from, Instant::now());
+        true
+    }
+
+    /// ANCH and MANCH packets for the channel, rebuilt only when the channel or port changes
+    pub fn packets(&mut self, channel: &secure_channel_code::Channel, port: u16) -> Option<(&[u8], Option<&[u8]>)> {
+        let fresh = matches!(&self.cached, Some((salt, p, _, _)) if *salt == channel.salt && *p == port);
+        if !fresh {
+            let announce = secure_channel_code::build_announcement(channel);
+            let payload = bincode::serde::encode_to_vec(&announce, bincode::config::standard()).ok()?;
+            let mut packet = Vec::from(b"ANCH");
+            packet.extend_from_slice(&payload);
+            secure_channel_code::append_announce_trailers(&mut packet, port, channel);
+
+            let man_packet = phone_protocol::build_MANCH(channel, port).ok().map(|man_json| {
+                let mut man_packet = Vec::from(b"MANCH");
+                man_packet.extend_from_slice(man_json.as_bytes());
+                man_packet
+            });
+            self.cached = Some((channel.salt, port, packet, man_packet));
+        }
+        let (_, _, anch, manch) = self.cached.as_ref()?;
+        Some((anch, manch.as_deref()))
+    }
+}
+
+/// One complete packet, FRAG pieces already put together
+pub struct Datagram<'a> {
+    pub bytes: &'a [u8],
+    pub from: SocketAddr,
+    /// our own broadcast looping back, for every packet type (see own_packets)
+    pub from_self: bool,
+    /// the UDP port it came in on
+    pub local_port: u16,
+}
+
+/// How receiving is going, for the log and the banner
+#[derive(Debug)]
+pub enum ReceiverEvent {
+    Log(String),
+    /// v4 receiving failed for a while, the app should re-bind. Carries the last error.
+    Down(String),
+    /// packets are arriving again after Down
+    Up,
+}
+
+type PacketFn = dyn Fn(Datagram<'_>) -> Vec<Vec<u8>> + Send + Sync;
+type EventFn = dyn Fn(ReceiverEvent) + Send + Sync;
+
+/// Receive on the v4 socket and, when there is one, the v6 socket until `running` goes false.
+/// `on_packet` sees every packet once and returns the replies to send back to its sender.
+/// `my_ip` keeps our own broadcasts out of the bandwidth stats.
+pub fn start_udp_receiver(
+    sock: Arc<SharedSocket>,
+    running: Arc<AtomicBool>,
+    my_ip: Option<IpAddr>,
+    on_packet: impl Fn(Datagram<'_>) -> Vec<Vec<u8>> + Send + Sync + 'static,
+    on_event: impl Fn(ReceiverEvent) + Send + Sync + 'static,
+) -> JoinHandle<()> {
+    let dedupe = Arc::new(Mutex::new(DualStackDedupe::new()));
+    let on_packet: Arc<PacketFn> = Arc::new(on_packet);
+    let on_event: Arc<EventFn> = Arc::new(on_event);
+
+    // IPv6 link-local multicast gets its own loop with the exact same handling
+    {
+        let swapped = sock.subscribe();
+        let sock = Arc::clone(&sock);
+        let _v6_handle = spawn_receive_loop(
+            move || sock.get_v6(),
+            swapped,
+            true,
+            Arc::clone(&dedupe),
+            Arc::clone(&running),
+            my_ip,
+            Arc::clone(&on_packet),
+            Arc::clone(&on_event),
+        );
+    }
+
+    let swapped = sock.subscribe();
+    spawn_receive_loop(move || Some(sock.get()), swapped, false, dedupe, running, my_ip, on_packet, on_event)
+}
+
+// ===================== supervision =====================
+// A failing recv_from used to mean silence until the next interface change. Now a short
+// streak of errors is logged, shows a banner and asks the main thread to re-bind, with the
+// wait between attempts doubling up to RECV_MAX_BACKOFF. The first packet clears it all.
+
+const RECV_FAILURES_BEFORE_REBIND: u32 = 5;
+const RECV_MIN_BACKOFF: Duration = Duration::from_millis(500);
+const RECV_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// when any loop last got a packet, for the status endpoint
+static LAST_PACKET: Mutex<Option<Instant>> = Mutex::new(None);
+
+pub fn last_packet_age() -> Option<Duration> {
+    LAST_PACKET.lock().unwrap().map(|at| at.elapsed())
+}
+
+struct RecvSupervisor {
+    failures: u32,
+    backoff: Duration,
+    down: bool,
+}
+
+impl RecvSupervisor {
+    fn new() -> Self {
+        Self { failures: 0, backoff: RECV_MIN_BACKOFF, down: false }
+    }
+
+    /// How long to wait before reading again, reports Down when due
+    fn failed(&mut self, e: &io::Error, is_v6: bool, on_event: &EventFn) -> Duration {
+        self.failures += 1;
+        if self.failures == 1 {
+            on_event(ReceiverEvent::Log(format!("recv_from failed ({}): {}", if is_v6 { "v6" } else { "v4" }, e)));
+        }
+        // v6 is best effort, v4 decides whether we're receiving at all
+        if is_v6 || self.failures < RECV_FAILURES_BEFORE_REBIND {
+            return Duration::from_millis(100);
+        }
+
+        if !self.down {
+            on_event(ReceiverEvent::Log(format!("receiving is down after {} errors, re-binding", self.failures)));
+        }
+        self.down = true;
+        let wait = self.backoff;
+        self.backoff = (self.backoff * 2).min(RECV_MAX_BACKOFF);
+
+        on_event(ReceiverEvent::Down(e.to_string()));
+        wait
+    }
+
+    fn received(&mut self, on_event: &EventFn) {
+        *LAST_PACKET.lock().unwrap() = Some(Instant::now());
+        if self.failures == 0 {
+            return;
+        }
+        if self.down {
+            on_event(ReceiverEvent::Log("receiving again".into()));
+            on_event(ReceiverEvent::Up);
+        }
+        *self = Self::new();
+    }
+}
+
+fn local_port(udp: &UdpSocket) -> u16 {
+    udp.local_addr().map(|a| a.port()).unwrap_or(0)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_receive_loop(
+    get_sock: impl Fn() -> Option<Arc<UdpSocket>> + Send + 'static,
+    mut swapped: watch::Receiver<u64>,
+    is_v6: bool,
+    dedupe: Arc<Mutex<DualStackDedupe>>,
+    running: Arc<AtomicBool>,
+    my_ip: Option<IpAddr>,
+    on_packet: Arc<PacketFn>,
+    on_event: Arc<EventFn>,
+) -> JoinHandle<()> {
+    net_runtime::spawn(async move {
+        let mut buf = [0u8; 2048];
+        let mut assembler = fragmentation::Reassembler::default();
+        let mut supervisor = RecvSupervisor::new();
+
+        while running.load(Ordering::Relaxed) {
+            // re-fetched every loop so a re-bound socket is picked up
+            let Some(udp) = get_sock() else {
+                // no v6 stack, wait for the next re-bind instead of polling
+                if swapped.changed().await.is_err() {
+                    break;
+                }
+                continue;
+            };
+            let received = tokio::select! {
+                r = udp.recv_from(&mut buf) => r,
+                // socket got replaced, drop this one and pick up the new one
+                _ = swapped.changed() => continue,
+            };
+            match received {
+                Ok((n, from)) => {
+                    supervisor.received(&*on_event);
+                    // our own broadcasts loop back, they were counted when sent
+                    if my_ip != Some(from.ip()) {
+                        bandwidth::record_received(bandwidth::Kind::Chat, from.ip(), n);
+                    }
+                    // large messages arrive as FRAG pieces, handle them once complete
+                    let reassembled;
+                    let msg_bytes: &[u8] = if fragmentation::is_fragment(&buf[..n]) {
+                        match assembler.push(from, &buf[..n]) {
+                            fragmentation::PushOutcome::Complete(full) => {
+                                reassembled = full;
+                                &reassembled
+                            }
+                            _ => continue,
+                        }
+                    } else {
+                        &buf[..n]
+                    };
+                    if !dedupe.lock().unwrap().accept(is_v6, msg_bytes) {
+                        continue;
+                    }
+
+                    let replies = on_packet(Datagram {
+                        bytes: msg_bytes,
+                        from,
+                        from_self: own_packets::is_own(msg_bytes),
+                        local_port: local_port(&udp),
+                    });
+                    for reply in replies {
+                        let _ = udp.send_to(&reply, from).await;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => {
+                    // Windows reports ICMP port unreachable from an earlier send here
+                }
+                Err(e) => {
+                    // usually transient (adapter going away), a streak means the socket is gone
+                    let wait = supervisor.failed(&e, is_v6, &*on_event);
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = swapped.changed() => {}
+                    }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,297 @@
+// File offers on the wire. Desktops broadcast FOFT + bincode(FileOffer) followed by trailers,
+// phones MFOFT + JSON. The hash of an offer can follow later on its own:
+// FOFH + offer_id(16) + sha256(32)
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+pub const FOFT_MAGIC: &[u8; 4] = b"FOFT";
+pub const MFOFT_MAGIC: &[u8; 5] = b"MFOFT";
+pub const FOFH_MAGIC: &[u8; 4] = b"FOFH";
+pub const FILE_PROTOCOL_VERSION: u8 = 1;
+// FOFR with this version carries the offset a resumed download continues from
+pub const FOFR_RESUME_VERSION: u8 = 2;
+// FOFR with this version wants the file sealed with the channel key (see sealed_stream)
+pub const FOFR_SEALED_VERSION: u8 = 3;
+pub const DEFAULT_TCP_PORT: u16 = 3001;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OfferKind {
+    SingleFile,
+    ZipBundle,
+    Folder, // directory tree, FOFS is followed by a FolderManifest. New variants go last, the index is on the wire
+}
+
+/// Sent after the FOFS header of a Folder offer, the files follow back to back in this order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderManifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String, // relative to the offered folder, '/' separated
+    pub size: u64,
+}
+
+pub const MAX_MANIFEST_BYTES: usize = 16 * 1024 * 1024;
+
+/// ✅ This goes over the network (safe, portable)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileOffer {
+    pub offer_id: [u8; 16],
+    pub name: String,
+    pub size: u64,
+    pub kind: OfferKind,
+    pub protocol_version: u8,
+    pub tcp_port: u16,
+    // receiver side: when the sender's announcements stop counting, None = never (own offers,
+    // older builds that don't re-announce). Not on the wire, set from the FOFT "TTLS" trailer.
+    #[serde(skip)]
+    pub expires_at: Option<Instant>,
+}
+
+/// Where a manifest path lands below `root`, None for anything that would leave it
+pub fn manifest_target(root: &Path, rel: &str) -> Option<PathBuf> {
+    let mut target = root.to_path_buf();
+    for part in rel.split('/') {
+        if part.is_empty() || part == "." || part == ".." || part.contains(['\\', ':']) {
+            return None;
+        }
+        target.push(part);
+    }
+    Some(target)
+}
+
+// ===================== FOFT =====================
+// Trailers right after the bincode payload, each tag + u8 len + bytes. Older builds stop
+// decoding before them.
+//   "NICK" utf8 nickname, so the panel can group offers by who sent them
+//   "SHA2" SHA-256 of the file, once we have it (a fresh offer sends it later in an FOFH)
+//   "TTLS" u32 LE seconds the offer stands unless announced again
+
+const SENDER_TRAILER: &[u8; 4] = b"NICK";
+const HASH_TRAILER: &[u8; 4] = b"SHA2";
+const TTL_TRAILER: &[u8; 4] = b"TTLS";
+const MAX_SENDER_LEN: usize = 64;
+
+/// "FOFT" + bincode(FileOffer) + trailers
+pub fn encode_foft(offer: &FileOffer, sender: &str, hash: Option<&[u8; 32]>, ttl: Duration) -> io::Result<Vec<u8>> {
+    let payload = bincode::serde::encode_to_vec(offer, bincode::config::standard())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    let mut packet = Vec::with_capacity(4 + payload.len());
+    packet.extend_from_slice(FOFT_MAGIC);
+    packet.extend_from_slice(&payload);
+    append_sender_trailer(&mut packet, sender);
+    if let Some(digest) = hash {
+        packet.extend_from_slice(HASH_TRAILER);
+        packet.push(digest.len() as u8);
+        packet.extend_from_slice(digest);
+    }
+    packet.extend_from_slice(TTL_TRAILER);
+    packet.push(4);
+    packet.extend_from_slice(&(ttl.as_secs() as u32).to_le_bytes());
+    Ok(packet)
+}
+
+fn append_sender_trailer(packet: &mut Vec<u8>, nickname: &str) {
+    let mut end = nickname.len().min(MAX_SENDER_LEN);
+    while !nickname.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end == 0 {
+        return;
+    }
+    packet.extend_from_slice(SENDER_TRAILER);
+    packet.push(end as u8);
+    packet.extend_from_slice(&nickname.as_bytes()[..end]);
+}
+
+fn foft_trailer<'a>(bytes: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let payload = bytes.get(4..)?;
+    let (_, used) = bincode::serde::decode_from_slice::<FileOffer, _>(payload, bincode::config::standard()).ok()?;
+    let mut rest = &payload[used..];
+    while rest.len() >= 5 {
+        let value = rest.get(5..5 + rest[4] as usize)?;
+        if &rest[..4] == tag {
+            return Some(value);
+        }
+        rest = &rest[5 + value.len()..];
+    }
+    None
+}
+
+pub fn decode_foft(bytes: &[u8]) -> Option<FileOffer> {
+    if bytes.len() < 4 || &bytes[..4] != FOFT_MAGIC {
+        return None;
+    }
+
+    let payload = &bytes[4..];
+    let (offer, _) =
+        bincode::serde::decode_from_slice::<FileOffer, _>(payload, bincode::config::standard())
+            .ok()?;
+
+    // reject conflicting protocol versions
+    if offer.protocol_version != FILE_PROTOCOL_VERSION {
+        return None;
+    }
+    Some(offer)
+}
+
+/// Nickname the sender put after the offer, None for builds that don't send it
+pub fn foft_sender(bytes: &[u8]) -> Option<String> {
+    let name = foft_trailer(bytes, SENDER_TRAILER)?;
+    String::from_utf8(name.to_vec()).ok().filter(|n| !n.trim().is_empty())
+}
+
+/// File hash the sender put after the offer, None until it has one (or for older builds)
+pub fn foft_hash(bytes: &[u8]) -> Option<[u8; 32]> {
+    foft_trailer(bytes, HASH_TRAILER)?.try_into().ok()
+}
+
+/// How long the offer stands unless announced again, None from builds without the TTL trailer
+pub fn foft_ttl(bytes: &[u8]) -> Option<Duration> {
+    let secs: [u8; 4] = foft_trailer(bytes, TTL_TRAILER)?.try_into().ok()?;
+    Some(Duration::from_secs(u32::from_le_bytes(secs) as u64))
+}
+
+// ===================== FOFH =====================
+
+pub fn hash_packet(offer_id: &[u8; 16], digest: &[u8; 32]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + 16 + 32);
+    packet.extend_from_slice(FOFH_MAGIC);
+    packet.extend_from_slice(offer_id);
+    packet.extend_from_slice(digest);
+    packet
+}
+
+pub fn parse_hash_packet(bytes: &[u8]) -> Option<([u8; 16], [u8; 32])> {
+    let rest = bytes.strip_prefix(FOFH_MAGIC)?;
+    Some((rest.get(..16)?.try_into().ok()?, rest.get(16..48)?.try_into().ok()?))
+}
+
+// ===================== MFOFT (mobile, Flutter) =====================
+
+#[derive(Debug, Deserialize, Serialize)]
+struct MobileFileOfferJson {
+    #[serde(rename = "offer_id")]
+    offer_id_hex: String,
+    name: String,
+    size: u64,
+    kind: String,
+    #[serde(rename = "protocol_version")]
+    protocol_version: u8,
+    #[serde(rename = "tcp_port")]
+    tcp_port: u16,
+    // the sender's TCP server answers "<offer_id> sha256" with "OK <sha256 hex>", see mobile protocol
+    #[serde(default)]
+    sha256: bool,
+}
+
+/// Request token asking a mobile-protocol server for the file's SHA-256 up front
+pub const MOBILE_CHECKSUM_TOKEN: &str = "sha256";
+
+/// The offer in an MFOFT payload, its id as hex and whether the sender can send a checksum
+pub fn parse_mfoft(payload: &[u8]) -> Option<(FileOffer, String, bool)> {
+    let m: MobileFileOfferJson = serde_json::from_slice(payload).ok()?;
+
+    // version guard
+    if m.protocol_version != FILE_PROTOCOL_VERSION {
+        return None;
+    }
+
+    // currently mobile only supports single file
+    if m.kind != "SingleFile" {
+        return None;
+    }
+
+    let offer_id = hex_to_offer_id(&m.offer_id_hex)?;
+    let offer = FileOffer {
+        offer_id,
+        name: m.name,
+        size: m.size,
+        kind: OfferKind::SingleFile,
+        protocol_version: m.protocol_version,
+        tcp_port: m.tcp_port,
+        expires_at: None,
+    };
+
+    Some((offer, m.offer_id_hex, m.sha256))
+}
+
+///build bytes ready to broadcast: "MFOFT" + utf8(json)
+pub fn encode_mfoft_packet(offer: &FileOffer) -> io::Result<Vec<u8>> {
+    let offer_id_hex = offer_id_to_hex(&offer.offer_id);
+    let mob = MobileFileOfferJson {
+        offer_id_hex,
+        name: offer.name.clone(),
+        size: offer.size,
+        kind: "SingleFile".to_string(),
+        protocol_version: offer.protocol_version,
+        tcp_port: offer.tcp_port,
+        sha256: true,
+    };
+    let payload = serde_json::to_vec(&mob).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let mut packet = Vec::with_capacity(MFOFT_MAGIC.len() + payload.len());
+    packet.extend_from_slice(MFOFT_MAGIC); // MFOFT
+    packet.extend_from_slice(&payload);    // JSON
+    Ok(packet)
+}
+
+// ===================== helpers =====================
+
+pub fn offer_id_to_hex(id: &[u8; 16]) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn hex_to_offer_id(hex: &str) -> Option<[u8; 16]> {
+    if hex.len() != 32 {
+        return None;
+    }
+    let mut out = [0u8; 16];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(out)
+}
+
+pub fn digest_to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    const GB: f64 = MB * 1024.0;
+    const TB: f64 = GB * 1024.0;
+
+    let b = bytes as f64;
+
+    if b < KB {
+        format!("{} B", bytes)
+    } else if b < 10.0 * KB {
+        format!("{} KB", (b / KB).round() as u64)
+    } else if b < MB {
+        format!("{:.1} KB", b / KB)
+    } else if b < GB {
+        format!("{:.1} MB", b / MB)
+    } else if b < TB {
+        format!("{:.1} GB", b / GB)
+    } else {
+        format!("{:.1} TB", b / TB)
+    }
+}
+
+// helper for both mobile and windows
+pub fn truncate_name(name: &str, max_chars: usize) -> String {
+    if name.chars().count() <= max_chars {
+        return name.to_string();
+    }
+    let mut s: String = name.chars().take(max_chars.saturating_sub(1)).collect();
+    s.push('…');
+    s
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+// Fragment layout:
+// FRAG + msg_id(u32 LE) + index(u16 LE) + count(u16 LE) + chunk
+pub const FRAG_MAGIC: &[u8; 4] = b"FRAG";
+pub const FRAG_HEADER_LEN: usize = 4 + 4 + 2 + 2;
+// keep every fragment comfortably under MAX_DATAGRAM
+pub const FRAG_CHUNK_SIZE: usize = 1300;
+// ~100 KB per logical message, anything bigger should go through file transfer
+pub const MAX_FRAGMENTS: usize = 80;
+
+pub struct Fragment<'a> {
+    pub msg_id: u32,
+    pub index: u16,
+    pub count: u16,
+    pub chunk: &'a [u8],
+}
+
+/// False when even fragmenting can't get `msg` across
+pub fn fits(msg: &[u8]) -> bool {
+    msg.len().div_ceil(FRAG_CHUNK_SIZE) <= MAX_FRAGMENTS
+}
+
+pub fn is_fragment(bytes: &[u8]) -> bool {
+    bytes.len() >= FRAG_HEADER_LEN && &bytes[..4] == FRAG_MAGIC
+}
+
+/// Split a payload into FRAG packets, every one of them fits into a single datagram
+pub fn split_into_fragments(msg: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let count = msg.len().div_ceil(FRAG_CHUNK_SIZE);
+    if count > MAX_FRAGMENTS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("message too long: {} bytes (max {})", msg.len(), MAX_FRAGMENTS * FRAG_CHUNK_SIZE),
+        ));
+    }
+
+    let msg_id: u32 = rand::random();
+    let mut packets = Vec::with_capacity(count);
+
+    for (index, chunk) in msg.chunks(FRAG_CHUNK_SIZE).enumerate() {
+        let mut packet = Vec::with_capacity(FRAG_HEADER_LEN + chunk.len());
+        packet.extend_from_slice(FRAG_MAGIC);
+        packet.extend_from_slice(&msg_id.to_le_bytes());
+        packet.extend_from_slice(&(index as u16).to_le_bytes());
+        packet.extend_from_slice(&(count as u16).to_le_bytes());
+        packet.extend_from_slice(chunk);
+        packets.push(packet);
+    }
+    Ok(packets)
+}
+
+pub fn parse_fragment(bytes: &[u8]) -> Option<Fragment<'_>> {
+    if !is_fragment(bytes) {
+        return None;
+    }
+    let msg_id = u32::from_le_bytes(bytes[4..8].try_into().ok()?);
+    let index = u16::from_le_bytes(bytes[8..10].try_into().ok()?);
+    let count = u16::from_le_bytes(bytes[10..12].try_into().ok()?);
+
+    // reject nonsense headers early
+    if count == 0 || index >= count || count as usize > MAX_FRAGMENTS {
+        return None;
+    }
+    Some(Fragment { msg_id, index, count, chunk: &bytes[FRAG_HEADER_LEN..] })
+}
+
+// ===================== reassembly =====================
+// FRAG pieces come from anyone on the LAN, in any order, some twice, some never. The
+// reassembler keeps what it buffers bounded so lossy or hostile traffic can't pin memory:
+// incomplete messages are abandoned after a timeout, a sender over its limits loses its
+// oldest incomplete messages first, and over the overall limits the sender buffering the
+// most does. Senders are told apart by IP, a new source port per datagram is still one sender.
+
+/// Bounds for one Reassembler
+#[derive(Debug, Clone, Copy)]
+pub struct ReassemblyLimits {
+    pub timeout: Duration,             // an incomplete message is abandoned after this long
+    pub max_pending_per_source: usize, // incomplete messages one sender can have at once
+    pub max_bytes_per_source: usize,   // chunk bytes buffered for one sender
+    pub max_pending_total: usize,      // incomplete messages from everyone together
+    pub max_bytes_total: usize,        // chunk bytes buffered for everyone together
+}
+
+impl Default for ReassemblyLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max_pending_per_source: 4,
+            // two maximum-size messages in flight
+            max_bytes_per_source: 2 * MAX_FRAGMENTS * FRAG_CHUNK_SIZE,
+            max_pending_total: 64,
+            max_bytes_total: 16 * MAX_FRAGMENTS * FRAG_CHUNK_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PushOutcome {
+    Complete(Vec<u8>), // that was the last missing piece
+    Incomplete,        // stored, waiting for the rest
+    Duplicate,         // already had this piece
+    Rejected,          // not a fragment, disagrees with the first piece, or over the limits
+}
+
+struct Partial {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+    bytes: usize,
+    started: Instant,
+}
+
+/// Collects FRAG packets per (sender IP, msg_id) until every chunk is there
+pub struct Reassembler {
+    limits: ReassemblyLimits,
+    pending: HashMap<(IpAddr, u32), Partial>,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new(ReassemblyLimits::default())
+    }
+}
+
+impl Reassembler {
+    pub fn new(limits: ReassemblyLimits) -> Self {
+        Self { limits, pending: HashMap::new() }
+    }
+
+    pub fn push(&mut self, from: SocketAddr, bytes: &[u8]) -> PushOutcome {
+        self.push_at(from, bytes, Instant::now())
+    }
+
+    /// push() with the caller's clock
+    pub fn push_at(&mut self, from: SocketAddr, bytes: &[u8], now: Instant) -> PushOutcome {
+        self.abandon_expired(now);
+
+        let Some(frag) = parse_fragment(bytes) else { return PushOutcome::Rejected; };
+        let source = from.ip();
+        let size = frag.chunk.len();
+        if size > FRAG_CHUNK_SIZE || size > self.limits.max_bytes_per_source || size > self.limits.max_bytes_total {
+            return PushOutcome::Rejected;
+        }
+        let key = (source, frag.msg_id);
+
+        if let Some(partial) = self.pending.get(&key) {
+            if partial.chunks.len() != frag.count as usize {
+                return PushOutcome::Rejected; // header disagrees with the first fragment we saw
+            }
+            if partial.chunks[frag.index as usize].is_some() {
+                return PushOutcome::Duplicate;
+            }
+        } else {
+            // a new message: make room among this sender's oldest ones, then among everyone's
+            while self.pending_from(source) >= self.limits.max_pending_per_source {
+                if !self.abandon_oldest(Some(source), key) {
+                    return PushOutcome::Rejected;
+                }
+            }
+            while self.pending.len() >= self.limits.max_pending_total {
+                if !self.abandon_oldest(None, key) {
+                    return PushOutcome::Rejected;
+                }
+            }
+        }
+        while self.buffered_bytes(source) + size > self.limits.max_bytes_per_source {
+            if !self.abandon_oldest(Some(source), key) {
+                // only this message left and it alone is too big
+                self.pending.remove(&key);
+                return PushOutcome::Rejected;
+            }
+        }
+        while self.total_bytes() + size > self.limits.max_bytes_total {
+            if !self.abandon_oldest(None, key) {
+                self.pending.remove(&key);
+                return PushOutcome::Rejected;
+            }
+        }
+
+        let partial = self.pending.entry(key).or_insert_with(|| Partial {
+            chunks: vec![None; frag.count as usize],
+            received: 0,
+            bytes: 0,
+            started: now,
+        });
+        partial.chunks[frag.index as usize] = Some(frag.chunk.to_vec());
+        partial.received += 1;
+        partial.bytes += size;
+        if partial.received < partial.chunks.len() {
+            return PushOutcome::Incomplete;
+        }
+
+        match self.pending.remove(&key) {
+            Some(done) => PushOutcome::Complete(done.chunks.into_iter().flatten().flatten().collect()),
+            None => PushOutcome::Rejected,
+        }
+    }
+
+    /// Drop incomplete messages older than the timeout, returns how many went
+    pub fn abandon_expired(&mut self, now: Instant) -> usize {
+        let before = self.pending.len();
+        let timeout = self.limits.timeout;
+        self.pending.retain(|_, p| now.saturating_duration_since(p.started) < timeout);
+        before - self.pending.len()
+    }
+
+    pub fn pending_from(&self, from: IpAddr) -> usize {
+        self.pending.keys().filter(|(src, _)| *src == from).count()
+    }
+
+    /// Chunk bytes buffered for one sender
+    pub fn buffered_bytes(&self, from: IpAddr) -> usize {
+        self.pending.iter().filter(|((src, _), _)| *src == from).map(|(_, p)| p.bytes).sum()
+    }
+
+    /// Chunk bytes buffered for everyone
+    pub fn total_bytes(&self) -> usize {
+        self.pending.values().map(|p| p.bytes).sum()
+    }
+
+    // oldest incomplete message other than `keep`, of `from` or, for None, of whoever buffers
+    // the most. False if there's none.
+    fn abandon_oldest(&mut self, from: Option<IpAddr>, keep: (IpAddr, u32)) -> bool {
+        let from = match from {
+            Some(ip) => ip,
+            None => {
+                let mut per_source: HashMap<IpAddr, usize> = HashMap::new();
+                for ((src, id), p) in &self.pending {
+                    if (*src, *id) != keep {
+                        *per_source.entry(*src).or_default() += p.bytes.max(1);
+                    }
+                }
+                match per_source.into_iter().max_by_key(|(_, bytes)| *bytes) {
+                    Some((ip, _)) => ip,
+                    None => return false,
+                }
+            }
+        };
+        let oldest = self
+            .pending
+            .iter()
+            .filter(|(key, _)| key.0 == from && **key != keep)
+            .min_by_key(|(_, p)| p.started)
+            .map(|(key, _)| *key);
+        match oldest {
+            Some(key) => self.pending.remove(&key).is_some(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    fn addr(last: u8, port: u16) -> SocketAddr {
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, last), port))
+    }
+
+    fn fragment(msg_id: u32, index: u16, count: u16, chunk: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::from(FRAG_MAGIC as &[u8]);
+        packet.extend_from_slice(&msg_id.to_le_bytes());
+        packet.extend_from_slice(&index.to_le_bytes());
+        packet.extend_from_slice(&count.to_le_bytes());
+        packet.extend_from_slice(chunk);
+        packet
+    }
+
+    #[test]
+    fn reassembles_out_of_order() {
+        let msg: Vec<u8> = (0..3 * FRAG_CHUNK_SIZE + 17).map(|i| i as u8).collect();
+        let mut packets = split_into_fragments(&msg).unwrap();
+        packets.reverse();
+        let mut r = Reassembler::default();
+        let last = packets.pop().unwrap();
+        for p in &packets {
+            assert_eq!(r.push(addr(2, 5000), p), PushOutcome::Incomplete);
+        }
+        assert_eq!(r.push(addr(2, 5000), &last), PushOutcome::Complete(msg));
+        assert_eq!(r.pending_from(addr(2, 5000).ip()), 0);
+    }
+
+    #[test]
+    fn same_ip_other_port_is_the_same_sender() {
+        let mut r = Reassembler::default();
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 0, 2, b"ab")), PushOutcome::Incomplete);
+        assert_eq!(r.push(addr(2, 6000), &fragment(1, 1, 2, b"cd")), PushOutcome::Complete(b"abcd".to_vec()));
+    }
+
+    #[test]
+    fn duplicate_piece() {
+        let mut r = Reassembler::default();
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 0, 2, b"ab")), PushOutcome::Incomplete);
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 0, 2, b"ab")), PushOutcome::Duplicate);
+        assert_eq!(r.buffered_bytes(addr(2, 5000).ip()), 2);
+    }
+
+    #[test]
+    fn out_of_range_and_disagreeing_headers() {
+        let mut r = Reassembler::default();
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 2, 2, b"ab")), PushOutcome::Rejected);
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 0, 0, b"ab")), PushOutcome::Rejected);
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 0, MAX_FRAGMENTS as u16 + 1, b"ab")), PushOutcome::Rejected);
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 0, 3, b"ab")), PushOutcome::Incomplete);
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 1, 4, b"cd")), PushOutcome::Rejected);
+        assert_eq!(r.push(addr(2, 5000), b"FRAG"), PushOutcome::Rejected);
+    }
+
+    #[test]
+    fn oversized_chunk() {
+        let mut r = Reassembler::default();
+        let chunk = vec![0u8; FRAG_CHUNK_SIZE + 1];
+        assert_eq!(r.push(addr(2, 5000), &fragment(1, 0, 2, &chunk)), PushOutcome::Rejected);
+        assert_eq!(r.total_bytes(), 0);
+    }
+
+    #[test]
+    fn per_source_limits_drop_the_oldest() {
+        let limits = ReassemblyLimits { max_pending_per_source: 2, max_bytes_per_source: 5, ..Default::default() };
+        let mut r = Reassembler::new(limits);
+        let start = Instant::now();
+        let sender = addr(2, 5000);
+        assert_eq!(r.push_at(sender, &fragment(1, 0, 2, b"ab"), start), PushOutcome::Incomplete);
+        assert_eq!(r.push_at(sender, &fragment(2, 0, 2, b"cd"), start + Duration::from_millis(1)), PushOutcome::Incomplete);
+        // a third message pushes out msg 1
+        assert_eq!(r.push_at(sender, &fragment(3, 0, 2, b"e"), start + Duration::from_millis(2)), PushOutcome::Incomplete);
+        assert_eq!(r.pending_from(sender.ip()), 2);
+        assert_eq!(r.push_at(sender, &fragment(1, 1, 2, b"zz"), start + Duration::from_millis(3)), PushOutcome::Incomplete);
+        // 6 bytes alone is over max_bytes_per_source
+        assert_eq!(r.push_at(sender, &fragment(4, 0, 2, b"abcdef"), start + Duration::from_millis(4)), PushOutcome::Rejected);
+        assert!(r.buffered_bytes(sender.ip()) <= 5);
+    }
+
+    #[test]
+    fn overall_limits_take_from_the_biggest_sender() {
+        let limits = ReassemblyLimits { max_pending_total: 3, max_bytes_total: 8, ..Default::default() };
+        let mut r = Reassembler::new(limits);
+        let start = Instant::now();
+        let (noisy, quiet) = (addr(2, 5000), addr(3, 5000));
+        assert_eq!(r.push_at(quiet, &fragment(1, 0, 2, b"q"), start), PushOutcome::Incomplete);
+        assert_eq!(r.push_at(noisy, &fragment(1, 0, 2, b"aaa"), start), PushOutcome::Incomplete);
+        assert_eq!(r.push_at(noisy, &fragment(2, 0, 2, b"bbb"), start), PushOutcome::Incomplete);
+        // over max_pending_total: one of noisy's goes, quiet keeps its message
+        assert_eq!(r.push_at(noisy, &fragment(3, 0, 2, b"c"), start), PushOutcome::Incomplete);
+        assert_eq!(r.pending.len(), 3);
+        assert_eq!(r.pending_from(quiet.ip()), 1);
+        // over max_bytes_total the same way
+        assert_eq!(r.push_at(quiet, &fragment(1, 1, 2, b"qqqq"), start), PushOutcome::Complete(b"qqqqq".to_vec()));
+        assert!(r.total_bytes() <= 8);
+    }
+
+    #[test]
+    fn expired_messages_are_abandoned() {
+        let mut r = Reassembler::default();
+        let start = Instant::now();
+        assert_eq!(r.push_at(addr(2, 5000), &fragment(1, 0, 2, b"ab"), start), PushOutcome::Incomplete);
+        assert_eq!(r.abandon_expired(start + Duration::from_secs(6)), 1);
+        assert_eq!(r.push_at(addr(2, 5000), &fragment(1, 1, 2, b"cd"), start + Duration::from_secs(6)), PushOutcome::Incomplete);
+    }
+}
@@ -0,0 +1,5 @@
+// Wire formats of the LAN protocol, usable without the Slint app
+
+pub mod file_offer;     // FOFT / MFOFT / FOFH: file offers and their hashes
+pub mod fragmentation;  // FRAG: messages too large for one UDP datagram
+pub mod rooms;          // named public chat rooms on one LAN
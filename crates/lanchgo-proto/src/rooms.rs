@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Room everyone is in by default. Its text goes out as plain UTF-8 like before,
+/// so mobile and older desktop clients keep seeing it.
+pub const DEFAULT_ROOM: &str = "general";
+
+// ROOM + name_len(u8) + name + text
+const ROOM_MAGIC: &[u8; 4] = b"ROOM";
+pub const MAX_ROOM_LEN: usize = 24;
+
+// a room nobody has talked in for this long drops out of the switcher
+const SEEN_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Lowercase, trimmed, only a-z 0-9 - _ so names can't be spoofed with look-alikes
+pub fn normalize_room_name(name: &str) -> Result<String, String> {
+    let name = name.trim().trim_start_matches('#').to_lowercase();
+    if name.is_empty() {
+        return Err("Room name can't be empty".into());
+    }
+    if name.len() > MAX_ROOM_LEN {
+        return Err(format!("Room name is limited to {} characters", MAX_ROOM_LEN));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("Room names use letters, digits, - and _ only".into());
+    }
+    Ok(name)
+}
+
+/// Text packet for `room`, the default room stays untagged
+pub fn encode_text(room: &str, text: &[u8]) -> Vec<u8> {
+    if room == DEFAULT_ROOM {
+        return text.to_vec();
+    }
+    let mut packet = Vec::with_capacity(5 + room.len() + text.len());
+    packet.extend_from_slice(ROOM_MAGIC);
+    packet.push(room.len() as u8);
+    packet.extend_from_slice(room.as_bytes());
+    packet.extend_from_slice(text);
+    packet
+}
+
+/// Room and text of a received packet, untagged text belongs to the default room
+pub fn decode_text(bytes: &[u8]) -> Option<(String, &[u8])> {
+    if bytes.len() < 5 || &bytes[..4] != ROOM_MAGIC {
+        return Some((DEFAULT_ROOM.to_string(), bytes));
+    }
+    let len = bytes[4] as usize;
+    let name = bytes.get(5..5 + len)?;
+    let room = normalize_room_name(std::str::from_utf8(name).ok()?).ok()?;
+    Some((room, &bytes[5 + len..]))
+}
+
+// ===================== rooms seen on the LAN =====================
+
+static SEEN: OnceLock<Mutex<BTreeMap<String, Instant>>> = OnceLock::new();
+
+fn seen() -> &'static Mutex<BTreeMap<String, Instant>> {
+    SEEN.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Remember a room someone is talking in, even if it isn't ours
+pub fn note_seen(room: &str) {
+    seen().lock().unwrap().insert(room.to_string(), Instant::now());
+}
+
+/// Rooms for the switcher: default, current, then anything active recently
+pub fn known_rooms(current: &str) -> Vec<String> {
+    let mut seen = seen().lock().unwrap();
+    seen.retain(|_, at| at.elapsed() < SEEN_TTL);
+
+    let mut rooms = vec![DEFAULT_ROOM.to_string()];
+    if current != DEFAULT_ROOM {
+        rooms.push(current.to_string());
+    }
+    rooms.extend(seen.keys().filter(|r| !rooms.contains(r)).cloned().collect::<Vec<_>>());
+    rooms
+}
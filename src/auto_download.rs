@@ -0,0 +1,74 @@
+// Zero-click downloads: offers under the size limit start by themselves, optionally only
+// from trusted peers. The trusted list lives in config.trusted_peers (managed with /trust),
+// contacts marked trusted count too and blocked ones never auto-download. With /autoaccept
+// on, what a trusted contact offers starts past the limit too (scan-to-PC style setups), up to
+// auto_accept_max_mb. That needs the contact to have proved its key from the sender's address
+// (identity::prove), an address on the /trust list alone isn't enough to skip the limit.
+
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::classes::Config;
+use crate::contacts::{self, Trust};
+use crate::identity;
+
+const MB: u64 = 1024 * 1024;
+
+static LIMIT: AtomicU64 = AtomicU64::new(0); // bytes, 0 = off
+static TRUSTED_ONLY: AtomicBool = AtomicBool::new(false);
+static ACCEPT_TRUSTED: AtomicBool = AtomicBool::new(false);
+static ACCEPT_MAX: AtomicU64 = AtomicU64::new(0); // bytes
+static TRUSTED: Mutex<BTreeSet<IpAddr>> = Mutex::new(BTreeSet::new());
+
+/// Mirror the config for the receive thread, call again after every change
+pub fn configure(cfg: &Config) {
+    LIMIT.store(cfg.auto_download_mb as u64 * MB, Ordering::Relaxed);
+    TRUSTED_ONLY.store(cfg.auto_download_trusted_only, Ordering::Relaxed);
+    ACCEPT_TRUSTED.store(cfg.auto_accept_trusted, Ordering::Relaxed);
+    ACCEPT_MAX.store(cfg.auto_accept_max_mb as u64 * MB, Ordering::Relaxed);
+    *TRUSTED.lock().unwrap() = cfg.trusted_peers.iter().filter_map(|p| p.parse().ok()).collect();
+}
+
+pub fn is_trusted(peer: IpAddr) -> bool {
+    TRUSTED.lock().unwrap().contains(&peer) || contacts::trust_for_ip(peer) == Trust::Trusted
+}
+
+// a trusted contact that proved its key from `peer` within identity's PROOF_TTL
+fn is_verified_trusted(peer: IpAddr) -> bool {
+    identity::proven_key(peer).is_some_and(|key| contacts::trust(&key) == Trust::Trusted)
+}
+
+/// Should an offer of `size` bytes from `sender` start downloading on its own
+pub fn wants(sender: IpAddr, size: u64) -> bool {
+    if contacts::trust_for_ip(sender) == Trust::Blocked {
+        return false;
+    }
+    if ACCEPT_TRUSTED.load(Ordering::Relaxed) && size <= ACCEPT_MAX.load(Ordering::Relaxed) && is_verified_trusted(sender) {
+        return true;
+    }
+    let limit = LIMIT.load(Ordering::Relaxed);
+    if limit == 0 || size > limit {
+        return false;
+    }
+    !TRUSTED_ONLY.load(Ordering::Relaxed) || is_trusted(sender)
+}
+
+/// "/autoaccept" status line
+pub fn accept_summary(cfg: &Config) -> String {
+    if cfg.auto_accept_trusted {
+        format!("🤝 Files up to {} MB from trusted contacts download on their own", cfg.auto_accept_max_mb)
+    } else {
+        "🤝 Files from trusted peers wait for a click (or /autodl)".into()
+    }
+}
+
+/// "/autodl" status line
+pub fn summary(cfg: &Config) -> String {
+    if cfg.auto_download_mb == 0 {
+        return "⬇️ Auto-download is off (/autodl 5 turns it on for files under 5 MB)".into();
+    }
+    let from = if cfg.auto_download_trusted_only { "trusted peers" } else { "any peer" };
+    format!("⬇️ Auto-downloading files under {} MB from {}", cfg.auto_download_mb, from)
+}
@@ -0,0 +1,84 @@
+// Channel offers live in lanchgo_core::channel_files, the download with its progress UI stays here.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use slint::Weak;
+
+use crate::file_transfer_protocol::{offer_id_to_hex, FileOffer};
+use crate::{download_queue, main_helpers, net_runtime, transfers, AppWindow};
+
+pub use lanchgo_core::channel_files::*;
+
+/// Pull a channel offer over FOFE, same progress / finish UI as the other download paths
+pub fn spawn_channel_download(
+    sender_addr: SocketAddr,
+    offer: FileOffer,
+    key: [u8; 32],
+    save_path: PathBuf,
+    weak_ui: Weak<AppWindow>,
+    permit: download_queue::Slot,
+) {
+    let offer_id_hex = offer_id_to_hex(&offer.offer_id);
+    net_runtime::spawn(async move {
+        let _permit = permit; // hold the download slot until we're done
+
+        {
+            let weak_ui0 = weak_ui.clone();
+            let offer_id0 = offer_id_hex.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(app) = weak_ui0.upgrade() {
+                    main_helpers::set_offer_progress_text(&app, &offer_id0, true, "0%");
+                }
+            });
+        }
+
+        let weak_ui_progress = weak_ui.clone();
+        let offer_id_progress = offer_id_hex.clone();
+        let mut last_bucket: u32 = 999;
+
+        let partial = transfers::partial_path(&save_path, false);
+        let res = transfers::download(&offer_id_hex, partial, crate::tcp_file_client::download_channel_offer(
+            sender_addr,
+            offer.tcp_port,
+            offer.offer_id,
+            key,
+            save_path.clone(),
+            move |done, total| {
+                let bucket = main_helpers::progress_bucket_3(done, total);
+                if bucket == last_bucket {
+                    return;
+                }
+                last_bucket = bucket;
+
+                let text = format!("{}%", bucket);
+                let weak_ui = weak_ui_progress.clone();
+                let offer_id = offer_id_progress.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak_ui.upgrade() {
+                        main_helpers::set_offer_progress_text(&app, &offer_id, true, &text);
+                    }
+                });
+            },
+        )).await;
+        crate::records::transfer(false, Some(sender_addr.ip()), &offer.name, offer.size, crate::records::outcome(&res));
+
+        let name = offer.name.clone();
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak_ui.upgrade() {
+                match res {
+                    Ok(_) => {
+                        main_helpers::set_offer_progress_text(&app, &offer_id_hex, false, "100%");
+                        main_helpers::set_offer_saved(&app, &offer_id_hex, save_path);
+                        app.invoke_show_temp_message(format!("🔒 Download complete: {}", name).into());
+                    }
+                    Err(e) if transfers::is_cancelled(&e) => {}
+                    Err(e) => {
+                        main_helpers::set_offer_progress_text(&app, &offer_id_hex, false, "ERR");
+                        app.invoke_show_temp_message(format!("❌ Download failed: {}", e).into());
+                    }
+                }
+            }
+        });
+    });
+}
@@ -0,0 +1,99 @@
+// Secure channels nobody uses close by themselves. Every message, file offer or member asking
+// in counts as activity (heartbeats and announcements don't). After Config.channel_idle_minutes
+// without any, a warning goes to the chat, a minute later the channel is closed like with the
+// Disconnect button: PIN gone, key wiped, joiners sent back to public.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::classes::Config;
+use crate::secure_channel_code;
+use crate::AppWindow;
+
+const WARNING: Duration = Duration::from_secs(60);
+const CHECK_EVERY: Duration = Duration::from_secs(5);
+
+static IDLE_MINUTES: AtomicU32 = AtomicU32::new(0);
+
+struct Idle {
+    last_activity: Option<Instant>, // None until the watcher first sees the channel
+    warned: bool,
+}
+
+static IDLE: Mutex<Idle> = Mutex::new(Idle { last_activity: None, warned: false });
+
+/// Config.channel_idle_minutes, 0 keeps idle channels open
+pub fn configure(cfg: &Config) {
+    IDLE_MINUTES.store(cfg.channel_idle_minutes, Ordering::Relaxed);
+}
+
+/// "/idleclose" without arguments
+pub fn summary(cfg: &Config) -> String {
+    match cfg.channel_idle_minutes {
+        0 => "🧹 Idle secure channels stay open".to_string(),
+        m => format!("🧹 Secure channels close after {} min without activity", m),
+    }
+}
+
+/// Something happened in the channel, the countdown starts over
+pub fn touch() {
+    let mut idle = IDLE.lock().unwrap();
+    idle.last_activity = Some(Instant::now());
+    idle.warned = false;
+}
+
+/// The channel is gone, the next one starts fresh
+pub fn reset() {
+    *IDLE.lock().unwrap() = Idle { last_activity: None, warned: false };
+}
+
+enum Step {
+    Wait,
+    Warn,
+    Close,
+}
+
+fn step(limit: Duration) -> Step {
+    let mut idle = IDLE.lock().unwrap();
+    let quiet = idle.last_activity.get_or_insert_with(Instant::now).elapsed();
+    if quiet >= limit {
+        return Step::Close;
+    }
+    if quiet + WARNING >= limit && !std::mem::replace(&mut idle.warned, true) {
+        return Step::Warn;
+    }
+    Step::Wait
+}
+
+/// Watch the channel we host or joined, closing it once it sat idle too long
+pub fn start(channel_mode: Arc<Mutex<String>>, ui_weak: slint::Weak<AppWindow>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CHECK_EVERY);
+        let minutes = IDLE_MINUTES.load(Ordering::Relaxed);
+        let in_channel = matches!(channel_mode.lock().unwrap().as_str(), "host" | "joined");
+        if minutes == 0 || !in_channel || secure_channel_code::get_active_channel().is_none() {
+            continue;
+        }
+        let weak = ui_weak.clone();
+        match step(Duration::from_secs(minutes as u64 * 60)) {
+            Step::Wait => {}
+            Step::Warn => {
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_append_message(format!("⏳ Nothing happened in this channel for {} min, it closes in a minute unless someone writes", minutes).into());
+                    }
+                });
+            }
+            Step::Close => {
+                reset();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_disconnect_channel();
+                        app.invoke_append_message("🧹 The secure channel was idle and has been closed".into());
+                    }
+                });
+            }
+        }
+    });
+}
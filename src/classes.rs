@@ -1,11 +1,16 @@
 use serde::{Deserialize, Serialize};
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{Ipv4Addr, SocketAddrV4, SocketAddrV6};
 use std::sync::Mutex;
 
+// shared with the UI-free core, Config embeds them
+pub use lanchgo_core::classes::{PairedDevice, RateLimit, SharedSocket, TransferTimeouts};
+
 #[derive(Debug)]
 pub struct BroadcastState {
     pub broadcast_address: Mutex<String>,
     pub port: Mutex<u16>,
+    pub multicast_group: Mutex<Option<Ipv4Addr>>, // Some = multicast mode, None = plain broadcast
 }
 
 impl BroadcastState {
@@ -21,18 +26,32 @@ impl BroadcastState {
     pub fn get_port(&self) -> u16 {
         *self.port.lock().unwrap()
     }
+    pub fn set_multicast_group(&self, group: Option<Ipv4Addr>) {
+        *self.multicast_group.lock().unwrap() = group;
+    }
+    pub fn get_multicast_group(&self) -> Option<Ipv4Addr> {
+        *self.multicast_group.lock().unwrap()
+    }
     pub fn target_v4(&self) -> SocketAddrV4 {
+        if let Some(group) = self.get_multicast_group() {
+            return SocketAddrV4::new(group, self.get_port());
+        }
         let ip: Ipv4Addr = self
             .get_broadcast_address()
             .parse()
             .unwrap_or(Ipv4Addr::new(255, 255, 255, 255));
         SocketAddrV4::new(ip, self.get_port())
     }
+    pub fn target_v6(&self) -> SocketAddrV6 {
+        SocketAddrV6::new(crate::main_helpers::V6_MULTICAST_GROUP, self.get_port(), 0, 0)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     pub version: String,
+    #[serde(default)]
+    pub schema: u32, // see config_migration.rs, 0 = written before schema numbers
     pub selected_interface: String,
     pub last_broadcast: String,
     pub last_gateway: String,
@@ -40,13 +59,173 @@ pub struct Config {
     pub save_to_folder: String,
     pub port: Option<u16>, // if none then it is automatically sat, if some x then user sat it manually
     pub ui_scale: Option<f32>,
+    #[serde(default)]
+    pub multicast_group: Option<String>, // none = broadcast, some "239.x.x.x" = multicast mode
+    #[serde(default)]
+    pub files_panel_width: Option<f32>, // splitter position, none = default width
+    #[serde(default)]
+    pub language_tags: bool, // tag incoming messages with the detected language
+    #[serde(default)]
+    pub glossary_translate: bool, // run tagged messages through glossary.json
+    #[serde(default = "default_own_language")]
+    pub own_language: String, // messages in this language are not translated
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>, // "/l" -> "/clearall", "/brb" -> "be right back"
+    #[serde(default = "default_room")]
+    pub room: String, // public chat room, see rooms.rs
+    #[serde(default = "crate::main_helpers::default_nickname")]
+    pub nickname: String, // what peers type after @ to mention us
+    #[serde(default = "crate::main_helpers::default_device_name")]
+    pub device_name: String, // tells our machines apart, "Sam (laptop)", sent over mDNS
+    #[serde(default)]
+    pub mentions_only: bool, // only sounds / notifications for @mentions
+    #[serde(default = "default_true")]
+    pub ping_sound: bool, // "ping" from a peer plays a sound
+    #[serde(default = "default_true")]
+    pub nutella_sound: bool, // the nutella easter egg
+    #[serde(default = "default_true")]
+    pub notifications: bool, // desktop notifications while unfocused, see notify.rs
+    #[serde(default = "default_true")]
+    pub public_alerts: bool, // sounds, notifications and the unread badge for the public channel
+    #[serde(default = "default_true")]
+    pub secure_alerts: bool, // ...and for a secure channel, muted independently
+    #[serde(default)]
+    pub network_fingerprint: String, // see main_helpers::network_fingerprint, empty in older configs
+    #[serde(default)]
+    pub auto_download_mb: u32, // offers up to this size download by themselves, 0 = off
+    #[serde(default)]
+    pub auto_download_trusted_only: bool, // ...but only from trusted_peers
+    #[serde(default)]
+    pub auto_accept_trusted: bool, // offers from trusted contacts start downloading up to auto_accept_max_mb (/autoaccept)
+    #[serde(default = "default_auto_accept_max_mb")]
+    pub auto_accept_max_mb: u32, // the cap on /autoaccept, trusted or not
+    #[serde(default)]
+    pub trusted_peers: BTreeSet<String>, // peer IPs the user marked as trusted
+    #[serde(default = "default_theme")]
+    pub theme: String, // "classic" or "midnight", settings page
+    #[serde(default = "default_chat_rows")]
+    pub chat_rows: u32, // messages kept in the chat view, older ones stay in the history
+    #[serde(default = "default_channel_idle_minutes")]
+    pub channel_idle_minutes: u32, // secure channels with no activity this long close, 0 = never (see channel_idle.rs)
+    #[serde(default = "default_parallel_downloads")]
+    pub max_parallel_downloads: u32, // downloads running at once, the rest wait in download_queue.rs
+    #[serde(default)]
+    pub file_server_port: Option<u16>, // TCP file server, none = 3001. Busy at startup = one the OS picks (/fileport)
+    #[serde(default)]
+    pub status_port: Option<u16>, // /health + /metrics on 127.0.0.1, none = off (/status, see status_endpoint.rs)
+    #[serde(default)]
+    pub serial_bridge: Option<SerialBridge>, // public channel mirrored to a serial port, none = off (/serial)
+    #[serde(default)]
+    pub clipboard_sync: ClipboardSync, // clipboard sharing in secure channels (/clip), see clip_sync.rs
+    #[serde(default)]
+    pub sign_transcripts: bool, // as host, sign the secure channel transcript on close (/signlog)
+    #[serde(default)]
+    pub forward_secrecy: bool, // channels we host get a session key over X25519, not the PIN's. Off by default, older desktop builds can't join (/secrecy)
+    #[serde(default)]
+    pub port_sharing: PortSharing, // how the UDP chat port is bound, see main_helpers::bind_single_port_socket (/portshare)
+    #[serde(default)]
+    pub transfer: TransferTimeouts, // TCP file transfer timeouts, see file_transfer_protocol::configure
+    #[serde(default)]
+    pub rate_limit: RateLimit, // file transfer speed caps (/limit), see rate_limit.rs
+    #[serde(default)]
+    pub paired_devices: BTreeMap<String, PairedDevice>, // our own phones by device id, see pairing.rs
+    #[serde(default)]
+    pub rules: Vec<AutomationRule>, // local automation, see rules.rs
+}
+
+/// What starts an automation rule
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleTrigger {
+    PeerJoined, // a LanChGo install we didn't see this session registers over mDNS
+}
+
+/// "When <trigger>, send <message> to that peer only". {name} becomes the peer's nickname.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AutomationRule {
+    pub trigger: RuleTrigger,
+    pub message: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub trusted_only: bool, // only contacts marked trusted
+}
+
+/// Serial port the public channel is bridged to, see serial_bridge.rs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SerialBridge {
+    pub port: String, // "COM3", "/dev/ttyUSB0"
+    pub baud: u32,
+}
+
+/// Binding the UDP chat port next to other sockets on it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PortSharing {
+    #[default]
+    Default,   // whatever the OS does for a plain bind
+    Shared,    // SO_REUSEADDR: several instances (profiles) or other tools on the same port
+    Exclusive, // nobody binds it after us, SO_EXCLUSIVEADDRUSE on Windows
+}
+
+/// Clipboard sharing inside a secure channel, see clip_sync.rs
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ClipboardSync {
+    pub auto: bool,    // send every clipboard change, not only on /clip send
+    pub confirm: bool, // received text waits for /clip accept
+    pub max_kib: u32,  // bigger clipboards aren't sent or applied
+}
+
+impl ClipboardSync {
+    pub const DEFAULT: Self = Self { auto: false, confirm: true, max_kib: 64 };
+}
+
+impl Default for ClipboardSync {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+fn default_own_language() -> String {
+    "en".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_theme() -> String {
+    "classic".to_string()
+}
+
+fn default_chat_rows() -> u32 {
+    10
+}
+
+fn default_channel_idle_minutes() -> u32 {
+    30
+}
+
+fn default_auto_accept_max_mb() -> u32 {
+    2048
+}
+
+fn default_parallel_downloads() -> u32 {
+    2
+}
+
+fn default_room() -> String {
+    crate::rooms::DEFAULT_ROOM.to_string()
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InterfacesInfo {
     pub name: String,
     pub address_to_broadcast: String,
+    pub ipv4_address: String,
     pub status: String,
+    pub ipv6_addresses: Vec<String>,
 }
 
 //#[derive(Clone)]
@@ -0,0 +1,157 @@
+// Clipboard sharing between our machines in a secure channel (/clip). "/clip send" sends the
+// clipboard text once, "/clip auto on" sends every change. ECLP + bincode(SecureMessage) of
+// CLIP_PREFIX + text, sealed with the channel key like EHBT. Received text is applied to the
+// clipboard right away, or waits for "/clip accept" while Config.clipboard_sync.confirm is on.
+// Anything over max_kib is refused on both ends.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::classes::{ClipboardSync, Config};
+use crate::secure_channel_code::{self, SecureMessage};
+use crate::{main_helpers, peers, AppWindow};
+
+pub const ECLP_MAGIC: &[u8; 4] = b"ECLP";
+const CLIP_PREFIX: &[u8] = b"LANCHGO_CLIP:";
+const POLL_EVERY: Duration = Duration::from_secs(1);
+
+static AUTO: AtomicBool = AtomicBool::new(false);
+static CONFIRM: AtomicBool = AtomicBool::new(true);
+static MAX_KIB: AtomicU32 = AtomicU32::new(ClipboardSync::DEFAULT.max_kib);
+
+// the clipboard as we last sent or applied it, the watcher must not send that back
+static LAST: Mutex<Option<String>> = Mutex::new(None);
+// received while confirm is on, waiting for /clip accept
+static PENDING: Mutex<Option<(String, String)>> = Mutex::new(None); // (from, text)
+
+/// Config.clipboard_sync, the next clipboard change or incoming clip follows it
+pub fn configure(cfg: &Config) {
+    AUTO.store(cfg.clipboard_sync.auto, Ordering::Relaxed);
+    CONFIRM.store(cfg.clipboard_sync.confirm, Ordering::Relaxed);
+    MAX_KIB.store(cfg.clipboard_sync.max_kib.max(1), Ordering::Relaxed);
+}
+
+/// "/clip" without arguments
+pub fn summary(cfg: &Config) -> String {
+    let sync = &cfg.clipboard_sync;
+    format!(
+        "📋 Clipboard: auto-sync {}, {}, up to {} KB",
+        if sync.auto { "on" } else { "off" },
+        if sync.confirm { "asks before applying" } else { "applies right away" },
+        sync.max_kib
+    )
+}
+
+fn too_big(text: &str) -> bool {
+    text.len() > MAX_KIB.load(Ordering::Relaxed) as usize * 1024
+}
+
+/// The ECLP packet for `text`, an error to show when it can't go
+pub fn packet(text: &str) -> Result<Vec<u8>, String> {
+    let Some(channel) = secure_channel_code::get_active_channel() else {
+        return Err("📋 Clipboard sharing works inside a secure channel".into());
+    };
+    if text.is_empty() {
+        return Err("📋 Nothing on the clipboard".into());
+    }
+    if too_big(text) {
+        return Err(format!("📋 Clipboard is over {} KB, /clip max to raise it", MAX_KIB.load(Ordering::Relaxed)));
+    }
+    let sealed = secure_channel_code::encrypt_bytes(&channel.key, &[CLIP_PREFIX, text.as_bytes()].concat());
+    let payload = bincode::serde::encode_to_vec(&sealed, bincode::config::standard()).map_err(|e| format!("❌ {}", e))?;
+    *LAST.lock().unwrap() = Some(text.to_string());
+    let mut packet = Vec::from(ECLP_MAGIC as &[u8]);
+    packet.extend_from_slice(&payload);
+    Ok(packet)
+}
+
+pub fn read_clipboard() -> Option<String> {
+    arboard::Clipboard::new().and_then(|mut c| c.get_text()).ok()
+}
+
+/// `bytes` after ECLP from a channel member: apply it, or keep it for /clip accept
+pub fn receive(ui_weak: &slint::Weak<AppWindow>, from: std::net::IpAddr, bytes: &[u8]) {
+    let Some(channel) = secure_channel_code::get_active_channel() else { return; };
+    let Ok((sealed, _)) = bincode::serde::decode_from_slice::<SecureMessage, _>(bytes, bincode::config::standard()) else { return; };
+    let Some(plain) = secure_channel_code::decrypt_bytes(&channel.key, &sealed) else { return; };
+    if !secure_channel_code::accept_nonce(&sealed.nonce) {
+        return;
+    }
+    let Some(text) = plain.strip_prefix(CLIP_PREFIX).and_then(|t| String::from_utf8(t.to_vec()).ok()) else { return; };
+    if too_big(&text) {
+        return;
+    }
+    let who = peers::label(from, None);
+    let confirm = CONFIRM.load(Ordering::Relaxed);
+    if confirm {
+        *PENDING.lock().unwrap() = Some((who.clone(), text.clone()));
+    }
+    let weak = ui_weak.clone();
+    let _ = slint::invoke_from_event_loop(move || {
+        let Some(app) = weak.upgrade() else { return; };
+        if confirm {
+            app.invoke_append_attention(format!("📋 {} shared their clipboard ({} characters), /clip accept to use it", who, text.chars().count()).into());
+        } else {
+            apply(&app, &who, text);
+        }
+    });
+}
+
+fn apply(app: &AppWindow, who: &str, text: String) {
+    match main_helpers::copy_to_clipboard(&text) {
+        Ok(()) => {
+            *LAST.lock().unwrap() = Some(text);
+            app.invoke_show_temp_message(format!("📋 Clipboard from {}", who).into());
+        }
+        Err(e) => app.invoke_show_temp_message(format!("❌ {}", e).into()),
+    }
+}
+
+/// "/clip accept": apply what's waiting, false when nothing is
+pub fn accept(app: &AppWindow) -> bool {
+    let Some((who, text)) = PENDING.lock().unwrap().take() else { return false; };
+    apply(app, &who, text);
+    true
+}
+
+/// Leaving the channel drops what's waiting
+pub fn reset() {
+    *PENDING.lock().unwrap() = None;
+    *LAST.lock().unwrap() = None;
+}
+
+/// Auto-sync: while on and in a channel, send the clipboard whenever its text changes.
+/// `send` broadcasts a packet.
+pub fn start(channel_mode: Arc<Mutex<String>>, send: impl Fn(&[u8]) + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut clipboard: Option<arboard::Clipboard> = None;
+        let mut watching = false;
+        loop {
+            std::thread::sleep(POLL_EVERY);
+            let in_channel = matches!(channel_mode.lock().unwrap().as_str(), "host" | "joined");
+            if !AUTO.load(Ordering::Relaxed) || !in_channel {
+                watching = false;
+                continue;
+            }
+            // kept open between polls, opening one every second is slow on some desktops
+            if clipboard.is_none() {
+                clipboard = arboard::Clipboard::new().ok();
+            }
+            let Some(Ok(text)) = clipboard.as_mut().map(|c| c.get_text()) else { continue; };
+            // only what's copied after auto-sync came on goes out, not what was already there
+            if !std::mem::replace(&mut watching, true) {
+                *LAST.lock().unwrap() = Some(text);
+                continue;
+            }
+            if LAST.lock().unwrap().as_deref() == Some(text.as_str()) {
+                continue;
+            }
+            match packet(&text) {
+                Ok(packet) => send(&packet),
+                // don't retry the same oversized text every second
+                Err(_) => *LAST.lock().unwrap() = Some(text),
+            }
+        }
+    });
+}
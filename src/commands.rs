@@ -0,0 +1,1191 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use slint::{ComponentHandle, SharedString, VecModel};
+
+use crate::classes::{AutomationRule, BroadcastState, PortSharing, RuleTrigger, SerialBridge, SharedSocket};
+use crate::contacts::Trust;
+use crate::file_transfer_protocol::{self, OfferRegistry};
+use crate::outbox::Outbox;
+use crate::redact::Redactor;
+use crate::settings::SettingsStore;
+use crate::{auto_download, bandwidth, channel_idle, clip_sync, contacts, diagnostics, download_queue, heartbeat, history, identity, main_helpers, mute, net_runtime, pairing, peers, ping, rate_limit, records, resume, rules, secure_channel_code, serial_bridge, session_bundle, status_endpoint, tcp_file_client, tcp_file_server, transcript, transfers, web_app, AppWindow, ChatMessageItem, FileOfferItem};
+
+/// A built-in slash command: /help, the command picker and dispatch all read this
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub args: &'static str, // usage hint, empty for commands that take none
+    pub help: &'static str,
+    pub confirm: bool,      // destructive, needs Enter twice
+    pub network: bool,      // reaches peers or opens a port, everything else stays on this machine
+    pub run: Handler,
+}
+
+/// What a handler may touch, borrowed from the send button for the duration of one command
+pub struct CommandContext<'a> {
+    pub app: &'a AppWindow,
+    pub settings: &'a SettingsStore,
+    pub sock: &'a Arc<SharedSocket>,
+    pub state: &'a Arc<BroadcastState>,
+    pub outbox: &'a Arc<Outbox>,
+    pub aliases: &'a BTreeMap<String, String>,
+    pub chat_model: &'a Rc<VecModel<ChatMessageItem>>,
+    pub file_offer_model: &'a Rc<VecModel<FileOfferItem>>,
+    pub offer_registry: &'a Arc<Mutex<OfferRegistry>>,
+    pub running: &'a AtomicBool,
+}
+
+/// Gets whatever followed the command name, trimmed
+pub type Handler = fn(&CommandContext, &str);
+
+const fn cmd(name: &'static str, args: &'static str, help: &'static str, run: Handler) -> CommandSpec {
+    CommandSpec { name, args, help, confirm: false, network: false, run }
+}
+
+impl CommandSpec {
+    const fn confirm(mut self) -> Self {
+        self.confirm = true;
+        self
+    }
+
+    const fn network(mut self) -> Self {
+        self.network = true;
+        self
+    }
+}
+
+// order here is the order of /help and the picker
+pub const COMMANDS: &[CommandSpec] = &[
+    cmd("/info", "", "Show app information", info),
+    cmd("/rescale", "", "Rescales the app's UI", rescale),
+    cmd("/help", "", "Show this help message", help),
+    cmd("/settings", "", "Opens the settings menu", open_settings),
+    cmd("/sendfolder", "", "Offer a whole folder, subfolders included", send_folder).network(),
+    cmd("/downloads", "", "Opens the download folder", downloads),
+    cmd("/clear", "", "Clear chat messages", clear),
+    cmd("/clearfiles", "", "Stop transfers, withdraw your offers, clear the files panel", clear_files).network(),
+    cmd("/clearall", "", "Clear chat, stop transfers and withdraw your offers", clear_all).confirm().network(),
+    cmd("/disconnect", "", "Disconnect from secure channel", disconnect).network(),
+    cmd("/webjoin", "", "Starts a web companion session", web_join).network(),
+    cmd("/webstop", "", "Stops a web companion session", web_stop).network(),
+    cmd("/langtags", "", "Toggle language tags on messages", lang_tags),
+    cmd("/translate", "", "Toggle glossary translation of tagged messages", translate),
+    cmd("/search", "term", "Search the chat history", search),
+    cmd("/nick", "[name]", "Show or set your @mention nickname", nick),
+    cmd("/device", "[name]", "Show or set this computer's name, shown after your nickname", device),
+    cmd("/mentions", "", "Toggle alerts only for @mentions", mentions),
+    cmd("/room", "[name]", "Switch chat room", room),
+    cmd("/whois", "name|ip", "Show what we know about a peer", whois),
+    cmd("/ping", "name|ip", "Ping one peer, shows the round trip time", ping).network(),
+    cmd("/mute", "[name|ip] [minutes]", "Hold a peer's messages in one collapsed row, an hour by default", mute_peer),
+    cmd("/unmute", "name|ip", "End a mute early", unmute_peer),
+    cmd("/push", "name|ip", "Send files straight to one peer, they accept or deny", push).network(),
+    cmd("/snippet", "[language]", "Share the clipboard text as a code block peers can expand and copy", snippet).network(),
+    cmd("/autodl", "[mb|off|trusted|all]", "Auto-download small files", auto_dl),
+    cmd("/limit", "[up|down] [KB/s|off]", "Cap file transfer speed so the chat stays responsive", limit),
+    cmd("/fileport", "[port|auto]", "File server port, takes effect on the next start", file_port),
+    cmd("/portshare", "[default|shared|exclusive]", "How the chat port is bound: shared lets several instances or other tools use it", port_share),
+    cmd("/trust", "[ip|name]", "List trusted peers or toggle one", trust),
+    cmd("/autoaccept", "[on [MB]|off]", "Download what trusted contacts offer, up to a cap (2048 MB)", auto_accept),
+    cmd("/contacts", "", "Known identities: trust, notes, remove", contact_book),
+    cmd("/welcome", "[text|off]", "Message new peers get from you, {name} = theirs", welcome),
+    cmd("/pair", "", "Pair your phone: trusted, encrypted, no prompts", pair).network(),
+    cmd("/unpair", "[name]", "List paired phones or forget one", unpair),
+    cmd("/idleclose", "[minutes|off]", "Close secure channels nobody used for this long", idle_close),
+    cmd("/clip", "[send|accept|auto on|off|confirm on|off|max KB]", "Share clipboard text with the secure channel", clip),
+    cmd("/signlog", "", "Toggle signing the secure channel transcript when you close it as host", sign_log),
+    cmd("/secrecy", "", "Toggle a fresh X25519 session key for channels you host (older builds can't join while it's on)", secrecy),
+    cmd("/verifylog", "file", "Check a saved transcript", verify_log),
+    cmd("/stats", "", "Show data used this session", stats),
+    cmd("/status", "[port|off]", "Serve /health and /metrics on localhost for monitoring", status),
+    cmd("/serial", "[port [baud]|off]", "Bridge the public channel to a serial port for UART-only devices", serial),
+    cmd("/export", "[redact [names]]", "Save this session's peers and transfers as CSV in the download folder", export),
+    cmd("/exportchat", "[redact [names]]", "Save the chat history as text in the download folder", export_chat),
+    cmd("/exportlog", "[redact [names]]", "Save diagnostics.log in the download folder, redact hides IPs and names", export_log),
+    cmd("/exportsession", "", "Save channel key, contacts and settings to an encrypted file for another machine", export_session),
+    cmd("/importsession", "", "Load a file from /exportsession: settings, contacts, and join its channel", import_session),
+    cmd("/restart", "", "Restarts the app ( might not always re-open )", restart).confirm(),
+    cmd("/exit", "", "Exit LanChGo", exit).confirm(),
+];
+
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    find(name).is_some()
+}
+
+/// Run the input if it is a built-in command, false means it's a chat message.
+/// Commands without arguments only match on their own ("/clear that" is sent as text).
+pub fn dispatch(ctx: &CommandContext, input: &str) -> bool {
+    let input = input.trim();
+    let (name, args) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let args = args.trim();
+    let Some(spec) = find(name) else { return false; };
+    if spec.args.is_empty() && !args.is_empty() {
+        return false;
+    }
+    (spec.run)(ctx, args);
+    ctx.app.set_input_text("".into());
+    true
+}
+
+// the second Enter has to come within this window
+const CONFIRM_WINDOW: Duration = Duration::from_secs(5);
+
+/// "Press Enter again" state for commands marked `confirm`
+#[derive(Default)]
+pub struct ConfirmGate {
+    pending: Option<(&'static str, Instant)>,
+}
+
+impl ConfirmGate {
+    /// true when the input may run now, false means ask the user to confirm first
+    pub fn check(&mut self, input: &str) -> bool {
+        let Some(spec) = find(input.trim()).filter(|c| c.confirm) else {
+            self.pending = None; // anything else cancels a pending confirmation
+            return true;
+        };
+        match self.pending.take() {
+            Some((name, at)) if name == spec.name && at.elapsed() < CONFIRM_WINDOW => true,
+            _ => {
+                self.pending = Some((spec.name, Instant::now()));
+                false
+            }
+        }
+    }
+}
+
+/// Expand a user alias before dispatch. One level only, so an alias can't loop into itself.
+pub fn resolve_alias(input: &str, aliases: &BTreeMap<String, String>) -> String {
+    let trimmed = input.trim();
+    aliases
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(trimmed))
+        .map(|(_, target)| target.clone())
+        .unwrap_or_else(|| input.to_string())
+}
+
+/// Alias names look like commands and must not hide a built-in one
+pub fn validate_alias(name: &str, target: &str) -> Result<(), String> {
+    let name = name.trim();
+    if !name.starts_with('/') || name.len() < 2 || name.contains(char::is_whitespace) {
+        return Err("Alias must look like /name (no spaces)".into());
+    }
+    if is_builtin(name) {
+        return Err(format!("{} is a built-in command", name));
+    }
+    if target.trim().is_empty() {
+        return Err("Alias needs a command or a message".into());
+    }
+    Ok(())
+}
+
+pub fn help_text(aliases: &BTreeMap<String, String>) -> String {
+    let mut out = String::from("Available Commands\n\n");
+    for c in COMMANDS {
+        let usage = if c.args.is_empty() { c.name.to_string() } else { format!("{} {}", c.name, c.args) };
+        let note = match (c.confirm, c.network) {
+            (true, _) => " (asks to confirm)",
+            (false, true) => " (uses the network)",
+            _ => "",
+        };
+        out.push_str(&format!("        {:<20} {}{}\n", usage, c.help, note));
+    }
+
+    if !aliases.is_empty() {
+        out.push_str("\n        Your aliases:\n");
+        for (name, target) in aliases {
+            out.push_str(&format!("        {:<20} → {}\n", name, target));
+        }
+    }
+
+    out.push_str("\n        Tip:\n        Commands are never sent as chat messages.");
+    out
+}
+
+/// Rows for the command picker: built-ins then aliases
+pub fn picker_entries(aliases: &BTreeMap<String, String>) -> Vec<SharedString> {
+    COMMANDS
+        .iter()
+        .map(|c| SharedString::from(c.name))
+        .chain(aliases.keys().map(|a| SharedString::from(a.as_str())))
+        .collect()
+}
+
+/// One line summary for the settings screen
+pub fn alias_summary(aliases: &BTreeMap<String, String>) -> String {
+    if aliases.is_empty() {
+        return "No aliases yet".into();
+    }
+    aliases.iter().map(|(n, t)| format!("{} → {}", n, t)).collect::<Vec<_>>().join("   ")
+}
+
+// ===================== handlers =====================
+
+fn info(ctx: &CommandContext, _: &str) {
+    ctx.app.invoke_append_message(main_helpers::info_message().into());
+}
+
+fn rescale(ctx: &CommandContext, _: &str) {
+    let current = ctx.app.get_global_scale();
+    let next = if current > 0.90 { 0.85 }
+        else if current > 0.80 { 0.75 }
+        else { 1.0 };
+    ctx.app.set_global_scale(next);
+    ctx.app.invoke_show_temp_message(format!("🔎 UI scale set to {:.2}", next).into());
+    ctx.settings.update(|cfg| cfg.ui_scale = Some(next));
+}
+
+fn help(ctx: &CommandContext, _: &str) {
+    ctx.app.invoke_append_message(help_text(ctx.aliases).into());
+}
+
+fn open_settings(ctx: &CommandContext, _: &str) {
+    ctx.app.set_show_settings(true);
+}
+
+// public chat only, phones and channel members get single files
+fn send_folder(ctx: &CommandContext, _: &str) {
+    if secure_channel_code::get_active_channel().is_some() {
+        ctx.app.invoke_show_temp_message("📁 Folders can be offered in public mode only".into());
+        return;
+    }
+    let Some(folder) = file_transfer_protocol::pick_folder() else { return; };
+    let packet = {
+        let mut reg = ctx.offer_registry.lock().unwrap();
+        file_transfer_protocol::build_foft_packet_for_folder(&folder, &mut reg)
+    };
+    match packet {
+        Ok(packet) if crate::broadcast_the_msg(ctx.sock, ctx.state, &packet).is_ok() => {
+            ctx.app.invoke_show_temp_message("📤 Folder offer broadcasted".into());
+        }
+        Ok(_) => ctx.app.invoke_show_popupmsg(),
+        Err(e) => ctx.app.invoke_show_temp_message(format!("❌ {}", e).into()),
+    }
+}
+
+fn downloads(ctx: &CommandContext, _: &str) {
+    match main_helpers::open_download_folder_from_config(&ctx.settings.shared()) {
+        Ok(()) => ctx.app.invoke_show_temp_message("📁 Download folder opened".into()),
+        Err(e) => ctx.app.invoke_show_temp_message(format!("❌ {}", e).into()),
+    }
+}
+
+fn clear(ctx: &CommandContext, _: &str) {
+    ctx.chat_model.set_vec(Vec::new());
+}
+
+// stop running downloads / uploads, tell peers our offers are gone, then empty the panel
+fn withdraw_and_clear_files(ctx: &CommandContext) {
+    let dropped = download_queue::clear(); // first, or cancelled downloads hand their slots on
+    let cancelled = transfers::cancel_all();
+    let own: Vec<[u8; 16]> = ctx.offer_registry.lock().unwrap().keys().copied().collect();
+    for offer_id in &own {
+        let _ = crate::broadcast_the_msg(ctx.sock, ctx.state, &transfers::withdraw_packet(offer_id));
+    }
+    main_helpers::cleanup_file_offers(ctx.offer_registry, Some(ctx.file_offer_model));
+    let paused = resume::discard_paused();
+
+    let mut done = Vec::new();
+    if cancelled > 0 {
+        done.push(format!("{} transfer(s) stopped", cancelled));
+    }
+    if paused > 0 {
+        done.push(format!("{} paused download(s) dropped", paused));
+    }
+    if dropped > 0 {
+        done.push(format!("{} queued download(s) dropped", dropped));
+    }
+    if !own.is_empty() {
+        done.push(format!("{} offer(s) withdrawn", own.len()));
+    }
+    if !done.is_empty() {
+        ctx.app.invoke_show_temp_message(format!("🧹 {}", done.join(", ")).into());
+    }
+}
+
+fn clear_files(ctx: &CommandContext, _: &str) {
+    withdraw_and_clear_files(ctx);
+}
+
+fn clear_all(ctx: &CommandContext, _: &str) {
+    ctx.chat_model.set_vec(Vec::new());
+    withdraw_and_clear_files(ctx);
+}
+
+fn disconnect(ctx: &CommandContext, _: &str) {
+    ctx.app.invoke_disconnect_channel();
+}
+
+fn web_join(ctx: &CommandContext, _: &str) {
+    match web_app::start_web_server() {
+        Ok(()) => {
+            ctx.app.set_web_session_active(true);
+            main_helpers::update_ui_qr_only(ctx.app);
+            if let Some(url) = web_app::get_url_to_main() {
+                ctx.app.set_url_link(url.into());
+            }
+            ctx.app.invoke_show_temp_message("🌐 Web session started".into());
+        }
+        Err(e) => ctx.app.invoke_show_temp_message(format!("❌ {e}").into()),
+    }
+}
+
+fn web_stop(ctx: &CommandContext, _: &str) {
+    match web_app::stop_web_server() {
+        Ok(()) => {
+            ctx.app.set_web_session_active(false);
+            ctx.app.invoke_show_temp_message("🛑 Web session stopped".into());
+        }
+        Err(e) => ctx.app.invoke_show_temp_message(format!("❌ {e}").into()),
+    }
+}
+
+fn lang_tags(ctx: &CommandContext, _: &str) {
+    let on = ctx.settings.update(|cfg| {
+        cfg.language_tags = !cfg.language_tags;
+        cfg.language_tags
+    });
+    ctx.app.invoke_show_temp_message(if on { "🏷️ Language tags on".into() } else { "🏷️ Language tags off".into() });
+}
+
+fn translate(ctx: &CommandContext, _: &str) {
+    let on = ctx.settings.update(|cfg| {
+        cfg.glossary_translate = !cfg.glossary_translate;
+        // translation needs to know the language first
+        if cfg.glossary_translate {
+            cfg.language_tags = true;
+        }
+        cfg.glossary_translate
+    });
+    ctx.app.invoke_show_temp_message(if on { "🌐 Glossary translation on".into() } else { "🌐 Glossary translation off".into() });
+}
+
+fn search(ctx: &CommandContext, term: &str) {
+    if term.is_empty() {
+        ctx.app.invoke_show_temp_message("🔎 Usage: /search <term>".into());
+    } else {
+        ctx.app.invoke_search_history(term.into());
+    }
+}
+
+// "/nick" shows the current nickname, "/nick name" changes it
+fn nick(ctx: &CommandContext, name: &str) {
+    let name = name.trim_start_matches('@');
+    if name.is_empty() {
+        let nickname = ctx.settings.read(|cfg| cfg.nickname.clone());
+        ctx.app.invoke_show_temp_message(format!("🏷️ Peers mention you as @{}", nickname).into());
+    } else if name.contains(char::is_whitespace) {
+        ctx.app.invoke_show_temp_message("⚠️ Nicknames can't contain spaces".into());
+    } else {
+        ctx.settings.update(|cfg| cfg.nickname = name.to_string());
+        ctx.app.invoke_show_temp_message(format!("🏷️ Peers can now mention you as @{}", name).into());
+    }
+}
+
+// "/device" shows the name peers see for this machine, "/device laptop" changes it
+fn device(ctx: &CommandContext, name: &str) {
+    let name: String = name.chars().filter(|c| !c.is_control()).take(32).collect();
+    if name.trim().is_empty() {
+        let device = ctx.settings.read(|cfg| cfg.device_name.clone());
+        let nickname = ctx.settings.read(|cfg| cfg.nickname.clone());
+        ctx.app.invoke_show_temp_message(format!("💻 Peers see you as {} ({})", nickname, device).into());
+    } else {
+        let name = name.trim().to_string();
+        ctx.settings.update(|cfg| cfg.device_name = name.clone());
+        ctx.app.invoke_show_temp_message(format!("💻 This computer is now called {}", name).into());
+    }
+}
+
+fn mentions(ctx: &CommandContext, _: &str) {
+    let on = ctx.settings.update(|cfg| {
+        cfg.mentions_only = !cfg.mentions_only;
+        cfg.mentions_only
+    });
+    ctx.app.set_mentions_only(on);
+    ctx.app.invoke_show_temp_message(if on { "🔔 Alerts only for @mentions".into() } else { "🔔 All alerts on".into() });
+}
+
+// "/room" alone opens the switcher, "/room name" goes straight there
+fn room(ctx: &CommandContext, name: &str) {
+    if name.is_empty() {
+        ctx.app.invoke_open_rooms();
+    } else {
+        ctx.app.invoke_switch_room(name.into());
+    }
+}
+
+fn whois(ctx: &CommandContext, query: &str) {
+    if query.is_empty() {
+        ctx.app.invoke_show_temp_message("👤 Usage: /whois <name|ip>".into());
+        return;
+    }
+    let Some(peer) = peers::whois(query) else {
+        ctx.app.invoke_show_temp_message(format!("👤 No peer called {} seen", query).into());
+        return;
+    };
+    let addresses = peer.addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+    let mode = match peer.hosting {
+        Some(true) => "hosting a secure channel",
+        Some(false) => "public",
+        None => "unknown",
+    };
+    let seen = match peer.last_seen.map(|d| d.as_secs()) {
+        None => "unknown".to_string(),
+        Some(s) if s < 10 => "just now".to_string(),
+        Some(s) if s < 60 => format!("{}s ago", s),
+        Some(s) if s < 3600 => format!("{}m ago", s / 60),
+        Some(s) => format!("{}h ago", s / 3600),
+    };
+    let key = match &peer.identity {
+        Some(key) if peer.addresses.iter().any(|ip| identity::proven_key(*ip).as_deref() == Some(key.as_str())) => {
+            format!("{} ({})", identity::fingerprint(key), contacts::trust(key).as_str())
+        }
+        Some(key) => format!("{} (unproven)", identity::fingerprint(key)),
+        None => "unknown".to_string(),
+    };
+    let features = match peer.caps {
+        None => "unknown".to_string(),
+        Some(caps) => {
+            let names: Vec<&str> = [(peers::CAP_FRAGMENTS, "long messages"), (peers::CAP_THUMBNAILS, "previews"), (peers::CAP_PUSH, "/push")]
+                .into_iter()
+                .filter(|(cap, _)| caps & cap != 0)
+                .map(|(_, name)| name)
+                .collect();
+            if names.is_empty() { "basic chat and files".to_string() } else { names.join(", ") }
+        }
+    };
+    let info = format!(
+        "👤 {}\n        Nickname:  {}\n        Device:    {}\n        Key:       {}\n        IP:        {}\n        Host:      {}\n        Version:   {}\n        Supports:  {}\n        Channel:   {}\n        Last seen: {}",
+        peer.name,
+        peer.nickname.as_deref().unwrap_or("unknown"),
+        peer.device.as_deref().unwrap_or("unknown"),
+        key,
+        addresses,
+        peer.hostname.as_deref().unwrap_or("unknown"),
+        peer.version.as_deref().unwrap_or("unknown"),
+        features,
+        mode,
+        seen,
+    );
+    ctx.app.invoke_append_message(info.into());
+}
+
+// "/mute" lists, "/mute sam" mutes for an hour, "/mute sam 15" for 15 minutes
+fn mute_peer(ctx: &CommandContext, arg: &str) {
+    if arg.is_empty() {
+        let muted = mute::list();
+        let text = if muted.is_empty() {
+            "🔇 Nobody is muted".to_string()
+        } else {
+            let names: Vec<String> = muted.iter().map(|(ip, left)| format!("{} ({} min left)", peers::label(*ip, None), left.as_secs().div_ceil(60))).collect();
+            format!("🔇 Muted: {}", names.join(", "))
+        };
+        ctx.app.invoke_show_temp_message(text.into());
+        return;
+    }
+    let (query, minutes) = match arg.rsplit_once(' ').map(|(query, m)| (query.trim(), m.parse::<u64>())) {
+        Some((query, Ok(m))) => (query, m),
+        _ => (arg, mute::DEFAULT.as_secs() / 60),
+    };
+    if minutes == 0 {
+        ctx.app.invoke_show_temp_message("🔇 Usage: /mute <name|ip> [minutes]".into());
+        return;
+    }
+    let Some(peer) = peers::whois(query) else {
+        ctx.app.invoke_show_temp_message(format!("🔇 No peer called {} seen", query).into());
+        return;
+    };
+    for ip in &peer.addresses {
+        mute::mute(*ip, std::time::Duration::from_secs(minutes * 60));
+    }
+    ctx.app.invoke_show_temp_message(format!("🔇 {} muted for {} min", peer.name, minutes).into());
+}
+
+fn unmute_peer(ctx: &CommandContext, query: &str) {
+    if query.is_empty() {
+        ctx.app.invoke_show_temp_message("🔔 Usage: /unmute <name|ip>".into());
+        return;
+    }
+    let Some(peer) = peers::whois(query) else {
+        ctx.app.invoke_show_temp_message(format!("🔔 No peer called {} seen", query).into());
+        return;
+    };
+    let was_muted = peer.addresses.iter().filter(|ip| mute::unmute(**ip)).count() > 0;
+    let text = if was_muted { format!("🔔 {} is no longer muted", peer.name) } else { format!("🔔 {} wasn't muted", peer.name) };
+    ctx.app.invoke_show_temp_message(text.into());
+}
+
+fn ping(ctx: &CommandContext, query: &str) {
+    if query.is_empty() {
+        ctx.app.invoke_show_temp_message("🏓 Usage: /ping <name|ip>".into());
+        return;
+    }
+    let Some(peer) = peers::whois(query) else {
+        ctx.app.invoke_show_temp_message(format!("🏓 No peer called {} seen", query).into());
+        return;
+    };
+    // IPv4 first, a v6 link-local address would need its scope id
+    let Some(ip) = peer.addresses.iter().find(|a| a.is_ipv4()).or(peer.addresses.first()).copied() else { return; };
+    let target = std::net::SocketAddr::new(ip, peer.port.unwrap_or_else(|| ctx.state.get_port()));
+
+    let nickname = ctx.settings.read(|cfg| cfg.nickname.clone());
+    let (id, packet) = ping::request_packet(&nickname, &peer.name);
+    let sent = if ip.is_ipv4() { ctx.sock.send_to(&packet, target) } else { ctx.sock.send_to_v6(&packet, target).unwrap_or(Err(std::io::ErrorKind::Unsupported.into())) };
+    if let Err(e) = sent {
+        ping::take_timed_out(id);
+        ctx.app.invoke_show_temp_message(format!("❌ Ping failed: {}", e).into());
+        return;
+    }
+
+    let weak = ctx.app.as_weak();
+    std::thread::spawn(move || {
+        std::thread::sleep(ping::PONG_TIMEOUT);
+        let Some(target) = ping::take_timed_out(id) else { return; };
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak.upgrade() {
+                app.invoke_append_message(format!("🏓 no pong from {}", target).into());
+            }
+        });
+    });
+}
+
+// "/snippet" shares what's on the clipboard, "/snippet rust" names the language instead of guessing
+fn snippet(ctx: &CommandContext, lang: &str) {
+    let lang = lang.to_ascii_lowercase();
+    if lang.len() > 16 || !lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '#') {
+        ctx.app.invoke_show_temp_message("🧩 Usage: /snippet [language], shares the clipboard text".into());
+        return;
+    }
+    let text = arboard::Clipboard::new().and_then(|mut c| c.get_text()).unwrap_or_default();
+    if text.trim().is_empty() {
+        ctx.app.invoke_show_temp_message("🧩 Copy some text first, /snippet shares the clipboard".into());
+        return;
+    }
+    let room = ctx.app.get_current_room();
+    let toast = crate::share_snippet(ctx.app, ctx.sock, ctx.state, ctx.offer_registry, &room, &text, &lang);
+    ctx.app.invoke_show_temp_message(toast.into());
+}
+
+// "/push bob": pick files and send them to bob's file server, bob gets an accept / deny prompt
+fn push(ctx: &CommandContext, query: &str) {
+    if query.is_empty() {
+        ctx.app.invoke_show_temp_message("📤 Usage: /push <name|ip>".into());
+        return;
+    }
+    // the bytes go over plain TCP, channel members share files with the channel instead
+    if secure_channel_code::get_active_channel().is_some() {
+        ctx.app.invoke_show_temp_message("📤 Pushing files works in public mode only".into());
+        return;
+    }
+    let Some(peer) = peers::whois(query) else {
+        ctx.app.invoke_show_temp_message(format!("📤 No peer called {} seen", query).into());
+        return;
+    };
+    // IPv4 first, a v6 link-local address would need its scope id
+    let Some(ip) = peer.addresses.iter().find(|a| a.is_ipv4()).or(peer.addresses.first()).copied() else { return; };
+    if !peers::supports(ip, peers::CAP_PUSH) {
+        ctx.app.invoke_show_temp_message(format!("📤 {}'s LanChGo is too old to receive pushed files", peer.name).into());
+        return;
+    }
+    let target = std::net::SocketAddr::new(ip, peers::file_port(ip));
+    let Some(paths) = file_transfer_protocol::pick_files() else { return; };
+
+    let weak = ctx.app.as_weak();
+    let toast = move |text: String| {
+        let weak = weak.clone();
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak.upgrade() {
+                app.invoke_show_temp_message(text.into());
+            }
+        });
+    };
+    net_runtime::spawn(async move {
+        for path in paths {
+            let file = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            toast(format!("📤 Waiting for {} to accept {}…", peer.name, file));
+            let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            let res = transfers::cancellable(None, tcp_file_client::push_file(target, path)).await;
+            if !matches!(res, Ok(false)) {
+                records::transfer(true, Some(target.ip()), &file, size, records::outcome(&res));
+            }
+            toast(match res {
+                Ok(true) => format!("✅ {} received {}", peer.name, file),
+                Ok(false) => format!("🚫 {} declined {}", peer.name, file),
+                Err(e) if transfers::is_cancelled(&e) => return,
+                Err(e) => format!("❌ Sending {} to {} failed: {}", file, peer.name, e),
+            });
+        }
+    });
+}
+
+// "/autodl" shows the setting, "/autodl 5" sets the limit in MB, "off", "trusted" and "all" change it
+fn auto_dl(ctx: &CommandContext, arg: &str) {
+    let arg = arg.to_ascii_lowercase();
+    let summary = if arg.is_empty() {
+        Some(ctx.settings.read(auto_download::summary))
+    } else {
+        ctx.settings.update(|cfg| {
+            match arg.as_str() {
+                "off" => cfg.auto_download_mb = 0,
+                "trusted" => cfg.auto_download_trusted_only = true,
+                "all" => cfg.auto_download_trusted_only = false,
+                mb => cfg.auto_download_mb = mb.trim_end_matches("mb").trim().parse().ok()?,
+            }
+            Some(auto_download::summary(cfg))
+        })
+    };
+    match summary {
+        Some(summary) => ctx.app.invoke_show_temp_message(summary.into()),
+        None => ctx.app.invoke_show_temp_message("⬇️ Usage: /autodl 5 | off | trusted | all".into()),
+    }
+}
+
+// "/limit" shows the caps, "/limit 500" caps both ways in KB/s, "/limit up 500" or "/limit down off" one way
+fn limit(ctx: &CommandContext, arg: &str) {
+    let arg = arg.to_ascii_lowercase();
+    let (up, down, value) = match arg.split_once(' ') {
+        Some(("up", value)) => (true, false, value.trim()),
+        Some(("down", value)) => (false, true, value.trim()),
+        _ => (true, true, arg.as_str()),
+    };
+    let summary = if arg.is_empty() {
+        Some(ctx.settings.read(|cfg| rate_limit::summary(&cfg.rate_limit)))
+    } else {
+        let rate = match value {
+            "off" | "0" => Some(0),
+            kb => kb.trim_end_matches("kb/s").trim_end_matches("kb").trim().parse::<u64>().ok(),
+        };
+        rate.map(|rate| {
+            ctx.settings.update(|cfg| {
+                if up {
+                    cfg.rate_limit.upload_kib_per_sec = rate;
+                }
+                if down {
+                    cfg.rate_limit.download_kib_per_sec = rate;
+                }
+                rate_limit::summary(&cfg.rate_limit)
+            })
+        })
+    };
+    match summary {
+        Some(summary) => ctx.app.invoke_show_temp_message(summary.into()),
+        None => ctx.app.invoke_show_temp_message("🚦 Usage: /limit 500 | off | up 500 | down off".into()),
+    }
+}
+
+// "/fileport" shows the port in use, "/fileport 3005" or "/fileport auto" (3001) for the next start
+fn file_port(ctx: &CommandContext, arg: &str) {
+    let running = file_transfer_protocol::tcp_port();
+    let port = match arg.to_ascii_lowercase().as_str() {
+        "" => {
+            let wanted = ctx.settings.read(|cfg| cfg.file_server_port).unwrap_or(file_transfer_protocol::DEFAULT_TCP_PORT);
+            let note = if wanted == running { String::new() } else { format!(", {} was busy", wanted) };
+            ctx.app.invoke_show_temp_message(format!("📡 File server on port {}{}", running, note).into());
+            return;
+        }
+        "auto" | "default" => None,
+        p => match p.parse::<u16>() {
+            Ok(p) if p >= 1024 => Some(p),
+            _ => {
+                ctx.app.invoke_show_temp_message("📡 Usage: /fileport 1024-65535 | auto".into());
+                return;
+            }
+        },
+    };
+    ctx.settings.update(|cfg| cfg.file_server_port = port);
+    let port = port.unwrap_or(file_transfer_protocol::DEFAULT_TCP_PORT);
+    ctx.app.invoke_show_temp_message(format!("📡 File server moves to port {} on the next start (/restart)", port).into());
+}
+
+// "/portshare" shows the mode, "/portshare shared" rebinds the chat socket with SO_REUSEADDR
+fn port_share(ctx: &CommandContext, arg: &str) {
+    let sharing = match arg.to_ascii_lowercase().as_str() {
+        "" => {
+            let sharing = ctx.settings.read(|cfg| cfg.port_sharing);
+            ctx.app.invoke_show_temp_message(format!("🔌 UDP port {} bound {}", ctx.state.get_port(), format!("{:?}", sharing).to_lowercase()).into());
+            return;
+        }
+        "default" => PortSharing::Default,
+        "shared" => PortSharing::Shared,
+        "exclusive" => PortSharing::Exclusive,
+        _ => {
+            ctx.app.invoke_show_temp_message("🔌 Usage: /portshare default | shared | exclusive".into());
+            return;
+        }
+    };
+    let before = ctx.settings.read(|cfg| cfg.port_sharing);
+    ctx.settings.update(|cfg| cfg.port_sharing = sharing);
+    ctx.app.invoke_show_temp_message("🔌 Rebinding the port…".into());
+
+    // a rebind waits for the OS to let go of the old socket, up to a second or two
+    let (sock, state, settings, weak) = (Arc::clone(ctx.sock), Arc::clone(ctx.state), ctx.settings.clone(), ctx.app.as_weak());
+    std::thread::spawn(move || {
+        let toast = match main_helpers::rebind_to_state_port(&sock, &state, &settings.shared()) {
+            // broadcasts reach every instance on the port, direct replies only the one bound last
+            Ok(()) if sharing == PortSharing::Shared => "🔌 Port shared, other instances can bind it too (direct replies reach only one of them)".to_string(),
+            Ok(()) => format!("🔌 Port bound {}", format!("{:?}", sharing).to_lowercase()),
+            Err(e) => {
+                // the old way still works, keep it
+                settings.update(|cfg| cfg.port_sharing = before);
+                let _ = main_helpers::rebind_to_state_port(&sock, &state, &settings.shared());
+                format!("❌ Couldn't rebind the port: {}", e)
+            }
+        };
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak.upgrade() {
+                app.invoke_show_temp_message(toast.into());
+            }
+        });
+    });
+}
+
+// "/idleclose" shows the setting, "/idleclose 15" closes idle channels after 15 minutes, "off" never
+fn idle_close(ctx: &CommandContext, arg: &str) {
+    let minutes = match arg.to_ascii_lowercase().as_str() {
+        "" => None,
+        "off" | "0" => Some(0),
+        m => match m.trim_end_matches("min").trim().parse::<u32>() {
+            Ok(m) => Some(m),
+            Err(_) => {
+                ctx.app.invoke_show_temp_message("🧹 Usage: /idleclose 30 | off".into());
+                return;
+            }
+        },
+    };
+    let summary = match minutes {
+        None => ctx.settings.read(channel_idle::summary),
+        Some(m) => ctx.settings.update(|cfg| {
+            cfg.channel_idle_minutes = m;
+            channel_idle::summary(cfg)
+        }),
+    };
+    ctx.app.invoke_show_temp_message(summary.into());
+}
+
+// "/welcome" shows the rule, "/welcome off" drops it, anything else replaces it
+fn welcome(ctx: &CommandContext, arg: &str) {
+    let summary = if arg.is_empty() {
+        ctx.settings.read(rules::summary)
+    } else {
+        ctx.settings.update(|cfg| {
+            cfg.rules.retain(|r| r.trigger != RuleTrigger::PeerJoined);
+            if !arg.eq_ignore_ascii_case("off") {
+                cfg.rules.push(AutomationRule { trigger: RuleTrigger::PeerJoined, message: arg.to_string(), enabled: true, trusted_only: false });
+            }
+            rules::summary(cfg)
+        })
+    };
+    ctx.app.invoke_show_temp_message(summary.into());
+}
+
+// "/trust" lists trusted peers, "/trust ip" adds or removes one
+fn trust(ctx: &CommandContext, arg: &str) {
+    if arg.is_empty() {
+        let trusted = ctx.settings.read(|cfg| cfg.trusted_peers.iter().cloned().collect::<Vec<_>>());
+        if trusted.is_empty() {
+            ctx.app.invoke_show_temp_message("🤝 No trusted peers yet (/trust 192.168.1.20)".into());
+        } else {
+            ctx.app.invoke_show_temp_message(format!("🤝 Trusted: {}", trusted.join(", ")).into());
+        }
+    } else if let Ok(ip) = arg.parse::<std::net::IpAddr>() {
+        let ip = ip.to_string();
+        let added = ctx.settings.update(|cfg| if cfg.trusted_peers.remove(&ip) { false } else { cfg.trusted_peers.insert(ip.clone()) });
+        ctx.app.invoke_show_temp_message(if added { format!("🤝 {} is now trusted", ip).into() } else { format!("🤝 {} is no longer trusted", ip).into() });
+    } else if let Some(contact) = contacts::find(arg) {
+        // a contact's trust follows its identity key, wherever its IP moves
+        let trust = if contact.trust == Trust::Trusted { Trust::Unknown } else { Trust::Trusted };
+        contacts::set_trust(&contact.key, trust);
+        let text = if trust == Trust::Trusted { format!("🤝 {} is now trusted", contact.nickname) } else { format!("🤝 {} is no longer trusted", contact.nickname) };
+        ctx.app.invoke_show_temp_message(text.into());
+    } else {
+        ctx.app.invoke_show_temp_message(format!("⚠️ No contact called {}, /trust takes a name or an IP address", arg).into());
+    }
+}
+
+// "/autoaccept" shows the setting, "on" / "off" change it, "on 500" sets the cap too
+fn auto_accept(ctx: &CommandContext, arg: &str) {
+    let arg = arg.to_ascii_lowercase();
+    let (switch, cap) = arg.split_once(' ').unwrap_or((arg.as_str(), ""));
+    let cap = match cap.trim().trim_end_matches("mb").trim() {
+        "" => None,
+        mb => match mb.parse::<u32>() {
+            Ok(mb) if mb > 0 => Some(mb),
+            _ => {
+                ctx.app.invoke_show_temp_message("🤝 Usage: /autoaccept on [MB] | off".into());
+                return;
+            }
+        },
+    };
+    let summary = match switch {
+        "" => ctx.settings.read(auto_download::accept_summary),
+        "on" | "off" => ctx.settings.update(|cfg| {
+            cfg.auto_accept_trusted = switch == "on";
+            if let Some(mb) = cap {
+                cfg.auto_accept_max_mb = mb;
+            }
+            auto_download::accept_summary(cfg)
+        }),
+        _ => "🤝 Usage: /autoaccept on [MB] | off".into(),
+    };
+    ctx.app.invoke_show_temp_message(summary.into());
+}
+
+fn contact_book(ctx: &CommandContext, _: &str) {
+    ctx.app.invoke_open_contacts();
+}
+
+fn pair(ctx: &CommandContext, _: &str) {
+    let Some(ip) = main_helpers::get_local_ipv4() else {
+        ctx.app.invoke_show_temp_message("⚠️ No network to pair over".into());
+        return;
+    };
+    let Some(link) = pairing::start(ip, file_transfer_protocol::tcp_port()) else {
+        ctx.app.invoke_show_temp_message("❌ Couldn't create a pairing code".into());
+        return;
+    };
+    secure_channel_code::generate_QR_code(Some(&link));
+    main_helpers::update_ui_qr_only(ctx.app);
+    ctx.app.set_pair_link(link.into());
+    ctx.app.invoke_show_pair_popup();
+}
+
+// "/unpair" lists paired phones, "/unpair name" forgets one and stops trusting its address
+fn unpair(ctx: &CommandContext, name: &str) {
+    if name.is_empty() {
+        let names = ctx.settings.read(|cfg| cfg.paired_devices.values().map(|d| d.name.clone()).collect::<Vec<_>>());
+        if names.is_empty() {
+            ctx.app.invoke_show_temp_message("📱 No paired phones (/pair)".into());
+        } else {
+            ctx.app.invoke_show_temp_message(format!("📱 Paired: {}", names.join(", ")).into());
+        }
+        return;
+    }
+    let removed = ctx.settings.update(|cfg| {
+        let id = cfg.paired_devices.iter().find(|(_, d)| d.name.eq_ignore_ascii_case(name)).map(|(id, _)| id.clone())?;
+        let device = cfg.paired_devices.remove(&id)?;
+        cfg.trusted_peers.remove(&device.last_ip);
+        Some(device.name)
+    });
+    match removed {
+        Some(name) => ctx.app.invoke_show_temp_message(format!("📱 {} is no longer paired", name).into()),
+        None => ctx.app.invoke_show_temp_message(format!("📱 No paired phone called {}", name).into()),
+    }
+}
+
+// "/clip send" sends the clipboard to the channel, "/clip accept" applies one that waits,
+// the rest changes Config.clipboard_sync
+fn clip(ctx: &CommandContext, arg: &str) {
+    let arg = arg.to_ascii_lowercase();
+    let (word, value) = arg.split_once(char::is_whitespace).map_or((arg.as_str(), ""), |(w, v)| (w, v.trim()));
+    let toast = match (word, value) {
+        ("", _) => ctx.settings.read(clip_sync::summary),
+        ("send", _) => match clip_sync::packet(&clip_sync::read_clipboard().unwrap_or_default()) {
+            Ok(packet) if crate::broadcast_the_msg(ctx.sock, ctx.state, &packet).is_ok() => {
+                channel_idle::touch();
+                "📋 Clipboard sent to the channel".into()
+            }
+            Ok(_) => "❌ Couldn't send the clipboard".into(),
+            Err(e) => e,
+        },
+        ("accept", _) => {
+            if clip_sync::accept(ctx.app) {
+                return;
+            }
+            "📋 No clipboard waiting".into()
+        }
+        ("auto", "on" | "off") => ctx.settings.update(|cfg| {
+            cfg.clipboard_sync.auto = value == "on";
+            clip_sync::summary(cfg)
+        }),
+        ("confirm", "on" | "off") => ctx.settings.update(|cfg| {
+            cfg.clipboard_sync.confirm = value == "on";
+            clip_sync::summary(cfg)
+        }),
+        ("max", kb) => match kb.parse::<u32>() {
+            Ok(kb) if (1..=1024).contains(&kb) => ctx.settings.update(|cfg| {
+                cfg.clipboard_sync.max_kib = kb;
+                clip_sync::summary(cfg)
+            }),
+            _ => "📋 Usage: /clip max 1-1024".into(),
+        },
+        _ => "📋 Usage: /clip send | accept | auto on|off | confirm on|off | max <KB>".into(),
+    };
+    ctx.app.invoke_show_temp_message(toast.into());
+}
+
+fn sign_log(ctx: &CommandContext, _: &str) {
+    let on = ctx.settings.update(|cfg| {
+        cfg.sign_transcripts = !cfg.sign_transcripts;
+        cfg.sign_transcripts
+    });
+    ctx.app.invoke_show_temp_message(if on { "🔏 Channels you host get a signed transcript when you close them".into() } else { "🔏 Transcript signing off".into() });
+}
+
+fn secrecy(ctx: &CommandContext, _: &str) {
+    let on = ctx.settings.update(|cfg| {
+        cfg.forward_secrecy = !cfg.forward_secrecy;
+        cfg.forward_secrecy
+    });
+    ctx.app.invoke_show_temp_message(if on {
+        "🔑 Channels you host get a fresh session key, a leaked PIN can't open recorded desktop traffic (older builds can't join, phones still use the PIN key)".into()
+    } else {
+        "🔑 Forward secrecy off, the next channel's key comes from its PIN".into()
+    });
+}
+
+// "/verifylog file" checks a transcript saved under transcripts/ (or a copy someone sent)
+fn verify_log(ctx: &CommandContext, path: &str) {
+    let path = path.trim_matches('"');
+    if path.is_empty() {
+        ctx.app.invoke_show_temp_message("🔏 Usage: /verifylog <file>".into());
+        return;
+    }
+    match transcript::verify_file(std::path::Path::new(path)) {
+        Ok(summary) => ctx.app.invoke_show_temp_message(format!("✅ Transcript intact: {}", summary).into()),
+        Err(e) => ctx.app.invoke_show_temp_message(format!("❌ Transcript check failed: {}", e).into()),
+    }
+}
+
+fn stats(ctx: &CommandContext, _: &str) {
+    ctx.app.invoke_append_message(bandwidth::summary().into());
+}
+
+// "/status" shows the endpoint, "/status 9464" serves it there, "/status off" stops it
+fn status(ctx: &CommandContext, arg: &str) {
+    let port = match arg.to_ascii_lowercase().as_str() {
+        "" => {
+            ctx.app.invoke_show_temp_message(ctx.settings.read(status_endpoint::summary).into());
+            return;
+        }
+        "off" => None,
+        p => match p.parse::<u16>() {
+            Ok(p) if p >= 1024 => Some(p),
+            _ => {
+                ctx.app.invoke_show_temp_message("📈 Usage: /status 1024-65535 | off".into());
+                return;
+            }
+        },
+    };
+    let summary = ctx.settings.update(|cfg| {
+        cfg.status_port = port;
+        status_endpoint::summary(cfg)
+    });
+    ctx.app.invoke_show_temp_message(summary.into());
+}
+
+// "/serial" shows the bridge, "/serial COM3 9600" opens it (115200 baud by default), "/serial off" closes it
+fn serial(ctx: &CommandContext, arg: &str) {
+    let mut parts = arg.split_whitespace();
+    let bridge = match (parts.next(), parts.next()) {
+        (None, _) => {
+            ctx.app.invoke_show_temp_message(ctx.settings.read(serial_bridge::summary).into());
+            return;
+        }
+        (Some(off), None) if off.eq_ignore_ascii_case("off") => None,
+        (Some(_), _) if !serial_bridge::AVAILABLE => {
+            ctx.app.invoke_show_temp_message(ctx.settings.read(serial_bridge::summary).into());
+            return;
+        }
+        (Some(port), baud) => match baud.map_or(Ok(serial_bridge::DEFAULT_BAUD), str::parse::<u32>) {
+            Ok(baud) if baud > 0 => Some(SerialBridge { port: port.to_string(), baud }),
+            _ => {
+                ctx.app.invoke_show_temp_message("🔌 Usage: /serial <port> [baud] | off".into());
+                return;
+            }
+        },
+    };
+    let summary = ctx.settings.update(|cfg| {
+        cfg.serial_bridge = bridge;
+        serial_bridge::summary(cfg)
+    });
+    ctx.app.invoke_show_temp_message(summary.into());
+}
+
+// "" = as is, "redact" = IPs and every known name, "redact Sam, Alex" = IPs and those names
+fn parse_redact(ctx: &CommandContext, arg: &str, command: &str) -> Option<Redactor> {
+    if arg.is_empty() {
+        return Some(Redactor::off());
+    }
+    let (word, names) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+    if !word.eq_ignore_ascii_case("redact") {
+        ctx.app.invoke_show_temp_message(format!("🕶 Usage: {} [redact [name, name...]]", command).into());
+        return None;
+    }
+    let names: Vec<String> = names.split(',').map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect();
+    Some(Redactor::new(&names))
+}
+
+// "file.csv" or "file.csv (redacted)" for the toasts
+fn saved_name(path: &std::path::Path, redactor: &Redactor) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    if redactor.is_on() { format!("{} (redacted)", name) } else { name }
+}
+
+fn export(ctx: &CommandContext, arg: &str) {
+    let Some(mut redactor) = parse_redact(ctx, arg, "/export") else { return; };
+    let folder = ctx.settings.read(|cfg| cfg.save_to_folder.clone());
+    match records::export(std::path::Path::new(&folder), &mut redactor) {
+        Ok((peers_csv, transfers_csv)) => {
+            ctx.app.invoke_show_temp_message(format!("📊 Saved {} and {} in the download folder", saved_name(&peers_csv, &redactor), saved_name(&transfers_csv, &redactor)).into());
+        }
+        Err(e) => ctx.app.invoke_show_temp_message(format!("❌ Export failed: {}", e).into()),
+    }
+}
+
+fn export_chat(ctx: &CommandContext, arg: &str) {
+    let Some(mut redactor) = parse_redact(ctx, arg, "/exportchat") else { return; };
+    let folder = ctx.settings.read(|cfg| cfg.save_to_folder.clone());
+    match history::export(std::path::Path::new(&folder), &mut redactor) {
+        Ok(path) => ctx.app.invoke_show_temp_message(format!("💬 Saved {} in the download folder", saved_name(&path, &redactor)).into()),
+        Err(e) => ctx.app.invoke_show_temp_message(format!("❌ Export failed: {}", e).into()),
+    }
+}
+
+fn export_log(ctx: &CommandContext, arg: &str) {
+    let Some(mut redactor) = parse_redact(ctx, arg, "/exportlog") else { return; };
+    let folder = ctx.settings.read(|cfg| cfg.save_to_folder.clone());
+    match diagnostics::export(std::path::Path::new(&folder), &mut redactor) {
+        Ok(path) => ctx.app.invoke_show_temp_message(format!("🩺 Saved {} in the download folder", saved_name(&path, &redactor)).into()),
+        Err(e) => ctx.app.invoke_show_temp_message(format!("❌ Export failed: {}", e).into()),
+    }
+}
+
+// the passphrase is typed in a masked popup, never on the chat line where it would show
+fn export_session(ctx: &CommandContext, _: &str) {
+    ctx.app.set_session_export(true);
+    ctx.app.invoke_show_passphrase_popup();
+}
+
+fn import_session(ctx: &CommandContext, _: &str) {
+    ctx.app.set_session_export(false);
+    ctx.app.invoke_show_passphrase_popup();
+}
+
+/// The passphrase popup was confirmed, `export` says which command opened it
+pub fn session_passphrase_entered(app: &AppWindow, settings: &SettingsStore, export: bool, passphrase: &str) {
+    if export {
+        save_session(app, settings, passphrase);
+    } else {
+        load_session(app, settings, passphrase);
+    }
+}
+
+fn save_session(app: &AppWindow, settings: &SettingsStore, passphrase: &str) {
+    if passphrase.chars().count() < session_bundle::MIN_PASSPHRASE {
+        app.invoke_show_temp_message(format!("🔐 The passphrase needs {}+ characters", session_bundle::MIN_PASSPHRASE).into());
+        return;
+    }
+    let Some(path) = rfd::FileDialog::new()
+        .set_file_name(format!("lanchgo-session.{}", session_bundle::EXTENSION))
+        .add_filter("LanChGo session", &[session_bundle::EXTENSION])
+        .save_file()
+    else {
+        return;
+    };
+    let config = settings.read(|cfg| cfg.clone());
+    let has_channel = secure_channel_code::get_active_channel().is_some();
+    let passphrase = passphrase.to_string();
+    let weak = app.as_weak();
+    // the key derivation takes a moment, keep it off the UI thread
+    std::thread::spawn(move || {
+        let text = match session_bundle::export(&path, &passphrase, &config) {
+            Ok(()) if has_channel => "🔐 Session saved with the channel key, keep the file and the passphrase apart".to_string(),
+            Ok(()) => "🔐 Session saved (no secure channel active, contacts and settings only)".to_string(),
+            Err(e) => format!("❌ Couldn't save the session: {}", e),
+        };
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak.upgrade() {
+                app.invoke_show_temp_message(text.into());
+            }
+        });
+    });
+}
+
+fn load_session(app: &AppWindow, settings: &SettingsStore, passphrase: &str) {
+    if passphrase.is_empty() {
+        app.invoke_show_temp_message("🔐 Enter the passphrase the session was saved with".into());
+        return;
+    }
+    let Some(path) = rfd::FileDialog::new().add_filter("LanChGo session", &[session_bundle::EXTENSION]).pick_file() else { return; };
+    let passphrase = passphrase.to_string();
+    let settings = settings.clone();
+    let weak = app.as_weak();
+    std::thread::spawn(move || {
+        let imported = session_bundle::import(&path, &passphrase);
+        let _ = slint::invoke_from_event_loop(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            let imported = match imported {
+                Ok(imported) => imported,
+                Err(e) => {
+                    app.invoke_show_temp_message(format!("❌ Couldn't import the session: {}", e).into());
+                    return;
+                }
+            };
+            settings.update(|cfg| *cfg = session_bundle::merge_config(imported.config, cfg));
+            settings.apply_to_ui(&app);
+
+            let mut done = vec![format!("settings, {} new contact(s)", imported.contacts_added)];
+            match imported.channel {
+                // already in one: leave it, the user decides
+                Some(_) if secure_channel_code::get_active_channel().is_some() => done.push("channel skipped, disconnect first".to_string()),
+                Some(channel) => {
+                    secure_channel_code::adopt_channel(channel);
+                    heartbeat::reset();
+                    app.invoke_change_channel_mode("joined".into());
+                    app.set_channel_mode("joined".into());
+                    app.set_public_secure_helper(true);
+                    done.push("joined its secure channel".to_string());
+                }
+                None => {}
+            }
+            let created = chrono::DateTime::from_timestamp(imported.created, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%d %b %H:%M").to_string())
+                .unwrap_or_default();
+            app.invoke_show_temp_message(format!("🔐 Session from {} imported: {}", created, done.join(", ")).into());
+        });
+    });
+}
+
+fn restart(_: &CommandContext, _: &str) {
+    main_helpers::restart_app_after_delay(900);
+}
+
+// queued messages and uploads in progress get this long to finish before the process ends
+const EXIT_DRAIN_LIMIT: Duration = Duration::from_secs(15);
+
+fn exit(ctx: &CommandContext, _: &str) {
+    let transfers = tcp_file_server::active_transfers();
+    ctx.app.invoke_append_message(match transfers {
+        0 if ctx.outbox.is_empty() => "🚪 Exiting...".into(),
+        0 => "🚪 Sending queued messages, then exiting...".into(),
+        1 => "🚪 Finishing 1 transfer, then exiting...".into(),
+        n => format!("🚪 Finishing {} transfers, then exiting...", n).into(),
+    });
+    ctx.running.store(false, Ordering::Relaxed);
+
+    // no new downloads from here on, the temp zips are deleted once the running ones are done
+    let mut offers = std::mem::take(&mut *ctx.offer_registry.lock().unwrap());
+    ctx.file_offer_model.set_vec(Vec::new());
+
+    let (sock, state, outbox) = (Arc::clone(ctx.sock), Arc::clone(ctx.state), Arc::clone(ctx.outbox));
+    std::thread::spawn(move || {
+        let started = Instant::now();
+        loop {
+            // the outbox task stopped with `running`, keep trying here
+            outbox.flush(|p| crate::broadcast_the_msg(&sock, &state, p));
+            if (outbox.is_empty() && tcp_file_server::active_transfers() == 0) || started.elapsed() >= EXIT_DRAIN_LIMIT {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        file_transfer_protocol::cleanup_temp_offers(&mut offers);
+        let _ = web_app::stop_web_server();
+        std::process::exit(0);
+    });
+}
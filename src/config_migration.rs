@@ -0,0 +1,49 @@
+// Upgrading config.json from older releases instead of throwing it away. The file is read
+// as plain JSON first: fields an old release never wrote are filled from the defaults,
+// then every migration newer than the file's schema runs in order.
+
+use serde_json::{Map, Value};
+
+use crate::classes::Config;
+
+/// Bump together with a new entry in MIGRATIONS
+pub const CURRENT_SCHEMA: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>);
+
+// (schema the migration upgrades to, migration), oldest first
+const MIGRATIONS: &[(u32, Migration)] = &[(1, to_v1)];
+
+/// Bring an older config up to date, None if it isn't a config at all
+pub fn migrate(raw: Value, default: &Config) -> Option<Config> {
+    let Value::Object(mut fields) = raw else { return None; };
+    let schema = fields.get("schema").and_then(Value::as_u64).unwrap_or(0) as u32;
+
+    // fields without a serde default (interface, broadcast...) must be there before parsing
+    if let Ok(Value::Object(defaults)) = serde_json::to_value(default) {
+        for (key, value) in defaults {
+            fields.entry(key).or_insert(value);
+        }
+    }
+
+    for (to, migration) in MIGRATIONS {
+        if schema < *to {
+            migration(&mut fields);
+        }
+    }
+
+    let mut config: Config = serde_json::from_value(Value::Object(fields)).ok()?;
+    config.schema = CURRENT_SCHEMA;
+    config.version = default.version.clone();
+    Some(config)
+}
+
+// configs from before schema numbers: "" meant broadcast and 0 meant an automatic port
+fn to_v1(fields: &mut Map<String, Value>) {
+    if fields.get("multicast_group").and_then(Value::as_str).is_some_and(|g| g.trim().is_empty()) {
+        fields.insert("multicast_group".into(), Value::Null);
+    }
+    if fields.get("port").and_then(Value::as_u64) == Some(0) {
+        fields.insert("port".into(), Value::Null);
+    }
+}
@@ -0,0 +1,253 @@
+// Contact book: every LanChGo install we've seen over mDNS, by its identity key (see
+// identity), kept in contacts.json next to the config. The key in a TXT record can be copied
+// like a nickname, so a contact is only added or moved to a new address after the peer there
+// proved it holds the secret half (identity::prove). The trust set here is what auto-download
+// goes by, what /ping and /whois resolve names against and what name conflicts are checked
+// with. Managed in the /contacts popup.
+//
+// Name conflict: a key shows up with a nickname another key had first. Its messages carry a
+// warning badge until the user resolves the conflict in the popup (or trusts the newcomer).
+
+use std::fs::File;
+use std::io::BufReader;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::main_helpers::get_config_path;
+
+// seen again sooner than this with nothing else changed: no rewrite of contacts.json
+const SAVE_INTERVAL_SECS: i64 = 60;
+const MAX_NOTES: usize = 200;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Trust {
+    #[default]
+    Unknown,
+    Trusted, // counts as a trusted peer for auto-download
+    Blocked, // never auto-downloads
+}
+
+impl Trust {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Trust::Unknown => "unknown",
+            Trust::Trusted => "trusted",
+            Trust::Blocked => "blocked",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "unknown" => Some(Trust::Unknown),
+            "trusted" => Some(Trust::Trusted),
+            "blocked" => Some(Trust::Blocked),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Contact {
+    pub key: String, // identity public key hex
+    pub nickname: String,
+    pub last_seen: i64, // unix seconds
+    pub last_ip: String,
+    #[serde(default)]
+    pub trust: Trust,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub conflict_with: Option<String>, // key that had our nickname first, until resolved
+}
+
+static CONTACTS: OnceLock<Mutex<Vec<Contact>>> = OnceLock::new();
+
+fn contacts_path() -> PathBuf {
+    get_config_path().with_file_name("contacts.json")
+}
+
+fn store() -> &'static Mutex<Vec<Contact>> {
+    CONTACTS.get_or_init(|| {
+        let list = File::open(contacts_path())
+            .ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .unwrap_or_default();
+        Mutex::new(list)
+    })
+}
+
+fn save(list: &[Contact]) {
+    if let Ok(file) = File::create(contacts_path()) {
+        let _ = serde_json::to_writer_pretty(file, list);
+    }
+}
+
+// the contact that already goes by `nickname` under another key, the earliest one first
+fn name_holder<'a>(list: &'a [Contact], key: &str, nickname: &str) -> Option<&'a Contact> {
+    list.iter()
+        .filter(|c| c.key != key && c.nickname.eq_ignore_ascii_case(nickname) && c.conflict_with.is_none())
+        .min_by_key(|c| c.last_seen)
+}
+
+/// A peer announced itself over mDNS and proved it holds `key`. Returns the earlier holder
+/// when this key just took a nickname that belongs to someone else.
+pub fn observe(key: &str, nickname: &str, ip: IpAddr) -> Option<Contact> {
+    let now = chrono::Local::now().timestamp();
+    let ip = ip.to_string();
+    let mut list = store().lock().unwrap();
+
+    let renamed = !list.iter().any(|c| c.key == key && c.nickname.eq_ignore_ascii_case(nickname));
+    // only checked when the name is new for this key, so a resolved conflict stays resolved
+    let holder = if renamed { name_holder(&list, key, nickname).cloned() } else { None };
+
+    match list.iter_mut().find(|c| c.key == key) {
+        Some(contact) => {
+            let changed = contact.nickname != nickname || contact.last_ip != ip || now - contact.last_seen >= SAVE_INTERVAL_SECS;
+            contact.nickname = nickname.to_string();
+            contact.last_ip = ip;
+            contact.last_seen = now;
+            if renamed {
+                contact.conflict_with = holder.as_ref().filter(|_| contact.trust != Trust::Trusted).map(|h| h.key.clone());
+            }
+            let flagged = renamed && contact.conflict_with.is_some();
+            if changed || renamed {
+                save(&list);
+            }
+            if !flagged {
+                return None;
+            }
+        }
+        None => {
+            list.push(Contact {
+                key: key.to_string(),
+                nickname: nickname.to_string(),
+                last_seen: now,
+                last_ip: ip,
+                trust: Trust::Unknown,
+                notes: String::new(),
+                conflict_with: holder.as_ref().map(|h| h.key.clone()),
+            });
+            save(&list);
+        }
+    }
+    holder
+}
+
+/// The user vouched for this key: its messages lose the name conflict badge. Returns the
+/// address the contact was last seen at.
+pub fn resolve_conflict(key: &str) -> Option<String> {
+    let mut list = store().lock().unwrap();
+    let contact = list.iter_mut().find(|c| c.key == key && c.conflict_with.is_some())?;
+    contact.conflict_with = None;
+    let ip = contact.last_ip.clone();
+    save(&list);
+    Some(ip)
+}
+
+/// Does whoever was last seen at `ip` use a nickname that belongs to another key
+pub fn has_name_conflict(ip: IpAddr) -> bool {
+    let ip = ip.to_string();
+    store()
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|c| c.last_ip == ip)
+        .max_by_key(|c| c.last_seen)
+        .is_some_and(|c| c.conflict_with.is_some())
+}
+
+/// Everyone, most recently seen first
+pub fn list() -> Vec<Contact> {
+    let mut list = store().lock().unwrap().clone();
+    list.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+    list
+}
+
+pub fn set_trust(key: &str, trust: Trust) -> bool {
+    let mut list = store().lock().unwrap();
+    let Some(contact) = list.iter_mut().find(|c| c.key == key) else { return false; };
+    contact.trust = trust;
+    save(&list);
+    true
+}
+
+pub fn set_notes(key: &str, notes: &str) {
+    let mut list = store().lock().unwrap();
+    if let Some(contact) = list.iter_mut().find(|c| c.key == key) {
+        contact.notes = notes.chars().take(MAX_NOTES).collect();
+        save(&list);
+    }
+}
+
+pub fn remove(key: &str) -> bool {
+    let mut list = store().lock().unwrap();
+    let before = list.len();
+    list.retain(|c| c.key != key);
+    if list.len() == before {
+        return false;
+    }
+    save(&list);
+    true
+}
+
+/// Contacts from a session bundle: new keys are added, known ones pick up trust and notes
+/// we didn't set here. Returns how many were new.
+pub fn merge(imported: Vec<Contact>) -> usize {
+    let mut list = store().lock().unwrap();
+    let mut added = 0;
+    for contact in imported {
+        match list.iter_mut().find(|c| c.key == contact.key) {
+            Some(known) => {
+                if known.trust == Trust::Unknown {
+                    known.trust = contact.trust;
+                }
+                if known.notes.is_empty() {
+                    known.notes = contact.notes;
+                }
+            }
+            None => {
+                list.push(contact);
+                added += 1;
+            }
+        }
+    }
+    save(&list);
+    added
+}
+
+pub fn get(key: &str) -> Option<Contact> {
+    store().lock().unwrap().iter().find(|c| c.key == key).cloned()
+}
+
+pub fn trust(key: &str) -> Trust {
+    store().lock().unwrap().iter().find(|c| c.key == key).map(|c| c.trust).unwrap_or_default()
+}
+
+/// Trust of whoever last proved its key from `ip`, Unknown when nobody did
+pub fn trust_for_ip(ip: IpAddr) -> Trust {
+    let ip = ip.to_string();
+    store()
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|c| c.last_ip == ip)
+        .max_by_key(|c| c.last_seen)
+        .map(|c| c.trust)
+        .unwrap_or_default()
+}
+
+/// By nickname (case insensitive, trusted first when several share it) or the start of the key
+pub fn find(query: &str) -> Option<Contact> {
+    let query = query.trim_start_matches('@').to_ascii_lowercase();
+    let list = store().lock().unwrap();
+    let by_name = list
+        .iter()
+        .filter(|c| c.nickname.to_ascii_lowercase() == query)
+        .max_by_key(|c| (c.trust == Trust::Trusted, c.last_seen));
+    let by_key = || list.iter().find(|c| query.len() >= 8 && c.key.starts_with(&query));
+    by_name.or_else(by_key).cloned()
+}
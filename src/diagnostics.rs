@@ -0,0 +1,53 @@
+// diagnostics.log next to the config: errors worth knowing about after the fact (receiver
+// failures, file server trouble). Moved to diagnostics.old.log once it passes MAX_BYTES.
+
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::main_helpers::get_config_path;
+use crate::redact::Redactor;
+
+const MAX_BYTES: u64 = 1024 * 1024;
+
+static WRITE: Mutex<()> = Mutex::new(());
+static COUNTS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+fn log_path() -> PathBuf {
+    get_config_path().with_file_name("diagnostics.log")
+}
+
+/// One line: "2025-01-31 14:02:11 [area] message"
+pub fn log(area: &str, message: impl AsRef<str>) {
+    *COUNTS.lock().unwrap().entry(area.to_string()).or_default() += 1;
+    let _guard = WRITE.lock().unwrap();
+    let path = log_path();
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_BYTES) {
+        let _ = std::fs::rename(&path, path.with_file_name("diagnostics.old.log"));
+    }
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        let _ = writeln!(file, "{} [{}] {}", now, area, message.as_ref());
+    }
+}
+
+/// Lines logged this session by area, for the status endpoint's error counters
+pub fn counts() -> Vec<(String, u64)> {
+    COUNTS.lock().unwrap().iter().map(|(area, n)| (area.clone(), *n)).collect()
+}
+
+/// Copy diagnostics.old.log + diagnostics.log into `dir` as one diagnostics_<date>.log
+pub fn export(dir: &Path, redactor: &mut Redactor) -> std::io::Result<PathBuf> {
+    let text = {
+        let _guard = WRITE.lock().unwrap();
+        let path = log_path();
+        let old = std::fs::read_to_string(path.with_file_name("diagnostics.old.log")).unwrap_or_default();
+        old + &std::fs::read_to_string(&path).unwrap_or_default()
+    };
+    std::fs::create_dir_all(dir)?;
+    let target = dir.join(format!("diagnostics_{}.log", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")));
+    std::fs::write(&target, redactor.apply(&text))?;
+    Ok(target)
+}
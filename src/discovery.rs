@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, Weak, atomic::{AtomicBool, Ordering}};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+
+use crate::classes::Config;
+use crate::{identity, peers};
+
+// DNS-SD service every LanChGo instance registers and browses for
+pub const SERVICE_TYPE: &str = "_lanchgo._udp.local.";
+
+// nickname and device name for the TXT record, and the running registration to refresh when
+// they change
+static NICKNAME: Mutex<String> = Mutex::new(String::new());
+static DEVICE: Mutex<String> = Mutex::new(String::new());
+static ACTIVE: Mutex<Option<Weak<Discovery>>> = Mutex::new(None);
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredPeer {
+    pub fullname: String,
+    pub display_name: String,
+    pub addresses: Vec<IpAddr>,
+    pub port: u16,
+    pub hosting: bool, // true while the peer hosts a secure channel
+    pub hostname: String, // "PC-1a2b.local."
+    pub version: Option<String>, // app version from the TXT record
+    pub identity: Option<String>, // public key hex, see identity
+    pub nickname: Option<String>,
+    pub device: Option<String>, // "laptop", which of the person's machines this is
+    pub caps: Option<u32>, // "caps" TXT bitfield, see peers::CAP_*. None = build from before it
+    pub tcp_port: Option<u16>, // "tcp" TXT, its file server port. None = build from before it, DEFAULT_TCP_PORT
+    pub resolved_at: Instant,
+}
+
+pub enum DiscoveryEvent {
+    PeerFound(DiscoveredPeer),
+    PeerLost(String),
+}
+
+/// Our own mDNS registration + browser.
+/// mDNS goes to 224.0.0.251 which most AP isolation heuristics let through even when broadcast is dropped.
+pub struct Discovery {
+    daemon: ServiceDaemon,
+    instance: String,
+    host_name: String,
+    port: Mutex<u16>,
+    hosting: Mutex<bool>,
+}
+
+impl Discovery {
+    fn build_info(&self, hosting: bool) -> Result<ServiceInfo, mdns_sd::Error> {
+        let identity = identity::public_hex();
+        let nickname = NICKNAME.lock().unwrap().clone();
+        let device = DEVICE.lock().unwrap().clone();
+        let caps = format!("{:x}", peers::OWN_CAPS);
+        let tcp_port = crate::file_transfer_protocol::tcp_port().to_string();
+        let properties = [
+            ("version", env!("CARGO_PKG_VERSION")),
+            ("channel", if hosting { "1" } else { "0" }),
+            ("id", identity.as_str()),
+            ("nick", nickname.as_str()),
+            ("device", device.as_str()),
+            ("caps", caps.as_str()),
+            ("tcp", tcp_port.as_str()),
+        ];
+        Ok(ServiceInfo::new(SERVICE_TYPE, &self.instance, &self.host_name, "", *self.port.lock().unwrap(), &properties[..])?
+            .enable_addr_auto())
+    }
+
+    pub fn fullname(&self) -> String {
+        format!("{}.{}", self.instance, SERVICE_TYPE)
+    }
+
+    /// Re-register with the new TXT record so browsers see the hosted channel come and go
+    pub fn set_hosting(&self, hosting: bool) {
+        let mut current = self.hosting.lock().unwrap();
+        if *current == hosting {
+            return;
+        }
+        *current = hosting;
+        if let Ok(info) = self.build_info(hosting) {
+            let _ = self.daemon.register(info);
+        }
+    }
+
+    /// The SRV record carries our UDP port, peers use it to spot a port mismatch
+    pub fn set_port(&self, port: u16) {
+        *self.port.lock().unwrap() = port;
+        self.reregister();
+    }
+
+    fn reregister(&self) {
+        let hosting = *self.hosting.lock().unwrap();
+        if let Ok(info) = self.build_info(hosting) {
+            let _ = self.daemon.register(info);
+        }
+    }
+
+    pub fn shutdown(&self) {
+        let _ = self.daemon.unregister(&self.fullname());
+        let _ = self.daemon.shutdown();
+    }
+}
+
+/// Mirror the config, a new nickname or device name is announced right away
+pub fn configure(cfg: &Config) {
+    let mut nickname = NICKNAME.lock().unwrap();
+    let mut device = DEVICE.lock().unwrap();
+    if *nickname == cfg.nickname && *device == cfg.device_name {
+        return;
+    }
+    *nickname = cfg.nickname.clone();
+    *device = cfg.device_name.clone();
+    drop(nickname);
+    drop(device);
+    let active = ACTIVE.lock().unwrap().as_ref().and_then(Weak::upgrade);
+    if let Some(discovery) = active {
+        discovery.reregister();
+    }
+}
+
+fn local_instance_name() -> String {
+    let host = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "LanChGo".to_string());
+    // the suffix keeps two instances on the same machine apart
+    format!("{}-{:04x}", host, rand::random::<u16>())
+}
+
+/// Register ourselves and browse for other instances, events are delivered on a background thread
+pub fn start_discovery( port: u16, running: Arc<AtomicBool>, on_event: impl Fn(DiscoveryEvent) + Send + 'static, ) -> Result<(Arc<Discovery>, JoinHandle<()>), mdns_sd::Error> {
+    let instance = local_instance_name();
+    let discovery = Arc::new(Discovery {
+        daemon: ServiceDaemon::new()?,
+        host_name: format!("{}.local.", instance),
+        instance,
+        port: Mutex::new(port),
+        hosting: Mutex::new(false),
+    });
+
+    discovery.daemon.register(discovery.build_info(false)?)?;
+    *ACTIVE.lock().unwrap() = Some(Arc::downgrade(&discovery));
+    let receiver = discovery.daemon.browse(SERVICE_TYPE)?;
+
+    let own_fullname = discovery.fullname();
+    let handle = thread::spawn(move || {
+        while running.load(Ordering::Relaxed) {
+            let event = match receiver.recv_timeout(Duration::from_millis(500)) {
+                Ok(ev) => ev,
+                Err(_) => continue,
+            };
+
+            match event {
+                ServiceEvent::ServiceResolved(info) => {
+                    if info.get_fullname() == own_fullname {
+                        continue;
+                    }
+                    let addresses: HashSet<IpAddr> = info.get_addresses().iter().copied().collect();
+                    let peer = DiscoveredPeer {
+                        fullname: info.get_fullname().to_string(),
+                        display_name: info.get_fullname().trim_end_matches(SERVICE_TYPE).trim_end_matches('.').to_string(),
+                        addresses: addresses.into_iter().collect(),
+                        port: info.get_port(),
+                        hosting: info.get_property_val_str("channel") == Some("1"),
+                        hostname: info.get_hostname().to_string(),
+                        version: info.get_property_val_str("version").map(str::to_string),
+                        identity: info.get_property_val_str("id").filter(|k| k.len() == 64 && k.chars().all(|c| c.is_ascii_hexdigit())).map(str::to_ascii_lowercase),
+                        nickname: info.get_property_val_str("nick").filter(|n| !n.is_empty()).map(|n| n.chars().filter(|c| !c.is_control()).take(32).collect()),
+                        device: info.get_property_val_str("device").filter(|d| !d.is_empty()).map(|d| d.chars().filter(|c| !c.is_control()).take(32).collect()),
+                        caps: info.get_property_val_str("caps").and_then(|c| u32::from_str_radix(c, 16).ok()),
+                        tcp_port: info.get_property_val_str("tcp").and_then(|p| p.parse().ok()).filter(|&p| p != 0),
+                        resolved_at: Instant::now(),
+                    };
+                    //println!("[MDNS] resolved {:?}", peer);
+                    on_event(DiscoveryEvent::PeerFound(peer));
+                }
+                ServiceEvent::ServiceRemoved(_ty, fullname) => {
+                    if fullname != own_fullname {
+                        on_event(DiscoveryEvent::PeerLost(fullname));
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+
+    Ok((discovery, handle))
+}
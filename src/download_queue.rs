@@ -0,0 +1,152 @@
+// Downloads take turns: at most Config.max_parallel_downloads run at once, the others wait in
+// click order with "#n" on their button. A download that ends (done, failed or cancelled) hands
+// its slot straight to the first one waiting, which then starts like a fresh click. Waiting
+// rows can be moved up / down (right-click) or dropped (✕, same as cancelling a running one).
+
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use slint::{Model, VecModel};
+
+use crate::classes::Config;
+use crate::{main_helpers, AppWindow, FileOfferItem};
+
+static MAX_PARALLEL: AtomicUsize = AtomicUsize::new(2);
+
+struct Queue {
+    active: usize,
+    waiting: VecDeque<String>, // offer ids, first in line first
+    handed: BTreeSet<String>,  // got a freed slot, its restart is on its way to the UI thread
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue { active: 0, waiting: VecDeque::new(), handed: BTreeSet::new() });
+
+/// Held by a running download, dropping it lets the next one in line start
+pub struct Slot(());
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let next = {
+            let mut queue = QUEUE.lock().unwrap();
+            queue.active = queue.active.saturating_sub(1);
+            fill(&mut queue)
+        };
+        start(next);
+    }
+}
+
+pub enum Admission {
+    Go(Slot),
+    Queued(usize), // place in line, 1 = next
+}
+
+/// Config.max_parallel_downloads, a higher limit starts waiting downloads right away
+pub fn configure(cfg: &Config) {
+    MAX_PARALLEL.store(cfg.max_parallel_downloads.max(1) as usize, Ordering::Relaxed);
+    // more room now, let the waiting ones in
+    let next = fill(&mut QUEUE.lock().unwrap());
+    start(next);
+}
+
+// move the first in line into free slots, returns the ids that should start
+fn fill(queue: &mut Queue) -> Vec<String> {
+    let max = MAX_PARALLEL.load(Ordering::Relaxed);
+    let mut next = Vec::new();
+    while queue.active < max {
+        let Some(id) = queue.waiting.pop_front() else { break; };
+        queue.active += 1;
+        queue.handed.insert(id.clone());
+        next.push(id);
+    }
+    next
+}
+
+fn start(next: Vec<String>) {
+    if next.is_empty() {
+        return;
+    }
+    let Some(weak) = main_helpers::app_handle() else { return; };
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(app) = weak.upgrade() {
+            refresh(&app);
+            for id in next {
+                app.invoke_download_offer(id.into());
+            }
+        }
+    });
+}
+
+/// A download was clicked: start it now or put it in line
+pub fn admit(offer_id: &str) -> Admission {
+    let mut queue = QUEUE.lock().unwrap();
+    // its turn came, the slot is already counted
+    if queue.handed.remove(offer_id) {
+        return Admission::Go(Slot(()));
+    }
+    if let Some(i) = queue.waiting.iter().position(|id| id == offer_id) {
+        return Admission::Queued(i + 1);
+    }
+    if queue.active < MAX_PARALLEL.load(Ordering::Relaxed) {
+        queue.active += 1;
+        return Admission::Go(Slot(()));
+    }
+    queue.waiting.push_back(offer_id.to_string());
+    Admission::Queued(queue.waiting.len())
+}
+
+/// Take a waiting download out of the line, false when it isn't waiting
+pub fn dequeue(offer_id: &str) -> bool {
+    let mut queue = QUEUE.lock().unwrap();
+    let before = queue.waiting.len();
+    queue.waiting.retain(|id| id != offer_id);
+    queue.waiting.len() != before
+}
+
+/// Move a waiting download `delta` places (negative = earlier), false when it isn't waiting
+pub fn shift(offer_id: &str, delta: i32) -> bool {
+    let mut queue = QUEUE.lock().unwrap();
+    let Some(i) = queue.waiting.iter().position(|id| id == offer_id) else { return false; };
+    let to = (i as i64 + delta as i64).clamp(0, queue.waiting.len() as i64 - 1) as usize;
+    let id = queue.waiting.remove(i).unwrap();
+    queue.waiting.insert(to, id);
+    true
+}
+
+/// (running, waiting), for the status endpoint
+pub fn depth() -> (usize, usize) {
+    let queue = QUEUE.lock().unwrap();
+    (queue.active, queue.waiting.len())
+}
+
+/// Drop everything waiting (/clearfiles), returns how many
+pub fn clear() -> usize {
+    let mut queue = QUEUE.lock().unwrap();
+    let dropped = queue.waiting.len();
+    queue.waiting.clear();
+    dropped
+}
+
+/// Show every row's place in line, 0 for rows that aren't waiting
+pub fn refresh(app: &AppWindow) {
+    let waiting: Vec<String> = QUEUE.lock().unwrap().waiting.iter().cloned().collect();
+    let model_rc = app.get_file_offer();
+    let Some(vec) = model_rc.as_any().downcast_ref::<VecModel<FileOfferItem>>() else { return; };
+    for i in 0..vec.row_count() {
+        let Some(mut row) = vec.row_data(i) else { continue; };
+        let place = waiting.iter().position(|id| id.as_str() == row.offer_id.as_str()).map_or(0, |p| p + 1) as i32;
+        if row.queued == place {
+            continue;
+        }
+        if place > 0 {
+            row.is_downloading = true;
+            row.progress_text = format!("#{}", place).into();
+        } else if row.queued > 0 && row.progress_text.starts_with('#') {
+            // left the line without starting (dropped), back to a plain ⬇
+            row.is_downloading = false;
+            row.progress_text = "".into();
+        }
+        row.queued = place;
+        vec.set_row_data(i, row);
+    }
+}
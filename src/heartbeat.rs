@@ -0,0 +1,86 @@
+// Host heartbeat inside secure channels. The host broadcasts EHBT + bincode(SecureMessage)
+// every BEAT_INTERVAL, sealed like ECLS (prefix + channel salt). A joiner that stops hearing
+// it for MISSED_BEATS beats tells the user the host is unreachable (laptop asleep, Wi-Fi gone)
+// and offers to ask again or go back to public. Hosts on older builds never beat, so a joiner
+// only starts watching after the first one arrives.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::secure_channel_code::{self, Channel, SecureMessage};
+use crate::AppWindow;
+
+pub const EHBT_MAGIC: &[u8; 4] = b"EHBT";
+const BEAT_PREFIX: &[u8] = b"LANCHGO_BEAT:";
+const BEAT_INTERVAL: Duration = Duration::from_secs(5);
+const MISSED_BEATS: u32 = 3;
+
+struct Watch {
+    last_beat: Option<Instant>, // None until the host proved it beats
+    lost: bool,                 // already told the user
+}
+
+static WATCH: Mutex<Watch> = Mutex::new(Watch { last_beat: None, lost: false });
+
+fn beat_packet(channel: &Channel) -> Option<Vec<u8>> {
+    let sealed = secure_channel_code::encrypt_bytes(&channel.key, &[BEAT_PREFIX, &channel.salt[..]].concat());
+    let payload = bincode::serde::encode_to_vec(&sealed, bincode::config::standard()).ok()?;
+    let mut packet = Vec::from(EHBT_MAGIC as &[u8]);
+    packet.extend_from_slice(&payload);
+    Some(packet)
+}
+
+/// Joiner side, `bytes` after EHBT. Returns true when this beat ends a "host unreachable".
+pub fn accept(bytes: &[u8]) -> bool {
+    let Some(channel) = secure_channel_code::get_active_channel() else { return false; };
+    let Ok((sealed, _)) = bincode::serde::decode_from_slice::<SecureMessage, _>(bytes, bincode::config::standard()) else { return false; };
+    let genuine = secure_channel_code::decrypt_bytes(&channel.key, &sealed)
+        .is_some_and(|plain| plain.strip_prefix(BEAT_PREFIX) == Some(&channel.salt[..]) && secure_channel_code::accept_nonce(&sealed.nonce));
+    if !genuine {
+        return false;
+    }
+    let mut watch = WATCH.lock().unwrap();
+    watch.last_beat = Some(Instant::now());
+    std::mem::take(&mut watch.lost)
+}
+
+/// Forget the last channel's beats, call on join and on leave
+pub fn reset() {
+    *WATCH.lock().unwrap() = Watch { last_beat: None, lost: false };
+}
+
+// true once per outage
+fn host_just_lost() -> bool {
+    let mut watch = WATCH.lock().unwrap();
+    let silent = watch.last_beat.is_some_and(|at| at.elapsed() > BEAT_INTERVAL * MISSED_BEATS);
+    if silent && !watch.lost {
+        watch.lost = true;
+        return true;
+    }
+    false
+}
+
+/// Beat while hosting, watch the host while joined. `send` broadcasts a packet.
+pub fn start(channel_mode: Arc<Mutex<String>>, ui_weak: slint::Weak<AppWindow>, send: impl Fn(&[u8]) + Send + 'static) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(BEAT_INTERVAL);
+        let mode = channel_mode.lock().unwrap().clone();
+        match mode.as_str() {
+            "host" => {
+                if let Some(packet) = secure_channel_code::get_active_channel().as_ref().and_then(beat_packet) {
+                    send(&packet);
+                }
+            }
+            "joined" if host_just_lost() => {
+                let weak = ui_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_append_message("⚠️ The channel host is unreachable, messages may not arrive".into());
+                        app.invoke_show_host_lost_popup();
+                    }
+                });
+            }
+            _ => {}
+        }
+    });
+}
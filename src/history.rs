@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::main_helpers::get_config_path;
+use crate::redact::Redactor;
+
+/// One chat line on disk (history.jsonl, one JSON object per line, oldest first)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub ts: i64, // unix seconds
+    pub sender: String,
+    pub text: String,
+    #[serde(default)]
+    pub channel: String, // "public" / "secure"
+}
+
+impl HistoryEntry {
+    pub fn local_date(&self) -> NaiveDate {
+        Local.timestamp_opt(self.ts, 0).single().unwrap_or_default().date_naive()
+    }
+    pub fn local_time(&self) -> String {
+        Local.timestamp_opt(self.ts, 0).single().map(|t| t.format("%H:%M").to_string()).unwrap_or_default()
+    }
+}
+
+struct HistoryStore {
+    path: PathBuf,
+    // day -> byte offset of its first entry, lets us seek instead of scanning the whole file
+    day_index: BTreeMap<NaiveDate, u64>,
+    end: u64,
+}
+
+static HISTORY: OnceLock<Mutex<HistoryStore>> = OnceLock::new();
+
+pub fn history_path() -> PathBuf {
+    get_config_path().with_file_name("history.jsonl")
+}
+
+fn store() -> &'static Mutex<HistoryStore> {
+    HISTORY.get_or_init(|| Mutex::new(build_index(history_path())))
+}
+
+/// One pass over the file at startup to find where every day begins
+fn build_index(path: PathBuf) -> HistoryStore {
+    let mut day_index = BTreeMap::new();
+    let mut offset = 0u64;
+
+    if let Ok(file) = File::open(&path) {
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        while let Ok(n) = reader.read_line(&mut line) {
+            if n == 0 {
+                break;
+            }
+            if let Ok(entry) = serde_json::from_str::<HistoryEntry>(line.trim_end()) {
+                day_index.entry(entry.local_date()).or_insert(offset);
+            }
+            offset += n as u64;
+            line.clear();
+        }
+    }
+    HistoryStore { path, day_index, end: offset }
+}
+
+/// Append a message, failures are ignored (history is a nice-to-have). Returns the timestamp used.
+pub fn append(sender: &str, text: &str, channel: &str) -> i64 {
+    let entry = HistoryEntry {
+        ts: Local::now().timestamp(),
+        sender: sender.to_string(),
+        text: text.to_string(),
+        channel: channel.to_string(),
+    };
+    let Ok(mut line) = serde_json::to_string(&entry) else { return entry.ts; };
+    line.push('\n');
+
+    let mut store = store().lock().unwrap();
+    if let Some(parent) = store.path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&store.path) else { return entry.ts; };
+    if file.write_all(line.as_bytes()).is_ok() {
+        let offset = store.end;
+        store.day_index.entry(entry.local_date()).or_insert(offset);
+        store.end += line.len() as u64;
+    }
+    entry.ts
+}
+
+/// Messages of `date`, or of the next day that has any. Returns the day actually loaded.
+pub fn load_day(date: NaiveDate, limit: usize) -> Option<(NaiveDate, Vec<HistoryEntry>)> {
+    let day = {
+        let store = store().lock().unwrap();
+        *store.day_index.range(date..).next()?.0
+    };
+    let mut entries = read_day(day)?;
+    entries.truncate(limit);
+    Some((day, entries))
+}
+
+/// Up to `radius` messages either side of the one sent at `ts`
+pub fn load_around(ts: i64, radius: usize) -> Option<Vec<HistoryEntry>> {
+    let day = Local.timestamp_opt(ts, 0).single()?.date_naive();
+    let entries = read_day(day)?;
+    let pos = entries.iter().position(|e| e.ts >= ts).unwrap_or(entries.len().saturating_sub(1));
+    let start = pos.saturating_sub(radius);
+    let end = (pos + radius + 1).min(entries.len());
+    Some(entries[start..end].to_vec())
+}
+
+/// Case-insensitive match on text or sender, newest first, at most `limit` hits
+pub fn search(term: &str, limit: usize) -> Vec<HistoryEntry> {
+    let needle = term.trim().to_lowercase();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let path = store().lock().unwrap().path.clone();
+    let Ok(file) = File::open(path) else { return Vec::new(); };
+
+    // oldest first on disk, keep only the newest `limit` while scanning
+    let mut hits = std::collections::VecDeque::with_capacity(limit);
+    for line in BufReader::new(file).lines() {
+        let Ok(line) = line else { break; };
+        let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) else { continue; };
+        if entry.text.to_lowercase().contains(&needle) || entry.sender.to_lowercase().contains(&needle) {
+            if hits.len() == limit {
+                hits.pop_front();
+            }
+            hits.push_back(entry);
+        }
+    }
+    hits.into_iter().rev().collect()
+}
+
+// every entry of one indexed day
+fn read_day(day: NaiveDate) -> Option<Vec<HistoryEntry>> {
+    let store = store().lock().unwrap();
+    let &offset = store.day_index.get(&day)?;
+
+    let mut file = File::open(&store.path).ok()?;
+    file.seek(SeekFrom::Start(offset)).ok()?;
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let Ok(line) = line else { break; };
+        let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) else { continue; };
+        if entry.local_date() != day {
+            break;
+        }
+        entries.push(entry);
+    }
+    Some(entries)
+}
+
+// ===================== starred =====================
+// small list, kept as one JSON array next to history.jsonl
+
+static STARRED: OnceLock<Mutex<Vec<HistoryEntry>>> = OnceLock::new();
+
+fn starred_path() -> PathBuf {
+    history_path().with_file_name("starred.json")
+}
+
+fn starred_store() -> &'static Mutex<Vec<HistoryEntry>> {
+    STARRED.get_or_init(|| {
+        let list = File::open(starred_path())
+            .ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .unwrap_or_default();
+        Mutex::new(list)
+    })
+}
+
+/// Write the whole history as chat_<date>.txt into `dir`, one "date time [channel] sender: text" line each
+pub fn export(dir: &Path, redactor: &mut Redactor) -> std::io::Result<PathBuf> {
+    let path = store().lock().unwrap().path.clone();
+    let mut out = String::new();
+    if let Ok(file) = File::open(path) {
+        for line in BufReader::new(file).lines() {
+            let Ok(line) = line else { break; };
+            let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) else { continue; };
+            let channel = if entry.channel.is_empty() { "public" } else { entry.channel.as_str() };
+            let line = format!("{} {} [{}] {}: {}\n", entry.local_date(), entry.local_time(), channel, entry.sender, entry.text);
+            out.push_str(&redactor.apply(&line));
+        }
+    }
+    std::fs::create_dir_all(dir)?;
+    let target = dir.join(format!("chat_{}.txt", Local::now().format("%Y-%m-%d_%H-%M-%S")));
+    std::fs::write(&target, out)?;
+    Ok(target)
+}
+
+/// Starred messages, newest first
+pub fn starred() -> Vec<HistoryEntry> {
+    let mut list = starred_store().lock().unwrap().clone();
+    list.sort_by(|a, b| b.ts.cmp(&a.ts));
+    list
+}
+
+pub fn is_starred(ts: i64, text: &str) -> bool {
+    starred_store().lock().unwrap().iter().any(|e| e.ts == ts && e.text == text)
+}
+
+/// Star / unstar the history entry sent at `ts` with this text, returns the new state
+pub fn toggle_star(ts: i64, text: &str) -> bool {
+    let mut list = starred_store().lock().unwrap();
+    let now_starred = if let Some(pos) = list.iter().position(|e| e.ts == ts && e.text == text) {
+        list.remove(pos);
+        false
+    } else {
+        // take sender / channel from the history itself
+        let day = Local.timestamp_opt(ts, 0).single().map(|t| t.date_naive());
+        let Some(entry) = day.and_then(read_day).and_then(|v| v.into_iter().find(|e| e.ts == ts && e.text == text)) else {
+            return false;
+        };
+        list.push(entry);
+        true
+    };
+
+    if let Ok(file) = File::create(starred_path()) {
+        let _ = serde_json::to_writer_pretty(file, &*list);
+    }
+    now_starred
+}
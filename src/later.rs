@@ -0,0 +1,128 @@
+// "Save for later": offers we didn't download yet, kept in later.json next to the config.
+// Fetching one asks the sender to offer it again (FOFQ + offer id, unicast), the FOFT it
+// answers with starts the download like a normal click.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_transfer_protocol::{hex_to_offer_id, FileOffer};
+use crate::main_helpers::get_config_path;
+
+pub const FOFQ_MAGIC: &[u8; 4] = b"FOFQ";
+
+// no FOFT back within this long = sender offline or the offer is gone
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LaterEntry {
+    pub offer_id: String, // hex
+    pub name: String,
+    pub size: u64,
+    pub sender: String, // label at the time it was saved, see peers::label
+    pub sender_addr: SocketAddr,
+    pub saved_at: i64, // unix seconds
+}
+
+static LATER: OnceLock<Mutex<Vec<LaterEntry>>> = OnceLock::new();
+// offer id hex -> when we asked the sender for it
+static PENDING: Mutex<BTreeMap<String, Instant>> = Mutex::new(BTreeMap::new());
+
+fn later_path() -> PathBuf {
+    get_config_path().with_file_name("later.json")
+}
+
+fn store() -> &'static Mutex<Vec<LaterEntry>> {
+    LATER.get_or_init(|| {
+        let list = File::open(later_path())
+            .ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .unwrap_or_default();
+        Mutex::new(list)
+    })
+}
+
+fn save(list: &[LaterEntry]) {
+    if let Ok(file) = File::create(later_path()) {
+        let _ = serde_json::to_writer_pretty(file, list);
+    }
+}
+
+/// Remember an offer, false if it's already on the list
+pub fn add(sender_addr: SocketAddr, sender: &str, offer: &FileOffer) -> bool {
+    let id_hex = crate::file_transfer_protocol::offer_id_to_hex(&offer.offer_id);
+    let mut list = store().lock().unwrap();
+    if list.iter().any(|e| e.offer_id == id_hex) {
+        return false;
+    }
+    list.push(LaterEntry {
+        offer_id: id_hex,
+        name: offer.name.clone(),
+        size: offer.size,
+        sender: sender.to_string(),
+        sender_addr,
+        saved_at: chrono::Local::now().timestamp(),
+    });
+    save(&list);
+    true
+}
+
+pub fn remove(id_hex: &str) {
+    let mut list = store().lock().unwrap();
+    list.retain(|e| e.offer_id != id_hex);
+    save(&list);
+}
+
+/// Saved offers, oldest first
+pub fn list() -> Vec<LaterEntry> {
+    store().lock().unwrap().clone()
+}
+
+pub fn get(id_hex: &str) -> Option<LaterEntry> {
+    store().lock().unwrap().iter().find(|e| e.offer_id == id_hex).cloned()
+}
+
+// ===================== re-request =====================
+
+pub fn request_packet(id_hex: &str) -> Option<Vec<u8>> {
+    let id = hex_to_offer_id(id_hex)?;
+    let mut packet = Vec::from(FOFQ_MAGIC as &[u8]);
+    packet.extend_from_slice(&id);
+    Some(packet)
+}
+
+/// Offer id a peer asks us to offer again
+pub fn parse_request(bytes: &[u8]) -> Option<[u8; 16]> {
+    if bytes.len() != 20 || &bytes[..4] != FOFQ_MAGIC {
+        return None;
+    }
+    bytes[4..].try_into().ok()
+}
+
+pub fn mark_requested(id_hex: &str) {
+    PENDING.lock().unwrap().insert(id_hex.to_string(), Instant::now());
+}
+
+/// The sender answered: true if we asked for this offer, it leaves the list then
+pub fn take_requested(id_hex: &str) -> bool {
+    let asked = PENDING
+        .lock()
+        .unwrap()
+        .remove(id_hex)
+        .is_some_and(|at| at.elapsed() < REQUEST_TIMEOUT);
+    if asked {
+        remove(id_hex);
+    }
+    asked
+}
+
+/// Still no answer, forget the request (called once REQUEST_TIMEOUT is over)
+pub fn request_timed_out(id_hex: &str) -> bool {
+    PENDING.lock().unwrap().remove(id_hex).is_some()
+}
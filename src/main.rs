@@ -3,30 +3,70 @@
 
 // Other code files
 mod secure_channel_code;    // Code to generate PIN decrypt and encrypt
-mod phone_protocol;         // For phone connection and protocol
 mod file_transfer_protocol; // For file transferring logic (future use)
 mod classes;
 mod main_helpers;
 mod udp_receiver;
-mod tcp_file_server;
-mod tcp_file_client;
 mod mobile_download;
 mod web_app;
 mod web_app_file_transfer;
+mod power_watch;
+mod discovery;              // mDNS/DNS-SD peer and channel discovery
+mod history;                // on-disk chat history + per-day index
+mod net_watch;              // adapter hot-plug detection
+mod platform;               // Windows / Linux specifics
+mod message_pipeline;       // post-processing of received messages (language tags, translation)
+mod commands;               // slash-command registry + user aliases
+mod outbox;                 // messages waiting for the network to come back
+mod peers;                  // who we know is on the LAN (mDNS + traffic)
+mod markdown;               // *bold* _italic_ `code` in chat messages
+mod channel_files;          // secure-mode "send to everyone in the channel" file offers
+mod tray;                   // minimize to tray + unread badge
+mod auto_download;          // download small offers without a click, trusted peers
+mod notify;                 // desktop notifications while the window is unfocused
+mod later;                  // offers saved for later, FOFQ re-requests
+mod settings;               // SettingsStore: change + save + apply Config in one place
+mod transcript;             // signed secure channel transcripts, /signlog /verifylog
+mod config_migration;       // upgrades config.json from older releases
+mod diagnostics;            // diagnostics.log for errors that happen out of sight
+mod phone_push;             // files the phone pushes to us (MPUSH), accepted from the files panel
+mod ping;                   // /ping <peer>: unicast attention ping with round trip time
+mod contacts;               // contact book of seen identities with trust levels, /contacts
+mod rules;                  // local automation rules, welcome message for new peers
+mod records;                // peer and transfer records of this session, /export as CSV
+mod heartbeat;              // host heartbeat in secure channels, "host unreachable" for joiners
+mod resume;                 // downloads interrupted by closing the app, offered again at startup
+mod reannounce;             // the host repeats ANCH / MANCH with jitter for late joiners
+mod thumbnails;             // previews of image offers in the files panel (FTHB)
+mod channel_idle;           // closes secure channels nobody used for a while
+mod download_queue;         // downloads wait their turn, Config.max_parallel_downloads at once
+mod mute;                   // timed mute of a peer, their messages wait in one collapsed row
+mod session_bundle;         // passphrase-protected channel key + contacts + settings for another machine
+mod status_endpoint;        // opt-in /health and /metrics on localhost for monitoring
+mod serial_bridge;          // public channel mirrored to a serial port (--features serial-bridge)
+mod snippets;               // long text shared as a code block, fetched over TCP (SNIP / FSNP)
+mod redact;                 // IPs and names swapped for placeholders in exports (/export redact)
+mod clip_sync;              // clipboard text shared inside a secure channel (/clip)
+mod offer_token;            // offers as "lanchgo-offer:..." text, pasted back into a files panel row
+mod screenshot;             // 📸 Screenshot: capture the screen, crop a region, offer it as a PNG
+#[cfg(feature = "test-hooks")]
+mod test_hooks;             // headless driving for end-to-end tests (--features test-hooks)
+
+// the parts without Slint live in the workspace crates (crates/), see Cargo.toml
+use lanchgo_core::{bandwidth, cipher_suite, identity, net_runtime, own_packets, pairing, phone_protocol, rate_limit, sealed_stream, tcp_file_client, tcp_file_server, transfers};
+use lanchgo_proto::{fragmentation, rooms};
 
-use semaphore::Semaphore;
 use slint::{ComponentHandle, LogicalSize, Model, ModelRc, VecModel};
+use std::cell::RefCell;
 use std::error::Error;
 use std::io;
-use std::io::ErrorKind;
-use std::net::UdpSocket;
+use std::net::SocketAddr;
 use std::rc::Rc;
-use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex };
-use std::thread::{self, sleep};
+use std::sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex };
+use std::thread;
 use std::time::Duration;
-use std::process;
 use bincode;
-use crate::classes::{BroadcastState, Config};
+use crate::classes::{BroadcastState, Config, SharedSocket};
 use crate::phone_protocol::build_MANCH;
 use crate::file_transfer_protocol::{ RemoteWindowsOfferRegistry, RemoteMobileOfferRegistry};
 use crate::udp_receiver::start_udp_receiver;
@@ -40,28 +80,206 @@ slint::include_modules!();
 //static APP_HANDLE: OnceLock<slint::Weak<AppWindow>> = OnceLock::new();
 const MAX_DATAGRAM: usize = 1400;
 
-fn broadcast_the_msg(sock: &UdpSocket, state: &BroadcastState, msg: &[u8]) -> io::Result<()> {
+fn broadcast_the_msg(sock: &SharedSocket, state: &BroadcastState, msg: &[u8]) -> io::Result<()> {
     let target = state.target_v4();
-    if msg.len() >= MAX_DATAGRAM {
-        return Err(io::Error::new(
-            ErrorKind::InvalidInput,
-            format!("message too long: {} > {}", msg.len(), MAX_DATAGRAM),
-        ));
+    let target_v6 = state.target_v6();
+
+    let packets = if msg.len() >= MAX_DATAGRAM {
+        // too big for one datagram -> FRAG packets, reassembled in udp_receiver
+        fragmentation::split_into_fragments(msg)?
+    } else {
+        vec![msg.to_vec()]
+    };
+
+    // the loopback copy of the whole message is recognised as ours (see own_packets)
+    own_packets::remember(msg);
+    for packet in &packets {
+        sock.send_to(packet, target)?;
+        bandwidth::record_sent(bandwidth::Kind::Chat, None, packet.len());
+        // v6 is best effort, receivers drop whichever copy arrives second
+        if let Some(Ok(n)) = sock.send_to_v6(packet, target_v6) {
+            bandwidth::record_sent(bandwidth::Kind::Chat, None, n);
+        }
+    }
+    Ok(())
+}
+
+/// Hash the file behind one of our offers in the background and broadcast it (FOFH) once it's
+/// known, so downloads of it can be verified. Folders aren't hashed.
+fn announce_offer_hash(sock: &Arc<SharedSocket>, state: &Arc<BroadcastState>, offer_id: [u8; 16], path: std::path::PathBuf) {
+    let (sock, state) = (Arc::clone(sock), Arc::clone(state));
+    thread::spawn(move || {
+        let Ok(digest) = file_transfer_protocol::sha256_file(&path) else { return; };
+        file_transfer_protocol::remember_hash(offer_id, digest);
+        let _ = broadcast_the_msg(&sock, &state, &file_transfer_protocol::hash_packet(&offer_id, &digest));
+    });
+}
+
+/// Offer one file to the LAN (FOFT + MFOFT for phones), as the paste paths do
+fn offer_file_publicly(sock: &Arc<SharedSocket>, state: &Arc<BroadcastState>, offer_registry: &Mutex<file_transfer_protocol::OfferRegistry>, path: std::path::PathBuf) -> io::Result<()> {
+    let packet = file_transfer_protocol::build_foft_packet_for_file(&path, &mut offer_registry.lock().unwrap())?;
+    broadcast_the_msg(sock, state, &packet).map_err(|_| io::Error::new(io::ErrorKind::Other, "Couldn't broadcast the offer"))?;
+    if let Some(offer) = file_transfer_protocol::decode_foft(&packet) {
+        if let Ok(mfoft_packet) = file_transfer_protocol::encode_mfoft_packet(&offer) {
+            let _ = broadcast_the_msg(sock, state, &mfoft_packet);
+        }
+        web_app_file_transfer::notify_web_file_offer(&offer.offer_id, &offer.name, offer.size);
+        announce_offer_hash(sock, state, offer.offer_id, path);
+    }
+    Ok(())
+}
+
+/// Offer one file to everyone in the secure channel (EFOF) and add our own row for it
+fn offer_file_to_channel(app: &AppWindow, sock: &SharedSocket, state: &BroadcastState, offer_registry: &Mutex<file_transfer_protocol::OfferRegistry>, path: &std::path::Path) {
+    let offer_id: [u8; 16] = *uuid::Uuid::new_v4().as_bytes();
+    let offer = {
+        let mut reg = offer_registry.lock().unwrap();
+        file_transfer_protocol::register_single_offer(path, offer_id, &mut reg)
+    };
+    let offer = match offer {
+        Ok(o) => o,
+        Err(e) => {
+            app.invoke_show_temp_message(format!("❌ {}", e).into());
+            return;
+        }
+    };
+    let Some(packet) = channel_files::build_offer_packet(&offer) else { return; };
+
+    channel_files::track_sent(offer_id);
+    if broadcast_the_msg(sock, state, &packet).is_err() {
+        app.invoke_show_popupmsg();
+        return;
+    }
+    channel_idle::touch();
+
+    // our own row, the button shows how many members have it
+    app.invoke_add_file_offer(FileOfferItem {
+        offer_id: file_transfer_protocol::offer_id_to_hex(&offer_id).into(),
+        name: file_transfer_protocol::truncate_name(&offer.name, 16).into(),
+        size_text: file_transfer_protocol::human_size(offer.size).into(),
+        is_downloading: false,
+        progress_text: "✓ 0".into(),
+        is_mobile: false,
+        is_own: true,
+        sender: "You".into(),
+        collapsed: false,
+        thumbnail: Default::default(),
+        saved: false,
+        queued: 0,
+    });
+    app.invoke_show_temp_message(format!("🔒 {} offered to the channel", offer.name).into());
+}
+
+/// Share text as a snippet (SNIP), peers fetch the text from our file server. A secure channel,
+/// or a peer that can't fetch snippets, gets it as a file instead. Returns the toast.
+fn share_snippet(app: &AppWindow, sock: &Arc<SharedSocket>, state: &Arc<BroadcastState>, offer_registry: &Mutex<file_transfer_protocol::OfferRegistry>, room: &str, text: &str, lang: &str) -> String {
+    if secure_channel_code::get_active_channel().is_some() {
+        return match file_transfer_protocol::save_pasted_text(text) {
+            Ok(path) => {
+                offer_file_to_channel(app, sock, state, offer_registry, &path);
+                "📄 Snippets are public only, sent to the channel as a file".to_string()
+            }
+            Err(e) => format!("❌ {}", e),
+        };
+    }
+    let lacking = peers::lacking(peers::CAP_SNIPPETS);
+    if !lacking.is_empty() {
+        return match file_transfer_protocol::save_pasted_text(text).and_then(|path| offer_file_publicly(sock, state, offer_registry, path)) {
+            Ok(()) => format!("📄 Sent as a file, {} can't receive snippets", lacking.join(", ")),
+            Err(e) => format!("❌ {}", e),
+        };
+    }
+    let packet = match snippets::share(text, lang, room) {
+        Ok(packet) => packet,
+        Err(e) => return format!("❌ {}", e),
+    };
+    if broadcast_the_msg(sock, state, &packet).is_err() {
+        return "❌ Couldn't broadcast the snippet".to_string();
+    }
+    let me = main_helpers::own_sender();
+    let row = ChatMessageItem {
+        sender: format!("{} (you)", me).into(),
+        ts: history::append(&me, text, "public").to_string().into(),
+        ..snippets::row(&me, text, lang)
+    };
+    snippets::push(app, row);
+    "🧩 Snippet shared".to_string()
+}
+
+/// ANCH (desktop) + MANCH (mobile) for the given channel
+fn broadcast_channel_announcement(sock: &SharedSocket, state: &BroadcastState, channel: &secure_channel_code::Channel) -> io::Result<()> {
+    let announce = secure_channel_code::build_announcement(channel);
+    if let Ok(payload) = bincode::serde::encode_to_vec(&announce, bincode::config::standard()) {
+        let mut packet = Vec::from(b"ANCH" as &[u8]);
+        packet.extend_from_slice(&payload);
+        secure_channel_code::append_announce_trailers(&mut packet, state.get_port(), channel);
+        broadcast_the_msg(sock, state, &packet)?;
+    }
+    if let Ok(man_json) = build_MANCH(channel, state.get_port()) {
+        let mut man_packet = Vec::from(b"MANCH" as &[u8]);
+        man_packet.extend_from_slice(man_json.as_bytes());
+        broadcast_the_msg(sock, state, &man_packet)?;
     }
-    sock.send_to(msg, target)?;
     Ok(())
 }
 
+/// Ask the host for the session key (EKXQ) a few times, back to public when it never answers
+fn request_session_key(sock: &Arc<SharedSocket>, state: &Arc<BroadcastState>, weak: slint::Weak<AppWindow>, channel_mode: Arc<Mutex<String>>) {
+    const ATTEMPTS: u32 = 3;
+    const WAIT: Duration = Duration::from_secs(1);
+    let (sock, state) = (Arc::clone(sock), Arc::clone(state));
+    thread::spawn(move || {
+        for _ in 0..ATTEMPTS {
+            let Some(packet) = secure_channel_code::key_request() else { return; };
+            let _ = broadcast_the_msg(&sock, &state, &packet);
+            thread::sleep(WAIT);
+            if !secure_channel_code::key_exchange_pending() {
+                return;
+            }
+        }
+        secure_channel_code::destroy_channel();
+        let _ = slint::invoke_from_event_loop(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            main_helpers::force_switch_to_public(&app, &channel_mode);
+            app.invoke_hide_connecting_popup();
+            app.invoke_show_temp_message("❌ The channel host didn't send the session key".into());
+        });
+    });
+}
+
+/// Leave the secure channel, as host first sending the signed transcript (/signlog)
+fn close_secure_channel(sock: &SharedSocket, state: &BroadcastState) {
+    if secure_channel_code::get_host_PIN().is_some() {
+        if let Some(channel) = secure_channel_code::get_active_channel() {
+            if let Some(packet) = transcript::sign_and_close(&channel) {
+                let _ = broadcast_the_msg(sock, state, &packet);
+            }
+            // joiners go back to public instead of failing to decrypt from here on
+            if let Some(packet) = secure_channel_code::build_close_packet(&channel) {
+                let _ = broadcast_the_msg(sock, state, &packet);
+            }
+        }
+    }
+    secure_channel_code::destroy_channel();
+}
+
 // ===================== main =====================
 
 fn main() -> Result<(), Box<dyn Error>> {
 
     let state = Arc::new(BroadcastState {
         broadcast_address: Mutex::new(String::new()),
-        port: Mutex::new(3000),
+        port: Mutex::new(main_helpers::DEFAULT_UDP_PORT),
+        multicast_group: Mutex::new(None),
     });
     get_broadcast_address(&state);
+    // identity_key.bin sits next to the config
+    if let Some(dir) = main_helpers::get_config_path().parent() {
+        identity::init(dir.to_path_buf());
+    }
 
+    #[cfg(feature = "test-hooks")]
+    test_hooks::init_headless_if_requested();
     let app = AppWindow::new()?;
     let w = app.window();
     w.set_fullscreen(false);
@@ -73,13 +291,12 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // -------- interfaces list -> UI
     let interfaces = collect_interfaces();
-    let iface_rows: Vec<slint::SharedString> = interfaces
-        .iter().map(|it| { format!( "Name: {}\nBroadcast Address: {}", it.name, it.address_to_broadcast ).into()}).collect();
+    let iface_rows = main_helpers::interface_rows(&interfaces);
     let iface_model = Rc::new(VecModel::from(iface_rows));
     app.set_interfaces(ModelRc::new(iface_model.clone()));
 
     // -------- chat model
-    let model = Rc::new(VecModel::from(Vec::<slint::SharedString>::new()));
+    let model = Rc::new(VecModel::from(Vec::<ChatMessageItem>::new()));
     app.set_messages(ModelRc::new(model.clone()));
     let model_for_clear = model.clone();
 
@@ -89,17 +306,82 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let offer_registry = Arc::new(Mutex::new(file_transfer_protocol::OfferRegistry::new()));
     web_app_file_transfer::register_offer_registry(Arc::clone(&offer_registry));
-    // start tcp listner and put it in idle here
-    let _tcp_handle = tcp_file_server::start_file_server(
-        Arc::clone(&offer_registry),
-        file_transfer_protocol::DEFAULT_TCP_PORT, )?; // <-- starts idle listener thread
     let remote_windows_offers: Arc<Mutex<RemoteWindowsOfferRegistry>> = Arc::new(Mutex::new(RemoteWindowsOfferRegistry::new()));
     let remote_mobile_offers: Arc<Mutex<RemoteMobileOfferRegistry>> = Arc::new(Mutex::new(RemoteMobileOfferRegistry::new()));
     // for pushing file offers in the Vector
     {
         let file_offer_model = file_offer_model.clone();
-        app.on_add_file_offer(move |item: FileOfferItem| {
-            file_offer_model.push(item);
+        app.on_add_file_offer(move |mut item: FileOfferItem| {
+            // keep each sender's offers together under one header, new ones at the end of the group
+            let last_of_sender = (0..file_offer_model.row_count())
+                .rev()
+                .find(|&i| file_offer_model.row_data(i).is_some_and(|row| row.sender == item.sender));
+            match last_of_sender {
+                Some(i) => {
+                    item.collapsed = file_offer_model.row_data(i).is_some_and(|row| row.collapsed);
+                    file_offer_model.insert(i + 1, item);
+                }
+                None => file_offer_model.push(item),
+            }
+        });
+    }
+
+    // downloads the last run didn't finish, their .part is still on disk
+    for (entry, have) in resume::orphans() {
+        let percent = main_helpers::progress_bucket_3(have, entry.offer.size);
+        remote_windows_offers.lock().unwrap().insert(entry.offer_id.clone(), (entry.sender_addr, entry.offer.clone()));
+        thumbnails::request(app.as_weak(), entry.sender_addr, &entry.offer);
+        app.invoke_add_file_offer(FileOfferItem {
+            offer_id: entry.offer_id.into(),
+            name: file_transfer_protocol::truncate_name(&entry.offer.name, 16).into(),
+            size_text: format!("{} · {}% kept", file_transfer_protocol::human_size(entry.offer.size), percent).into(),
+            is_downloading: false,
+            progress_text: "resume".into(),
+            is_mobile: false,
+            is_own: false,
+            sender: entry.sender.into(),
+            collapsed: false,
+            thumbnail: Default::default(),
+            saved: false,
+            queued: 0,
+        });
+    }
+
+    // fold / unfold a sender's offers
+    {
+        let file_offer_model = file_offer_model.clone();
+        app.on_toggle_offer_group(move |sender| {
+            let rows: Vec<usize> = (0..file_offer_model.row_count())
+                .filter(|&i| file_offer_model.row_data(i).is_some_and(|row| row.sender == sender))
+                .collect();
+            let collapse = rows.first().and_then(|&i| file_offer_model.row_data(i)).is_some_and(|row| !row.collapsed);
+            for i in rows {
+                if let Some(mut row) = file_offer_model.row_data(i) {
+                    row.collapsed = collapse;
+                    file_offer_model.set_row_data(i, row);
+                }
+            }
+        });
+    }
+
+    // "⬇ All" on a sender header: everything from them not started yet
+    {
+        let file_offer_model = file_offer_model.clone();
+        let weak = app.as_weak();
+        app.on_download_all_from(move |sender| {
+            let Some(app) = weak.upgrade() else { return; };
+            let ids: Vec<slint::SharedString> = file_offer_model
+                .iter()
+                .filter(|row| row.sender == sender && !row.is_own && !row.is_downloading && row.progress_text.is_empty())
+                .map(|row| row.offer_id)
+                .collect();
+            if ids.is_empty() {
+                app.invoke_show_temp_message(format!("📦 Nothing new from {}", sender).into());
+                return;
+            }
+            for id in ids {
+                app.invoke_download_offer(id);
+            }
         });
     }
 
@@ -107,7 +389,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     {
         let file_offer_model = file_offer_model.clone();
         let offer_registry = Arc::clone(&offer_registry);
-        app.on_clear_file_transfer_panel(move || { cleanup_file_offers(&offer_registry, Some(&file_offer_model)); });
+        app.on_clear_file_transfer_panel(move || {
+            download_queue::clear();
+            cleanup_file_offers(&offer_registry, Some(&file_offer_model));
+            resume::discard_paused();
+        });
     }
 
     // -------- channel mode shared state
@@ -132,8 +418,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     {
         let model = model.clone();
         app.on_append_message(move |msg: slint::SharedString| {
-            model.push(msg.clone());
-            if model.row_count() > 10 {
+            model.push(main_helpers::system_row(msg.clone()));
+            while model.row_count() > main_helpers::chat_row_limit() {
                 model.remove(0);
             }
             // 🔥 send to web clients
@@ -142,12 +428,235 @@ fn main() -> Result<(), Box<dyn Error>> {
         });
     }
 
+    // a peer /pinged us: highlighted like a mention
+    {
+        let model = model.clone();
+        app.on_append_attention(move |msg: slint::SharedString| {
+            let mut row = main_helpers::system_row(msg);
+            row.mentioned = true;
+            model.push(row);
+            while model.row_count() > main_helpers::chat_row_limit() {
+                model.remove(0);
+            }
+        });
+    }
+
+    // append peer message handler (colored by sender)
+    {
+        let model = model.clone();
+        let channel_mode = Arc::clone(&channel_mode);
+        app.on_append_peer_message(move |sender: slint::SharedString, msg: slint::SharedString| {
+            let channel = if *channel_mode.lock().unwrap() == "public" { "public" } else { "secure" };
+            let processed = message_pipeline::process(sender.as_str(), msg.as_str());
+            let ts = history::append(sender.as_str(), &processed.text, channel);
+            let text = match &processed.translation {
+                Some(t) => format!("{}\n🌐 {}", processed.text, t),
+                None => processed.text.clone(),
+            };
+            let rich = main_helpers::rich_lines(&text);
+            let name_conflict = sender.parse().is_ok_and(contacts::has_name_conflict);
+            // by clock, not arrival: a message can land above one that overtook it on the wire
+            let index = message_pipeline::insert_index(model.row_count(), |i| model.row_data(i).map(|r| r.order.to_string()).unwrap_or_default(), &processed.order);
+            model.insert(index, ChatMessageItem {
+                color_index: main_helpers::sender_color_index(sender.as_str()),
+                text: text.into(),
+                sender,
+                ts: ts.to_string().into(),
+                time: "".into(),
+                starred: false,
+                lang: processed.lang.unwrap_or_default().into(),
+                mentioned: processed.mentioned,
+                pending: false,
+                rich,
+                order: processed.order.into(),
+                name_conflict,
+                muted_from: "".into(),
+                snippet: Default::default(),
+                snippet_lang: "".into(),
+                snippet_lines: 0,
+                snippet_open: false,
+            });
+            if processed.mentioned && !main_helpers::channel_muted() {
+                thread::spawn(main_helpers::play_mention_sound);
+            }
+            tray::note_incoming();
+            notify::message(sender.as_str(), &processed.text, processed.mentioned);
+            while model.row_count() > main_helpers::chat_row_limit() {
+                model.remove(0);
+            }
+            let payload = serde_json::json!({ "type": "chat", "sender": "app", "text": processed.text});
+            web_app::broadcast_to_web_clients(payload.to_string());
+            if channel == "public" {
+                serial_bridge::mirror(sender.as_str(), &processed.text);
+            }
+        });
+    }
+
+    // jump the chat view to a day from the history
+    {
+        let model = model.clone();
+        let weak = app.as_weak();
+        app.on_jump_to_date(move |year, month, day| {
+            let Some(app) = weak.upgrade() else { return; };
+            let Some(date) = chrono::NaiveDate::from_ymd_opt(year, month as u32, day as u32) else { return; };
+
+            match history::load_day(date, main_helpers::HISTORY_PAGE_SIZE) {
+                Some((found, entries)) => {
+                    let mut rows = vec![main_helpers::system_row(format!("📅 {}", found.format("%A, %d %B %Y")))];
+                    rows.extend(entries.iter().map(|e| main_helpers::history_row(e, e.local_time())));
+                    model.set_vec(rows);
+                    if found != date {
+                        app.invoke_show_temp_message(format!("📅 Nothing on {}, showing {}", date, found).into());
+                    }
+                }
+                None => app.invoke_show_temp_message(format!("📭 No history on or after {}", date).into()),
+            }
+        });
+    }
+
+    // -------- starred messages
+    let starred_model = Rc::new(VecModel::from(Vec::<ChatMessageItem>::new()));
+    app.set_starred_messages(ModelRc::new(starred_model.clone()));
+    {
+        let starred_model = starred_model.clone();
+        app.on_refresh_starred(move || {
+            let rows = history::starred()
+                .iter()
+                .map(|e| main_helpers::history_row(e, format!("{} {} · {}", e.local_date(), e.local_time(), e.channel)))
+                .collect::<Vec<_>>();
+            starred_model.set_vec(rows);
+        });
+    }
+    {
+        let model = model.clone();
+        let weak = app.as_weak();
+        app.on_toggle_star(move |index| {
+            let Some(mut row) = model.row_data(index as usize) else { return; };
+            let Ok(ts) = row.ts.parse::<i64>() else { return; };
+            row.starred = history::toggle_star(ts, row.text.as_str());
+            let starred = row.starred;
+            model.set_row_data(index as usize, row);
+            if let Some(app) = weak.upgrade() {
+                app.invoke_refresh_starred();
+                app.invoke_show_temp_message(if starred { "⭐ Starred".into() } else { "☆ Unstarred".into() });
+            }
+        });
+    }
+    // statistics popup (click on the network strip)
+    {
+        let weak = app.as_weak();
+        app.on_refresh_stats(move || {
+            if let Some(app) = weak.upgrade() {
+                app.set_stats_text(bandwidth::summary().into());
+            }
+        });
+    }
+    // right-click / long-press → Copy on a chat bubble
+    {
+        let model = model.clone();
+        let weak = app.as_weak();
+        app.on_copy_message(move |index| {
+            let Some(row) = model.row_data(index as usize) else { return; };
+            let Some(app) = weak.upgrade() else { return; };
+            match main_helpers::copy_to_clipboard(&row.text) {
+                Ok(()) => app.invoke_show_temp_message("📋 Copied".into()),
+                Err(e) => app.invoke_show_temp_message(format!("❌ {}", e).into()),
+            }
+        });
+    }
+    // right-click → Mute for 1 hour / Show muted messages
+    {
+        let model = model.clone();
+        let weak = app.as_weak();
+        app.on_mute_sender(move |index| {
+            let Some(row) = model.row_data(index as usize) else { return; };
+            let Some(app) = weak.upgrade() else { return; };
+            let Ok(peer) = row.sender.parse() else {
+                app.invoke_show_temp_message("🔇 Only peers can be muted".into());
+                return;
+            };
+            mute::mute(peer, mute::DEFAULT);
+            app.invoke_show_temp_message(format!("🔇 {} muted for an hour, /unmute to end it", peers::label(peer, None)).into());
+        });
+    }
+    {
+        let model = model.clone();
+        let weak = app.as_weak();
+        app.on_show_muted(move |index| {
+            let Some(row) = model.row_data(index as usize) else { return; };
+            let Some(app) = weak.upgrade() else { return; };
+            if let Ok(peer) = row.muted_from.parse() {
+                mute::show(&app, peer);
+            }
+        });
+    }
+    // "Show all" / "Collapse" under a shared snippet
+    {
+        let model = model.clone();
+        app.on_toggle_snippet(move |index| {
+            let Some(mut row) = model.row_data(index as usize) else { return; };
+            snippets::toggle(&mut row);
+            model.set_row_data(index as usize, row);
+        });
+    }
+    {
+        let model = model.clone();
+        let starred_model = starred_model.clone();
+        app.on_jump_to_starred(move |index| {
+            let Some(item) = starred_model.row_data(index as usize) else { return; };
+            let Ok(ts) = item.ts.parse::<i64>() else { return; };
+            if let Some(rows) = main_helpers::context_rows(ts, format!("⭐ Context · {}", item.time)) {
+                model.set_vec(rows);
+            }
+        });
+    }
+
+    // -------- history search (/search)
+    let search_model = Rc::new(VecModel::from(Vec::<ChatMessageItem>::new()));
+    app.set_search_results(ModelRc::new(search_model.clone()));
+    {
+        let search_model = search_model.clone();
+        let weak = app.as_weak();
+        app.on_search_history(move |term| {
+            let Some(app) = weak.upgrade() else { return; };
+            let rows = history::search(term.as_str(), main_helpers::SEARCH_LIMIT)
+                .iter()
+                .map(|e| main_helpers::history_row(e, format!("{} {} · {}", e.local_date(), e.local_time(), e.channel)))
+                .collect::<Vec<_>>();
+            search_model.set_vec(rows);
+            app.set_search_term(term.trim().into());
+            app.invoke_open_search();
+        });
+    }
+    {
+        let model = model.clone();
+        let search_model = search_model.clone();
+        let weak = app.as_weak();
+        app.on_jump_to_search_result(move |index| {
+            let Some(item) = search_model.row_data(index as usize) else { return; };
+            let Ok(ts) = item.ts.parse::<i64>() else { return; };
+            match main_helpers::context_rows(ts, format!("🔎 Context · {}", item.time)) {
+                Some(rows) => model.set_vec(rows),
+                None => {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_show_temp_message("📭 That message is no longer in the history".into());
+                    }
+                }
+            }
+        });
+    }
+
     // ===================== config creation + download folder =====================
 
     let default_iface_name = match_getifadd_ipconfig(&state);
     let default_broadcast = get_broadcast_for_name(&interfaces, &default_iface_name)
         .unwrap_or_else(|| state.get_broadcast_address());
     let default_gateway = get_gateway_for_adapter(&default_iface_name);
+    let default_fingerprint = interfaces
+        .iter()
+        .find(|it| it.name == default_iface_name)
+        .map(main_helpers::network_fingerprint)
+        .unwrap_or_default();
 
     let default_download_folder = dirs::download_dir()
         .unwrap_or_else(|| std::path::PathBuf::from("."))
@@ -157,16 +666,102 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let default_config = Config {
         version: env!("CARGO_PKG_VERSION").to_string(),
+        schema: config_migration::CURRENT_SCHEMA,
         selected_interface: default_iface_name.clone(),
         last_broadcast: default_broadcast.clone(),
         last_gateway: default_gateway.clone(),
         save_to_folder: default_download_folder,
         port: None,
-        ui_scale: None
+        ui_scale: None,
+        multicast_group: None,
+        files_panel_width: None,
+        language_tags: false,
+        glossary_translate: false,
+        own_language: "en".to_string(),
+        aliases: Default::default(),
+        room: rooms::DEFAULT_ROOM.to_string(),
+        nickname: main_helpers::default_nickname(),
+        device_name: main_helpers::default_device_name(),
+        mentions_only: false,
+        ping_sound: true,
+        nutella_sound: true,
+        notifications: true,
+        public_alerts: true,
+        secure_alerts: true,
+        network_fingerprint: default_fingerprint,
+        auto_download_mb: 0,
+        auto_download_trusted_only: false,
+        auto_accept_trusted: false,
+        auto_accept_max_mb: 2048,
+        trusted_peers: Default::default(),
+        theme: "classic".to_string(),
+        chat_rows: 10,
+        channel_idle_minutes: 30,
+        max_parallel_downloads: 2,
+        file_server_port: None,
+        status_port: None,
+        serial_bridge: None,
+        clipboard_sync: Default::default(),
+        sign_transcripts: false,
+        forward_secrecy: false,
+        port_sharing: Default::default(),
+        transfer: Default::default(),
+        rate_limit: Default::default(),
+        paired_devices: Default::default(),
+        rules: Vec::new(),
     };
 
     let (config_loaded, first_run) = load_or_create_config(&default_config, &app);
-    let config = Arc::new(Mutex::new(config_loaded));
+    let settings = settings::SettingsStore::new(config_loaded);
+    let config = settings.shared();
+
+    // start tcp listner and put it in idle here, without it chat still works but files don't.
+    // A busy port (another LanChGo on this machine, another app) falls back to one the OS picks,
+    // offers carry the real port so peers still find it.
+    let wanted_port = config.lock().unwrap().file_server_port.unwrap_or(file_transfer_protocol::DEFAULT_TCP_PORT);
+    let file_server = Arc::new(tcp_file_server::FileServer {
+        registry: Arc::clone(&offer_registry),
+        // pushes, previews and snippets need the UI, the core serves the offers themselves
+        protocols: vec![
+            tcp_file_server::Protocol { magic: phone_push::MPUSH_MAGIC, serve: |stream, _, read, write| Box::pin(phone_push::handle_push(stream, read, write)) },
+            tcp_file_server::Protocol { magic: phone_push::DPUSH_MAGIC, serve: |stream, _, read, write| Box::pin(phone_push::handle_push(stream, read, write)) },
+            tcp_file_server::Protocol { magic: thumbnails::FTHB_MAGIC, serve: |stream, registry, read, write| Box::pin(thumbnails::serve(stream, registry, read, write)) },
+            tcp_file_server::Protocol { magic: snippets::FSNP_MAGIC, serve: |stream, _, read, write| Box::pin(snippets::serve(stream, read, write)) },
+        ],
+        on_event: Box::new(|event| match event {
+            tcp_file_server::ServerEvent::Log(line) => diagnostics::log("file server", line),
+            tcp_file_server::ServerEvent::Sent { peer, name, size } => records::transfer(true, peer, &name, size, "completed".into()),
+            tcp_file_server::ServerEvent::Delivered { offer_id, member, count } => main_helpers::notify_channel_delivery(offer_id, member, count),
+            tcp_file_server::ServerEvent::Paired => {
+                if let Some(weak) = main_helpers::app_handle() {
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(app) = weak.upgrade() {
+                            app.invoke_device_paired();
+                        }
+                    });
+                }
+            }
+        }),
+    });
+    let started = match tcp_file_server::start_file_server(Arc::clone(&file_server), wanted_port) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            diagnostics::log("file server", format!("port {} in use, letting the OS pick one", wanted_port));
+            tcp_file_server::start_file_server(file_server, 0)
+        }
+        started => started,
+    };
+    let _tcp_handle = match started {
+        Ok((handle, port)) => {
+            file_transfer_protocol::set_tcp_port(port);
+            Some(handle)
+        }
+        Err(e) => {
+            diagnostics::log("file server", format!("bind on port {} failed: {}", wanted_port, e));
+            let reason = if e.kind() == io::ErrorKind::AddrInUse { format!("port {} in use", wanted_port) } else { format!("port {}: {}", wanted_port, e) };
+            main_helpers::set_file_server_error(&app, reason);
+            None
+        }
+    };
 
     // ensure folder exists + push to UI
     {
@@ -181,18 +776,54 @@ fn main() -> Result<(), Box<dyn Error>> {
         app.set_download_folder(cfg.save_to_folder.clone().into());
     }
 
+    // public chat room, the receiver drops text from every other room
+    let current_room = Arc::new(Mutex::new(config.lock().unwrap().room.clone()));
+    app.set_current_room(current_room.lock().unwrap().clone().into());
+
+    // configured adapter is gone (docked / undocked...) -> take the best one instead of asking again
+    let auto_picked = {
+        let configured = config.lock().unwrap().selected_interface.clone();
+        if interfaces.iter().any(|it| it.name == configured) {
+            None
+        } else {
+            main_helpers::best_interface(&interfaces).map(|best| {
+                main_helpers::remember_interface(&config, &best);
+                (configured, best.name)
+            })
+        }
+    };
+
     // ===================== network change checks (using locked config) =====================
     let (current_broadcast_for_config, _current_gateway_for_config, lan_changed, selected_iface_for_ui) =
     {
-        let cfg = config.lock().unwrap();
-        if let Some(scale) = cfg.ui_scale { app.set_global_scale(scale); }
+        settings.apply_to_ui(&app);
+        let mut cfg = config.lock().unwrap();
+        if let Some(width) = cfg.files_panel_width { app.set_files_panel_width(width); }
+        main_helpers::apply_aliases_to_ui(&app, &cfg.aliases);
         let current_broadcast_for_config =
             get_broadcast_for_name(&interfaces, &cfg.selected_interface)
                 .unwrap_or_else(|| state.get_broadcast_address());
         let current_gateway_for_config = get_gateway_for_adapter(&cfg.selected_interface);
 
-        let lan_changed = cfg.last_broadcast != current_broadcast_for_config
-            || cfg.last_gateway != current_gateway_for_config;
+        let current_fingerprint = interfaces
+            .iter()
+            .find(|it| it.name == cfg.selected_interface)
+            .map(main_helpers::network_fingerprint);
+
+        let lan_changed = match &current_fingerprint {
+            Some(fp) if !cfg.network_fingerprint.is_empty() => *fp != cfg.network_fingerprint,
+            // config from before fingerprints (or adapter gone), old check one last time
+            _ => cfg.last_broadcast != current_broadcast_for_config
+                || cfg.last_gateway != current_gateway_for_config,
+        };
+
+        // same network, just upgrade an old config quietly
+        if !lan_changed && cfg.network_fingerprint.is_empty() {
+            if let Some(fp) = current_fingerprint {
+                cfg.network_fingerprint = fp;
+                save_config(&cfg);
+            }
+        }
 
         (current_broadcast_for_config, current_gateway_for_config, lan_changed, cfg.selected_interface.clone())
     };
@@ -227,14 +858,15 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 
     app.set_show_welcome(first_run || lan_changed);
+    if let Some((missing, picked)) = &auto_picked {
+        if !first_run {
+            app.invoke_show_temp_message(format!("🔌 {} isn't here, using {}", missing, picked).into());
+        }
+    }
     app.set_selected_interface(selected_iface_for_ui.clone().into());
     app.set_broadcast_address(state.get_broadcast_address().into());
 
-    if let Some(info) = interfaces.iter().find(|it| it.name == selected_iface_for_ui) {
-        app.set_interface_status(info.status.clone().into());
-    } else {
-        app.set_interface_status("IfOperStatusDown".into());
-    }
+    main_helpers::set_active_interface(&app, interfaces.iter().find(|it| it.name == selected_iface_for_ui));
 
     //main_helpers::checking_ports(&state);
 
@@ -256,181 +888,324 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 
     // ===================== UDP receiver =====================
-    let sock = bind_single_port_socket(state.get_port())?;
+    let sock = Arc::new(bind_single_port_socket(state.get_port())?);
     let running = Arc::new(AtomicBool::new(true));
 
+    // multicast mode from config (networks that filter directed broadcast)
+    {
+        let saved_group = config.lock().unwrap().multicast_group.clone();
+        if let Some(text) = saved_group {
+            match main_helpers::parse_multicast_group(&text) {
+                Ok(group) => {
+                    let iface_ip = main_helpers::selected_interface_ipv4(&config);
+                    match main_helpers::join_multicast_group(&sock, group, iface_ip) {
+                        Ok(()) => state.set_multicast_group(Some(group)),
+                        Err(e) => app.invoke_show_temp_message(format!("⚠️ Could not join multicast group {}: {}", group, e).into()),
+                    }
+                }
+                Err(e) => app.invoke_show_temp_message(format!("⚠️ {}", e).into()),
+            }
+            app.set_multicast_group(text.into());
+        }
+        app.set_multicast_mode(state.get_multicast_group().is_some());
+    }
+
     let _recv_handle = start_udp_receiver(
         Arc::clone(&sock),
         Arc::clone(&running),
         app.as_weak(),
         Arc::clone(&channel_mode),
+        Arc::clone(&current_room),
         Arc::clone(&remote_windows_offers),
         Arc::clone(&remote_mobile_offers),
+        Arc::clone(&offer_registry),
     );
 
-    // ===================== Send button =====================
+    // the receiver keeps failing: banner + a fresh socket (RecvSupervisor paces the calls)
     {
-        let st = Arc::clone(&state);
-        let s = Arc::clone(&sock);
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        let config = Arc::clone(&config);
         let weak = app.as_weak();
-
-        let offer_registry2 = Arc::clone(&offer_registry);
-        let running2 = Arc::clone(&running);
-        let file_offer_model2 = file_offer_model.clone();
-        let model2 = model.clone();
-        let config_for_commands = Arc::clone(&config);
-
-        app.on_send_clicked(move || {
-            let Some(app) = weak.upgrade() else { return; };
-
-            let msg = app.get_input_text().to_string();
-            let trimmed = msg.trim();
-
-            if msg.eq_ignore_ascii_case("/exit") {
-                app.invoke_append_message("🚪 Exiting in 1 seconds...".into());
-
-                running2.store(false, Ordering::Relaxed);
-
-                {
-                    let mut reg = offer_registry2.lock().unwrap();
-                    file_transfer_protocol::cleanup_temp_offers(&mut reg);
-                    reg.clear();
-                }
-
-                file_offer_model2.set_vec(Vec::new());
-
-                thread::spawn(|| {
-                    sleep(Duration::from_secs(1));
-                    let _ = crate::web_app::stop_web_server();
-                    process::exit(0);
-                });
-
-                return;
+        let rebinding = Arc::new(AtomicBool::new(false));
+        app.on_receiver_down(move |error| {
+            if let Some(app) = weak.upgrade() {
+                main_helpers::set_receive_error(&app, Some(error.to_string()));
             }
-
-            if msg.eq_ignore_ascii_case("/clear") {
-                model2.set_vec(Vec::new());
-                app.set_input_text("".into());
+            if rebinding.swap(true, Ordering::SeqCst) {
                 return;
             }
+            let (sock, state, config, rebinding) = (Arc::clone(&sock), Arc::clone(&state), Arc::clone(&config), Arc::clone(&rebinding));
+            thread::spawn(move || {
+                if let Err(e) = main_helpers::rebind_to_state_port(&sock, &state, &config) {
+                    diagnostics::log("receiver", format!("re-bind on port {} failed: {}", state.get_port(), e));
+                }
+                rebinding.store(false, Ordering::SeqCst);
+            });
+        });
+    }
 
-            if msg.eq_ignore_ascii_case("/disconnect") {
-                app.invoke_disconnect_channel();
-                app.set_input_text("".into());
-                return;
+    // a phone answered the /pair QR code: remember it and trust its address
+    {
+        let settings = settings.clone();
+        let weak = app.as_weak();
+        app.on_device_paired(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            for (device_id, device) in pairing::take_completed() {
+                let name = device.name.clone();
+                settings.update(|cfg| {
+                    cfg.trusted_peers.insert(device.last_ip.clone());
+                    cfg.paired_devices.insert(device_id, device);
+                });
+                app.set_pair_link("".into());
+                app.invoke_show_temp_message(format!("📱 Paired with {}", name).into());
             }
+        });
+    }
+    app.on_pair_closed(|| pairing::cancel());
 
-            if msg.eq_ignore_ascii_case("/webstop") {
-                match web_app::stop_web_server() {
-                    Ok(()) => {
-                        app.set_web_session_active(false);
-                        app.invoke_show_temp_message("🛑 Web session stopped".into());
+    // ===================== mDNS discovery =====================
+    let mdns = {
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        let current_room = Arc::clone(&current_room);
+        let weak = app.as_weak();
+
+        let started = discovery::start_discovery(state.get_port(), Arc::clone(&running), move |event| {
+            let text = match event {
+                discovery::DiscoveryEvent::PeerFound(peer) => {
+                    // the TXT key is only a claim, the contact book hears about it once the peer proved it
+                    if let (Some(key), Some(ip)) = (peer.identity.clone(), peer.addresses.iter().copied().find(|ip| ip.is_ipv4())) {
+                        let nickname = peer.nickname.clone().unwrap_or_else(|| peer.display_name.clone());
+                        let display_name = peer.display_name.clone();
+                        let tcp_port = peer.tcp_port.unwrap_or(file_transfer_protocol::DEFAULT_TCP_PORT);
+                        let weak = weak.clone();
+                        net_runtime::spawn(async move {
+                            if !identity::prove(ip, tcp_port, &key).await {
+                                return;
+                            }
+                            let Some(holder) = contacts::observe(&key, &nickname, ip) else { return; };
+                            let _ = slint::invoke_from_event_loop(move || {
+                                if let Some(app) = weak.upgrade() {
+                                    app.invoke_show_temp_message(format!("⚠️ {} now also calls itself {}, a name another device had first — its messages are marked until you resolve it in /contacts", display_name, holder.nickname).into());
+                                }
+                            });
+                        });
                     }
-                    Err(e) => {
-                        app.invoke_show_temp_message(format!("❌ {e}").into());
+                    let was_hosting = peers::upsert_discovered(peer.clone());
+                    if peer.hosting {
+                        // ask the host directly, its ANCH may never reach us as a broadcast
+                        for ip in peer.addresses.iter().filter(|ip| ip.is_ipv4()) {
+                            let _ = sock.send_to(b"REQA", SocketAddr::new(*ip, peer.port));
+                        }
+                    }
+                    let our_port = state.get_port();
+                    // welcome rules, unicast so only the newcomer sees them
+                    if was_hosting.is_none() && peer.port == our_port {
+                        if let Some(ip) = peer.addresses.iter().find(|ip| ip.is_ipv4()) {
+                            let name = peer.nickname.as_deref().unwrap_or(&peer.display_name);
+                            for message in rules::peer_joined(*ip, name) {
+                                let stamped = message_pipeline::stamp_outgoing(&message);
+                                let packet = rooms::encode_text(&current_room.lock().unwrap(), stamped.as_bytes());
+                                let _ = sock.send_to(&packet, SocketAddr::new(*ip, peer.port));
+                            }
+                        }
+                    }
+                    match was_hosting {
+                        None if peer.port != our_port => format!("⚠️ {} uses UDP port {}, you are on {} — you won't see each other", peer.display_name, peer.port, our_port),
+                        None if peer.hosting => format!("🔐 Found secure channel hosted by {}", peer.display_name),
+                        None => format!("🔎 Found LanChGo on {}", peer.display_name),
+                        Some(false) if peer.hosting => format!("🔐 {} is now hosting a secure channel", peer.display_name),
+                        _ => String::new(),
                     }
                 }
-                app.set_input_text("".into());
-                return;
+                discovery::DiscoveryEvent::PeerLost(fullname) => {
+                    peers::remove_discovered(&fullname);
+                    String::new()
+                }
+            };
+            let weak = weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(app) = weak.upgrade() {
+                    main_helpers::refresh_network_status(&app);
+                    if !text.is_empty() {
+                        app.invoke_show_temp_message(text.into());
+                    }
+                }
+            });
+        });
+        // mDNS is optional, broadcast keeps working without it
+        started.ok().map(|(mdns, _handle)| mdns)
+    };
+
+    // ===================== Sleep / resume =====================
+    {
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        let config = Arc::clone(&config);
+        let channel_mode = Arc::clone(&channel_mode);
+        let weak = app.as_weak();
+
+        let _power_handle = power_watch::start_resume_watcher(Arc::clone(&running), move || {
+            // sockets are often stale after resume, get a fresh one
+            // the DHCP lease / broadcast address may have changed while asleep
+            let (interfaces, selected) = main_helpers::refresh_selected_interface(&state, &config);
+            let rebind_ok = main_helpers::rebind_to_state_port(&sock, &state, &config).is_ok();
+
+            // re-announce ourselves so peers pick us up again
+            let mode = channel_mode.lock().unwrap().clone();
+            match mode.as_str() {
+                "host" => {
+                    if let Some(channel) = secure_channel_code::get_active_channel() {
+                        let _ = broadcast_channel_announcement(&sock, &state, &channel);
+                        reannounce::activity();
+                    }
+                }
+                "joined" => {
+                    let _ = broadcast_the_msg(&sock, &state, b"REQA");
+                }
+                _ => {}
             }
 
-            if msg.eq_ignore_ascii_case("/webjoin") {
-                match web_app::start_web_server() {
-                    Ok(()) => {
-                        app.set_web_session_active(true);
-                        update_ui_qr_only(&app);
-                        match crate::web_app::get_url_to_main() {
-                            Some(url) => app.set_url_link(url.into()),
-                            None => {}
+            let weak = weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                let Some(app) = weak.upgrade() else { return; };
+                main_helpers::apply_interfaces_to_ui(&app, &interfaces, selected.as_ref());
+                if rebind_ok {
+                    app.invoke_show_temp_message("💤 Resumed — network reconnected".into());
+                } else {
+                    app.invoke_show_temp_message("⚠️ Resumed — could not re-bind the UDP port".into());
+                }
+            });
+        });
+    }
+
+    // ===================== Interface hot-plug =====================
+    {
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        let config = Arc::clone(&config);
+        let weak = app.as_weak();
+        let last_selected = Mutex::new(
+            interfaces.iter().find(|it| it.name == selected_iface_for_ui).cloned()
+        );
+
+        let _net_handle = net_watch::start_interface_watcher(Arc::clone(&running), move |now| {
+            let (interfaces, selected) = main_helpers::apply_selected_interface(&state, &config, now);
+            let mut last = last_selected.lock().unwrap();
+
+            let toast = match (last.as_ref(), selected.as_ref()) {
+                // same adapter, same address -> only other adapters changed
+                (Some(old), Some(new)) if old.address_to_broadcast == new.address_to_broadcast && old.status == new.status => None,
+                (_, Some(new)) => {
+                    // new lease or the adapter came back, the old socket may be tied to a dead route
+                    let ok = main_helpers::rebind_to_state_port(&sock, &state, &config).is_ok();
+                    Some(if ok {
+                        format!("🔌 {} is active ({})", new.name, new.address_to_broadcast)
+                    } else {
+                        format!("⚠️ {} changed but the UDP port could not be re-bound", new.name)
+                    })
+                }
+                (Some(old), None) => {
+                    // keep talking on whatever is still up until the user picks something
+                    match main_helpers::best_interface(&interfaces) {
+                        Some(fallback) => {
+                            state.set_broadcast_address(fallback.address_to_broadcast.clone());
+                            Some(format!("⚠️ {} went away, using {} for now", old.name, fallback.name))
                         }
-                        app.invoke_show_temp_message("🌐 Web session started".into());
-                    }
-                    Err(e) => {
-                        app.invoke_show_temp_message(format!("❌ {e}").into());
+                        None => Some(format!("⚠️ {} went away — no active network", old.name)),
                     }
                 }
-                app.set_input_text("".into());
-                return;
-            }
+                (None, None) => None,
+            };
+            *last = selected.clone();
+            drop(last);
 
-            if msg.eq_ignore_ascii_case("/clearfiles") {
-                cleanup_file_offers(&offer_registry2, Some(&file_offer_model2));
-                app.set_input_text("".into());
-                return;
-            }
+            let weak = weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                let Some(app) = weak.upgrade() else { return; };
+                main_helpers::apply_interfaces_to_ui(&app, &interfaces, selected.as_ref());
+                if let Some(text) = toast {
+                    app.invoke_show_temp_message(text.into());
+                }
+            });
+        });
+    }
 
-            if msg.eq_ignore_ascii_case("/clearall") {
-                model2.set_vec(Vec::new());
-                cleanup_file_offers(&offer_registry2, Some(&file_offer_model2));
-                app.set_input_text("".into());
-                return;
-            }
-            
-            if trimmed.eq_ignore_ascii_case("/info") {
-                let info = main_helpers::info_message();
-                app.invoke_append_message(info.into());
-                app.set_input_text("".into());
-                return;
-            }
+    // messages typed while offline, flushed by the retry task below
+    let outbox = Arc::new(outbox::Outbox::default());
 
-            if trimmed.eq_ignore_ascii_case("/help") {
-                let info = main_helpers::help_message();
-                app.invoke_append_message(info.into());
-                app.set_input_text("".into());
-                return;
-            }
+    // "Send as message" in the large paste preview lets the next Send through unasked
+    let long_paste_ok = Rc::new(std::cell::Cell::new(false));
 
-            if trimmed.eq_ignore_ascii_case("/settings") {
-                app.set_show_welcome(true);
-                app.set_input_text("".into());
-                return;
-            }            
+    // ===================== Send button =====================
+    {
+        let outbox = Arc::clone(&outbox);
+        let st = Arc::clone(&state);
+        let s = Arc::clone(&sock);
+        let weak = app.as_weak();
 
-            if trimmed.eq_ignore_ascii_case("/restart") {
-                main_helpers::restart_app_after_delay(900);
-                app.set_input_text("".into());
-                return;
-            }  
+        let offer_registry2 = Arc::clone(&offer_registry);
+        let running2 = Arc::clone(&running);
+        let file_offer_model2 = file_offer_model.clone();
+        let model2 = model.clone();
+        let settings = settings.clone();
+        let room_for_send = Arc::clone(&current_room);
+        let confirm_gate = RefCell::new(commands::ConfirmGate::default());
+        let long_paste_ok = Rc::clone(&long_paste_ok);
 
-            if msg.eq_ignore_ascii_case("/downloads") {
-                match main_helpers::open_download_folder_from_config(&config_for_commands) {
-                    Ok(()) => {
-                        app.invoke_show_temp_message("📁 Download folder opened".into());
-                    }
-                    Err(e) => {
-                        app.invoke_show_temp_message(format!("❌ {}", e).into());
-                    }
-                }
+        app.on_send_clicked(move || {
+            let Some(app) = weak.upgrade() else { return; };
 
-                app.set_input_text("".into());
+            // aliases are expanded before anything else looks at the input
+            let aliases = settings.read(|cfg| cfg.aliases.clone());
+            let msg = commands::resolve_alias(&app.get_input_text(), &aliases);
+            let trimmed = msg.trim();
+
+            // destructive commands need a second Enter, the input is kept for it
+            if !confirm_gate.borrow_mut().check(trimmed) {
+                app.invoke_show_temp_message(format!("⚠️ Press Enter again to confirm {}", trimmed).into());
                 return;
             }
 
-            if msg.eq_ignore_ascii_case("/rescale") {
-                let current = app.get_global_scale();
-                let next = if current > 0.90 { 0.85 }
-                    else if current > 0.80 { 0.75 }
-                    else { 1.0 };
-                app.set_global_scale(next);
-                app.set_input_text("".into());
-                app.invoke_show_temp_message(format!("🔎 UI scale set to {:.2}", next).into());
-                // Save to config  <-- add this block
-                {
-                    let mut cfg = config_for_commands.lock().unwrap();
-                    cfg.ui_scale = Some(next);
-                    save_config(&cfg);
-                }
+            let ctx = commands::CommandContext {
+                app: &app,
+                settings: &settings,
+                sock: &s,
+                state: &st,
+                outbox: &outbox,
+                aliases: &aliases,
+                chat_model: &model2,
+                file_offer_model: &file_offer_model2,
+                offer_registry: &offer_registry2,
+                running: &running2,
+            };
+            if commands::dispatch(&ctx, trimmed) {
                 return;
             }
 
-            if trimmed.is_empty() {
+            // sanitized and checked by the outbound middleware, nothing left = nothing to send
+            let Some(cleaned) = message_pipeline::outbound(secure_channel_code::get_active_channel().is_some(), trimmed) else {
                 app.set_input_text("".into());
                 return;
+            };
+            let trimmed = cleaned.as_str();
+
+            // a wall of text gets a preview first, the input stays as it is meanwhile
+            if !long_paste_ok.replace(false) {
+                if let Some((preview, lines)) = main_helpers::paste_preview(trimmed) {
+                    app.set_paste_preview(preview.into());
+                    app.set_paste_preview_lines(lines as i32);
+                    app.invoke_show_paste_preview();
+                    return;
+                }
             }
 
-            if let Some(channel) = secure_channel_code::get_active_channel() {
+            // phones get the bare text, desktops the clock stamp too (see message_pipeline, ordering)
+            let stamped = message_pipeline::stamp_outgoing(trimmed);
+            let packets = if let Some(channel) = secure_channel_code::get_active_channel() {
                 let encrypted =
-                    secure_channel_code::encrypt_message(&channel.key, trimmed);
+                    secure_channel_code::encrypt_message(&channel.key, &stamped);
                 let payload = bincode::serde::encode_to_vec(
                     &encrypted,
                     bincode::config::standard(),
@@ -439,27 +1214,184 @@ fn main() -> Result<(), Box<dyn Error>> {
 
                 let mut packet_win = Vec::from(b"ENCM" as &[u8]);
                 packet_win.extend_from_slice(&payload);
-                let _ = broadcast_the_msg(&s, &st, &packet_win);
 
+                // phones only know the PIN, they never get the session key
                 let packet_mob =
-                    phone_protocol::encrypt_message_phone(&channel.key, trimmed);
-                let _ = broadcast_the_msg(&s, &st, &packet_mob);
+                    phone_protocol::encrypt_message_phone(&channel.auth_key, trimmed);
+                vec![packet_win, packet_mob]
             } else {
-                if let Err(_e) = broadcast_the_msg(&s, &st, trimmed.as_bytes()) {
-                    app.invoke_show_popupmsg();
+                vec![rooms::encode_text(&room_for_send.lock().unwrap(), stamped.as_bytes())]
+            };
+
+            if !packets.iter().all(|p| fragmentation::fits(p)) {
+                app.invoke_show_popupmsg();
+                return;
+            }
+
+            // this needs FRAG packets but someone on the LAN can't put them back together:
+            // everyone gets it as a file instead
+            let lacking = peers::lacking(peers::CAP_FRAGMENTS);
+            if packets.iter().any(|p| p.len() >= MAX_DATAGRAM) && !lacking.is_empty() {
+                let offered = file_transfer_protocol::save_pasted_text(trimmed).and_then(|path| {
+                    if secure_channel_code::get_active_channel().is_some() {
+                        offer_file_to_channel(&app, &s, &st, &offer_registry2, &path);
+                        Ok(())
+                    } else {
+                        offer_file_publicly(&s, &st, &offer_registry2, path)
+                    }
+                });
+                match offered {
+                    Ok(()) => {
+                        app.set_input_text("".into());
+                        app.invoke_show_temp_message(format!("📄 Sent as a file, {} can't receive long messages", lacking.join(", ")).into());
+                    }
+                    Err(e) => app.invoke_show_temp_message(format!("❌ {}", e).into()),
                 }
+                return;
+            }
+
+            // local echo: our row shows up right away, greyed until the packets went out.
+            // The copy our own broadcast brings back is dropped by the receiver (own_packets)
+            let me = main_helpers::own_sender();
+            let order = message_pipeline::split_stamp(&stamped).1.map(|stamp| message_pipeline::order_key(stamp, &me)).unwrap_or_default();
+            model2.push(main_helpers::own_row(&me, trimmed, order));
+            while model2.row_count() > main_helpers::chat_row_limit() {
+                model2.remove(0);
+            }
+
+            if secure_channel_code::get_active_channel().is_some() {
+                channel_idle::touch();
+            }
+
+            // always through the outbox, a new message must not overtake queued ones
+            if let Some(dropped) = outbox.push(trimmed, packets) {
+                main_helpers::remove_pending_row(&model2, &dropped);
+            }
+            for sent in outbox.flush(|p| broadcast_the_msg(&s, &st, p)) {
+                main_helpers::confirm_own_row(&model2, &sent);
+            }
+            if !outbox.is_empty() {
+                app.invoke_show_temp_message("📴 Network is down, message queued".into());
             }
 
             app.set_input_text("".into());
         });
     }
 
+    // the large paste preview's other answers
+    {
+        let weak = app.as_weak();
+        app.on_paste_send_as_message(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            long_paste_ok.set(true);
+            app.invoke_send_clicked();
+        });
+    }
+    {
+        let st = Arc::clone(&state);
+        let s = Arc::clone(&sock);
+        let weak = app.as_weak();
+        let offer_registry = Arc::clone(&offer_registry);
+        app.on_paste_send_as_file(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            let text = app.get_input_text().to_string();
+            let path = match file_transfer_protocol::save_pasted_text(&text) {
+                Ok(path) => path,
+                Err(e) => {
+                    app.invoke_show_temp_message(format!("❌ {}", e).into());
+                    return;
+                }
+            };
+            app.set_input_text("".into());
+            if secure_channel_code::get_active_channel().is_some() {
+                offer_file_to_channel(&app, &s, &st, &offer_registry, &path);
+                return;
+            }
+            match offer_file_publicly(&s, &st, &offer_registry, path) {
+                Ok(()) => app.invoke_show_temp_message("📤 Pasted text offered as a file".into()),
+                Err(e) => app.invoke_show_temp_message(format!("❌ {}", e).into()),
+            }
+        });
+    }
+    {
+        let st = Arc::clone(&state);
+        let s = Arc::clone(&sock);
+        let weak = app.as_weak();
+        let offer_registry = Arc::clone(&offer_registry);
+        let room = Arc::clone(&current_room);
+        app.on_paste_share_snippet(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            let text = app.get_input_text().to_string();
+            let toast = share_snippet(&app, &s, &st, &offer_registry, &room.lock().unwrap(), &text, "");
+            if !toast.starts_with('❌') {
+                app.set_input_text("".into());
+            }
+            app.invoke_show_temp_message(toast.into());
+        });
+    }
+
+    // queued messages: retry until the network is back, then the grey rows turn normal
+    {
+        let model = model.clone();
+        app.on_queued_message_sent(move |text| {
+            main_helpers::confirm_own_row(&model, text.as_str());
+        });
+    }
+    {
+        let outbox = Arc::clone(&outbox);
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        let running = Arc::clone(&running);
+        let weak = app.as_weak();
+        net_runtime::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(2));
+            while running.load(Ordering::Relaxed) {
+                tick.tick().await;
+                if outbox.is_empty() {
+                    continue;
+                }
+                let sent = outbox.flush(|p| broadcast_the_msg(&sock, &state, p));
+                if sent.is_empty() {
+                    continue;
+                }
+                let weak = weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(app) = weak.upgrade() else { return; };
+                    let count = sent.len();
+                    for text in sent {
+                        app.invoke_queued_message_sent(text.into());
+                    }
+                    app.invoke_show_temp_message(format!("📶 Back online, sent {} queued message(s)", count).into());
+                });
+            }
+        });
+    }
+
+    // status strip: heard-only peers age out, so refresh it now and then
+    {
+        let running = Arc::clone(&running);
+        let weak = app.as_weak();
+        net_runtime::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(10));
+            while running.load(Ordering::Relaxed) {
+                tick.tick().await;
+                let weak = weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        main_helpers::refresh_network_status(&app);
+                    }
+                });
+            }
+        });
+    }
+
     // Second change_channel_mode handler
     {
         let weak = app.as_weak();
         let sock = Arc::clone(&sock);
         let state = Arc::clone(&state);
         let channel_mode = Arc::clone(&channel_mode);
+        let mdns = mdns.clone();
 
         app.on_change_channel_mode(move |new_mode: slint::SharedString| {
             if let Some(app) = weak.upgrade() {
@@ -468,7 +1400,8 @@ fn main() -> Result<(), Box<dyn Error>> {
 
                 match new_mode_str {
                     "public" => {
-                        secure_channel_code::destroy_channel();
+                        close_secure_channel(&sock, &state);
+                        if let Some(d) = &mdns { d.set_hosting(false); }
                         app.set_host_PIN("N/A".into());
                         app.set_host_PIN_masked("N/A".into());
                         app.set_public_secure_helper(false);
@@ -498,41 +1431,65 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Interface selected
     app.on_interface_selected({
         let state = Arc::clone(&state);
-        let interfaces = interfaces.clone();
+        let sock = Arc::clone(&sock);
         let weak = app.as_weak();
+        let settings = settings.clone();
         let config = Arc::clone(&config);
 
         move |iface_display: slint::SharedString| {
+            // re-read, adapters may have come and gone since startup
+            let interfaces = collect_interfaces();
             if let Some(info) = interfaces.iter().find(|it| iface_display.contains(&it.name)) {
                 state.set_broadcast_address(info.address_to_broadcast.clone());
                 let gw = get_gateway_for_adapter(&info.name);
+                // before the config moves on, the membership to drop is on the old interface
+                let old_iface_ip = main_helpers::selected_interface_ipv4(&config);
 
-                {
-                    let mut cfg = config.lock().unwrap();
+                settings.update(|cfg| {
                     cfg.selected_interface = info.name.clone();
                     cfg.last_broadcast = info.address_to_broadcast.clone();
                     cfg.last_gateway = gw;
-                    save_config(&cfg);
+                    cfg.network_fingerprint = main_helpers::network_fingerprint(info);
+                });
+
+                // membership follows the interface in multicast mode
+                if let (Some(group), Ok(iface_ip)) = (state.get_multicast_group(), info.ipv4_address.parse()) {
+                    main_helpers::leave_multicast_group(&sock, group, old_iface_ip);
+                    let _ = main_helpers::join_multicast_group(&sock, group, iface_ip);
                 }
 
                 if let Some(app) = weak.upgrade() {
                     app.set_selected_interface(info.name.clone().into());
                     app.set_broadcast_address(state.get_broadcast_address().into());
                     app.set_ui_port(state.get_port() as i32);
-                    app.set_interface_status(info.status.clone().into());
+                    main_helpers::set_active_interface(&app, Some(&info));
                 }
             }
         }
     });
 
+    // Refresh in the interface picker, the hot-plug watcher keeps it current on its own too
+    {
+        let weak = app.as_weak();
+        app.on_refresh_interfaces(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            let interfaces = collect_interfaces();
+            main_helpers::update_interface_rows(&app, &interfaces);
+            app.invoke_show_temp_message(format!("🔄 {} interface(s) found", interfaces.len()).into());
+        });
+    }
+
     // Create channel
     {
         let st = Arc::clone(&state);
         let s = Arc::clone(&sock);
         let weak = app.as_weak();
+        let mdns = mdns.clone();
 
         app.on_create_channel(move || {
             let channel = secure_channel_code::create_new_channel();
+            reannounce::reset();
+            if let Some(d) = &mdns { d.set_hosting(true); }
             let announce = secure_channel_code::build_announcement(&channel);
 
             if let Ok(payload) =
@@ -541,6 +1498,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 const ANNOUNCE_MAGIC: &[u8] = b"ANCH";
                 let mut packet = Vec::from(ANNOUNCE_MAGIC);
                 packet.extend_from_slice(&payload);
+                secure_channel_code::append_announce_trailers(&mut packet, st.get_port(), &channel);
 
                 if let Err(_e) = broadcast_the_msg(&s, &st, &packet) {
                     if let Some(app) = weak.upgrade() {
@@ -549,7 +1507,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 }
             }
 
-            if let Ok(man_json) = build_MANCH(&channel) {
+            if let Ok(man_json) = build_MANCH(&channel, st.get_port()) {
                 const MANCH_MAGIC: &[u8] = b"MANCH";
                 let mut man_packet = Vec::from(MANCH_MAGIC);
                 man_packet.extend_from_slice(man_json.as_bytes());
@@ -575,6 +1533,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         app.on_generate_new_PIN(move || {
             let channel = secure_channel_code::regenerate_PIN();
+            reannounce::reset();
 
             let announce = secure_channel_code::build_announcement(&channel);
             if let Ok(payload) =
@@ -583,6 +1542,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 const ANNOUNCE_MAGIC: &[u8] = b"ANCH";
                 let mut packet = Vec::from(ANNOUNCE_MAGIC);
                 packet.extend_from_slice(&payload);
+                secure_channel_code::append_announce_trailers(&mut packet, st.get_port(), &channel);
 
                 if let Err(_e) = broadcast_the_msg(&s, &st, &packet) {
                     if let Some(app) = weak.upgrade() {
@@ -591,7 +1551,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 }
             }
 
-            if let Ok(man_json) = build_MANCH(&channel) {
+            if let Ok(man_json) = build_MANCH(&channel, st.get_port()) {
                 const MANCH_MAGIC: &[u8] = b"MANCH";
                 let mut man_packet = Vec::from(MANCH_MAGIC);
                 man_packet.extend_from_slice(man_json.as_bytes());
@@ -614,9 +1574,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     {
         let weak = app.as_weak();
         let channel_mode = Arc::clone(&channel_mode);
+        let mdns = mdns.clone();
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
 
         app.on_disconnect_channel(move || {
-            secure_channel_code::destroy_channel();
+            close_secure_channel(&sock, &state);
+            if let Some(d) = &mdns { d.set_hosting(false); }
 
             if let Some(app) = weak.upgrade() {
                 set_channel_mode_only(&channel_mode, "public");
@@ -628,9 +1592,123 @@ fn main() -> Result<(), Box<dyn Error>> {
         });
     }
 
+    // File offers expire: ours go out again now and then, peers' stale ones leave the panel
+    {
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        let offer_registry = Arc::clone(&offer_registry);
+        thread::spawn(move || loop {
+            thread::sleep(file_transfer_protocol::REANNOUNCE_INTERVAL);
+            let packets = file_transfer_protocol::reannounce_packets(&offer_registry.lock().unwrap());
+            for packet in packets {
+                let _ = broadcast_the_msg(&sock, &state, &packet);
+            }
+        });
+    }
+    {
+        let remote_windows_offers = Arc::clone(&remote_windows_offers);
+        let weak = app.as_weak();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(30));
+            let expired = file_transfer_protocol::sweep_expired(&mut remote_windows_offers.lock().unwrap());
+            if expired.is_empty() {
+                continue;
+            }
+            let weak = weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(app) = weak.upgrade() {
+                    for id in &expired {
+                        main_helpers::remove_file_offer_row(&app, id);
+                    }
+                }
+            });
+        });
+    }
+
+    // Repeated channel announcements while hosting
+    {
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        reannounce::start(Arc::clone(&channel_mode), move |channel| {
+            let _ = broadcast_channel_announcement(&sock, &state, channel);
+        });
+    }
+
+    // Host heartbeat, and what a joiner can do once it stops
+    {
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        heartbeat::start(Arc::clone(&channel_mode), app.as_weak(), move |packet| {
+            let _ = broadcast_the_msg(&sock, &state, packet);
+        });
+    }
+
+    // clipboard auto-sync inside a channel (/clip auto)
+    {
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        clip_sync::start(Arc::clone(&channel_mode), move |packet| {
+            let _ = broadcast_the_msg(&sock, &state, packet);
+        });
+    }
+    channel_idle::start(Arc::clone(&channel_mode), app.as_weak());
+    mute::start(app.as_weak());
+    {
+        let sock = Arc::clone(&sock);
+        let state = Arc::clone(&state);
+        let weak = app.as_weak();
+        app.on_host_lost_retry(move || {
+            // a host that's awake answers with ANCH and starts beating again
+            let _ = broadcast_the_msg(&sock, &state, b"REQA");
+            if let Some(app) = weak.upgrade() {
+                app.invoke_show_temp_message("🔁 Asking the channel host again…".into());
+            }
+        });
+    }
+    {
+        let weak = app.as_weak();
+        let channel_mode = Arc::clone(&channel_mode);
+        app.on_host_lost_leave(move || {
+            secure_channel_code::destroy_channel();
+            if let Some(app) = weak.upgrade() {
+                force_switch_to_public(&app, &channel_mode);
+                app.set_web_join_enabled(true);
+                app.invoke_show_temp_message("🔌 Left the channel — returned to public mode".into());
+            }
+        });
+    }
+
+    // accept / deny prompt of a pushed file
+    app.on_push_request_accept(|offer_id| {
+        phone_push::accept(offer_id.as_str());
+    });
+    {
+        let weak = app.as_weak();
+        app.on_push_request_deny(move |offer_id| {
+            if phone_push::deny(offer_id.as_str()) {
+                if let Some(app) = weak.upgrade() {
+                    app.invoke_show_temp_message("🚫 File declined".into());
+                }
+            }
+        });
+    }
+
+    // passphrase popup of /exportsession and /importsession
+    {
+        let weak = app.as_weak();
+        let settings = settings.clone();
+        app.on_session_passphrase_entered(move |export, passphrase| {
+            if let Some(app) = weak.upgrade() {
+                commands::session_passphrase_entered(&app, &settings, export, passphrase.as_str());
+            }
+        });
+    }
+
     // Join channel
     #[allow(nonstandard_style)]
     {
+        let st = Arc::clone(&state);
+        let s = Arc::clone(&sock);
         let weak = app.as_weak();
         let channel_mode = Arc::clone(&channel_mode);
         app.on_join_channel(move |PIN: slint::SharedString| {
@@ -640,10 +1718,16 @@ fn main() -> Result<(), Box<dyn Error>> {
                 let success = secure_channel_code::join_with_PIN(&join_PIN);
                 app.invoke_show_connecting_popup();
                 if success {
-                    secure_channel_code::play_ping_sound();
+                    heartbeat::reset();
                     set_channel_mode_only(&channel_mode, "joined");
                     app.set_channel_mode("joined".into());
                     app.set_public_secure_helper(true);
+                    // a host with forward secrecy sends the session key first, see udp_receiver (EKXR)
+                    if secure_channel_code::key_exchange_pending() {
+                        request_session_key(&s, &st, weak.clone(), Arc::clone(&channel_mode));
+                        return;
+                    }
+                    secure_channel_code::play_ping_sound();
                     app.invoke_hide_connecting_popup();
                     app.invoke_show_temp_message("✅ Joined secure channel successfully!".into());
                 } else {
@@ -657,31 +1741,270 @@ fn main() -> Result<(), Box<dyn Error>> {
         });
     }
 
-    // Clear chatbox button
+    // Clear chatbox button
+    {
+        let model = model_for_clear.clone();
+        app.on_clear_chatbox(move || {
+            clear_chatbox(&model);
+        });
+    }
+
+    // Fix bug button
+    {
+        let weak = app.as_weak();
+        let channel_mode = Arc::clone(&channel_mode);
+
+        app.on_fix_the_bug_please(move || {
+            if let Some(app) = weak.upgrade() {
+                force_switch_to_public(&app, &channel_mode);
+            }
+        });
+    }
+
+    // Exit app
+    {
+        app.on_exit_app(move || {
+            let _ = crate::web_app::stop_web_server();
+            std::process::exit(0);
+        });
+    }
+
+    // Ctrl+V of a screenshot: a PNG in the temp offer dir, broadcast like a picked file
+    {
+        let st = Arc::clone(&state);
+        let s = Arc::clone(&sock);
+        let weak = app.as_weak();
+        let offer_registry = Arc::clone(&offer_registry);
+        let channel_mode = Arc::clone(&channel_mode);
+        let remote_windows_offers = Arc::clone(&remote_windows_offers);
+
+        app.on_paste_image(move || {
+            let Ok(mut clipboard) = arboard::Clipboard::new() else { return false; };
+            if let Ok(text) = clipboard.get_text() {
+                // an offer token isn't typed into the input, its offer lands in the files panel
+                if let Some(token) = offer_token::parse(&text) {
+                    if let Some(app) = weak.upgrade() {
+                        let toast = offer_token::materialize(&app, &remote_windows_offers, token, st.get_port());
+                        app.invoke_show_temp_message(toast.into());
+                    }
+                    return true;
+                }
+                // text wins, spreadsheets and editors put a picture of the selection next to it
+                if !text.is_empty() {
+                    return false;
+                }
+            }
+            let Ok(image) = clipboard.get_image() else { return false; };
+            let Some(app) = weak.upgrade() else { return true; };
+            if *channel_mode.lock().unwrap() != "public" {
+                app.invoke_show_temp_message("📋 Pasted images are shared in public mode, use 🔒 Files inside a channel".into());
+                return true;
+            }
+            app.invoke_show_temp_message("📋 Preparing the pasted image...".into());
+
+            let (s, st, offer_registry, weak) = (Arc::clone(&s), Arc::clone(&st), Arc::clone(&offer_registry), weak.clone());
+            // encoding a big screenshot takes a moment, keep it off the UI thread
+            thread::spawn(move || {
+                let offered = file_transfer_protocol::save_pasted_image(image.width as u32, image.height as u32, image.bytes.into_owned())
+                    .and_then(|path| offer_file_publicly(&s, &st, &offer_registry, path));
+                let text = match offered {
+                    Ok(()) => "📤 Pasted image offered".to_string(),
+                    Err(e) => format!("❌ {}", e),
+                };
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_show_temp_message(text.into());
+                    }
+                });
+            });
+            true
+        });
+    }
+
+    // 📸 Screenshot: the picked region goes out like a pasted image, to the members in a channel
+    {
+        let weak = app.as_weak();
+        app.on_take_screenshot(move || {
+            if let Some(app) = weak.upgrade() {
+                screenshot::take(&app);
+            }
+        });
+    }
+    {
+        let weak = app.as_weak();
+        app.on_discard_screenshot(move || {
+            if let Some(app) = weak.upgrade() {
+                screenshot::discard(&app);
+            }
+        });
+    }
+    {
+        let st = Arc::clone(&state);
+        let s = Arc::clone(&sock);
+        let weak = app.as_weak();
+        let offer_registry = Arc::clone(&offer_registry);
+        let channel_mode = Arc::clone(&channel_mode);
+
+        app.on_share_screenshot(move |x, y, w, h| {
+            let Some(app) = weak.upgrade() else { return; };
+            app.set_screenshot_preview(Default::default());
+            let public = *channel_mode.lock().unwrap() == "public";
+            app.invoke_show_temp_message("📸 Preparing the screenshot...".into());
+
+            let (s, st, offer_registry, weak) = (Arc::clone(&s), Arc::clone(&st), Arc::clone(&offer_registry), weak.clone());
+            // PNG-encoding a whole screen takes a moment, keep it off the UI thread
+            thread::spawn(move || {
+                let saved = screenshot::save(x, y, w, h);
+                if public {
+                    let text = match saved.and_then(|path| offer_file_publicly(&s, &st, &offer_registry, path)) {
+                        Ok(()) => "📤 Screenshot offered".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(app) = weak.upgrade() {
+                            app.invoke_show_temp_message(text.into());
+                        }
+                    });
+                    return;
+                }
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(app) = weak.upgrade() else { return; };
+                    match saved {
+                        Ok(path) if secure_channel_code::get_active_channel().is_some() => offer_file_to_channel(&app, &s, &st, &offer_registry, &path),
+                        Ok(_) => app.invoke_show_temp_message("🔒 Create or join a channel first".into()),
+                        Err(e) => app.invoke_show_temp_message(format!("❌ {}", e).into()),
+                    }
+                });
+            });
+        });
+    }
+
+    // "send to everyone in the channel" (secure mode): the picked files, offered over EFOF
+    {
+        let st = Arc::clone(&state);
+        let s = Arc::clone(&sock);
+        let weak = app.as_weak();
+        let offer_registry = Arc::clone(&offer_registry);
+
+        app.on_pick_files_channel(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            if secure_channel_code::get_active_channel().is_none() {
+                app.invoke_show_temp_message("🔒 Create or join a channel first".into());
+                return;
+            }
+            let Some(paths) = file_transfer_protocol::pick_files() else { return; };
+            // every picked file is an offer of its own, members fetch the ones they want
+            for path in &paths {
+                offer_file_to_channel(&app, &s, &st, &offer_registry, path);
+            }
+        });
+    }
+
+    // the offer as text to paste elsewhere, Ctrl+V of it in another LanChGo gives its row back
+    {
+        let remote_windows_offers = Arc::clone(&remote_windows_offers);
+        let weak = app.as_weak();
+        app.on_copy_offer_token(move |id_hex| {
+            let Some(app) = weak.upgrade() else { return; };
+            let Some((sender_addr, offer)) = remote_windows_offers.lock().unwrap().get(id_hex.as_str()).cloned() else {
+                app.invoke_show_temp_message("⚠️ Only offers from desktop peers have a token".into());
+                return;
+            };
+            if matches!(offer.kind, file_transfer_protocol::OfferKind::Folder) {
+                app.invoke_show_temp_message("⚠️ Folders don't have a token, only files".into());
+                return;
+            }
+            let toast = match main_helpers::copy_to_clipboard(&offer_token::encode(&offer, sender_addr.ip())) {
+                Ok(()) => "🔗 Offer token copied".to_string(),
+                Err(e) => format!("❌ {}", e),
+            };
+            app.invoke_show_temp_message(toast.into());
+        });
+    }
+
+    // ===================== download later =====================
     {
-        let model = model_for_clear.clone();
-        app.on_clear_chatbox(move || {
-            clear_chatbox(&model);
+        let file_offer_model = file_offer_model.clone();
+        let remote_windows_offers = Arc::clone(&remote_windows_offers);
+        let weak = app.as_weak();
+        app.on_save_offer_for_later(move |id_hex| {
+            let Some(app) = weak.upgrade() else { return; };
+            // only FOFT offers, phones and channel members can't answer a re-request
+            let Some((sender_addr, offer)) = remote_windows_offers.lock().unwrap().get(id_hex.as_str()).cloned() else {
+                app.invoke_show_temp_message("⚠️ Only offers from desktop peers can be saved".into());
+                return;
+            };
+            let sender = file_offer_model
+                .iter()
+                .find(|row| row.offer_id == id_hex)
+                .map(|row| row.sender.to_string())
+                .unwrap_or_else(|| sender_addr.ip().to_string());
+            if later::add(sender_addr, &sender, &offer) {
+                main_helpers::refresh_later_ui(&app);
+                app.invoke_show_temp_message(format!("🕒 Saved {} for later", offer.name).into());
+            } else {
+                app.invoke_show_temp_message("🕒 Already saved".into());
+            }
         });
     }
-
-    // Fix bug button
     {
+        let s = Arc::clone(&sock);
         let weak = app.as_weak();
-        let channel_mode = Arc::clone(&channel_mode);
+        app.on_fetch_later(move |id_hex| {
+            let Some(app) = weak.upgrade() else { return; };
+            let Some(entry) = later::get(id_hex.as_str()) else { return; };
+            let Some(packet) = later::request_packet(&entry.offer_id) else { return; };
 
-        app.on_fix_the_bug_please(move || {
-            if let Some(app) = weak.upgrade() {
-                force_switch_to_public(&app, &channel_mode);
+            let sent = if entry.sender_addr.is_ipv6() {
+                s.send_to_v6(&packet, entry.sender_addr).is_some_and(|r| r.is_ok())
+            } else {
+                s.send_to(&packet, entry.sender_addr).is_ok()
+            };
+            if !sent {
+                app.invoke_show_temp_message("❌ Couldn't reach the sender".into());
+                return;
             }
+            later::mark_requested(&entry.offer_id);
+            app.invoke_show_temp_message(format!("🕒 Asking {} for {}…", entry.sender, entry.name).into());
+
+            // the FOFT answer starts the download in udp_receiver, this only reports silence
+            let weak = weak.clone();
+            thread::spawn(move || {
+                thread::sleep(later::REQUEST_TIMEOUT);
+                if !later::request_timed_out(&entry.offer_id) {
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(app) = weak.upgrade() {
+                            main_helpers::refresh_later_ui(&app);
+                        }
+                    });
+                    return;
+                }
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        main_helpers::refresh_later_ui(&app);
+                        app.invoke_show_temp_message(
+                            format!("⚪ {} is offline or no longer offers {}", entry.sender, entry.name).into(),
+                        );
+                    }
+                });
+            });
         });
     }
-
-    // Exit app
     {
-        app.on_exit_app(move || {
-            let _ = crate::web_app::stop_web_server();
-            std::process::exit(0);
+        let weak = app.as_weak();
+        app.on_remove_later(move |id_hex| {
+            let Some(app) = weak.upgrade() else { return; };
+            later::remove(id_hex.as_str());
+            main_helpers::refresh_later_ui(&app);
+        });
+    }
+    {
+        main_helpers::refresh_later_ui(&app);
+        let weak = app.as_weak();
+        app.on_refresh_later(move || {
+            if let Some(app) = weak.upgrade() {
+                main_helpers::refresh_later_ui(&app);
+            }
         });
     }
 
@@ -733,6 +2056,10 @@ fn main() -> Result<(), Box<dyn Error>> {
                             let _ = broadcast_the_msg(&s, &st, &mfoft_packet);
                         }
                         crate::web_app_file_transfer::notify_web_file_offer(&offer.offer_id, &offer.name, offer.size);
+                        let local = offer_registry.lock().unwrap().get(&offer.offer_id).cloned();
+                        if let Some(local) = local.filter(|l| !matches!(l.kind, file_transfer_protocol::OfferKind::Folder)) {
+                            announce_offer_hash(&s, &st, offer.offer_id, local.path);
+                        }
                     }
                     app.invoke_show_temp_message("📤 File offer broadcasted".into());
                 }
@@ -813,6 +2140,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                                     // temporary fix cause the local_size is gone afterwards i need to figure something out with this one to fix a problem with line 673
                                     let local_name = local.name.clone();
                                     let local_size = local.size;
+                                    let local_path = local.path.clone();
                                     // insert into registry
                                     {
                                         let mut reg = offer_registry2.lock().unwrap();
@@ -830,7 +2158,8 @@ fn main() -> Result<(), Box<dyn Error>> {
                                             size: local_size,
                                             kind: crate::file_transfer_protocol::OfferKind::SingleFile, // android limitation
                                             protocol_version: crate::file_transfer_protocol::FILE_PROTOCOL_VERSION,
-                                            tcp_port: crate::file_transfer_protocol::DEFAULT_TCP_PORT,
+                                            tcp_port: crate::file_transfer_protocol::tcp_port(),
+                                            expires_at: None,
                                         };
 
                                         match crate::file_transfer_protocol::encode_mfoft_packet(&offer) {
@@ -840,6 +2169,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                                     };
 
                                     let ok = ok_foft || ok_mfoft;
+                                    if ok {
+                                        announce_offer_hash(&s2, &st2, offer_id, local_path);
+                                    }
 
                                     let weak_ui = weak2.clone();
                                     let _ = slint::invoke_from_event_loop(move || {
@@ -887,7 +2219,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Save to… button
     {
         let weak = app.as_weak();
-        let config = Arc::clone(&config);
+        let settings = settings.clone();
 
         app.on_pick_download_folder(move || {
             let Some(app) = weak.upgrade() else { return; };
@@ -902,11 +2234,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
                 let folder_str = folder.display().to_string();
 
-                {
-                    let mut cfg = config.lock().unwrap();
-                    cfg.save_to_folder = folder_str.clone();
-                    save_config(&cfg);
-                }
+                settings.update(|cfg| cfg.save_to_folder = folder_str.clone());
 
                 app.set_download_folder(folder_str.into());
                 app.invoke_show_temp_message("📁 Download folder updated".into());
@@ -932,32 +2260,58 @@ fn main() -> Result<(), Box<dyn Error>> {
             }
         });
     }
-    // download thread cap to two
-    let download_semaphore: Arc<Semaphore<()>> = Arc::new(Semaphore::new(2, ()));
-    // clicking download on a file transfer offer
+    // link in a chat message, the popup already asked
+    {
+        let weak = app.as_weak();
+        app.on_open_link(move |url| {
+            let Some(app) = weak.upgrade() else { return; };
+            // only what the parser marked as a link, never hand anything else to the shell
+            let lower = url.to_ascii_lowercase();
+            if !(lower.starts_with("http://") || lower.starts_with("https://")) {
+                return;
+            }
+            if let Err(e) = open::that(url.as_str()) {
+                app.invoke_show_temp_message(format!("❌ Couldn't open link: {}", e).into());
+            }
+        });
+    }
+    // clicking download on a file transfer offer, download_queue decides when it runs
     {
         let remote_windows_offers = Arc::clone(&remote_windows_offers);
         let remote_mobile_offers = Arc::clone(&remote_mobile_offers);
         let config = Arc::clone(&config);
         let weak = app.as_weak();
-        let sem = Arc::clone(&download_semaphore);
 
         app.on_download_offer(move |offer_id_hex| {
-        // Try to take a slot (non-blocking)
-            let permit = match sem.try_access() {
-                Ok(guard) => guard, // SemaphoreGuard<()> held while download runs :contentReference[oaicite:3]{index=3}
-                Err(_e) => {
-                    let weak_ui = weak.clone();
-                    let _ = slint::invoke_from_event_loop(move || {
-                        if let Some(app) = weak_ui.upgrade() {
-                            app.invoke_show_temp_message("⚠️ Maximum 2 downloads at a time".into());
-                        }
-                    });
+            // a file the phone is pushing, clicking it is the accept
+            if phone_push::accept(offer_id_hex.as_str()) {
+                return;
+            }
+            let permit = match download_queue::admit(offer_id_hex.as_str()) {
+                download_queue::Admission::Go(slot) => slot, // held while the download runs
+                download_queue::Admission::Queued(place) => {
+                    if let Some(app) = weak.upgrade() {
+                        download_queue::refresh(&app);
+                        app.invoke_show_temp_message(format!("⏳ Queued, #{} in line", place).into());
+                    }
                     return;
                 }
             };
 
-            // 1) Lookup sender_ip + offer from remote_offers, and check if it is mobile or windows
+            // 0) secure channel offers go over the encrypted path
+            if let Some((sender_addr, offer)) = channel_files::remote(offer_id_hex.as_str()) {
+                let Some(channel) = secure_channel_code::get_active_channel() else {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_show_temp_message("🔒 Join the channel again to download this file".into());
+                    }
+                    return;
+                };
+                let save_path = main_helpers::build_download_save_path(&config, &offer.name, offer_id_hex.as_str());
+                channel_files::spawn_channel_download(sender_addr, offer, channel.key, save_path, weak.clone(), permit);
+                return;
+            }
+
+            // 1) Lookup sender_addr + offer from remote_offers, and check if it is mobile or windows
             let mut is_mobile: bool = false;
             // println!(
             //     "[DL] clicked id={} windows_has={} mobile_has={}",
@@ -965,7 +2319,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             //     remote_windows_offers.lock().unwrap().contains_key(offer_id_hex.as_str()),
             //     remote_mobile_offers.lock().unwrap().contains_key(offer_id_hex.as_str()),
             // );
-            let (sender_ip, offer) = {
+            let (sender_addr, offer) = {
                 // 1️⃣ try Windows offers first
                 if let Some(v) = remote_windows_offers.lock().unwrap().get(offer_id_hex.as_str()).cloned()
                 { v }
@@ -989,7 +2343,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             let save_path = main_helpers::build_download_save_path( &config, &offer.name, offer_id_hex.as_str(),);
             // if it is mobile go to another function to deal with it else just continue (it is like that so i don't rewrite the code when it works perfectly)
             if is_mobile {
-                mobile_download::spawn_mobile_download( sender_ip, offer, offer_id_hex.to_string(), save_path, weak.clone(), permit, );
+                mobile_download::spawn_mobile_download( sender_addr, offer, offer_id_hex.to_string(), save_path, weak.clone(), permit, );
                 return;
             }
             // 2) Convert offer_id_hex -> [u8;16]
@@ -1002,18 +2356,16 @@ fn main() -> Result<(), Box<dyn Error>> {
                 }
             };
 
-            //println!( "[DOWNLOAD] Requested {} from {}:{} → {}", offer.name, sender_ip, offer.tcp_port, save_path.display() );
+            //println!( "[DOWNLOAD] Requested {} from {}:{} → {}", offer.name, sender_addr.ip(), offer.tcp_port, save_path.display() );
 
-            // 4) Spawn download thread
+            // 4) Spawn download task
             let weak_ui_thread = weak.clone();
             let offer_id_str_thread = offer_id_hex.to_string();
 
-            std::thread::spawn(move || {
-                // Hold permit for entire download lifetime (IMPORTANT)
+            net_runtime::spawn(async move {
+                // hold the queue slot for the entire download
                 let _permit = permit;
 
-                let mut last_bucket: u32 = 999;
-
                 // --- 0% immediately ---
                 {
                     let weak_ui0 = weak_ui_thread.clone();
@@ -1025,31 +2377,74 @@ fn main() -> Result<(), Box<dyn Error>> {
                     });
                 }
 
-                // Clone for progress closure
-                let weak_ui_progress = weak_ui_thread.clone();
-                let offer_id_progress = offer_id_str_thread.clone();
-
-                let res = crate::tcp_file_client::download_offer(
-                    sender_ip,
-                    offer.tcp_port,
-                    offer_id,
-                    save_path,
+                // bytes done so far, the watchdog reconnects once when it stops moving
+                let progress = Arc::new(AtomicU64::new(0));
+                let make_on_progress = || {
+                    let weak_ui = weak_ui_thread.clone();
+                    let offer_id = offer_id_str_thread.clone();
+                    let progress = Arc::clone(&progress);
+                    let mut last_bucket: u32 = 999;
                     move |done, total| {
+                        progress.store(done, Ordering::Relaxed);
                         let bucket = main_helpers::progress_bucket_3(done, total);
                         if bucket == last_bucket { return; }
                         last_bucket = bucket;
 
                         let text = format!("{}%", bucket);
 
-                        let weak_ui = weak_ui_progress.clone();
-                        let offer_id = offer_id_progress.clone();
+                        let weak_ui = weak_ui.clone();
+                        let offer_id = offer_id.clone();
                         let _ = slint::invoke_from_event_loop(move || {
                             if let Some(app) = weak_ui.upgrade() {
                                 main_helpers::set_offer_progress_text(&app, &offer_id, true, &text);
                             }
                         });
-                    },
-                );
+                    }
+                };
+                let is_folder = matches!(offer.kind, file_transfer_protocol::OfferKind::Folder);
+                let partial = if is_folder {
+                    transfers::partial_path(&save_path, true)
+                } else {
+                    file_transfer_protocol::resume_part_path(&save_path, &offer_id)
+                };
+                if !is_folder {
+                    resume::started(sender_addr, &peers::label(sender_addr.ip(), None), &offer, save_path.clone());
+                }
+                let mut retried = false;
+                let res = loop {
+                    let attempt = if is_folder {
+                        let download = crate::tcp_file_client::download_folder_offer(sender_addr, offer.tcp_port, offer_id, save_path.clone(), make_on_progress());
+                        transfers::download(&offer_id_str_thread, partial.clone(), transfers::watchdog(&progress, file_transfer_protocol::stall_timeout(), download))
+                            .await
+                            .map(|()| false)
+                    } else {
+                        let download = crate::tcp_file_client::download_offer(sender_addr, offer.tcp_port, offer_id, save_path.clone(), make_on_progress());
+                        transfers::download(&offer_id_str_thread, partial.clone(), transfers::watchdog(&progress, file_transfer_protocol::stall_timeout(), download)).await
+                    };
+                    match attempt {
+                        // once: a file carries on from its .part, a folder starts over
+                        Err(e) if transfers::is_stalled(&e) && !retried => {
+                            retried = true;
+                            let weak_ui = weak_ui_thread.clone();
+                            let offer_id = offer_id_str_thread.clone();
+                            let name = offer.name.clone();
+                            let _ = slint::invoke_from_event_loop(move || {
+                                if let Some(app) = weak_ui.upgrade() {
+                                    main_helpers::set_offer_progress_text(&app, &offer_id, true, "stalled ⟳");
+                                    app.invoke_show_temp_message(format!("⏳ {} stopped moving, reconnecting…", name).into());
+                                }
+                            });
+                        }
+                        res => break res,
+                    }
+                };
+                records::transfer(false, Some(sender_addr.ip()), &offer.name, offer.size, records::outcome(&res));
+                if !is_folder {
+                    match &res {
+                        Err(e) if !transfers::is_cancelled(e) && file_transfer_protocol::is_resumable(e) => resume::interrupted(&offer_id_str_thread),
+                        _ => resume::finished(&offer_id_str_thread),
+                    }
+                }
 
                 // Finish/error UI
                 let weak_ui_done = weak_ui_thread.clone();
@@ -1058,11 +2453,22 @@ fn main() -> Result<(), Box<dyn Error>> {
                 let _ = slint::invoke_from_event_loop(move || {
                     if let Some(app) = weak_ui_done.upgrade() {
                         match res {
-                            Ok(_) => {
-                                main_helpers::set_offer_progress_text(&app, &offer_id_done, false, "100%");
+                            Ok(verified) => {
+                                let status = if verified { "verified ✓" } else { "100%" };
+                                main_helpers::set_offer_progress_text(&app, &offer_id_done, false, status);
+                                main_helpers::set_offer_saved(&app, &offer_id_done, save_path);
                                 secure_channel_code::play_ping_sound();
                                 app.invoke_show_temp_message("✅ Download complete".into());
                             }
+                            Err(e) if transfers::is_cancelled(&e) => {}
+                            Err(e) if file_transfer_protocol::is_corrupt(&e) => {
+                                main_helpers::set_offer_progress_text(&app, &offer_id_done, false, "corrupt ✗");
+                                app.invoke_show_temp_message(format!("❌ {} didn't arrive intact, the copy was discarded", offer.name).into());
+                            }
+                            Err(e) if !is_folder && file_transfer_protocol::is_resumable(&e) => {
+                                main_helpers::set_offer_progress_text(&app, &offer_id_done, false, "ERR");
+                                app.invoke_show_temp_message(format!("⚠️ Download interrupted ({}), click it again to resume", e).into());
+                            }
                             Err(e) => {
                                 main_helpers::set_offer_progress_text(&app, &offer_id_done, false, "ERR");
                                 app.invoke_show_temp_message(format!("❌ Download failed: {}", e).into());
@@ -1071,10 +2477,63 @@ fn main() -> Result<(), Box<dyn Error>> {
                     }
                 });
 
-                // when thread ends, _permit is dropped -> slot released
+                // when the task ends _permit drops, the next one in line starts
             });
         });
     }
+    // 📄 / 📂 on a finished download
+    {
+        let weak = app.as_weak();
+        app.on_open_saved(move |offer_id_hex| {
+            let Some(app) = weak.upgrade() else { return; };
+            match main_helpers::saved_path(offer_id_hex.as_str()) {
+                Some(path) => {
+                    if let Err(e) = open::that(&path) {
+                        app.invoke_show_temp_message(format!("❌ Couldn't open it: {}", e).into());
+                    }
+                }
+                None => app.invoke_show_temp_message("⚠️ The file isn't there anymore".into()),
+            }
+        });
+    }
+    {
+        let weak = app.as_weak();
+        app.on_reveal_saved(move |offer_id_hex| {
+            let Some(app) = weak.upgrade() else { return; };
+            match main_helpers::saved_path(offer_id_hex.as_str()) {
+                Some(path) => {
+                    if let Err(e) = platform::current().reveal(&path) {
+                        app.invoke_show_temp_message(format!("❌ Couldn't open the folder: {}", e).into());
+                    }
+                }
+                None => app.invoke_show_temp_message("⚠️ The file isn't there anymore".into()),
+            }
+        });
+    }
+    // ✕ on a downloading row
+    {
+        let weak = app.as_weak();
+        app.on_cancel_download(move |offer_id_hex| {
+            let Some(app) = weak.upgrade() else { return; };
+            if download_queue::dequeue(offer_id_hex.as_str()) {
+                download_queue::refresh(&app);
+                app.invoke_show_temp_message("🛑 Removed from the download queue".into());
+            } else if transfers::cancel(offer_id_hex.as_str()) {
+                main_helpers::set_offer_progress_text(&app, offer_id_hex.as_str(), false, "");
+                app.invoke_show_temp_message("🛑 Download cancelled".into());
+            }
+        });
+    }
+    // 🔼 / 🔽 on a waiting download
+    {
+        let weak = app.as_weak();
+        app.on_move_queued(move |offer_id_hex, delta| {
+            let Some(app) = weak.upgrade() else { return; };
+            if download_queue::shift(offer_id_hex.as_str(), delta) {
+                download_queue::refresh(&app);
+            }
+        });
+    }
     // web join button clicked
     {
         let weak = app.as_weak();
@@ -1126,15 +2585,8 @@ fn main() -> Result<(), Box<dyn Error>> {
 
             let url = app.get_url_link().to_string();
 
-            match arboard::Clipboard::new() {
-                Ok(mut clipboard) => {
-                    if clipboard.set_text(url).is_err() {
-                        app.invoke_show_temp_message("❌ Failed to copy link".into());
-                    }
-                }
-                Err(_) => {
-                    app.invoke_show_temp_message("❌ Failed to access clipboard".into());
-                }
+            if let Err(e) = main_helpers::copy_to_clipboard(&url) {
+                app.invoke_show_temp_message(format!("❌ {}", e).into());
             }
         });
     }
@@ -1142,21 +2594,25 @@ fn main() -> Result<(), Box<dyn Error>> {
     {        
         app.on_apply_manual_port({
             let state = Arc::clone(&state);
+            let sock = Arc::clone(&sock);
+            let mdns = mdns.clone();
             let weak = app.as_weak();
             let config = Arc::clone(&config);
             move |port| {
                 if let Some(app) = weak.upgrade() {
                     match main_helpers::try_set_manual_port(&state, &config, port as u16) {
                         Ok(p) => {
+                            // move the live socket over, no restart needed
+                            if let Err(e) = main_helpers::rebind_to_state_port(&sock, &state, &config) {
+                                app.set_port_status(format!("Could not bind port {}: {}", p, e).into());
+                                return;
+                            }
+                            if let Some(d) = &mdns { d.set_port(p); }
+
                             app.set_ui_port(p as i32);
                             app.set_port_status("✅ Port available".into());
-                            app.invoke_show_temp_message(
-                                format!("✅ Port set to {}. Restarting LanChGo...", p).into()
-                            );
-
+                            app.invoke_show_temp_message(format!("✅ Now listening on UDP port {}", p).into());
                             app.set_show_welcome(false);
-
-                            main_helpers::restart_app_after_delay(900);
                         }
                         Err(e) => {
                             app.set_port_status(e.into());
@@ -1170,27 +2626,318 @@ fn main() -> Result<(), Box<dyn Error>> {
     {
         app.on_reset_port_to_auto({
             let state = Arc::clone(&state);
+            let sock = Arc::clone(&sock);
+            let mdns = mdns.clone();
             let weak = app.as_weak();
             let config = Arc::clone(&config);
             move || {
                 main_helpers::reset_port_to_auto(&state, &config);
+                let rebind = main_helpers::rebind_to_state_port(&sock, &state, &config);
+                if let Some(d) = &mdns { d.set_port(state.get_port()); }
+
                 if let Some(app) = weak.upgrade() {
                     app.set_ui_port(state.get_port() as i32);
                     app.set_port_status("".into());
                     app.set_manual_port_mode(false);
-                    app.invoke_show_temp_message(
-                        "🔄 Returning to automatic port mode... Restarting LanChGo.".into()
-                    );
+                    match rebind {
+                        Ok(()) => app.invoke_show_temp_message(format!("🔄 Back to automatic port {}", state.get_port()).into()),
+                        Err(e) => app.invoke_show_temp_message(format!("❌ Could not bind port {}: {}", state.get_port(), e).into()),
+                    }
                     app.set_show_welcome(false);
-                    main_helpers::restart_app_after_delay(900);
                 }
             }
         });
     }
 
+    // remember where the chat / files splitter was left
+    {
+        let settings = settings.clone();
+        app.on_files_panel_resized(move |width| {
+            settings.update(|cfg| cfg.files_panel_width = Some(width));
+        });
+    }
+
+    // sound / notification preferences (settings screen)
+    {
+        let weak = app.as_weak();
+        let settings = settings.clone();
+        app.on_alert_prefs_changed(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            settings.update(|cfg| {
+                cfg.ping_sound = app.get_ping_sound();
+                cfg.nutella_sound = app.get_nutella_sound();
+                cfg.notifications = app.get_notifications();
+                cfg.mentions_only = app.get_mentions_only();
+                cfg.public_alerts = app.get_public_alerts();
+                cfg.secure_alerts = app.get_secure_alerts();
+            });
+        });
+    }
+
+    // theme / chat length (settings page)
+    {
+        app.on_set_theme({
+            let weak = app.as_weak();
+            let settings = settings.clone();
+            move |theme| {
+                let Some(app) = weak.upgrade() else { return; };
+                settings.update(|cfg| cfg.theme = theme.to_string());
+                settings.apply_to_ui(&app);
+            }
+        });
+        app.on_set_chat_rows({
+            let weak = app.as_weak();
+            let settings = settings.clone();
+            let model = model.clone();
+            move |rows| {
+                let Some(app) = weak.upgrade() else { return; };
+                let Some(&rows) = settings::CHAT_ROW_CHOICES.iter().find(|&&r| r as i32 == rows) else { return; };
+                settings.update(|cfg| cfg.chat_rows = rows);
+                settings.apply_to_ui(&app);
+                // shrinking drops the oldest rows right away, they're still in the history
+                while model.row_count() > main_helpers::chat_row_limit() {
+                    model.remove(0);
+                }
+            }
+        });
+        app.on_set_parallel_downloads({
+            let weak = app.as_weak();
+            let settings = settings.clone();
+            move |n| {
+                let Some(app) = weak.upgrade() else { return; };
+                let Some(&n) = settings::PARALLEL_DOWNLOAD_CHOICES.iter().find(|&&c| c as i32 == n) else { return; };
+                settings.update(|cfg| cfg.max_parallel_downloads = n);
+                settings.apply_to_ui(&app);
+            }
+        });
+    }
+
+    // slash-command aliases (settings screen)
+    {
+        app.on_add_alias({
+            let weak = app.as_weak();
+            let settings = settings.clone();
+            move |name, target| {
+                let Some(app) = weak.upgrade() else { return; };
+                if let Err(e) = commands::validate_alias(&name, &target) {
+                    app.set_alias_status(e.into());
+                    return;
+                }
+                let aliases = settings.update(|cfg| {
+                    cfg.aliases.insert(name.trim().to_lowercase(), target.trim().to_string());
+                    cfg.aliases.clone()
+                });
+                main_helpers::apply_aliases_to_ui(&app, &aliases);
+                app.set_alias_status("✅ Saved".into());
+            }
+        });
+        app.on_remove_alias({
+            let weak = app.as_weak();
+            let settings = settings.clone();
+            move |name| {
+                let Some(app) = weak.upgrade() else { return; };
+                let key = name.trim().to_lowercase();
+                if !settings.read(|cfg| cfg.aliases.contains_key(&key)) {
+                    app.set_alias_status(format!("No alias {}", name.trim()).into());
+                    return;
+                }
+                let aliases = settings.update(|cfg| {
+                    cfg.aliases.remove(&key);
+                    cfg.aliases.clone()
+                });
+                main_helpers::apply_aliases_to_ui(&app, &aliases);
+                app.set_alias_status("✅ Removed".into());
+            }
+        });
+    }
+
+    // public chat rooms
+    {
+        let rooms_model = Rc::new(VecModel::<slint::SharedString>::default());
+        app.set_known_rooms(ModelRc::new(rooms_model.clone()));
+
+        app.on_refresh_rooms({
+            let current_room = Arc::clone(&current_room);
+            move || {
+                let current = current_room.lock().unwrap().clone();
+                rooms_model.set_vec(rooms::known_rooms(&current).into_iter().map(slint::SharedString::from).collect::<Vec<_>>());
+            }
+        });
+        app.on_switch_room({
+            let weak = app.as_weak();
+            let settings = settings.clone();
+            let current_room = Arc::clone(&current_room);
+            let channel_mode = Arc::clone(&channel_mode);
+            let model = model.clone();
+            move |name| {
+                let Some(app) = weak.upgrade() else { return; };
+                let room = match rooms::normalize_room_name(&name) {
+                    Ok(room) => room,
+                    Err(e) => {
+                        app.invoke_show_temp_message(format!("⚠️ {}", e).into());
+                        return;
+                    }
+                };
+                if *current_room.lock().unwrap() == room {
+                    app.invoke_show_temp_message(format!("💬 Already in #{}", room).into());
+                    return;
+                }
+
+                *current_room.lock().unwrap() = room.clone();
+                settings.update(|cfg| cfg.room = room.clone());
+                app.set_current_room(room.clone().into());
+                model.set_vec(vec![main_helpers::system_row(format!("💬 You are now in #{}", room))]);
+
+                // secure channels are separate already, rooms only split the public chat
+                if *channel_mode.lock().unwrap() != "public" {
+                    app.invoke_show_temp_message(format!("💬 #{} applies once you are back in public mode", room).into());
+                }
+            }
+        });
+    }
+
+    // contact book (/contacts)
+    {
+        app.on_refresh_contacts({
+            let weak = app.as_weak();
+            move || {
+                if let Some(app) = weak.upgrade() {
+                    main_helpers::refresh_contacts_ui(&app);
+                }
+            }
+        });
+        app.on_set_contact_trust({
+            let weak = app.as_weak();
+            let model = model.clone();
+            move |key, trust| {
+                let Some(app) = weak.upgrade() else { return; };
+                let Some(trust) = contacts::Trust::parse(&trust) else { return; };
+                // trusting a key settles its name conflict too
+                if trust == contacts::Trust::Trusted {
+                    if let Some(ip) = contacts::resolve_conflict(&key) {
+                        main_helpers::clear_conflict_badges(&model, &ip);
+                    }
+                }
+                contacts::set_trust(&key, trust);
+                main_helpers::refresh_contacts_ui(&app);
+            }
+        });
+        app.on_resolve_contact_conflict({
+            let weak = app.as_weak();
+            let model = model.clone();
+            move |key| {
+                let Some(app) = weak.upgrade() else { return; };
+                if let Some(ip) = contacts::resolve_conflict(&key) {
+                    main_helpers::clear_conflict_badges(&model, &ip);
+                    main_helpers::refresh_contacts_ui(&app);
+                }
+            }
+        });
+        app.on_set_contact_notes({
+            let weak = app.as_weak();
+            move |key, notes| {
+                contacts::set_notes(&key, &notes);
+                if let Some(app) = weak.upgrade() {
+                    app.invoke_show_temp_message("📇 Notes saved".into());
+                }
+            }
+        });
+        app.on_remove_contact({
+            let weak = app.as_weak();
+            move |key| {
+                let Some(app) = weak.upgrade() else { return; };
+                if contacts::remove(&key) {
+                    main_helpers::refresh_contacts_ui(&app);
+                }
+            }
+        });
+    }
+
+    // switching between broadcast and a multicast group
+    {
+        app.on_apply_multicast_group({
+            let state = Arc::clone(&state);
+            let sock = Arc::clone(&sock);
+            let weak = app.as_weak();
+            let config = Arc::clone(&config);
+            let settings = settings.clone();
+            move |text| {
+                let Some(app) = weak.upgrade() else { return; };
+                let group = match main_helpers::parse_multicast_group(&text) {
+                    Ok(g) => g,
+                    Err(e) => {
+                        app.set_multicast_status(e.into());
+                        return;
+                    }
+                };
+
+                let iface_ip = main_helpers::selected_interface_ipv4(&config);
+                if let Some(old) = state.get_multicast_group() {
+                    main_helpers::leave_multicast_group(&sock, old, iface_ip);
+                }
+                if let Err(e) = main_helpers::join_multicast_group(&sock, group, iface_ip) {
+                    app.set_multicast_status(format!("Could not join {}: {}", group, e).into());
+                    return;
+                }
+                state.set_multicast_group(Some(group));
+
+                settings.update(|cfg| cfg.multicast_group = Some(group.to_string()));
+
+                app.set_multicast_mode(true);
+                app.set_multicast_group(group.to_string().into());
+                app.set_multicast_status("✅ Joined".into());
+                app.invoke_show_temp_message(format!("📡 Using multicast group {}", group).into());
+            }
+        });
+    }
+    {
+        app.on_use_broadcast_mode({
+            let state = Arc::clone(&state);
+            let sock = Arc::clone(&sock);
+            let weak = app.as_weak();
+            let config = Arc::clone(&config);
+            let settings = settings.clone();
+            move || {
+                if let Some(old) = state.get_multicast_group() {
+                    let iface_ip = main_helpers::selected_interface_ipv4(&config);
+                    main_helpers::leave_multicast_group(&sock, old, iface_ip);
+                }
+                state.set_multicast_group(None);
+
+                settings.update(|cfg| cfg.multicast_group = None);
+
+                if let Some(app) = weak.upgrade() {
+                    app.set_multicast_mode(false);
+                    app.set_multicast_status("".into());
+                    app.invoke_show_temp_message("📢 Back to broadcast mode".into());
+                }
+            }
+        });
+    }
+
+    // closing the window hides it to the tray when there is one, the tray's Quit ends the loop
+    let has_tray = tray::init(&app);
+    notify::track_focus(&app);
+    app.window().on_close_requested(move || {
+        if has_tray {
+            tray::set_hidden();
+        } else {
+            let _ = slint::quit_event_loop();
+        }
+        slint::CloseRequestResponse::HideWindow
+    });
+
+    #[cfg(feature = "test-hooks")]
+    {
+        let state = state.clone();
+        test_hooks::start_script(move || *state.port.lock().unwrap());
+    }
+
     // run
-    app.run()?;
+    app.show()?;
+    slint::run_event_loop_until_quit()?;
     running.store(false, Ordering::Relaxed);
+    if let Some(d) = &mdns { d.shutdown(); }
     cleanup_file_offers(&offer_registry, Some(&file_offer_model));
     Ok(())
 }
@@ -1,21 +1,26 @@
 use crate::{AppWindow};
-use crate::classes::{BroadcastState, Config, InterfacesInfo};
+use crate::classes::{BroadcastState, Config, InterfacesInfo, PortSharing, SharedSocket};
 use crate::file_transfer_protocol;
-use crate::FileOfferItem;
+use crate::config_migration;
+use crate::{ChatMessageItem, FileOfferItem, TextLine, TextSpan};
 use crate::secure_channel_code;
+use crate::history::HistoryEntry;
 use get_if_addrs::{get_if_addrs, IfAddr};
-use ipconfig;
-use slint::{VecModel, Weak};
+use crate::platform;
+use crate::net_runtime;
+use slint::{ModelRc, VecModel, Weak};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io;
 use std::io::Cursor;
-use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
 use std::path::{PathBuf, Path};
 use std::rc::Rc;
-use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock, atomic::{AtomicBool, AtomicU32, Ordering}};
 use std::time::Duration;
 use slint::{ComponentHandle, SharedString, Model};
-use rodio::{Decoder, OutputStreamBuilder, Sink};
+use rodio::{Decoder, OutputStreamBuilder, Sink, Source};
+use rodio::source::SineWave;
 use std::env;
 use std::process::Command;
 
@@ -33,10 +38,158 @@ pub fn force_switch_to_public(app: &AppWindow, channel_mode: &Arc<Mutex<String>>
 }
 
 /// To clear the chatbox by a button
-pub fn clear_chatbox(model: &Rc<VecModel<slint::SharedString>>) {
+pub fn clear_chatbox(model: &Rc<VecModel<ChatMessageItem>>) {
     model.set_vec(Vec::new());
 }
 
+/// Must match the length of Theme.sender_colors in types.slint
+pub const SENDER_COLOR_COUNT: u32 = 8;
+// how many history lines a date jump loads into the chat
+pub const HISTORY_PAGE_SIZE: usize = 50;
+// messages shown either side of a starred message or search hit
+pub const JUMP_CONTEXT_RADIUS: usize = 10;
+// /search shows at most this many hits
+pub const SEARCH_LIMIT: usize = 100;
+
+/// Local / system line (no sender, no accent, can't be starred)
+pub fn system_row(text: impl Into<SharedString>) -> ChatMessageItem {
+    ChatMessageItem {
+        text: text.into(),
+        sender: "".into(),
+        color_index: -1,
+        ts: "".into(),
+        time: "".into(),
+        starred: false,
+        lang: "".into(),
+        mentioned: false,
+        pending: false,
+        rich: ModelRc::default(),
+        order: "".into(),
+        name_conflict: false,
+        muted_from: "".into(),
+        snippet: ModelRc::default(),
+        snippet_lang: "".into(),
+        snippet_lines: 0,
+        snippet_open: false,
+    }
+}
+
+/// Styled lines for the chat bubble, an empty model when there's no markup
+pub fn rich_lines(text: &str) -> ModelRc<TextLine> {
+    let Some(lines) = crate::markdown::parse(text) else { return ModelRc::default(); };
+    let lines: Vec<TextLine> = lines
+        .into_iter()
+        .map(|spans| TextLine {
+            spans: ModelRc::new(VecModel::from(
+                spans
+                    .into_iter()
+                    .map(|s| TextSpan { text: s.text.into(), bold: s.bold, italic: s.italic, code: s.code, link: s.link })
+                    .collect::<Vec<_>>(),
+            )),
+        })
+        .collect();
+    ModelRc::new(VecModel::from(lines))
+}
+
+/// How our own messages are labelled, the address peers see them from
+pub fn own_sender() -> String {
+    get_local_ipv4().map(|ip| ip.to_string()).unwrap_or_else(|| "You".into())
+}
+
+// more lines than this and Send asks first: as a message, as a file or not at all
+const LARGE_PASTE_LINES: usize = 10;
+const PASTE_PREVIEW_LINES: usize = 12;
+
+/// Preview text and line count for a message long enough to ask about, None for normal ones
+pub fn paste_preview(text: &str) -> Option<(String, usize)> {
+    let lines = text.lines().count();
+    if lines <= LARGE_PASTE_LINES {
+        return None;
+    }
+    let mut preview = text.lines().take(PASTE_PREVIEW_LINES).collect::<Vec<_>>().join("\n");
+    if lines > PASTE_PREVIEW_LINES {
+        preview.push_str(&format!("\n… {} more lines", lines - PASTE_PREVIEW_LINES));
+    }
+    Some((preview, lines))
+}
+
+/// Our message, shown as soon as Send is pressed and greyed until it went out
+pub fn own_row(sender: &str, text: &str, order: String) -> ChatMessageItem {
+    ChatMessageItem {
+        sender: format!("{} (you)", sender).into(),
+        color_index: sender_color_index(sender),
+        pending: true,
+        rich: rich_lines(text),
+        order: order.into(),
+        ..system_row(text.to_string())
+    }
+}
+
+/// The oldest pending row with this text went out: no longer grey, stored in the history
+/// and passed on to the web companion
+pub fn confirm_own_row(model: &VecModel<ChatMessageItem>, text: &str) {
+    let Some(i) = model.iter().position(|row| row.pending && row.text == text) else { return; };
+    let Some(mut row) = model.row_data(i) else { return; };
+    let channel = if crate::secure_channel_code::get_active_channel().is_some() { "secure" } else { "public" };
+    row.ts = crate::history::append(&own_sender(), text, channel).to_string().into();
+    row.pending = false;
+    model.set_row_data(i, row);
+
+    let payload = serde_json::json!({ "type": "chat", "sender": "app", "text": text });
+    crate::web_app::broadcast_to_web_clients(payload.to_string());
+    if channel == "public" {
+        crate::serial_bridge::mirror(&own_sender(), text);
+    }
+}
+
+/// Drop the oldest pending row with this text (pushed out of a full queue)
+pub fn remove_pending_row(model: &VecModel<ChatMessageItem>, text: &str) {
+    if let Some(i) = model.iter().position(|row| row.pending && row.text == text) {
+        model.remove(i);
+    }
+}
+
+/// The chat view around the stored message sent at `ts`, under a header line
+pub fn context_rows(ts: i64, header: String) -> Option<Vec<ChatMessageItem>> {
+    let entries = crate::history::load_around(ts, JUMP_CONTEXT_RADIUS)?;
+    let mut rows = vec![system_row(header)];
+    rows.extend(entries.iter().map(|e| history_row(e, e.local_time())));
+    Some(rows)
+}
+
+/// Chat row for a stored message, `time` is shown next to the sender
+pub fn history_row(e: &HistoryEntry, time: String) -> ChatMessageItem {
+    ChatMessageItem {
+        text: e.text.clone().into(),
+        sender: e.sender.clone().into(),
+        color_index: sender_color_index(&e.sender),
+        ts: e.ts.to_string().into(),
+        time: time.into(),
+        starred: crate::history::is_starred(e.ts, &e.text),
+        lang: "".into(),
+        mentioned: crate::message_pipeline::mentions_me(&e.text),
+        pending: false,
+        rich: rich_lines(&e.text),
+        order: "".into(),
+        name_conflict: false,
+        muted_from: "".into(),
+        snippet: ModelRc::default(),
+        snippet_lang: "".into(),
+        snippet_lines: 0,
+        snippet_open: false,
+    }
+}
+
+/// Stable color slot for a sender identity (FNV-1a, same result on every run and machine)
+pub fn sender_color_index(identity: &str) -> i32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for b in identity.bytes() {
+        hash ^= b as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    (hash % SENDER_COLOR_COUNT) as i32
+}
+
 /// Only change the mode the rest of the logic is built in another block of code
 pub fn set_channel_mode_only(channel_mode: &Arc<Mutex<String>>, new_mode: &str) {
     let mut cm = channel_mode.lock().unwrap();
@@ -44,28 +197,7 @@ pub fn set_channel_mode_only(channel_mode: &Arc<Mutex<String>>, new_mode: &str)
 }
 
 pub fn get_local_ipv4() -> Option<Ipv4Addr> {
-    // Iterate through all network adapters
-    match ipconfig::get_adapters() {
-        Ok(adapters) => {
-            for adapter in adapters {
-                // Skip adapters that are down
-                if format!("{:?}", adapter.oper_status()) != "IfOperStatusUp" {
-                    continue;
-                }
-                // Look through adapter IPs
-                for ip in adapter.ip_addresses() {
-                    if let IpAddr::V4(v4) = ip {
-                        // Skip loopback addresses (127.x.x.x)
-                        if !v4.is_loopback() {
-                            return Some(*v4);
-                        }
-                    }
-                }
-            }
-            None
-        }
-        Err(_e) => None,
-    }
+    platform::current().local_ipv4()
 }
 
 #[allow(nonstandard_style)]
@@ -88,64 +220,12 @@ pub fn update_ui_PIN(app: &AppWindow) {
 
 /// Gather user-friendly interfaces (name + broadcast)
 pub fn collect_interfaces() -> Vec<InterfacesInfo> {
-    let mut collection = Vec::new();
-    let ifaces = get_if_addrs().unwrap_or_default();
-
-    for adapter in ipconfig::get_adapters().unwrap_or_default() {
-        let name = adapter.friendly_name().to_string();
-        let status = format!("{:?}", adapter.oper_status());
-
-        // Default fallback broadcast
-        let mut broadcast_address = "255.255.255.255".to_string();
-
-        // Match adapter IPs against get_if_addrs to find broadcast
-        for ip in adapter.ip_addresses().iter().map(|ip| ip.to_string()) {
-            for iface in &ifaces {
-                if let IfAddr::V4(v4) = &iface.addr {
-                    if v4.ip.to_string() == ip {
-                        if let Some(b) = v4.broadcast {
-                            broadcast_address = b.to_string();
-                        }
-                    }
-                }
-            }
-        }
-
-        // Only skip loopback and "all 255s"
-        if broadcast_address != "127.255.255.255" && broadcast_address != "255.255.255.255" {
-            collection.push(InterfacesInfo {
-                name,
-                address_to_broadcast: broadcast_address,
-                status,
-            });
-        }
-    }
-    collection
+    platform::current().collect_interfaces()
 }
 
 /// Return the adapter’s first IPv4 gateway as string (or "0.0.0.0" if none)
 pub fn get_gateway_for_adapter(name: &str) -> String {
-    for adapter in ipconfig::get_adapters().unwrap_or_default() {
-        if adapter.friendly_name() == name {
-            // Prefer IPv4 gateways
-            if let Some(gw) = adapter
-                .gateways()
-                .iter()
-                .find_map(|ip| match ip {
-                    IpAddr::V4(v4) => Some(v4.to_string()),
-                    _ => None,
-                })
-            {
-                return gw;
-            }
-            // If only IPv6 or none:
-            if let Some(gw_any) = adapter.gateways().get(0) {
-                return gw_any.to_string();
-            }
-            return "0.0.0.0".to_string();
-        }
-    }
-    "0.0.0.0".to_string()
+    platform::current().gateway_for_adapter(name)
 }
 
 pub fn get_broadcast_for_name(interfaces: &Vec<InterfacesInfo>, name: &str) -> Option<String> {
@@ -177,10 +257,8 @@ pub fn match_getifadd_ipconfig(state: &BroadcastState) -> String {
     }
 
     if let Some(ip) = matched_ip {
-        for adapter in ipconfig::get_adapters().unwrap_or_default() {
-            if adapter.ip_addresses().iter().any(|a| a.to_string() == ip) {
-                return adapter.friendly_name().to_string();
-            }
+        if let Some(name) = platform::current().adapter_name_for_ip(&ip) {
+            return name;
         }
     }
 
@@ -188,28 +266,28 @@ pub fn match_getifadd_ipconfig(state: &BroadcastState) -> String {
 }
 
 pub fn get_config_path() -> PathBuf {
-    #[cfg(target_os = "windows")]
-    let config_path = dirs::data_dir()
-        .unwrap()
-        .join("LanChGoApp")
-        .join("config.json");
-    config_path
+    platform::current().app_data_dir().join("config.json")
 }
 
 pub fn load_or_create_config(default: &Config, app: &AppWindow) -> (Config, bool) {
     let config_path = get_config_path();
     if config_path.exists() {
-        let file = File::open(&config_path).expect("Failed to open config file");
-        let config: Config =
-            serde_json::from_reader(file).expect("Failed to parse config file");
-
-        let current_version = env!("CARGO_PKG_VERSION").to_string();
-        if config.version != current_version {
-            std::fs::remove_file(&config_path).ok();
-            let weak = app.as_weak();
-            if let Some(app) = weak.upgrade() {
-                app.invoke_show_new_version_popup();
-            }
+        let raw: Option<serde_json::Value> = File::open(&config_path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(io::BufReader::new(file)).ok());
+        let old_version = raw.as_ref().and_then(|v| v.get("version")?.as_str().map(str::to_string));
+
+        let Some(config) = raw.and_then(|raw| config_migration::migrate(raw, default)) else {
+            // unreadable: keep it next to the fresh one instead of deleting what the user had
+            std::fs::rename(&config_path, config_path.with_extension("json.bak")).ok();
+            save_config(default);
+            return (default.clone(), true);
+        };
+
+        if old_version.as_deref() != Some(default.version.as_str()) {
+            // upgraded: settings carried over, tell the user what's new
+            save_config(&config);
+            app.invoke_show_new_version_popup();
         }
 
         (config, false)
@@ -240,11 +318,325 @@ pub fn get_broadcast_address(state: &BroadcastState) {
     state.set_broadcast_address(address);
 }
 
-pub fn bind_single_port_socket(port: u16) -> io::Result<Arc<UdpSocket>> {
-    let sock = UdpSocket::bind(("0.0.0.0", port))?;
+// Config.port_sharing, read by every bind so a rebind picks up a change
+static PORT_SHARING: Mutex<PortSharing> = Mutex::new(PortSharing::Default);
+
+pub fn set_port_sharing(cfg: &Config) {
+    *PORT_SHARING.lock().unwrap() = cfg.port_sharing;
+}
+
+// a UDP bind with the socket options Config.port_sharing asks for
+fn bind_udp(addr: SocketAddr) -> io::Result<UdpSocket> {
+    let sock = socket2::Socket::new(socket2::Domain::for_address(addr), socket2::Type::DGRAM, Some(socket2::Protocol::UDP))?;
+    if addr.is_ipv6() {
+        // dual-stack by default on Linux, [::] would then take the v4 port the other socket needs
+        sock.set_only_v6(true)?;
+    }
+    match *PORT_SHARING.lock().unwrap() {
+        PortSharing::Default => {}
+        PortSharing::Shared => sock.set_reuse_address(true)?,
+        PortSharing::Exclusive => platform::current().exclusive_bind(&sock)?,
+    }
+    sock.bind(&addr.into())?;
+    Ok(sock.into())
+}
+
+pub fn bind_single_port_socket(port: u16) -> io::Result<SharedSocket> {
+    let sock = bind_udp(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))?;
     sock.set_broadcast(true)?;
-    sock.set_read_timeout(Some(Duration::from_millis(250)))?;
-    Ok(Arc::new(sock))
+    sock.set_nonblocking(true)?;
+
+    // IPv6 is best effort, a missing v6 stack must not stop the app
+    let sock_v6 = bind_v6_multicast_socket(port).ok();
+
+    // tokio sockets register with the reactor of the runtime they are created in
+    let _guard = net_runtime::runtime().enter();
+    let sock_v6 = sock_v6.and_then(|s| tokio::net::UdpSocket::from_std(s).ok()).map(Arc::new);
+    Ok(SharedSocket::new(Arc::new(tokio::net::UdpSocket::from_std(sock)?), sock_v6))
+}
+
+/// Link-local multicast group all LanChGo instances join (ff02::/16 never leaves the link)
+pub const V6_MULTICAST_GROUP: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x4c47);
+
+fn bind_v6_multicast_socket(port: u16) -> io::Result<UdpSocket> {
+    let sock = bind_udp(SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)))?;
+    // interface 0 = let the OS pick the default link
+    sock.join_multicast_v6(&V6_MULTICAST_GROUP, 0)?;
+    // our own packets come back like the v4 ones, the receiver drops the second copy
+    // (DualStackDedupe) and own_packets tells them apart, the transcript needs them
+    sock.set_multicast_loop_v6(true)?;
+    sock.set_nonblocking(true)?;
+    Ok(sock)
+}
+
+/// Drop the current socket and bind a fresh one on the same port (stale sockets after sleep etc.)
+pub fn rebind_shared_socket(shared: &SharedSocket, port: u16) -> io::Result<()> {
+    // park an ephemeral socket so the old one can actually be released
+    shared.replace(bind_single_port_socket(0)?);
+
+    // the receiver lets go of the old socket as soon as it sees the swap, give the OS a moment anyway
+    let mut last_err = None;
+    for _ in 0..10 {
+        match bind_single_port_socket(port) {
+            Ok(sock) => {
+                shared.replace(sock);
+                return Ok(());
+            }
+            Err(e) => {
+                last_err = Some(e);
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, "rebind failed")))
+}
+
+/// Parse a user supplied group, only IPv4 multicast (224.0.0.0/4) is accepted
+pub fn parse_multicast_group(text: &str) -> Result<Ipv4Addr, String> {
+    let group: Ipv4Addr = text.trim().parse().map_err(|_| format!("\"{}\" is not an IPv4 address", text.trim()))?;
+    if !group.is_multicast() {
+        return Err(format!("{} is not a multicast address (224.0.0.0 – 239.255.255.255)", group));
+    }
+    Ok(group)
+}
+
+/// IGMP join on the selected interface and send multicast out of it too
+pub fn join_multicast_group(shared: &SharedSocket, group: Ipv4Addr, iface_ip: Ipv4Addr) -> io::Result<()> {
+    let sock = shared.get();
+    sock.join_multicast_v4(group, iface_ip)?;
+    if !iface_ip.is_unspecified() {
+        socket2::SockRef::from(&*sock).set_multicast_if_v4(&iface_ip)?;
+    }
+    // keep hearing ourselves like with broadcast: our own lines are on screen already
+    // (own_packets), but a host's transcript records them as they come back
+    sock.set_multicast_loop_v4(true)?;
+    Ok(())
+}
+
+pub fn leave_multicast_group(shared: &SharedSocket, group: Ipv4Addr, iface_ip: Ipv4Addr) {
+    let _ = shared.get().leave_multicast_v4(group, iface_ip);
+}
+
+/// IPv4 of the configured interface, UNSPECIFIED lets the OS pick
+pub fn selected_interface_ipv4(config: &Arc<Mutex<Config>>) -> Ipv4Addr {
+    let name = config.lock().unwrap().selected_interface.clone();
+    collect_interfaces()
+        .into_iter()
+        .find(|it| it.name == name)
+        .and_then(|it| it.ipv4_address.parse().ok())
+        .unwrap_or(Ipv4Addr::UNSPECIFIED)
+}
+
+/// Rows shown in the interface picker
+pub fn interface_rows(interfaces: &[InterfacesInfo]) -> Vec<SharedString> {
+    interfaces
+        .iter()
+        .map(|it| {
+            let status = if it.status == platform::STATUS_UP { "🟢 Up" } else { "🔴 Down" };
+            match it.ipv6_addresses.first() {
+                Some(v6) => format!("Name: {}\nBroadcast Address: {}\nIPv6: {}\n{}", it.name, it.address_to_broadcast, v6, status).into(),
+                None => format!("Name: {}\nBroadcast Address: {}\n{}", it.name, it.address_to_broadcast, status).into(),
+            }
+        })
+        .collect()
+}
+
+/// Update the picker rows in place, only touching rows that changed (keeps the grid from flickering)
+pub fn update_interface_rows(app: &AppWindow, interfaces: &[InterfacesInfo]) {
+    let rows = interface_rows(interfaces);
+    let model = app.get_interfaces();
+    let Some(model) = model.as_any().downcast_ref::<VecModel<SharedString>>() else { return; };
+    let selected = app.get_selected_interface();
+    for (i, row) in rows.iter().enumerate() {
+        match model.row_data(i) {
+            Some(old) if old == *row => {}
+            Some(old) => {
+                // the picker selects by row text, follow the row when its status line flips
+                if old == selected {
+                    app.set_selected_interface(row.clone());
+                }
+                model.set_row_data(i, row.clone());
+            }
+            None => model.push(row.clone()),
+        }
+    }
+    while model.row_count() > rows.len() {
+        model.remove(model.row_count() - 1);
+    }
+}
+
+/// Re-read the adapters and point the broadcast state at the configured interface again
+pub fn refresh_selected_interface(state: &BroadcastState, config: &Arc<Mutex<Config>>) -> (Vec<InterfacesInfo>, Option<InterfacesInfo>) {
+    apply_selected_interface(state, config, collect_interfaces())
+}
+
+/// Same as refresh_selected_interface but with an adapter list we already have
+pub fn apply_selected_interface(state: &BroadcastState, config: &Arc<Mutex<Config>>, interfaces: Vec<InterfacesInfo>) -> (Vec<InterfacesInfo>, Option<InterfacesInfo>) {
+    let selected = {
+        let cfg = config.lock().unwrap();
+        interfaces.iter().find(|it| it.name == cfg.selected_interface).cloned()
+    };
+    if let Some(info) = &selected {
+        state.set_broadcast_address(info.address_to_broadcast.clone());
+    }
+    (interfaces, selected)
+}
+
+// hypervisor / container bridges are up and have addresses but nobody chats on them
+const VIRTUAL_ADAPTER_HINTS: &[&str] = &["vethernet", "virtualbox", "vmware", "hyper-v", "docker", "veth", "br-", "virbr", "vboxnet", "tailscale", "zerotier"];
+
+/// Same subnet as the adapter, judged from its broadcast address (x.y.z.255 covers x.y.z.*)
+fn in_broadcast_domain(ip: Ipv4Addr, broadcast: Ipv4Addr) -> bool {
+    ip.octets().iter().zip(broadcast.octets()).all(|(a, b)| b == 255 || *a == b)
+}
+
+/// Score an adapter for auto-selection, None = not usable at all
+fn interface_score(info: &InterfacesInfo, peer_ips: &[Ipv4Addr]) -> Option<i32> {
+    let ip: Ipv4Addr = info.ipv4_address.parse().ok()?;
+    if info.status != platform::STATUS_UP || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() {
+        return None;
+    }
+
+    let mut score = 0;
+    let gateway = get_gateway_for_adapter(&info.name);
+    if gateway.parse::<Ipv4Addr>().is_ok_and(|gw| !gw.is_unspecified()) {
+        score += 4;
+    }
+    if ip.is_private() {
+        score += 2;
+    }
+    let lower = info.name.to_lowercase();
+    if VIRTUAL_ADAPTER_HINTS.iter().any(|hint| lower.contains(hint)) {
+        score -= 3;
+    }
+    if let Ok(broadcast) = info.address_to_broadcast.parse::<Ipv4Addr>() {
+        score += peer_ips.iter().filter(|p| in_broadcast_domain(**p, broadcast)).count() as i32;
+    }
+    Some(score)
+}
+
+/// Best adapter to talk on: up, has a gateway, private range, most peers seen on it
+pub fn best_interface(interfaces: &[InterfacesInfo]) -> Option<InterfacesInfo> {
+    let peer_ips: Vec<Ipv4Addr> = crate::peers::addresses()
+        .into_iter()
+        .filter_map(|ip| match ip {
+            std::net::IpAddr::V4(v4) => Some(v4),
+            std::net::IpAddr::V6(_) => None,
+        })
+        .collect();
+
+    interfaces
+        .iter()
+        .filter_map(|it| interface_score(it, &peer_ips).map(|score| (score, it)))
+        // max_by_key keeps the last of equal scores, reverse so the OS order wins ties
+        .rev()
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, it)| it.clone())
+}
+
+/// What "the same LAN" means for lan_changed: adapter identity + Wi-Fi SSID + subnet.
+/// Gateway and host address are left out on purpose, DHCP renewals move those around.
+pub fn network_fingerprint(info: &InterfacesInfo) -> String {
+    let p = platform::current();
+    let adapter = p.adapter_id(&info.name).unwrap_or_else(|| info.name.clone());
+    let ssid = p.wifi_ssid(&info.name).unwrap_or_default();
+
+    // the broadcast address ends in the host bits, its trailing ones give the prefix length
+    let subnet = match info.address_to_broadcast.parse::<Ipv4Addr>() {
+        Ok(bcast) => {
+            let bits = u32::from(bcast);
+            let host_bits = bits.trailing_ones();
+            let network = if host_bits >= 32 { 0 } else { bits & !((1u32 << host_bits) - 1) };
+            format!("{}/{}", Ipv4Addr::from(network), 32 - host_bits)
+        }
+        Err(_) => info.address_to_broadcast.clone(),
+    };
+    format!("{}|{}|{}", adapter, ssid, subnet)
+}
+
+/// Point the config at `info` (selected + the values lan_changed compares against)
+pub fn remember_interface(config: &Arc<Mutex<Config>>, info: &InterfacesInfo) {
+    let mut cfg = config.lock().unwrap();
+    cfg.selected_interface = info.name.clone();
+    cfg.last_broadcast = info.address_to_broadcast.clone();
+    cfg.last_gateway = get_gateway_for_adapter(&info.name);
+    cfg.network_fingerprint = network_fingerprint(info);
+    save_config(&cfg);
+}
+
+/// Push a fresh interface list (and status of the selected one) into the UI
+pub fn apply_interfaces_to_ui(app: &AppWindow, interfaces: &[InterfacesInfo], selected: Option<&InterfacesInfo>) {
+    update_interface_rows(app, interfaces);
+    if let Some(info) = selected {
+        app.set_broadcast_address(info.address_to_broadcast.clone().into());
+    }
+    set_active_interface(app, selected);
+}
+
+// what the status strip reports on, updated by everything that (re)selects an adapter
+static ACTIVE_INTERFACE: Mutex<Option<InterfacesInfo>> = Mutex::new(None);
+
+pub fn set_active_interface(app: &AppWindow, info: Option<&InterfacesInfo>) {
+    let status = info.map(|i| i.status.clone()).unwrap_or_else(|| platform::STATUS_DOWN.to_string());
+    app.set_interface_status(status.into());
+    *ACTIVE_INTERFACE.lock().unwrap() = info.cloned();
+    refresh_network_status(app);
+}
+
+// set while the UDP receiver keeps failing, see udp_receiver::RecvSupervisor
+static RECEIVE_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+/// Show (Some) or clear (None) the "not receiving" banner
+pub fn set_receive_error(app: &AppWindow, error: Option<String>) {
+    *RECEIVE_ERROR.lock().unwrap() = error;
+    refresh_network_status(app);
+}
+
+pub fn receive_error() -> Option<String> {
+    RECEIVE_ERROR.lock().unwrap().clone()
+}
+
+// set when the TCP file server couldn't start, chat still works without it
+static FILE_SERVER_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+/// Show the "file sharing disabled" banner, stays until the next start
+pub fn set_file_server_error(app: &AppWindow, error: String) {
+    *FILE_SERVER_ERROR.lock().unwrap() = Some(error);
+    refresh_network_status(app);
+}
+
+pub fn file_server_error() -> Option<String> {
+    FILE_SERVER_ERROR.lock().unwrap().clone()
+}
+
+/// "Connected via Wi-Fi (192.168.1.23) — 4 peers" or a warning when there's nothing to send on
+pub fn refresh_network_status(app: &AppWindow) {
+    if let Some(error) = RECEIVE_ERROR.lock().unwrap().clone() {
+        app.set_network_status(format!("📴 Not receiving messages ({}), retrying…", error).into());
+        app.set_network_ok(false);
+        return;
+    }
+    if let Some(error) = FILE_SERVER_ERROR.lock().unwrap().clone() {
+        app.set_network_status(format!("⚠️ File sharing disabled: {}", error).into());
+        app.set_network_ok(false);
+        return;
+    }
+    let active = ACTIVE_INTERFACE.lock().unwrap().clone();
+    match active {
+        Some(info) if info.status == platform::STATUS_UP && !info.ipv4_address.is_empty() => {
+            let peers = match crate::peers::count() {
+                0 => "no peers seen yet".to_string(),
+                1 => "1 peer".to_string(),
+                n => format!("{} peers", n),
+            };
+            app.set_network_status(format!("Connected via {} ({}) — {}", info.name, info.ipv4_address, peers).into());
+            app.set_network_ok(true);
+        }
+        _ => {
+            app.set_network_status("⚠️ No usable interface".into());
+            app.set_network_ok(false);
+        }
+    }
 }
 // to clear up the registry of sent file offers bundles in the temp
 pub fn cleanup_file_offers( offer_registry: &Arc<Mutex<file_transfer_protocol::OfferRegistry>>, file_offer_model: Option<&Rc<VecModel<FileOfferItem>>>, ) {
@@ -260,6 +652,36 @@ pub fn cleanup_file_offers( offer_registry: &Arc<Mutex<file_transfer_protocol::O
 
     println!("[FOFT][CLEANUP] temp offers deleted + registry cleared");
 }
+/// The offer was withdrawn by its sender, its row goes away
+pub fn remove_file_offer_row(app: &AppWindow, offer_id: &str) {
+    let model_rc = app.get_file_offer();
+    if let Some(vec) = model_rc.as_any().downcast_ref::<VecModel<FileOfferItem>>() {
+        if let Some(i) = (0..vec.row_count()).find(|&i| vec.row_data(i).is_some_and(|row| row.offer_id.as_str() == offer_id)) {
+            vec.remove(i);
+        }
+    }
+}
+// where finished downloads went, by offer id, for the open / show buttons on their rows
+static SAVED: Mutex<BTreeMap<String, PathBuf>> = Mutex::new(BTreeMap::new());
+
+/// A download is on disk: remember where and give its row the open / show buttons
+pub fn set_offer_saved(app: &AppWindow, offer_id: &str, path: PathBuf) {
+    SAVED.lock().unwrap().insert(offer_id.to_string(), path);
+    let model_rc = app.get_file_offer();
+    if let Some(vec) = model_rc.as_any().downcast_ref::<VecModel<FileOfferItem>>() {
+        if let Some(i) = (0..vec.row_count()).find(|&i| vec.row_data(i).is_some_and(|row| row.offer_id.as_str() == offer_id)) {
+            let mut row = vec.row_data(i).unwrap();
+            row.saved = true;
+            vec.set_row_data(i, row);
+        }
+    }
+}
+
+/// Where the download of `offer_id` was saved, None when it isn't (or no longer) there
+pub fn saved_path(offer_id: &str) -> Option<PathBuf> {
+    SAVED.lock().unwrap().get(offer_id).filter(|p| p.exists()).cloned()
+}
+
 // to show download progress 
 pub fn progress_bucket_3(done: u64, total: u64) -> u32 {
     if total == 0 { return 0; }
@@ -303,6 +725,108 @@ pub fn play_nutella_sound() {
     }
 }
 
+/// Two-tone chime for @mentions, generated so it can't be confused with ping / nutella
+pub fn play_mention_sound() {
+    if let Ok(builder) = OutputStreamBuilder::from_default_device() {
+        if let Ok(stream) = builder.open_stream() {
+            let mixer = stream.mixer();
+            let sink = Sink::connect_new(&mixer);
+            sink.append(SineWave::new(880.0).take_duration(Duration::from_millis(120)).amplify(0.2));
+            sink.append(SineWave::new(1320.0).take_duration(Duration::from_millis(180)).amplify(0.2));
+            sink.detach();
+
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                drop(stream);
+            });
+        }
+    }
+}
+
+// alert preferences from the settings screen, read by the receive thread.
+// "only alert on mentions": ping / nutella sounds and notifications stay quiet unless we're @mentioned
+static MENTIONS_ONLY: AtomicBool = AtomicBool::new(false);
+static PING_SOUND: AtomicBool = AtomicBool::new(true);
+static NUTELLA_SOUND: AtomicBool = AtomicBool::new(true);
+static NOTIFICATIONS: AtomicBool = AtomicBool::new(true);
+// per channel on top of the above: a muted channel makes no sound, toast or unread count
+static PUBLIC_MUTED: AtomicBool = AtomicBool::new(false);
+static SECURE_MUTED: AtomicBool = AtomicBool::new(false);
+
+pub fn apply_alert_prefs(cfg: &Config) {
+    MENTIONS_ONLY.store(cfg.mentions_only, Ordering::Relaxed);
+    PING_SOUND.store(cfg.ping_sound, Ordering::Relaxed);
+    NUTELLA_SOUND.store(cfg.nutella_sound, Ordering::Relaxed);
+    NOTIFICATIONS.store(cfg.notifications, Ordering::Relaxed);
+    PUBLIC_MUTED.store(!cfg.public_alerts, Ordering::Relaxed);
+    SECURE_MUTED.store(!cfg.secure_alerts, Ordering::Relaxed);
+}
+
+/// The channel we're in is muted. Messages and offers only arrive for the channel we're in,
+/// so this is also the channel of whatever is being alerted about.
+pub fn channel_muted() -> bool {
+    if secure_channel_code::get_active_channel().is_some() {
+        SECURE_MUTED.load(Ordering::Relaxed)
+    } else {
+        PUBLIC_MUTED.load(Ordering::Relaxed)
+    }
+}
+
+pub fn mentions_only() -> bool {
+    MENTIONS_ONLY.load(Ordering::Relaxed)
+}
+
+pub fn ping_sound_enabled() -> bool {
+    PING_SOUND.load(Ordering::Relaxed) && !mentions_only()
+}
+
+pub fn nutella_sound_enabled() -> bool {
+    NUTELLA_SOUND.load(Ordering::Relaxed) && !mentions_only()
+}
+
+pub fn notifications_enabled() -> bool {
+    NOTIFICATIONS.load(Ordering::Relaxed)
+}
+
+/// The ping / nutella sound a peer's message asks for, None if there's none or it's turned off
+pub fn peer_message_sound(text: &str) -> Option<fn()> {
+    let (text, _) = crate::message_pipeline::split_stamp(text);
+    if channel_muted() {
+        None
+    } else if text.eq_ignore_ascii_case("ping") {
+        ping_sound_enabled().then_some(secure_channel_code::play_ping_sound as fn())
+    } else if text.to_ascii_lowercase().contains("nutella") {
+        nutella_sound_enabled().then_some(play_nutella_sound as fn())
+    } else {
+        None
+    }
+}
+
+// how many messages the chat view keeps, settings page (history.jsonl keeps everything)
+static CHAT_ROW_LIMIT: AtomicU32 = AtomicU32::new(10);
+
+pub fn set_chat_row_limit(rows: u32) {
+    CHAT_ROW_LIMIT.store(rows.max(1), Ordering::Relaxed);
+}
+
+pub fn chat_row_limit() -> usize {
+    CHAT_ROW_LIMIT.load(Ordering::Relaxed) as usize
+}
+
+/// Nickname others @mention us with, the machine name until the user picks one
+pub fn default_nickname() -> String {
+    env::var("COMPUTERNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "me".to_string())
+}
+
+/// The machine's hostname until the user names it (/device)
+pub fn default_device_name() -> String {
+    env::var("COMPUTERNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "computer".to_string())
+}
+
 pub fn build_download_save_path( config: &Arc<Mutex<Config>>, offer_name: &str, offer_id_hex: &str, ) -> PathBuf {
     let download_dir = {
         let cfg = config.lock().unwrap();
@@ -332,26 +856,60 @@ pub fn info_message() -> String {
     .to_string()
 }
 
-pub fn help_message() -> String {
-        "Available Commands
-
-        /info        Show app information
-        /rescale     Rescales the app's UI
-        /help        Show this help message
-        /settings    Opens the settings menu
-        /downloads   Opens the download folder
-        /clear       Clear chat messages
-        /clearfiles  Clear file transfer panel
-        /clearall    Clear chat and files
-        /disconnect  Disconnect from secure channel
-        /webjoin     Starts a web companion session
-        /webstop     Stops a web companion session
-        /restart     Restarts the app ( might not always re-open )
-        /exit        Exit LanChGo
-
-        Tip:
-        Commands are local and not sent over the network."
-    .to_string()
+/// Command picker + settings summary after the aliases changed
+pub fn apply_aliases_to_ui(app: &AppWindow, aliases: &BTreeMap<String, String>) {
+    let entries = crate::commands::picker_entries(aliases);
+    app.set_chat_commands(slint::ModelRc::new(VecModel::from(entries)));
+    app.set_alias_summary(crate::commands::alias_summary(aliases).into());
+}
+
+/// Rebuild the Later tab, online dots from what peers.rs knows right now
+pub fn refresh_later_ui(app: &AppWindow) {
+    let items: Vec<crate::LaterItem> = crate::later::list()
+        .into_iter()
+        .map(|e| crate::LaterItem {
+            offer_id: e.offer_id.into(),
+            name: file_transfer_protocol::truncate_name(&e.name, 24).into(),
+            size_text: file_transfer_protocol::human_size(e.size).into(),
+            sender: e.sender.into(),
+            online: crate::peers::is_online(e.sender_addr.ip()),
+        })
+        .collect();
+    app.set_later_items(ModelRc::new(VecModel::from(items)));
+}
+
+/// Drop the name conflict badge from the rows of `sender` already in the chat
+pub fn clear_conflict_badges(model: &VecModel<ChatMessageItem>, sender: &str) {
+    for i in 0..model.row_count() {
+        if let Some(mut row) = model.row_data(i) {
+            if row.name_conflict && row.sender == sender {
+                row.name_conflict = false;
+                model.set_row_data(i, row);
+            }
+        }
+    }
+}
+
+/// Rebuild the contacts popup list, most recently seen first
+pub fn refresh_contacts_ui(app: &AppWindow) {
+    let items: Vec<crate::ContactItem> = crate::contacts::list()
+        .into_iter()
+        .map(|c| crate::ContactItem {
+            fingerprint: crate::identity::fingerprint(&c.key).into(),
+            nickname: c.nickname.into(),
+            last_seen: chrono::DateTime::from_timestamp(c.last_seen, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+                .into(),
+            online: c.last_ip.parse().is_ok_and(crate::peers::is_online),
+            last_ip: c.last_ip.into(),
+            trust: c.trust.as_str().into(),
+            notes: c.notes.into(),
+            name_conflict: c.conflict_with.is_some(),
+            key: c.key.into(),
+        })
+        .collect();
+    app.set_contacts(ModelRc::new(VecModel::from(items)));
 }
 
 pub fn update_ui_qr_only(app: &AppWindow) {
@@ -362,6 +920,26 @@ pub fn update_ui_qr_only(app: &AppWindow) {
 
 pub fn set_app_handle(handle: Weak<AppWindow>) { let _ = APP_HANDLE.set(handle); }
 
+pub fn app_handle() -> Option<Weak<AppWindow>> { APP_HANDLE.get().cloned() }
+
+/// A member finished one of our channel offers: tick count on the row + who it was
+pub fn notify_channel_delivery(offer_id: [u8; 16], member: std::net::IpAddr, count: usize) {
+    let Some(app_weak) = APP_HANDLE.get() else { return; };
+    let app_weak = app_weak.clone();
+    let id_hex = file_transfer_protocol::offer_id_to_hex(&offer_id);
+    let _ = slint::invoke_from_event_loop(move || {
+        let Some(app) = app_weak.upgrade() else { return; };
+        let name = app
+            .get_file_offer()
+            .iter()
+            .find(|row| row.offer_id.as_str() == id_hex)
+            .map(|row| row.name.to_string())
+            .unwrap_or_default();
+        set_offer_progress_text(&app, &id_hex, false, &format!("✓ {}", count));
+        app.invoke_show_temp_message(format!("✅ {} received {}", member, name).into());
+    });
+}
+
 pub fn append_message_from_web(text: String) {
     if let Some(app_weak) = APP_HANDLE.get() {
         let app_weak = app_weak.clone();
@@ -397,11 +975,21 @@ pub fn append_message_from_web(text: String) {
 //     }
 // }
 
+pub const DEFAULT_UDP_PORT: u16 = 3000;
+
 pub fn try_set_manual_port(state: &BroadcastState, config: &Arc<Mutex<Config>>, port: u16) -> Result<u16, String> {
     if !(1024..=65535).contains(&port) {
         return Err("Port out of valid range (1024–65535)".into());
     }
-    match UdpSocket::bind(("0.0.0.0", port)) {
+    // we already hold this port, nothing to probe
+    if port == state.get_port() {
+        let mut cfg = config.lock().unwrap();
+        cfg.port = Some(port);
+        save_config(&cfg);
+        return Ok(port);
+    }
+    // a plain bind: with /portshare shared a probe with SO_REUSEADDR would always get the port
+    match UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))) {
         Ok(_) => {
             state.set_port(port);
             let mut cfg = config.lock().unwrap();
@@ -414,12 +1002,22 @@ pub fn try_set_manual_port(state: &BroadcastState, config: &Arc<Mutex<Config>>,
     }
 }
 
-pub fn reset_port_to_auto(_state: &BroadcastState, config: &Arc<Mutex<Config>>) {
+pub fn reset_port_to_auto(state: &BroadcastState, config: &Arc<Mutex<Config>>) {
     let mut cfg = config.lock().unwrap();
     cfg.port = None;
     save_config(&cfg);
     drop(cfg); // drop before checking_ports
     //checking_ports(state);
+    state.set_port(DEFAULT_UDP_PORT);
+}
+
+/// Move the live sockets to the state's port (and re-join the multicast group if any)
+pub fn rebind_to_state_port(shared: &SharedSocket, state: &BroadcastState, config: &Arc<Mutex<Config>>) -> io::Result<()> {
+    rebind_shared_socket(shared, state.get_port())?;
+    if let Some(group) = state.get_multicast_group() {
+        join_multicast_group(shared, group, selected_interface_ipv4(config))?;
+    }
+    Ok(())
 }
 
 pub fn restart_app_after_delay(ms: u64) {
@@ -476,6 +1074,12 @@ pub fn check_for_update() -> Result<Option<String>, Box<dyn std::error::Error>>
     }
 }
 
+/// Put `text` on the system clipboard
+pub fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|_| "Failed to access clipboard".to_string())?;
+    clipboard.set_text(text).map_err(|_| "Failed to copy".to_string())
+}
+
 pub fn open_download_folder_from_config( config: &Arc<Mutex<Config>>, ) -> Result<(), String> {
     let folder = {
         let cfg = config.lock().unwrap();
@@ -0,0 +1,109 @@
+/// One run of text with a single style
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub link: bool, // text is an http(s) URL
+}
+
+impl Span {
+    fn plain(text: String) -> Self {
+        Self { text, bold: false, italic: false, code: false, link: false }
+    }
+}
+
+// trailing punctuation is almost always the sentence, not the URL ("see https://x.org.")
+const URL_TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '\'', '"', '>'];
+
+/// End of an http(s) URL starting at `i`, if there is one
+fn url_end(chars: &[char], i: usize) -> Option<usize> {
+    if i > 0 && chars[i - 1].is_alphanumeric() {
+        return None;
+    }
+    let head: String = chars[i..chars.len().min(i + 8)].iter().collect::<String>().to_ascii_lowercase();
+    let scheme = ["https://", "http://"].into_iter().find(|s| head.starts_with(s))?;
+    let start = i + scheme.len();
+    let mut end = start;
+    while end < chars.len() && !chars[end].is_whitespace() {
+        end += 1;
+    }
+    while end > start && URL_TRAILING.contains(&chars[end - 1]) {
+        end -= 1;
+    }
+    (end > start).then_some(end)
+}
+
+// a marker only opens at a word start and closes at a word end,
+// so snake_case_names and 2*3*4 stay as they are
+fn can_open(chars: &[char], i: usize) -> bool {
+    let before_ok = i == 0 || !chars[i - 1].is_alphanumeric();
+    let after_ok = chars.get(i + 1).is_some_and(|c| !c.is_whitespace());
+    before_ok && after_ok
+}
+
+fn can_close(chars: &[char], i: usize) -> bool {
+    let before_ok = i > 0 && !chars[i - 1].is_whitespace();
+    let after_ok = chars.get(i + 1).is_none_or(|c| !c.is_alphanumeric());
+    before_ok && after_ok
+}
+
+fn find_close(chars: &[char], from: usize, marker: char) -> Option<usize> {
+    (from..chars.len()).find(|&j| chars[j] == marker && (marker == '`' || can_close(chars, j)))
+}
+
+/// `*bold*`, `_italic_`, `code` and links for one line. Markers can't nest, unmatched ones stay literal.
+/// URLs are taken whole first so their underscores never turn into italics.
+pub fn parse_line(line: &str) -> Vec<Span> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if let Some(end) = url_end(&chars, i) {
+            if !plain.is_empty() {
+                spans.push(Span::plain(std::mem::take(&mut plain)));
+            }
+            spans.push(Span { link: true, ..Span::plain(chars[i..end].iter().collect()) });
+            i = end;
+            continue;
+        }
+
+        let c = chars[i];
+        let close = match c {
+            '`' => find_close(&chars, i + 1, '`').filter(|&j| j > i + 1),
+            '*' | '_' if can_open(&chars, i) => find_close(&chars, i + 1, c),
+            _ => None,
+        };
+        let Some(j) = close else {
+            plain.push(c);
+            i += 1;
+            continue;
+        };
+
+        if !plain.is_empty() {
+            spans.push(Span::plain(std::mem::take(&mut plain)));
+        }
+        spans.push(Span {
+            text: chars[i + 1..j].iter().collect(),
+            bold: c == '*',
+            italic: c == '_',
+            code: c == '`',
+            link: false,
+        });
+        i = j + 1;
+    }
+    if !plain.is_empty() || spans.is_empty() {
+        spans.push(Span::plain(plain));
+    }
+    spans
+}
+
+/// Lines of spans, None when there's no markup at all (plain rendering is cheaper and selectable)
+pub fn parse(text: &str) -> Option<Vec<Vec<Span>>> {
+    let lines: Vec<Vec<Span>> = text.split('\n').map(parse_line).collect();
+    let styled = lines.iter().flatten().any(|s| s.bold || s.italic || s.code || s.link);
+    styled.then_some(lines)
+}
@@ -0,0 +1,576 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::classes::Config;
+use crate::main_helpers::get_config_path;
+
+/// A received chat message on its way to the chat view.
+/// Only the sanitize stage rewrites `text`, the others just decorate it.
+#[derive(Debug, Clone)]
+pub struct IncomingMessage {
+    pub sender: String,
+    pub text: String,
+    pub lang: Option<String>,        // ISO 639-1 code once detected
+    pub translation: Option<String>, // filled by a Translator stage
+    pub mentioned: bool,             // @<my nickname> somewhere in the text
+    pub order: String,               // sort key in the chat view, see order_key
+}
+
+/// One post-processing step, stages run in the order they were added
+pub trait MessageStage: Send + Sync {
+    fn process(&self, msg: &mut IncomingMessage);
+}
+
+/// Offline translation hook, plug in anything that can turn `text` in `lang` into something readable
+pub trait Translator: Send + Sync {
+    fn translate(&self, text: &str, lang: &str) -> Option<String>;
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn MessageStage>>,
+}
+
+impl Pipeline {
+    pub fn with_stage(mut self, stage: impl MessageStage + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+    pub fn run(&self, sender: &str, text: &str) -> IncomingMessage {
+        let (text, stamp) = split_stamp(text);
+        // unstamped (phones, older builds): ordered as of now, i.e. after everything seen so far
+        let stamp = match stamp {
+            Some(remote) => { observe(remote); remote }
+            None => tick(),
+        };
+        let mut msg = IncomingMessage {
+            sender: sender.to_string(),
+            text: text.to_string(),
+            lang: None,
+            translation: None,
+            mentioned: false,
+            order: order_key(stamp, sender),
+        };
+        for stage in &self.stages {
+            stage.process(&mut msg);
+        }
+        msg
+    }
+}
+
+// ===================== ordering =====================
+// Every desktop message carries a hybrid logical clock stamp: wall clock milliseconds plus
+// a counter that moves the clock past anything we've already seen. A reply always sorts
+// after what it answers even when the two machines' clocks disagree, and everyone sorts
+// the same stamps the same way. The stamp rides at the end of the text as an OSC escape,
+// which the sanitizer of every build (older ones too) removes before showing the text.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hlc {
+    pub wall_ms: u64,
+    pub counter: u32,
+}
+
+static CLOCK: Mutex<Hlc> = Mutex::new(Hlc { wall_ms: 0, counter: 0 });
+
+const STAMP_START: &str = "\u{1B}]hlc;";
+const STAMP_END: char = '\u{07}';
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Advance the clock for something happening here (sending, an unstamped arrival)
+pub fn tick() -> Hlc {
+    let now = now_ms();
+    let mut clock = CLOCK.lock().unwrap();
+    *clock = if now > clock.wall_ms { Hlc { wall_ms: now, counter: 0 } } else { Hlc { wall_ms: clock.wall_ms, counter: clock.counter + 1 } };
+    *clock
+}
+
+/// Move the clock past a stamp we received
+pub fn observe(remote: Hlc) {
+    let now = now_ms();
+    let mut clock = CLOCK.lock().unwrap();
+    let wall_ms = now.max(clock.wall_ms).max(remote.wall_ms);
+    let counter = match (wall_ms == clock.wall_ms, wall_ms == remote.wall_ms) {
+        (true, true) => clock.counter.max(remote.counter) + 1,
+        (true, false) => clock.counter + 1,
+        (false, true) => remote.counter + 1,
+        (false, false) => 0,
+    };
+    *clock = Hlc { wall_ms, counter };
+}
+
+/// Outgoing text with a fresh stamp appended
+pub fn stamp_outgoing(text: &str) -> String {
+    let stamp = tick();
+    format!("{}{}{}.{}{}", text, STAMP_START, stamp.wall_ms, stamp.counter, STAMP_END)
+}
+
+/// Text without the stamp, and the stamp if there was one
+pub fn split_stamp(text: &str) -> (&str, Option<Hlc>) {
+    let Some(start) = text.rfind(STAMP_START) else { return (text, None); };
+    let stamp = text[start + STAMP_START.len()..]
+        .strip_suffix(STAMP_END)
+        .and_then(|s| s.split_once('.'))
+        .and_then(|(wall, counter)| Some(Hlc { wall_ms: wall.parse().ok()?, counter: counter.parse().ok()? }));
+    match stamp {
+        Some(stamp) => (&text[..start], Some(stamp)),
+        None => (text, None),
+    }
+}
+
+/// Sort key for the chat view, fixed width so plain string order is clock order.
+/// The sender breaks ties between two peers that stamped the same instant.
+pub fn order_key(stamp: Hlc, sender: &str) -> String {
+    format!("{:016}.{:010}.{}", stamp.wall_ms, stamp.counter, sender)
+}
+
+/// Where a message with `order` goes among the last rows: before every live message that
+/// sorts after it, but never above a row without a key (system lines, history)
+pub fn insert_index(len: usize, order_at: impl Fn(usize) -> String, order: &str) -> usize {
+    let mut index = len;
+    while index > 0 {
+        let previous = order_at(index - 1);
+        if previous.is_empty() || previous.as_str() <= order {
+            break;
+        }
+        index -= 1;
+    }
+    index
+}
+
+// ===================== sanitizing =====================
+// a peer controls every byte we show, so terminal escapes, bidi overrides and
+// invisible characters are removed before anything renders or gets stored
+
+// more stacked diacritics than this on one letter is "zalgo", not a language
+const MAX_COMBINING_MARKS: usize = 3;
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}')
+}
+
+// bidi overrides / isolates can reorder what the reader sees, the rest is invisible
+fn is_invisible_trick(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{180E}' | '\u{200B}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}' | '\u{2066}'..='\u{2069}' | '\u{FEFF}'
+    )
+}
+
+// ZWJ glues emoji sequences (👨‍👩‍👧) and ZWNJ is real spelling in Persian, keep them there
+fn joiner_allowed(c: char, prev: Option<char>) -> bool {
+    let Some(prev) = prev else { return false; };
+    match c {
+        '\u{200D}' => prev as u32 >= 0x1F000 || matches!(prev, '\u{2600}'..='\u{27BF}' | '\u{FE0F}'),
+        '\u{200C}' => prev.is_alphabetic() && !prev.is_ascii(),
+        _ => false,
+    }
+}
+
+/// Strip ANSI escapes, control characters (except newline / tab) and zero-width tricks
+pub fn sanitize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut marks = 0;
+
+    while let Some(c) = chars.next() {
+        if c == '\u{1B}' {
+            match chars.peek() {
+                // CSI: ESC [ params final-byte
+                Some('[') => {
+                    chars.next();
+                    for p in chars.by_ref() {
+                        if ('\u{40}'..='\u{7E}').contains(&p) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: ESC ] ... BEL or ESC \
+                Some(']') => {
+                    chars.next();
+                    while let Some(p) = chars.next() {
+                        if p == '\u{07}' || (p == '\u{1B}' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if c.is_control() && c != '\n' && c != '\t' {
+            continue;
+        }
+        if is_invisible_trick(c) || (matches!(c, '\u{200C}' | '\u{200D}') && !joiner_allowed(c, out.chars().last())) {
+            continue;
+        }
+        if is_combining_mark(c) {
+            marks += 1;
+            if marks > MAX_COMBINING_MARKS {
+                continue;
+            }
+        } else {
+            marks = 0;
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub struct SanitizeStage;
+
+impl MessageStage for SanitizeStage {
+    fn process(&self, msg: &mut IncomingMessage) {
+        msg.text = sanitize(&msg.text);
+    }
+}
+
+// ===================== mentions =====================
+
+/// True when `text` has @nickname as a whole word (case-insensitive, "@bob" doesn't match "@bobby")
+pub fn mentions(text: &str, nickname: &str) -> bool {
+    let nick = nickname.trim().to_lowercase();
+    if nick.is_empty() {
+        return false;
+    }
+    let lower = text.to_lowercase();
+    lower.match_indices('@').any(|(at, _)| {
+        let rest = &lower[at + 1..];
+        rest.starts_with(&nick) && !rest[nick.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+pub struct MentionStage {
+    nickname: String,
+}
+
+impl MessageStage for MentionStage {
+    fn process(&self, msg: &mut IncomingMessage) {
+        msg.mentioned = mentions(&msg.text, &self.nickname);
+    }
+}
+
+// ===================== language detection =====================
+
+pub struct LanguageTagStage;
+
+// short messages ("ok", "lol") are guessed wrong more often than not
+const MIN_DETECT_CHARS: usize = 6;
+
+impl MessageStage for LanguageTagStage {
+    fn process(&self, msg: &mut IncomingMessage) {
+        if msg.text.trim().chars().count() < MIN_DETECT_CHARS {
+            return;
+        }
+        if let Some(info) = whatlang::detect(&msg.text) {
+            if info.is_reliable() {
+                msg.lang = Some(iso_639_1(info.lang()).to_string());
+            }
+        }
+    }
+}
+
+// whatlang speaks ISO 639-3, the UI and glossary use the two letter codes
+fn iso_639_1(lang: whatlang::Lang) -> &'static str {
+    use whatlang::Lang::*;
+    match lang {
+        Ara => "ar",
+        Eng => "en",
+        Heb => "he",
+        Fra => "fr",
+        Deu => "de",
+        Spa => "es",
+        Tur => "tr",
+        Rus => "ru",
+        Ita => "it",
+        Por => "pt",
+        Nld => "nl",
+        Pes => "fa",
+        Urd => "ur",
+        Hin => "hi",
+        Cmn => "zh",
+        Jpn => "ja",
+        Kor => "ko",
+        other => other.code(),
+    }
+}
+
+// ===================== glossary translation =====================
+
+/// Word by word lookup in glossary.json next to the config:
+/// { "ar": { "مرحبا": "hello", ... }, "de": { ... } }
+/// Good enough for team jargon, not meant to be a real translator.
+pub struct GlossaryTranslator {
+    entries: HashMap<String, HashMap<String, String>>,
+}
+
+impl GlossaryTranslator {
+    pub fn load() -> Self {
+        let path = get_config_path().with_file_name("glossary.json");
+        let entries = File::open(path)
+            .ok()
+            .and_then(|f| serde_json::from_reader(f).ok())
+            .unwrap_or_default();
+        Self { entries }
+    }
+}
+
+impl Translator for GlossaryTranslator {
+    fn translate(&self, text: &str, lang: &str) -> Option<String> {
+        let words = self.entries.get(lang)?;
+        let mut hits = 0;
+        let out: Vec<String> = text
+            .split_whitespace()
+            .map(|w| {
+                let key = w.trim_matches(|c: char| c.is_ascii_punctuation()).to_lowercase();
+                match words.get(&key) {
+                    Some(t) => {
+                        hits += 1;
+                        t.clone()
+                    }
+                    None => w.to_string(),
+                }
+            })
+            .collect();
+        (hits > 0).then(|| out.join(" "))
+    }
+}
+
+pub struct TranslateStage {
+    translator: Box<dyn Translator>,
+    // messages already in this language are left alone
+    own_lang: String,
+}
+
+impl TranslateStage {
+    pub fn new(translator: impl Translator + 'static, own_lang: &str) -> Self {
+        Self { translator: Box::new(translator), own_lang: own_lang.to_string() }
+    }
+}
+
+impl MessageStage for TranslateStage {
+    fn process(&self, msg: &mut IncomingMessage) {
+        let Some(lang) = msg.lang.as_deref() else { return; };
+        if lang == self.own_lang {
+            return;
+        }
+        msg.translation = self.translator.translate(&msg.text, lang);
+    }
+}
+
+// ===================== global pipeline =====================
+
+static PIPELINE: OnceLock<RwLock<Pipeline>> = OnceLock::new();
+static NICKNAME: RwLock<String> = RwLock::new(String::new());
+
+// sanitizing is not optional, it runs first whatever the config says
+fn base_pipeline() -> Pipeline {
+    Pipeline::default().with_stage(SanitizeStage)
+}
+
+fn pipeline() -> &'static RwLock<Pipeline> {
+    PIPELINE.get_or_init(|| RwLock::new(base_pipeline()))
+}
+
+/// Rebuild the stages from the config (call again after toggling)
+pub fn configure(cfg: &Config) {
+    *NICKNAME.write().unwrap() = cfg.nickname.clone();
+    let mut p = base_pipeline().with_stage(MentionStage { nickname: cfg.nickname.clone() });
+    if cfg.language_tags {
+        p = p.with_stage(LanguageTagStage);
+        if cfg.glossary_translate {
+            p = p.with_stage(TranslateStage::new(GlossaryTranslator::load(), &cfg.own_language));
+        }
+    }
+    *pipeline().write().unwrap() = p;
+}
+
+/// Our @mention nickname, also sent along with our file offers
+pub fn nickname() -> String {
+    NICKNAME.read().unwrap().clone()
+}
+
+/// Mention check for rows rebuilt from history
+pub fn mentions_me(text: &str) -> bool {
+    mentions(text, &NICKNAME.read().unwrap())
+}
+
+pub fn process(sender: &str, text: &str) -> IncomingMessage {
+    pipeline().read().unwrap().run(sender, text)
+}
+
+// ===================== middleware =====================
+// Ordered steps every chat message goes through on its way in or out, a new cross-cutting
+// feature is one more stage here instead of one more if in the receiver or on_send_clicked.
+// Inbound runs on the receive thread and may drop the message (control text, duplicates,
+// floods) or act on it (sound cues). What's left goes to the append handler, where the stages
+// above decorate it, history stores it and the chat view renders it. Outbound runs before
+// the packets are built.
+
+/// A chat message in either direction. `peer` is None for our own.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    pub peer: Option<IpAddr>,
+    pub secure: bool, // sealed with the channel key
+    pub text: String,  // clock stamp still attached on the way in
+}
+
+pub enum Flow {
+    Continue,
+    Drop,
+}
+
+pub trait Middleware: Send + Sync {
+    fn inbound(&self, _msg: &mut Envelope) -> Flow {
+        Flow::Continue
+    }
+    fn outbound(&self, _msg: &mut Envelope) -> Flow {
+        Flow::Continue
+    }
+}
+
+// typed commands a phone sends as text, never meant as chat
+const COMMAND_ECHOES: [&str; 9] = ["/exit", "/clear", "/disconnect", "/clearfiles", "/clearall", "/webjoin", "/webstop", "/restart", "/downloads"];
+
+/// Protocol words and command echoes that arrive as text
+pub struct ControlText;
+
+impl Middleware for ControlText {
+    fn inbound(&self, msg: &mut Envelope) -> Flow {
+        let (text, _) = split_stamp(&msg.text);
+        let control = if msg.secure {
+            COMMAND_ECHOES.iter().any(|c| text.eq_ignore_ascii_case(c))
+        } else {
+            text.starts_with('/') || text.starts_with("MANCH") || text.starts_with("REQA")
+        };
+        if control { Flow::Drop } else { Flow::Continue }
+    }
+}
+
+// the same message twice this close together came over two paths (multicast + broadcast)
+const DEDUPE_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+pub struct Dedupe {
+    seen: Mutex<VecDeque<(u64, Instant)>>,
+}
+
+impl Middleware for Dedupe {
+    fn inbound(&self, msg: &mut Envelope) -> Flow {
+        let mut hasher = DefaultHasher::new();
+        (msg.peer, &msg.text).hash(&mut hasher);
+        let hash = hasher.finish();
+        let mut seen = self.seen.lock().unwrap();
+        while seen.front().is_some_and(|(_, at)| at.elapsed() > DEDUPE_WINDOW) {
+            seen.pop_front();
+        }
+        if seen.iter().any(|(h, _)| *h == hash) {
+            return Flow::Drop;
+        }
+        seen.push_back((hash, Instant::now()));
+        Flow::Continue
+    }
+}
+
+// per peer: FLOOD_BURST messages at once, then one per FLOOD_REFILL
+const FLOOD_BURST: f64 = 10.0;
+const FLOOD_REFILL: Duration = Duration::from_secs(1);
+
+/// Keeps one peer from burying the chat
+#[derive(Default)]
+pub struct FloodLimit {
+    peers: Mutex<HashMap<IpAddr, (f64, Instant)>>,
+}
+
+impl Middleware for FloodLimit {
+    fn inbound(&self, msg: &mut Envelope) -> Flow {
+        let Some(peer) = msg.peer else { return Flow::Continue; };
+        let mut peers = self.peers.lock().unwrap();
+        let (tokens, last) = peers.entry(peer).or_insert((FLOOD_BURST, Instant::now()));
+        *tokens = (*tokens + last.elapsed().as_secs_f64() / FLOOD_REFILL.as_secs_f64()).min(FLOOD_BURST);
+        *last = Instant::now();
+        if *tokens < 1.0 {
+            return Flow::Drop;
+        }
+        *tokens -= 1.0;
+        Flow::Continue
+    }
+}
+
+/// Muted peers: held back and counted in their collapsed row, see mute.rs
+pub struct Mute;
+
+impl Middleware for Mute {
+    fn inbound(&self, msg: &mut Envelope) -> Flow {
+        let Some(peer) = msg.peer else { return Flow::Continue; };
+        if crate::mute::hold(peer, &msg.text) { Flow::Drop } else { Flow::Continue }
+    }
+}
+
+/// "ping" and friends play their sound, @mentions chime later from the append handler
+pub struct SoundCues;
+
+impl Middleware for SoundCues {
+    fn inbound(&self, msg: &mut Envelope) -> Flow {
+        // opening the audio device can block, keep it off the receive thread
+        if let Some(play) = crate::main_helpers::peer_message_sound(&msg.text) {
+            std::thread::spawn(play);
+        }
+        Flow::Continue
+    }
+}
+
+/// Peers sanitize too, but don't send what they'd have to strip. Nothing left = nothing sent.
+pub struct SanitizeOutgoing;
+
+impl Middleware for SanitizeOutgoing {
+    fn outbound(&self, msg: &mut Envelope) -> Flow {
+        msg.text = sanitize(&msg.text).trim().to_string();
+        if msg.text.is_empty() { Flow::Drop } else { Flow::Continue }
+    }
+}
+
+static MIDDLEWARE: OnceLock<Vec<Box<dyn Middleware>>> = OnceLock::new();
+
+fn middleware() -> &'static [Box<dyn Middleware>] {
+    MIDDLEWARE.get_or_init(|| {
+        vec![
+            Box::new(ControlText),
+            Box::new(Dedupe::default()),
+            Box::new(FloodLimit::default()),
+            Box::new(Mute),
+            Box::new(SoundCues),
+            Box::new(SanitizeOutgoing),
+        ]
+    })
+}
+
+/// A peer's message off the wire, None when a stage dropped it
+pub fn inbound(peer: IpAddr, secure: bool, text: String) -> Option<String> {
+    let mut msg = Envelope { peer: Some(peer), secure, text };
+    for stage in middleware() {
+        if let Flow::Drop = stage.inbound(&mut msg) {
+            return None;
+        }
+    }
+    Some(msg.text)
+}
+
+/// What we're about to send, None when a stage dropped it
+pub fn outbound(secure: bool, text: &str) -> Option<String> {
+    let mut msg = Envelope { peer: None, secure, text: text.to_string() };
+    for stage in middleware() {
+        if let Flow::Drop = stage.outbound(&mut msg) {
+            return None;
+        }
+    }
+    Some(msg.text)
+}
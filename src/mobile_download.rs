@@ -1,23 +1,22 @@
-use std::net::IpAddr;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
-use semaphore::SemaphoreGuard;
 use slint::Weak;
 
-use crate::{main_helpers, AppWindow, file_transfer_protocol::FileOffer};
+use crate::{download_queue, file_transfer_protocol::{self, FileOffer}, main_helpers, net_runtime, transfers, AppWindow};
 
 pub fn spawn_mobile_download(
-    sender_ip: IpAddr,
+    sender_addr: SocketAddr,
     offer: FileOffer,
     offer_id_hex: String,
     save_path: PathBuf,
     weak_ui: Weak<AppWindow>,
-    permit: SemaphoreGuard<()>,
+    permit: download_queue::Slot,
 ) {
-    std::thread::spawn(move || {
+    net_runtime::spawn(async move {
         let _permit = permit; // ✅ hold slot for entire download
 
-        //println!( "[MOBILE-DL] starting: sender_ip={} tcp_port={} offer_id_hex={} size={}", sender_ip, offer.tcp_port, offer_id_hex, offer.size );
+        //println!( "[MOBILE-DL] starting: sender={} tcp_port={} offer_id_hex={} size={}", sender_addr, offer.tcp_port, offer_id_hex, offer.size );
 
         // --- 0% immediately ---
         {
@@ -37,11 +36,13 @@ pub fn spawn_mobile_download(
         let mut last_bucket: u32 = 999;
         let mut next_log_at: u64 = 5 * 1024 * 1024; // log every ~5MB
 
-        let res = crate::tcp_file_client::download_offer_mobile(
-            sender_ip,
+        let partial = transfers::partial_path(&save_path, false);
+        let res = transfers::download(&offer_id_hex, partial, crate::tcp_file_client::download_offer_mobile(
+            sender_addr,
             offer.tcp_port,
             &offer_id_hex,
-            save_path,
+            crate::file_transfer_protocol::mobile_offer_has_checksum(&offer_id_hex),
+            save_path.clone(),
             move |done, total| {
                 // Flutter path passes total=0 -> substitute expected total
                 let total = if total == 0 { total_expected } else { total };
@@ -68,7 +69,8 @@ pub fn spawn_mobile_download(
                     }
                 });
             },
-        );
+        )).await;
+        crate::records::transfer(false, Some(sender_addr.ip()), &offer.name, offer.size, crate::records::outcome(&res));
 
         // --- finish UI ---
         let weak_ui_done = weak_ui.clone();
@@ -81,8 +83,14 @@ pub fn spawn_mobile_download(
                     Ok(_) => {
                         //println!("[MOBILE-DL] finished OK: {}", id);
                         main_helpers::set_offer_progress_text(&app, &id, false, "100%");
+                        main_helpers::set_offer_saved(&app, &id, save_path);
                         app.invoke_show_temp_message(format!("✅ Download complete: {}", name).into());
                     }
+                    Err(e) if transfers::is_cancelled(&e) => {}
+                    Err(e) if file_transfer_protocol::is_corrupt(&e) => {
+                        main_helpers::set_offer_progress_text(&app, &id, false, "corrupt ✗");
+                        app.invoke_show_temp_message(format!("❌ {} didn't arrive intact, the copy was discarded", name).into());
+                    }
                     Err(e) => {
                         //println!("[MOBILE-DL] finished ERR: {} -> {}", id, e);
                         main_helpers::set_offer_progress_text(&app, &id, false, "ERR");
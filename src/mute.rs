@@ -0,0 +1,156 @@
+// Timed mute of one peer ("Mute for 1 hour" on a message, /mute). Their messages don't ring,
+// notify or fill the chat: the inbound middleware holds them back and one collapsed
+// "🔇 12 muted messages from Sam" row counts them. Right-click it to show them, they land in
+// their original order. The watcher ends mutes when their time is up.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use slint::{Model, VecModel};
+
+use crate::{main_helpers, peers, AppWindow, ChatMessageItem};
+
+pub const DEFAULT: Duration = Duration::from_secs(60 * 60);
+const CHECK_EVERY: Duration = Duration::from_secs(10);
+
+struct Muted {
+    until: Option<Instant>, // None once it ran out, the held messages wait to be shown
+    held: Vec<String>,      // message text, clock stamp still attached
+}
+
+static MUTED: Mutex<BTreeMap<IpAddr, Muted>> = Mutex::new(BTreeMap::new());
+
+pub fn mute(peer: IpAddr, duration: Duration) {
+    let mut muted = MUTED.lock().unwrap();
+    let entry = muted.entry(peer).or_insert(Muted { until: None, held: Vec::new() });
+    entry.until = Some(Instant::now() + duration);
+}
+
+/// End a mute early, false when the peer wasn't muted
+pub fn unmute(peer: IpAddr) -> bool {
+    let mut muted = MUTED.lock().unwrap();
+    let Some(entry) = muted.get_mut(&peer) else { return false; };
+    let was = entry.until.take().is_some();
+    if entry.held.is_empty() {
+        muted.remove(&peer);
+    }
+    was
+}
+
+/// Peers still muted and how long for, for /mute without arguments
+pub fn list() -> Vec<(IpAddr, Duration)> {
+    let now = Instant::now();
+    MUTED
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|(ip, m)| m.until.filter(|u| *u > now).map(|u| (*ip, u - now)))
+        .collect()
+}
+
+/// Muted right now, for things that skip the pipeline (snippets)
+pub fn is_muted(peer: IpAddr) -> bool {
+    MUTED.lock().unwrap().get(&peer).is_some_and(|m| m.until.is_some_and(|u| u > Instant::now()))
+}
+
+/// Inbound middleware: keep a muted peer's message back, false when they aren't muted
+pub fn hold(peer: IpAddr, text: &str) -> bool {
+    let count = {
+        let mut muted = MUTED.lock().unwrap();
+        let Some(entry) = muted.get_mut(&peer) else { return false; };
+        if !entry.until.is_some_and(|u| u > Instant::now()) {
+            return false;
+        }
+        entry.held.push(text.to_string());
+        entry.held.len()
+    };
+    if let Some(weak) = main_helpers::app_handle() {
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak.upgrade() {
+                set_row(&app, peer, Some(count));
+            }
+        });
+    }
+    true
+}
+
+fn row_text(peer: IpAddr, count: usize) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    format!("🔇 {} muted message{} from {}", count, plural, peers::label(peer, None))
+}
+
+// the collapsed row of a peer: Some(count) creates or updates it, None removes it
+fn set_row(app: &AppWindow, peer: IpAddr, count: Option<usize>) {
+    let model_rc = app.get_messages();
+    let Some(vec) = model_rc.as_any().downcast_ref::<VecModel<ChatMessageItem>>() else { return; };
+    let key = peer.to_string();
+    let index = (0..vec.row_count()).find(|&i| vec.row_data(i).is_some_and(|r| r.muted_from.as_str() == key));
+    match (index, count) {
+        (Some(i), Some(count)) => {
+            let mut row = vec.row_data(i).unwrap();
+            row.text = row_text(peer, count).into();
+            vec.set_row_data(i, row);
+        }
+        (None, Some(count)) => {
+            let mut row = main_helpers::system_row(row_text(peer, count));
+            row.muted_from = key.into();
+            vec.push(row);
+            while vec.row_count() > main_helpers::chat_row_limit() {
+                vec.remove(0);
+            }
+        }
+        (Some(i), None) => {
+            vec.remove(i);
+        }
+        (None, None) => {}
+    }
+}
+
+/// "Show muted messages" on the collapsed row: it opens up into the messages it held
+pub fn show(app: &AppWindow, peer: IpAddr) {
+    let held = {
+        let mut muted = MUTED.lock().unwrap();
+        let Some(entry) = muted.get_mut(&peer) else { return; };
+        let held = std::mem::take(&mut entry.held);
+        if entry.until.is_none() {
+            muted.remove(&peer);
+        }
+        held
+    };
+    set_row(app, peer, None);
+    for text in held {
+        app.invoke_append_peer_message(peer.to_string().into(), text.into());
+    }
+}
+
+/// End mutes whose time is up
+pub fn start(ui_weak: slint::Weak<AppWindow>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CHECK_EVERY);
+        let ended: Vec<IpAddr> = {
+            let now = Instant::now();
+            let mut muted = MUTED.lock().unwrap();
+            let ended: Vec<IpAddr> = muted.iter().filter(|(_, m)| m.until.is_some_and(|u| u <= now)).map(|(ip, _)| *ip).collect();
+            for ip in &ended {
+                if let Some(entry) = muted.get_mut(ip) {
+                    entry.until = None;
+                }
+            }
+            muted.retain(|_, m| m.until.is_some() || !m.held.is_empty());
+            ended
+        };
+        if ended.is_empty() {
+            continue;
+        }
+        let weak = ui_weak.clone();
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak.upgrade() {
+                for ip in ended {
+                    app.invoke_show_temp_message(format!("🔔 {} is no longer muted", peers::label(ip, None)).into());
+                }
+            }
+        });
+    });
+}
@@ -0,0 +1,26 @@
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::classes::InterfacesInfo;
+use crate::main_helpers::collect_interfaces;
+
+const POLL: Duration = Duration::from_secs(5);
+
+/// Polls the adapter list and calls `on_change` with the new list whenever it differs
+/// (Wi-Fi dropped, cable plugged in, new DHCP lease...).
+pub fn start_interface_watcher( running: Arc<AtomicBool>, on_change: impl Fn(Vec<InterfacesInfo>) + Send + 'static, ) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut last = collect_interfaces();
+
+        while running.load(Ordering::Relaxed) {
+            thread::sleep(POLL);
+            let now = collect_interfaces();
+            if now != last {
+                //println!("[NET] interfaces changed: {:?}", now);
+                last = now.clone();
+                on_change(now);
+            }
+        }
+    })
+}
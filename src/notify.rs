@@ -0,0 +1,100 @@
+// Desktop notifications for messages and file offers while the window is not focused
+// (including while it sits in the tray). Clicking one brings the window back.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use slint::winit_030::{winit, EventResult, WinitWindowAccessor};
+use slint::ComponentHandle;
+
+use crate::{main_helpers, AppWindow};
+
+static FOCUSED: AtomicBool = AtomicBool::new(true);
+
+/// Follow the window's focus, call once at startup
+pub fn track_focus(app: &AppWindow) {
+    app.window().on_winit_window_event(|_, event| {
+        if let winit::event::WindowEvent::Focused(focused) = event {
+            FOCUSED.store(*focused, Ordering::Relaxed);
+        }
+        EventResult::Propagate
+    });
+}
+
+/// Raise the window above the others, used when coming back from the tray / a notification
+pub fn focus_window(app: &AppWindow) {
+    app.window().with_winit_window(|w| {
+        w.set_minimized(false);
+        w.focus_window();
+    });
+}
+
+pub fn message(sender: &str, text: &str, mentioned: bool) {
+    if main_helpers::channel_muted() || (main_helpers::mentions_only() && !mentioned) {
+        return;
+    }
+    show(format!("💬 {}", sender), text.to_string());
+}
+
+pub fn file_offer(sender: &str, name: &str, size_text: &str) {
+    if main_helpers::mentions_only() || main_helpers::channel_muted() {
+        return;
+    }
+    show(format!("📁 {} offers a file", sender), format!("{} ({})", name, size_text));
+}
+
+fn show(title: String, body: String) {
+    if !main_helpers::notifications_enabled() || (FOCUSED.load(Ordering::Relaxed) && !crate::tray::is_hidden()) {
+        return;
+    }
+    let Some(weak) = main_helpers::app_handle() else { return; };
+    // toast APIs block for a moment, keep them off the UI / receive threads
+    std::thread::spawn(move || imp::show(&title, &body, weak));
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use tauri_winrt_notification::Toast;
+
+    use crate::AppWindow;
+
+    pub fn show(title: &str, body: &str, weak: slint::Weak<AppWindow>) {
+        // an unpackaged app has no AppUserModelID of its own, borrow PowerShell's like everyone does
+        let _ = Toast::new(Toast::POWERSHELL_APP_ID)
+            .title(title)
+            .text1(body)
+            .sound(None) // the app plays its own sounds
+            .on_activated(move |_| {
+                crate::tray::restore_later(weak.clone());
+                Ok(())
+            })
+            .show();
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use crate::AppWindow;
+
+    pub fn show(title: &str, body: &str, weak: slint::Weak<AppWindow>) {
+        let Ok(handle) = notify_rust::Notification::new()
+            .appname("LanChGo")
+            .summary(title)
+            .body(body)
+            .action("default", "Open")
+            .show()
+        else {
+            return;
+        };
+        // blocks until the notification is clicked or closed, we're on our own thread
+        handle.wait_for_action(|action| {
+            if action == "default" {
+                crate::tray::restore_later(weak);
+            }
+        });
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+mod imp {
+    pub fn show(_title: &str, _body: &str, _weak: slint::Weak<crate::AppWindow>) {}
+}
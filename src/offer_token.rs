@@ -0,0 +1,124 @@
+// Offers as text, magnet-style, for tools that only carry text (a ticket, a mail, another chat):
+// "lanchgo-offer:<offer id hex>:<ip>:<tcp port>:<size>?dn=<name>", IPv6 in [brackets], the
+// name percent-encoded and optional. Ctrl+V of one in the input adds the offer's row to the files
+// panel as if its FOFT had arrived, the download then goes to that address like any other.
+
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use crate::file_transfer_protocol::{self, FileOffer, OfferKind, RemoteWindowsOfferRegistry, FILE_PROTOCOL_VERSION};
+use crate::{peers, thumbnails, AppWindow, FileOfferItem};
+
+pub const PREFIX: &str = "lanchgo-offer:";
+
+pub struct Token {
+    pub offer: FileOffer,
+    pub ip: IpAddr,
+}
+
+fn encode_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{:02X}", b);
+        }
+    }
+    out
+}
+
+fn decode_name(encoded: &str) -> Option<String> {
+    let bytes = encoded.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// The token for an offer `ip` serves. Folders have none, the token doesn't say what kind it is
+pub fn encode(offer: &FileOffer, ip: IpAddr) -> String {
+    let host = match ip {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("[{}]", v6),
+    };
+    format!(
+        "{}{}:{}:{}:{}?dn={}",
+        PREFIX,
+        file_transfer_protocol::offer_id_to_hex(&offer.offer_id),
+        host,
+        offer.tcp_port,
+        offer.size,
+        encode_name(&offer.name)
+    )
+}
+
+/// `text` is exactly one token (surrounding whitespace aside)
+pub fn parse(text: &str) -> Option<Token> {
+    let rest = text.trim().strip_prefix(PREFIX)?;
+    let (fields, name) = match rest.split_once("?dn=") {
+        Some((fields, name)) => (fields, Some(decode_name(name)?)),
+        None => (rest, None),
+    };
+    let (id_hex, rest) = fields.split_once(':')?;
+    if !id_hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let offer_id = file_transfer_protocol::hex_to_offer_id(id_hex)?;
+    let id_hex = file_transfer_protocol::offer_id_to_hex(&offer_id);
+    let (host, rest) = match rest.strip_prefix('[') {
+        Some(v6) => {
+            let (host, rest) = v6.split_once(']')?;
+            (host, rest.strip_prefix(':')?)
+        }
+        None => rest.split_once(':')?,
+    };
+    let ip: IpAddr = host.parse().ok()?;
+    let (port, size) = rest.split_once(':')?;
+    let tcp_port: u16 = port.parse().ok().filter(|p| *p != 0)?;
+    let size: u64 = size.parse().ok()?;
+    // no name: the start of the id, so the saved file can still be told apart
+    let name = name.filter(|n| !n.trim().is_empty()).unwrap_or_else(|| format!("lanchgo-{}", &id_hex[..8]));
+    let offer = FileOffer { offer_id, name, size, kind: OfferKind::SingleFile, protocol_version: FILE_PROTOCOL_VERSION, tcp_port, expires_at: None };
+    Some(Token { offer, ip })
+}
+
+/// The token's row in the files panel, registered like a FOFT from its ip. `udp_port` is where
+/// "download later" re-requests go. Returns the toast.
+pub fn materialize(app: &AppWindow, remote: &Arc<Mutex<RemoteWindowsOfferRegistry>>, token: Token, udp_port: u16) -> String {
+    let id_hex = file_transfer_protocol::offer_id_to_hex(&token.offer.offer_id);
+    let sender_addr = SocketAddr::new(token.ip, udp_port);
+    {
+        let mut remote = remote.lock().unwrap();
+        if remote.contains_key(&id_hex) {
+            return format!("🔗 {} is already in the files panel", token.offer.name);
+        }
+        remote.insert(id_hex.clone(), (sender_addr, token.offer.clone()));
+    }
+    thumbnails::request(app.as_weak(), sender_addr, &token.offer);
+    app.invoke_add_file_offer(FileOfferItem {
+        offer_id: id_hex.into(),
+        name: file_transfer_protocol::truncate_name(&token.offer.name, 16).into(),
+        size_text: file_transfer_protocol::human_size(token.offer.size).into(),
+        is_downloading: false,
+        progress_text: "".into(),
+        is_mobile: false,
+        is_own: false,
+        sender: peers::label(token.ip, None).into(),
+        collapsed: false,
+        thumbnail: Default::default(),
+        saved: false,
+        queued: 0,
+    });
+    format!("🔗 {} added to the files panel", token.offer.name)
+}
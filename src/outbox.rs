@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::Mutex;
+
+// beyond this the oldest queued message is dropped, it's a chat not a mail server
+const MAX_QUEUED: usize = 50;
+
+/// A message that couldn't go out, with every packet it needs (ENCM + MENCM etc.)
+struct QueuedMessage {
+    text: String,
+    packets: Vec<Vec<u8>>,
+    next: usize, // packets before this one already went out
+}
+
+/// Messages waiting for the network to come back, sent in the order they were typed
+#[derive(Default)]
+pub struct Outbox {
+    queue: Mutex<VecDeque<QueuedMessage>>,
+}
+
+impl Outbox {
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+
+    /// Returns the text of a message dropped to make room, if any
+    pub fn push(&self, text: &str, packets: Vec<Vec<u8>>) -> Option<String> {
+        let mut queue = self.queue.lock().unwrap();
+        let dropped = if queue.len() >= MAX_QUEUED { queue.pop_front().map(|m| m.text) } else { None };
+        queue.push_back(QueuedMessage { text: text.to_string(), packets, next: 0 });
+        dropped
+    }
+
+    /// Send as much as possible, stops at the first failure. Returns the texts that went out.
+    pub fn flush(&self, mut send: impl FnMut(&[u8]) -> io::Result<()>) -> Vec<String> {
+        let mut queue = self.queue.lock().unwrap();
+        let mut sent = Vec::new();
+
+        while let Some(msg) = queue.front_mut() {
+            while msg.next < msg.packets.len() {
+                if send(&msg.packets[msg.next]).is_err() {
+                    return sent;
+                }
+                msg.next += 1;
+            }
+            if let Some(done) = queue.pop_front() {
+                sent.push(done.text);
+            }
+        }
+        sent
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::contacts;
+use crate::discovery::DiscoveredPeer;
+
+// a peer we only know from its chat traffic counts for this long after its last packet
+const HEARD_TTL: Duration = Duration::from_secs(5 * 60);
+
+// Optional features a desktop peer understands, sent as a hex bitfield in its mDNS TXT record.
+// For a peer without a bit we fall back instead of sending it something it can't handle.
+pub const CAP_FRAGMENTS: u32 = 1 << 0; // puts FRAG packets back together, else long messages go as a file
+pub const CAP_THUMBNAILS: u32 = 1 << 1; // answers FTHB, else its image offers aren't asked for a preview
+pub const CAP_PUSH: u32 = 1 << 2; // takes DPUSH, else /push refuses before picking files
+pub const CAP_SNIPPETS: u32 = 1 << 3; // fetches SNIP snippets (FSNP), else a snippet goes as a file
+pub const OWN_CAPS: u32 = CAP_FRAGMENTS | CAP_THUMBNAILS | CAP_PUSH | CAP_SNIPPETS;
+// registered over mDNS but no "caps": an older desktop build, FRAG is older than mDNS
+const LEGACY_CAPS: u32 = CAP_FRAGMENTS;
+
+/// Everyone we currently believe is on the LAN: mDNS registrations plus
+/// addresses we got packets from (phones and older builds don't register over mDNS)
+#[derive(Default)]
+struct PeerRegistry {
+    discovered: HashMap<String, DiscoveredPeer>, // by mDNS fullname
+    heard: HashMap<IpAddr, Instant>,
+}
+
+static PEERS: OnceLock<Mutex<PeerRegistry>> = OnceLock::new();
+
+fn registry() -> &'static Mutex<PeerRegistry> {
+    PEERS.get_or_init(|| Mutex::new(PeerRegistry::default()))
+}
+
+/// Store / update an mDNS peer, returns its previous hosting flag (None = new peer)
+pub fn upsert_discovered(peer: DiscoveredPeer) -> Option<bool> {
+    registry().lock().unwrap().discovered.insert(peer.fullname.clone(), peer).map(|old| old.hosting)
+}
+
+pub fn remove_discovered(fullname: &str) {
+    registry().lock().unwrap().discovered.remove(fullname);
+}
+
+/// Called for every packet from someone else
+pub fn note_heard(ip: IpAddr) {
+    registry().lock().unwrap().heard.insert(ip, Instant::now());
+    crate::records::peer_seen(ip);
+}
+
+/// What to call a peer in the UI: the nickname it sent ("Sam (laptop)" once we know the
+/// device from mDNS), its mDNS name, or just the address
+pub fn label(ip: IpAddr, nickname: Option<String>) -> String {
+    let reg = registry().lock().unwrap();
+    let peer = reg.discovered.values().find(|p| p.addresses.contains(&ip));
+    match (nickname, peer) {
+        (Some(nick), Some(peer)) => match &peer.device {
+            Some(device) if !device.eq_ignore_ascii_case(&nick) => format!("{} ({})", nick, device),
+            _ => nick,
+        },
+        (Some(nick), None) => nick,
+        (None, Some(peer)) => peer.display_name.clone(),
+        (None, None) => ip.to_string(),
+    }
+}
+
+fn caps_of(peer: &DiscoveredPeer) -> u32 {
+    peer.caps.unwrap_or(LEGACY_CAPS)
+}
+
+/// Whether the peer at `ip` can take `cap`. Phones and peers we only heard from don't tell,
+/// they keep getting what they always got.
+pub fn supports(ip: IpAddr, cap: u32) -> bool {
+    let reg = registry().lock().unwrap();
+    reg.discovered.values().find(|p| p.addresses.contains(&ip)).is_none_or(|p| caps_of(p) & cap != 0)
+}
+
+/// The file server port of the peer at `ip`, for connections that don't follow an offer (/push)
+pub fn file_port(ip: IpAddr) -> u16 {
+    let reg = registry().lock().unwrap();
+    reg.discovered
+        .values()
+        .find(|p| p.addresses.contains(&ip))
+        .and_then(|p| p.tcp_port)
+        .unwrap_or(crate::file_transfer_protocol::DEFAULT_TCP_PORT)
+}
+
+/// Names of the peers on the LAN that can't take `cap`, for broadcasts that reach everyone
+pub fn lacking(cap: u32) -> Vec<String> {
+    let reg = registry().lock().unwrap();
+    reg.discovered.values().filter(|p| caps_of(p) & cap == 0).map(|p| p.nickname.clone().unwrap_or_else(|| p.display_name.clone())).collect()
+}
+
+/// Registered over mDNS or heard from within HEARD_TTL
+pub fn is_online(ip: IpAddr) -> bool {
+    let reg = registry().lock().unwrap();
+    reg.discovered.values().any(|p| p.addresses.contains(&ip))
+        || reg.heard.get(&ip).is_some_and(|at| at.elapsed() < HEARD_TTL)
+}
+
+/// Every address we know a peer by, used to score adapters
+pub fn addresses() -> Vec<IpAddr> {
+    let reg = registry().lock().unwrap();
+    reg.discovered
+        .values()
+        .flat_map(|p| p.addresses.iter().copied())
+        .chain(reg.heard.keys().copied())
+        .collect()
+}
+
+/// Every name the LAN shows us (mDNS names, hostnames, nicknames, devices), for redaction
+pub fn names() -> Vec<String> {
+    let reg = registry().lock().unwrap();
+    reg.discovered
+        .values()
+        .flat_map(|p| [Some(p.display_name.clone()), Some(p.hostname.clone()), p.nickname.clone(), p.device.clone()])
+        .flatten()
+        .collect()
+}
+
+/// mDNS peers plus recently heard addresses that don't belong to one of them
+pub fn count() -> usize {
+    let mut reg = registry().lock().unwrap();
+    reg.heard.retain(|_, at| at.elapsed() < HEARD_TTL);
+
+    let reg = &*reg;
+    let heard_only = reg
+        .heard
+        .keys()
+        .filter(|ip| !reg.discovered.values().any(|p| p.addresses.contains(ip)))
+        .count();
+    reg.discovered.len() + heard_only
+}
+
+/// What /whois shows about one peer
+pub struct PeerDetails {
+    pub name: String,
+    pub addresses: Vec<IpAddr>,
+    pub port: Option<u16>, // UDP port from mDNS, None when we only know it from traffic
+    pub hostname: Option<String>,
+    pub version: Option<String>,
+    pub hosting: Option<bool>, // None when we only know it from traffic
+    pub last_seen: Option<Duration>,
+    pub nickname: Option<String>,
+    pub device: Option<String>,
+    pub identity: Option<String>, // public key hex from mDNS, see contacts
+    pub caps: Option<u32>, // CAP_* it understands, None when we only know it from traffic
+}
+
+fn name_matches(peer: &DiscoveredPeer, query: &str) -> bool {
+    peer.display_name.to_ascii_lowercase().starts_with(query)
+        || peer.hostname.to_ascii_lowercase().starts_with(query)
+        || peer.nickname.as_ref().is_some_and(|n| n.to_ascii_lowercase().starts_with(query))
+        || peer.device.as_ref().is_some_and(|d| d.to_ascii_lowercase().starts_with(query))
+}
+
+/// Look a peer up by IP, by (the start of) its mDNS name or nickname, or by a contact's nickname
+pub fn whois(query: &str) -> Option<PeerDetails> {
+    let reg = registry().lock().unwrap();
+    let mut ip = query.parse::<IpAddr>().ok();
+    let query = query.trim_start_matches('@').to_ascii_lowercase();
+
+    // a contact we know by name, wherever it was last seen
+    let mut contact = None;
+    if ip.is_none() && !reg.discovered.values().any(|p| name_matches(p, &query)) {
+        contact = contacts::find(&query);
+        ip = contact.as_ref().and_then(|c| c.last_ip.parse().ok());
+    }
+
+    let found = reg.discovered.values().find(|p| match ip {
+        Some(ip) => p.addresses.contains(&ip),
+        None => name_matches(p, &query),
+    });
+
+    if let Some(peer) = found {
+        // whichever is newer, the mDNS answer or the last packet from one of its addresses
+        let heard = peer.addresses.iter().filter_map(|a| reg.heard.get(a)).map(|at| at.elapsed()).min();
+        let resolved = peer.resolved_at.elapsed();
+        return Some(PeerDetails {
+            name: peer.display_name.clone(),
+            addresses: peer.addresses.clone(),
+            port: Some(peer.port),
+            hostname: Some(peer.hostname.trim_end_matches('.').to_string()),
+            version: peer.version.clone(),
+            hosting: Some(peer.hosting),
+            last_seen: Some(heard.map_or(resolved, |h| h.min(resolved))),
+            nickname: peer.nickname.clone(),
+            device: peer.device.clone(),
+            identity: peer.identity.clone(),
+            caps: Some(caps_of(peer)),
+        });
+    }
+
+    // phones and older builds: all we have is the address and when it last spoke
+    let ip = ip?;
+    let at = reg.heard.get(&ip)?;
+    Some(PeerDetails {
+        name: ip.to_string(),
+        addresses: vec![ip],
+        port: None,
+        hostname: None,
+        version: None,
+        hosting: None,
+        last_seen: Some(at.elapsed()),
+        nickname: contact.as_ref().map(|c| c.nickname.clone()),
+        device: None,
+        identity: contact.map(|c| c.key),
+        caps: None,
+    })
+}
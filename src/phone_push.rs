@@ -0,0 +1,344 @@
+// Files a phone or another desktop pushes to us (MPUSH / DPUSH), the other way round from an
+// offer where we pull. The push shows up in the offers panel like any mobile offer with an
+// accept / deny prompt, clicking the row accepts too; small files from trusted peers are taken
+// right away (see auto_download). Nothing is read from the sender before the user said yes.
+// Desktops push with /push (tcp_file_client::push_file).
+//
+// MPUSH <size> <sha256 hex or -> <file name>\n   from the phone (DPUSH from a desktop)
+// OK\n | NO\n                                    accepted / declined or timed out
+// <size> raw bytes
+// DONE\n | ERR\n                                 saved / checksum or write failed
+//
+// A phone paired with /pair sends "MPUSHE <device id>\n" instead and is accepted without asking.
+// Everything after that line is sealed with the pairing key as len(u32) + bincode(SecureMessage)
+// (sealed_stream::read_frame):
+// first the header "<size> <sha256 hex> <file name>" (the checksum isn't optional here) under
+// HEADER_AAD || device id, we answer "OK <offer id hex>\n", then the file as chunks under
+// offer id || chunk index (u64 LE) from 0, len 0 ends the file. A chunk can't be dropped,
+// reordered or replayed into another push without failing to open.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::classes::Config;
+use crate::file_transfer_protocol::{self, build_unique_download_path};
+use crate::{auto_download, bandwidth, main_helpers, notify, pairing, peers, rate_limit, sealed_stream, secure_channel_code, FileOfferItem};
+
+pub const MPUSH_MAGIC: &[u8; 5] = b"MPUSH";
+pub const DPUSH_MAGIC: &[u8; 5] = b"DPUSH";
+
+/// How long a push waits for the user to click
+pub const ACCEPT_TIMEOUT: Duration = crate::tcp_file_client::PUSH_ACCEPT_TIMEOUT;
+const MAX_HEADER_LINE: u64 = 1024;
+// a sealed 1 MB chunk plus nonce, tag and bincode framing
+const MAX_SEALED_CHUNK: usize = 1024 * 1024 + 1024;
+const HEADER_AAD: &[u8] = b"LanChGo MPUSHE header";
+// pushes past the header at once, waiting for a click or streaming, more get NO
+const MAX_PUSHES: usize = 4;
+const SEEN_HEADERS_KEPT: usize = 256;
+
+static SAVE_FOLDER: Mutex<String> = Mutex::new(String::new());
+// pushes waiting for a click, by the offer id shown in the panel. true = accepted
+static PENDING: Mutex<Option<HashMap<String, oneshot::Sender<bool>>>> = Mutex::new(None);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+// nonces of the last sealed headers, the same MPUSHE header twice is a replay
+static SEEN_HEADERS: Mutex<VecDeque<[u8; 12]>> = Mutex::new(VecDeque::new());
+
+// one of the MAX_PUSHES places, given back on drop
+struct PushSlot(());
+
+impl PushSlot {
+    fn take() -> Option<Self> {
+        IN_FLIGHT
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < MAX_PUSHES).then_some(n + 1))
+            .ok()
+            .map(|_| PushSlot(()))
+    }
+}
+
+impl Drop for PushSlot {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Config.save_to_folder, where the next accepted push is saved
+pub fn configure(cfg: &Config) {
+    *SAVE_FOLDER.lock().unwrap() = cfg.save_to_folder.clone();
+}
+
+fn answer(offer_id: &str, accepted: bool) -> bool {
+    match PENDING.lock().unwrap().get_or_insert_with(HashMap::new).remove(offer_id) {
+        Some(tx) => tx.send(accepted).is_ok(),
+        None => false,
+    }
+}
+
+/// The user clicked a pushed file in the panel or Accept on its prompt, false if `offer_id` isn't one
+pub fn accept(offer_id: &str) -> bool {
+    answer(offer_id, true)
+}
+
+/// Deny on the prompt, the sender hears NO right away instead of waiting out ACCEPT_TIMEOUT
+pub fn deny(offer_id: &str) -> bool {
+    answer(offer_id, false)
+}
+
+struct PushHeader {
+    size: u64,
+    sha256: Option<String>,
+    name: String,
+    key: Option<[u8; 32]>, // paired phone, chunks are sealed
+    desktop: bool,         // DPUSH
+}
+
+fn parse_header(line: &str) -> Option<PushHeader> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    match line.split_once(' ')? {
+        ("MPUSH", rest) => parse_fields(rest, None, false),
+        ("DPUSH", rest) => parse_fields(rest, None, true),
+        _ => None,
+    }
+}
+
+// the sealed header after "MPUSHE <device id>", None if it doesn't open with that phone's key
+async fn read_paired_header(reader: &mut BufReader<TcpStream>, device_id: &str, read_limit: Duration) -> Option<PushHeader> {
+    let key = pairing::device_key(device_id)?;
+    let aad = [HEADER_AAD, device_id.as_bytes()].concat();
+    let (plain, nonce) = read_sealed_chunk(reader, &key, &aad, read_limit).await.ok()??;
+    {
+        let mut seen = SEEN_HEADERS.lock().unwrap();
+        if seen.contains(&nonce) {
+            return None;
+        }
+        if seen.len() >= SEEN_HEADERS_KEPT {
+            seen.pop_front();
+        }
+        seen.push_back(nonce);
+    }
+    parse_fields(std::str::from_utf8(&plain).ok()?, Some(key), false).filter(|h| h.sha256.is_some())
+}
+
+fn parse_fields(rest: &str, key: Option<[u8; 32]>, desktop: bool) -> Option<PushHeader> {
+    let mut parts = rest.splitn(3, ' ');
+    let size = parts.next()?.parse().ok()?;
+    let sha256 = match parts.next()? {
+        "-" => None,
+        hash if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) => Some(hash.to_ascii_lowercase()),
+        _ => return None,
+    };
+    // only the last path component, a name like "../x" must not leave the download folder
+    let name = Path::new(parts.next()?).file_name()?.to_str()?.to_string();
+    Some(PushHeader { size, sha256, name, key, desktop })
+}
+
+/// One MPUSH / DPUSH connection, called by the file server once it saw the magic
+pub async fn handle_push(stream: TcpStream, read_limit: Duration, write_limit: Duration) -> std::io::Result<()> {
+    let phone = stream.peer_addr()?.ip();
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    tokio::time::timeout(read_limit, (&mut reader).take(MAX_HEADER_LINE).read_line(&mut line))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "MPUSH header timed out"))??;
+    let header = match line.trim_end_matches(['\r', '\n']).strip_prefix("MPUSHE ") {
+        Some(device_id) => read_paired_header(&mut reader, device_id, read_limit).await,
+        None => parse_header(&line),
+    };
+    let Some(header) = header else {
+        let _ = reader.get_mut().write_all(b"ERR\n").await;
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "bad MPUSH header"));
+    };
+    let Some(_slot) = PushSlot::take() else {
+        tokio::time::timeout(write_limit, reader.get_mut().write_all(b"NO\n")).await.ok();
+        return Err(std::io::Error::other("too many pushes at once"));
+    };
+
+    let id = *Uuid::new_v4().as_bytes();
+    let offer_id = file_transfer_protocol::offer_id_to_hex(&id);
+    let size_text = file_transfer_protocol::human_size(header.size);
+    let sender = if header.desktop { peers::label(phone, None) } else { format!("📱 {}", peers::label(phone, None)) };
+    let (tx, rx) = oneshot::channel();
+    PENDING.lock().unwrap().get_or_insert_with(HashMap::new).insert(offer_id.clone(), tx);
+
+    let auto = header.key.is_some() || auto_download::wants(phone, header.size);
+    show_push(&offer_id, &header.name, &size_text, &sender, auto);
+    notify::file_offer(&sender, &header.name, &size_text);
+
+    let answer = if auto { Some(true) } else { tokio::time::timeout(ACCEPT_TIMEOUT, rx).await.ok().and_then(Result::ok) };
+    PENDING.lock().unwrap().get_or_insert_with(HashMap::new).remove(&offer_id);
+    if answer != Some(true) {
+        set_progress(&offer_id, false, if answer.is_some() { "declined" } else { "expired" });
+        tokio::time::timeout(write_limit, reader.get_mut().write_all(b"NO\n")).await.ok();
+        return Ok(());
+    }
+
+    // a paired phone seals its chunks under the offer id, it only learns it here
+    let ok = match header.key {
+        Some(_) => format!("OK {}\n", offer_id),
+        None => "OK\n".to_string(),
+    };
+    tokio::time::timeout(write_limit, reader.get_mut().write_all(ok.as_bytes()))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "MPUSH write timed out"))??;
+
+    let folder = SAVE_FOLDER.lock().unwrap().clone();
+    let save_path = build_unique_download_path(Path::new(&folder), &header.name, &offer_id);
+    let res = receive(&mut reader, phone, &header, &save_path, &id, read_limit).await;
+
+    let reply: &[u8] = if res.is_ok() { b"DONE\n" } else { b"ERR\n" };
+    tokio::time::timeout(write_limit, reader.get_mut().write_all(reply)).await.ok();
+
+    let name = header.name.clone();
+    let done = res.is_ok();
+    let error = res.as_ref().err().map(|e| e.to_string());
+    let id = offer_id.clone();
+    if let Some(weak) = main_helpers::app_handle() {
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak.upgrade() {
+                if done {
+                    main_helpers::set_offer_progress_text(&app, &id, false, "100%");
+                    main_helpers::set_offer_saved(&app, &id, save_path);
+                    app.invoke_show_temp_message(format!("✅ Received from {}: {}", sender, name).into());
+                } else {
+                    main_helpers::set_offer_progress_text(&app, &id, false, "ERR");
+                    app.invoke_show_temp_message(format!("❌ Transfer from {} failed: {}", sender, error.unwrap_or_default()).into());
+                }
+            }
+        });
+    }
+    res
+}
+
+// stream exactly header.size bytes into a .part file, publish it once the checksum matches
+async fn receive(reader: &mut BufReader<TcpStream>, phone: IpAddr, header: &PushHeader, save_path: &PathBuf, id: &[u8; 16], read_limit: Duration) -> std::io::Result<()> {
+    let offer_id = &file_transfer_protocol::offer_id_to_hex(id);
+    let part_path = save_path.with_extension("part");
+    let file = tokio::fs::File::create(&part_path).await?;
+    let mut out = BufWriter::with_capacity(1024 * 1024, file);
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut hasher = Sha256::new();
+    let mut got: u64 = 0;
+    let mut last_bucket = u32::MAX;
+    let mut last_ui = Instant::now();
+    let mut index: u64 = 0;
+
+    let res: std::io::Result<()> = async {
+        loop {
+            let n = match &header.key {
+                Some(key) => {
+                    let aad = [&id[..], &index.to_le_bytes()].concat();
+                    let Some((plain, _)) = read_sealed_chunk(reader, key, &aad, read_limit).await? else { break; };
+                    index += 1;
+                    buf.clear();
+                    buf.extend_from_slice(&plain);
+                    plain.len()
+                }
+                None if got < header.size => {
+                    buf.resize(1024 * 1024, 0);
+                    let want = buf.len().min((header.size - got) as usize);
+                    let n = timed(read_limit, reader.read(&mut buf[..want])).await?;
+                    if n == 0 {
+                        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "phone stopped sending"));
+                    }
+                    n
+                }
+                None => break,
+            };
+            if got + n as u64 > header.size {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "phone sent more than announced"));
+            }
+            out.write_all(&buf[..n]).await?;
+            hasher.update(&buf[..n]);
+            bandwidth::record_received(bandwidth::Kind::File, phone, n);
+            rate_limit::download(n).await;
+            got += n as u64;
+
+            let bucket = main_helpers::progress_bucket_3(got, header.size);
+            if bucket != last_bucket && last_ui.elapsed() >= Duration::from_millis(150) {
+                last_bucket = bucket;
+                last_ui = Instant::now();
+                set_progress(offer_id, true, &format!("{}%", bucket));
+            }
+        }
+        if got != header.size {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "phone stopped sending"));
+        }
+        out.flush().await?;
+        if let Some(expected) = &header.sha256 {
+            if file_transfer_protocol::digest_to_hex(&hasher.finalize()) != *expected {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "checksum mismatch, file discarded"));
+            }
+        }
+        tokio::fs::rename(&part_path, save_path).await?;
+        Ok(())
+    }
+    .await;
+
+    if res.is_err() {
+        let _ = tokio::fs::remove_file(&part_path).await;
+    }
+    res
+}
+
+async fn timed<T>(limit: Duration, fut: impl std::future::Future<Output = std::io::Result<T>>) -> std::io::Result<T> {
+    tokio::time::timeout(limit, fut)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "Phone stalled"))?
+}
+
+// one sealed chunk and its nonce, None at the 0 length end marker
+async fn read_sealed_chunk(reader: &mut BufReader<TcpStream>, key: &[u8; 32], aad: &[u8], read_limit: Duration) -> std::io::Result<Option<(Vec<u8>, [u8; 12])>> {
+    let Some((msg, _)) = sealed_stream::read_frame(reader, MAX_SEALED_CHUNK, read_limit).await? else { return Ok(None); };
+    secure_channel_code::decrypt_bytes_aad(key, &msg, aad)
+        .map(|plain| Some((plain, msg.nonce)))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::PermissionDenied, "chunk doesn't open with the pairing key"))
+}
+
+fn show_push(offer_id: &str, name: &str, size_text: &str, sender: &str, auto: bool) {
+    let Some(weak) = main_helpers::app_handle() else { return; };
+    let (offer_id, name, size_text, sender) = (offer_id.to_string(), name.to_string(), size_text.to_string(), sender.to_string());
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(app) = weak.upgrade() {
+            app.invoke_add_file_offer(FileOfferItem {
+                offer_id: offer_id.into(),
+                name: file_transfer_protocol::truncate_name(&name, 16).into(),
+                size_text: size_text.clone().into(),
+                is_downloading: false,
+                progress_text: "".into(),
+                is_mobile: true, // clicking the row accepts, see on_download_offer
+                is_own: false,
+                sender: sender.clone().into(),
+                collapsed: false,
+                thumbnail: Default::default(),
+                saved: false,
+                queued: 0,
+            });
+            if !auto {
+                app.set_push_request_id(offer_id.into());
+                app.set_push_request_text(format!("{} wants to send you {} ({})", sender, name, size_text).into());
+                app.invoke_show_push_request_popup();
+            }
+        }
+    });
+}
+
+fn set_progress(offer_id: &str, downloading: bool, text: &str) {
+    let Some(weak) = main_helpers::app_handle() else { return; };
+    let (offer_id, text) = (offer_id.to_string(), text.to_string());
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(app) = weak.upgrade() {
+            main_helpers::set_offer_progress_text(&app, &offer_id, downloading, &text);
+        }
+    });
+}
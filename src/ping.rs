@@ -0,0 +1,70 @@
+// "/ping bob": a unicast PNGQ that plays the ping sound and shows an attention line only on
+// bob's machine, which answers right away with PNGR so we can show the round trip time.
+//
+// PNGQ + id(u64 le) + utf8 nickname of the sender
+// PNGR + id(u64 le)
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const PNGQ_MAGIC: &[u8; 4] = b"PNGQ";
+pub const PNGR_MAGIC: &[u8; 4] = b"PNGR";
+
+// no pong within this long = the peer is gone or runs an older build
+pub const PONG_TIMEOUT: Duration = Duration::from_secs(3);
+// one ping sound per peer this often, a loop of /ping can't turn into an alarm
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+
+// our pings waiting for a pong: id -> (sent at, who)
+static PENDING: Mutex<Option<HashMap<u64, (Instant, String)>>> = Mutex::new(None);
+static LAST_PINGED_BY: Mutex<Option<HashMap<IpAddr, Instant>>> = Mutex::new(None);
+
+/// A PNGQ for `target`, remembered until its pong comes back
+pub fn request_packet(nickname: &str, target: &str) -> (u64, Vec<u8>) {
+    let id = rand::random::<u64>();
+    PENDING.lock().unwrap().get_or_insert_with(HashMap::new).insert(id, (Instant::now(), target.to_string()));
+    let mut packet = Vec::from(PNGQ_MAGIC as &[u8]);
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(nickname.as_bytes());
+    (id, packet)
+}
+
+/// A peer pinged us: (id, their nickname)
+pub fn parse_request(bytes: &[u8]) -> Option<(u64, String)> {
+    let rest = bytes.strip_prefix(PNGQ_MAGIC)?;
+    let id = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?);
+    let nickname = String::from_utf8_lossy(&rest[8..]).chars().filter(|c| !c.is_control()).take(32).collect();
+    Some((id, nickname))
+}
+
+pub fn reply_packet(id: u64) -> Vec<u8> {
+    let mut packet = Vec::from(PNGR_MAGIC as &[u8]);
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet
+}
+
+/// The pong for one of our pings: (who, round trip)
+pub fn take_reply(bytes: &[u8]) -> Option<(String, Duration)> {
+    let rest = bytes.strip_prefix(PNGR_MAGIC)?;
+    let id = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?);
+    let (sent, target) = PENDING.lock().unwrap().get_or_insert_with(HashMap::new).remove(&id)?;
+    Some((target, sent.elapsed()))
+}
+
+/// Still no pong after PONG_TIMEOUT: forget the ping, returns who didn't answer
+pub fn take_timed_out(id: u64) -> Option<String> {
+    PENDING.lock().unwrap().get_or_insert_with(HashMap::new).remove(&id).map(|(_, target)| target)
+}
+
+/// Sound and attention line for this ping, false while the peer is still inside PING_INTERVAL
+pub fn allow_alert(from: IpAddr) -> bool {
+    let mut last = LAST_PINGED_BY.lock().unwrap();
+    let last = last.get_or_insert_with(HashMap::new);
+    if last.get(&from).is_some_and(|at| at.elapsed() < PING_INTERVAL) {
+        return false;
+    }
+    last.insert(from, Instant::now());
+    true
+}
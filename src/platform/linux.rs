@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use get_if_addrs::{get_if_addrs, IfAddr};
+
+use super::Platform;
+use crate::classes::InterfacesInfo;
+
+pub struct LinuxPlatform;
+
+// "up" / "unknown" (some drivers, tun devices) count as usable
+fn oper_status(name: &str) -> &'static str {
+    match std::fs::read_to_string(format!("/sys/class/net/{}/operstate", name)) {
+        Ok(state) if matches!(state.trim(), "up" | "unknown") => super::STATUS_UP,
+        _ => super::STATUS_DOWN,
+    }
+}
+
+impl Platform for LinuxPlatform {
+    fn local_ipv4(&self) -> Option<Ipv4Addr> {
+        get_if_addrs().unwrap_or_default().into_iter().find_map(|iface| match iface.addr {
+            IfAddr::V4(v4) if !v4.ip.is_loopback() && oper_status(&iface.name) == super::STATUS_UP => Some(v4.ip),
+            _ => None,
+        })
+    }
+
+    fn collect_interfaces(&self) -> Vec<InterfacesInfo> {
+        // get_if_addrs returns one row per address, fold them into one entry per adapter
+        let mut by_name: BTreeMap<String, InterfacesInfo> = BTreeMap::new();
+
+        for iface in get_if_addrs().unwrap_or_default() {
+            if iface.is_loopback() {
+                continue;
+            }
+            let entry = by_name.entry(iface.name.clone()).or_insert_with(|| InterfacesInfo {
+                name: iface.name.clone(),
+                address_to_broadcast: String::new(),
+                ipv4_address: String::new(),
+                status: oper_status(&iface.name).to_string(),
+                ipv6_addresses: Vec::new(),
+            });
+            match iface.addr {
+                IfAddr::V4(v4) => {
+                    if entry.ipv4_address.is_empty() {
+                        entry.ipv4_address = v4.ip.to_string();
+                        if let Some(b) = v4.broadcast {
+                            entry.address_to_broadcast = b.to_string();
+                        }
+                    }
+                }
+                IfAddr::V6(v6) => entry.ipv6_addresses.push(v6.ip.to_string()),
+            }
+        }
+
+        // same rule as Windows: no broadcast address, nothing to talk on
+        by_name
+            .into_values()
+            .filter(|it| !it.address_to_broadcast.is_empty() && it.address_to_broadcast != "255.255.255.255")
+            .collect()
+    }
+
+    fn gateway_for_adapter(&self, name: &str) -> String {
+        // /proc/net/route: Iface Destination Gateway ... (hex, little endian)
+        let table = std::fs::read_to_string("/proc/net/route").unwrap_or_default();
+        for line in table.lines().skip(1) {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 3 || cols[0] != name || cols[1] != "00000000" {
+                continue;
+            }
+            if let Ok(raw) = u32::from_str_radix(cols[2], 16) {
+                return Ipv4Addr::from(raw.to_le_bytes()).to_string();
+            }
+        }
+        "0.0.0.0".to_string()
+    }
+
+    fn adapter_name_for_ip(&self, ip: &str) -> Option<String> {
+        get_if_addrs()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|iface| iface.ip().to_string() == ip)
+            .map(|iface| iface.name)
+    }
+
+    fn adapter_id(&self, name: &str) -> Option<String> {
+        let mac = std::fs::read_to_string(format!("/sys/class/net/{}/address", name)).ok()?;
+        Some(mac.trim().to_string()).filter(|m| !m.is_empty())
+    }
+
+    fn wifi_ssid(&self, name: &str) -> Option<String> {
+        // wireless-tools may not be installed, that just means no SSID in the fingerprint
+        let out = Command::new("iwgetid").args([name, "--raw"]).output().ok()?;
+        let ssid = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        (out.status.success() && !ssid.is_empty()).then_some(ssid)
+    }
+
+    fn app_data_dir(&self) -> PathBuf {
+        // ~/.config/LanChGoApp
+        dirs::config_dir().unwrap_or_else(|| PathBuf::from(".")).join("LanChGoApp")
+    }
+
+    fn reveal(&self, path: &Path) -> io::Result<()> {
+        // no portable "select this file", the folder it's in is the next best thing
+        let folder = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+        open::that(folder)
+    }
+
+    fn exclusive_bind(&self, _socket: &socket2::Socket) -> io::Result<()> {
+        // already the case, Linux only shares a port when every socket on it asked for that
+        Ok(())
+    }
+
+    fn watch_resume(&self, on_resume: Box<dyn Fn() + Send + Sync>) {
+        // logind's PrepareForSleep(false) on the system bus, through gdbus (part of glib) so we
+        // don't need a D-Bus crate. No gdbus or no logind: the monitor ends, on_resume is dropped.
+        let Ok(mut monitor) = Command::new("gdbus")
+            .args(["monitor", "--system", "--dest", "org.freedesktop.login1", "--object-path", "/org/freedesktop/login1"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return;
+        };
+        let Some(stdout) = monitor.stdout.take() else { return; };
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                // "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (false,)"
+                if line.contains(".PrepareForSleep (false") {
+                    on_resume();
+                }
+            }
+            let _ = monitor.wait();
+        });
+    }
+}
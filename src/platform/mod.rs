@@ -0,0 +1,48 @@
+// OS specific bits (adapters, gateways, where the config lives) behind one trait.
+// Everything else in the app talks to `platform::current()`.
+
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use crate::classes::InterfacesInfo;
+
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(target_os = "linux")]
+mod linux;
+
+// adapter status strings, the UI compares against these (they are the Windows IfOperStatus names)
+pub const STATUS_UP: &str = "IfOperStatusUp";
+pub const STATUS_DOWN: &str = "IfOperStatusDown";
+
+pub trait Platform: Sync {
+    /// First non-loopback IPv4 of an adapter that is up
+    fn local_ipv4(&self) -> Option<Ipv4Addr>;
+    /// User-facing adapters with a usable broadcast address
+    fn collect_interfaces(&self) -> Vec<InterfacesInfo>;
+    /// First IPv4 gateway of the adapter, "0.0.0.0" if none
+    fn gateway_for_adapter(&self, name: &str) -> String;
+    /// Adapter name that owns this IP
+    fn adapter_name_for_ip(&self, ip: &str) -> Option<String>;
+    /// Hardware identity of the adapter (GUID on Windows, MAC on Linux), survives DHCP renewals
+    fn adapter_id(&self, name: &str) -> Option<String>;
+    /// SSID the adapter is associated with, None for wired / unknown
+    fn wifi_ssid(&self, name: &str) -> Option<String>;
+    /// Folder holding config.json, history etc.
+    fn app_data_dir(&self) -> PathBuf;
+    /// Show a file in the file manager, selected where the OS allows it
+    fn reveal(&self, path: &Path) -> io::Result<()>;
+    /// Keep other sockets off the address `socket` is about to bind
+    fn exclusive_bind(&self, socket: &socket2::Socket) -> io::Result<()>;
+    /// Call `on_resume` whenever the OS says it woke up from sleep. Dropping it means the OS
+    /// can't tell us (anymore), see power_watch.
+    fn watch_resume(&self, on_resume: Box<dyn Fn() + Send + Sync>);
+}
+
+pub fn current() -> &'static dyn Platform {
+    #[cfg(target_os = "windows")]
+    return &windows::WindowsPlatform;
+    #[cfg(target_os = "linux")]
+    return &linux::LinuxPlatform;
+}
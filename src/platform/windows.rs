@@ -0,0 +1,213 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use get_if_addrs::{get_if_addrs, IfAddr};
+
+use super::Platform;
+use crate::classes::InterfacesInfo;
+
+pub struct WindowsPlatform;
+
+impl Platform for WindowsPlatform {
+    fn local_ipv4(&self) -> Option<Ipv4Addr> {
+        // Iterate through all network adapters
+        match ipconfig::get_adapters() {
+            Ok(adapters) => {
+                for adapter in adapters {
+                    // Skip adapters that are down
+                    if format!("{:?}", adapter.oper_status()) != super::STATUS_UP {
+                        continue;
+                    }
+                    // Look through adapter IPs
+                    for ip in adapter.ip_addresses() {
+                        if let IpAddr::V4(v4) = ip {
+                            // Skip loopback addresses (127.x.x.x)
+                            if !v4.is_loopback() {
+                                return Some(*v4);
+                            }
+                        }
+                    }
+                }
+                None
+            }
+            Err(_e) => None,
+        }
+    }
+
+    fn collect_interfaces(&self) -> Vec<InterfacesInfo> {
+        let mut collection = Vec::new();
+        let ifaces = get_if_addrs().unwrap_or_default();
+
+        for adapter in ipconfig::get_adapters().unwrap_or_default() {
+            let name = adapter.friendly_name().to_string();
+            let status = format!("{:?}", adapter.oper_status());
+
+            // Default fallback broadcast
+            let mut broadcast_address = "255.255.255.255".to_string();
+            let mut ipv4_address = String::new();
+
+            // IPv6 addresses (link-local included, that's what ff02:: multicast runs on)
+            let ipv6_addresses: Vec<String> = adapter
+                .ip_addresses()
+                .iter()
+                .filter_map(|ip| match ip {
+                    IpAddr::V6(v6) if !v6.is_loopback() => Some(v6.to_string()),
+                    _ => None,
+                })
+                .collect();
+
+            // Match adapter IPs against get_if_addrs to find broadcast
+            for ip in adapter.ip_addresses().iter().map(|ip| ip.to_string()) {
+                for iface in &ifaces {
+                    if let IfAddr::V4(v4) = &iface.addr {
+                        if v4.ip.to_string() == ip {
+                            ipv4_address = ip.clone();
+                            if let Some(b) = v4.broadcast {
+                                broadcast_address = b.to_string();
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Only skip loopback and "all 255s"
+            if broadcast_address != "127.255.255.255" && broadcast_address != "255.255.255.255" {
+                collection.push(InterfacesInfo {
+                    name,
+                    address_to_broadcast: broadcast_address,
+                    ipv4_address,
+                    status,
+                    ipv6_addresses,
+                });
+            }
+        }
+        collection
+    }
+
+    fn gateway_for_adapter(&self, name: &str) -> String {
+        for adapter in ipconfig::get_adapters().unwrap_or_default() {
+            if adapter.friendly_name() == name {
+                // Prefer IPv4 gateways
+                if let Some(gw) = adapter
+                    .gateways()
+                    .iter()
+                    .find_map(|ip| match ip {
+                        IpAddr::V4(v4) => Some(v4.to_string()),
+                        _ => None,
+                    })
+                {
+                    return gw;
+                }
+                // If only IPv6 or none:
+                if let Some(gw_any) = adapter.gateways().first() {
+                    return gw_any.to_string();
+                }
+                return "0.0.0.0".to_string();
+            }
+        }
+        "0.0.0.0".to_string()
+    }
+
+    fn adapter_name_for_ip(&self, ip: &str) -> Option<String> {
+        ipconfig::get_adapters()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|adapter| adapter.ip_addresses().iter().any(|a| a.to_string() == ip))
+            .map(|adapter| adapter.friendly_name().to_string())
+    }
+
+    fn adapter_id(&self, name: &str) -> Option<String> {
+        ipconfig::get_adapters()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|adapter| adapter.friendly_name() == name)
+            .map(|adapter| adapter.adapter_name().to_string())
+    }
+
+    fn wifi_ssid(&self, name: &str) -> Option<String> {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+        let out = Command::new("netsh")
+            .args(["wlan", "show", "interfaces"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+
+        // one block per wireless adapter: "Name : Wi-Fi" ... "SSID : home" ("BSSID" is a different line)
+        let mut in_block = false;
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once(':') else { continue; };
+            match key.trim() {
+                "Name" => in_block = value.trim() == name,
+                "SSID" if in_block => return Some(value.trim().to_string()).filter(|s| !s.is_empty()),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn app_data_dir(&self) -> PathBuf {
+        dirs::data_dir().unwrap().join("LanChGoApp")
+    }
+
+    fn reveal(&self, path: &Path) -> io::Result<()> {
+        use std::os::windows::process::CommandExt;
+        // explorer wants "/select,<path>" as one argument, quoted its own way
+        Command::new("explorer").raw_arg(format!("/select,\"{}\"", path.display())).spawn().map(|_| ())
+    }
+
+    fn exclusive_bind(&self, socket: &socket2::Socket) -> io::Result<()> {
+        use std::os::windows::io::AsRawSocket;
+        // SO_EXCLUSIVEADDRUSE is ~SO_REUSEADDR, neither std nor socket2 set it
+        const SOL_SOCKET: i32 = 0xffff;
+        const SO_EXCLUSIVEADDRUSE: i32 = !0x0004;
+        #[link(name = "ws2_32")]
+        extern "system" {
+            fn setsockopt(s: usize, level: i32, optname: i32, optval: *const u8, optlen: i32) -> i32;
+        }
+        let on: u32 = 1;
+        let rc = unsafe { setsockopt(socket.as_raw_socket() as usize, SOL_SOCKET, SO_EXCLUSIVEADDRUSE, (&on as *const u32).cast(), 4) };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn watch_resume(&self, on_resume: Box<dyn Fn() + Send + Sync>) {
+        // the WM_POWERBROADCAST events without a window: a callback registered with powrprof
+        use std::ffi::c_void;
+        const DEVICE_NOTIFY_CALLBACK: u32 = 2;
+        const PBT_APMRESUMEAUTOMATIC: u32 = 0x12; // every resume, user present or not
+        type Callback = extern "system" fn(context: *mut c_void, kind: u32, setting: *mut c_void) -> u32;
+        #[repr(C)]
+        struct SubscribeParameters {
+            callback: Callback,
+            context: *mut c_void,
+        }
+        #[link(name = "powrprof")]
+        extern "system" {
+            fn PowerRegisterSuspendResumeNotification(flags: u32, recipient: *const c_void, handle: *mut *mut c_void) -> u32;
+        }
+        extern "system" fn on_power_event(context: *mut c_void, kind: u32, _setting: *mut c_void) -> u32 {
+            if kind == PBT_APMRESUMEAUTOMATIC {
+                let on_resume = unsafe { &*(context as *const Box<dyn Fn() + Send + Sync>) };
+                on_resume();
+            }
+            0
+        }
+
+        // registered for the life of the process, both are leaked on purpose
+        let context = Box::into_raw(Box::new(on_resume));
+        let params = Box::leak(Box::new(SubscribeParameters { callback: on_power_event, context: context.cast() }));
+        let mut handle = std::ptr::null_mut();
+        let rc = unsafe { PowerRegisterSuspendResumeNotification(DEVICE_NOTIFY_CALLBACK, (params as *const SubscribeParameters).cast(), &mut handle) };
+        if rc != 0 {
+            // not registered, nobody else holds it
+            drop(unsafe { Box::from_raw(context) });
+        }
+    }
+}
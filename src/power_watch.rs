@@ -0,0 +1,47 @@
+use std::sync::{mpsc, Arc, atomic::{AtomicBool, Ordering}};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use crate::platform;
+
+const TICK: Duration = Duration::from_secs(2);
+// if the wall clock jumped this much more than we slept, the machine was suspended
+const RESUME_GAP: Duration = Duration::from_secs(15);
+
+/// Runs `on_resume` after sleep/resume. The OS tells us where it can (WM_POWERBROADCAST's
+/// resume event on Windows, logind's PrepareForSleep on Linux, see Platform::watch_resume),
+/// without that we fall back to watching for wall-clock jumps between short ticks.
+pub fn start_resume_watcher( running: Arc<AtomicBool>, on_resume: impl Fn() + Send + 'static, ) -> JoinHandle<()> {
+    let (tx, rx) = mpsc::channel();
+    platform::current().watch_resume(Box::new(move || {
+        let _ = tx.send(());
+    }));
+
+    thread::spawn(move || {
+        let mut native = true;
+        let mut last = SystemTime::now();
+
+        while running.load(Ordering::Relaxed) {
+            if native {
+                match rx.recv_timeout(TICK) {
+                    Ok(()) => on_resume(),
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    // the platform couldn't watch or stopped, the heuristic takes over
+                    Err(mpsc::RecvTimeoutError::Disconnected) => native = false,
+                }
+                last = SystemTime::now();
+                continue;
+            }
+
+            thread::sleep(TICK);
+            let now = SystemTime::now();
+            let elapsed = now.duration_since(last).unwrap_or_default();
+            last = now;
+
+            if elapsed > TICK + RESUME_GAP {
+                //println!("[POWER] resume detected after {:?}", elapsed);
+                on_resume();
+            }
+        }
+    })
+}
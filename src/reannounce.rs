@@ -0,0 +1,88 @@
+// The host repeats its ANCH / MANCH on its own, so a joiner that started late or lost the
+// first announcement still finds the channel. Every repeat is jittered, the gap doubles up to
+// MAX_INTERVAL while nothing changes and drops back to BASE_INTERVAL as soon as someone asks
+// (REQA) or a member we didn't know speaks.
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::secure_channel_code::{self, Channel};
+
+const BASE_INTERVAL: Duration = Duration::from_secs(15);
+const MAX_INTERVAL: Duration = Duration::from_secs(120);
+const JITTER_MS: i64 = 3000; // either way
+
+struct Cadence {
+    interval: Duration,
+    due: Option<Instant>, // None until the first announcement of this channel went out
+    members: HashSet<IpAddr>,
+}
+
+static CADENCE: Mutex<Option<Cadence>> = Mutex::new(None);
+
+fn jittered(interval: Duration) -> Instant {
+    let ms = interval.as_millis() as i64 + rand::random_range(-JITTER_MS..=JITTER_MS);
+    Instant::now() + Duration::from_millis(ms.max(1000) as u64)
+}
+
+fn with<R>(f: impl FnOnce(&mut Cadence) -> R) -> R {
+    let mut cadence = CADENCE.lock().unwrap();
+    f(cadence.get_or_insert_with(|| Cadence { interval: BASE_INTERVAL, due: None, members: HashSet::new() }))
+}
+
+/// A channel was created, got a new PIN or was closed: start from the short gap
+pub fn reset() {
+    with(|c| {
+        c.interval = BASE_INTERVAL;
+        c.due = Some(jittered(BASE_INTERVAL));
+        c.members.clear();
+    });
+}
+
+/// Someone is looking for a channel (REQA), keep announcing often
+pub fn activity() {
+    with(|c| {
+        if c.interval != BASE_INTERVAL {
+            c.interval = BASE_INTERVAL;
+            c.due = Some(jittered(BASE_INTERVAL));
+        }
+    });
+}
+
+/// A member's message came in, a new one counts as activity
+pub fn member_heard(ip: IpAddr) {
+    if with(|c| c.members.insert(ip)) {
+        activity();
+    }
+}
+
+// true when an announcement is due, the next one is scheduled further out
+fn take_due() -> bool {
+    with(|c| {
+        let now = Instant::now();
+        let interval = c.interval;
+        let due = *c.due.get_or_insert_with(|| jittered(interval));
+        if now < due {
+            return false;
+        }
+        c.interval = (c.interval * 2).min(MAX_INTERVAL);
+        c.due = Some(jittered(c.interval));
+        true
+    })
+}
+
+/// Announce on schedule while hosting. `send` broadcasts ANCH + MANCH for the channel.
+pub fn start(channel_mode: Arc<Mutex<String>>, send: impl Fn(&Channel) + Send + 'static) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(1));
+        if *channel_mode.lock().unwrap() != "host" {
+            continue;
+        }
+        let Some(channel) = secure_channel_code::get_active_channel() else { continue; };
+        if take_due() {
+            send(&channel);
+        }
+    });
+}
@@ -0,0 +1,123 @@
+// Plain records of who was on the LAN and what moved across it this session, written out as
+// CSV by /export for teams that keep a log of their (often air-gapped) network. Bytes per peer
+// come from bandwidth, the rest is collected here.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local};
+
+use crate::redact::Redactor;
+use crate::{bandwidth, peers, transfers};
+
+struct PeerRecord {
+    first_seen: DateTime<Local>,
+    last_seen: DateTime<Local>,
+    messages: u64,
+}
+
+struct TransferRecord {
+    at: DateTime<Local>,
+    sent: bool, // one of our offers went out, otherwise a download
+    peer: Option<IpAddr>,
+    name: String,
+    size: u64,
+    outcome: String,
+}
+
+static PEERS: Mutex<Option<BTreeMap<IpAddr, PeerRecord>>> = Mutex::new(None);
+static TRANSFERS: Mutex<Vec<TransferRecord>> = Mutex::new(Vec::new());
+
+fn peer_entry(peers: &mut Option<BTreeMap<IpAddr, PeerRecord>>, ip: IpAddr) -> &mut PeerRecord {
+    let now = Local::now();
+    let entry = peers
+        .get_or_insert_with(BTreeMap::new)
+        .entry(ip)
+        .or_insert(PeerRecord { first_seen: now, last_seen: now, messages: 0 });
+    entry.last_seen = now;
+    entry
+}
+
+/// A packet from someone else arrived
+pub fn peer_seen(ip: IpAddr) {
+    peer_entry(&mut PEERS.lock().unwrap(), ip);
+}
+
+/// A chat message from someone else arrived
+pub fn message_from(ip: IpAddr) {
+    peer_entry(&mut PEERS.lock().unwrap(), ip).messages += 1;
+}
+
+/// A download or upload ended, `outcome` as outcome() puts it
+pub fn transfer(sent: bool, peer: Option<IpAddr>, name: &str, size: u64, outcome: String) {
+    TRANSFERS.lock().unwrap().push(TransferRecord { at: Local::now(), sent, peer, name: name.to_string(), size, outcome });
+}
+
+/// "completed", "cancelled", "corrupt" or "failed: <why>"
+pub fn outcome<T>(res: &io::Result<T>) -> String {
+    match res {
+        Ok(_) => "completed".into(),
+        Err(e) if transfers::is_cancelled(e) => "cancelled".into(),
+        Err(e) if crate::file_transfer_protocol::is_corrupt(e) => "corrupt".into(),
+        Err(e) => format!("failed: {}", e),
+    }
+}
+
+// quoted when it has to be, names and reasons can contain anything
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn stamp(at: &DateTime<Local>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Write peers_<date>.csv and transfers_<date>.csv into `dir`, returns both paths
+pub fn export(dir: &Path, redactor: &mut Redactor) -> io::Result<(PathBuf, PathBuf)> {
+    let suffix = Local::now().format("%Y-%m-%d_%H-%M-%S");
+    let bytes: BTreeMap<IpAddr, (u64, u64)> = bandwidth::per_peer().into_iter().map(|(ip, sent, received)| (ip, (sent, received))).collect();
+
+    let mut peers_csv = String::from("address,name,first_seen,last_seen,messages,bytes_sent,bytes_received\n");
+    if let Some(records) = PEERS.lock().unwrap().as_ref() {
+        for (ip, r) in records {
+            let (sent, received) = bytes.get(ip).copied().unwrap_or_default();
+            peers_csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                ip,
+                field(&peers::label(*ip, None)),
+                stamp(&r.first_seen),
+                stamp(&r.last_seen),
+                r.messages,
+                sent,
+                received,
+            ));
+        }
+    }
+
+    let mut transfers_csv = String::from("time,direction,peer,name,size,outcome\n");
+    for t in TRANSFERS.lock().unwrap().iter() {
+        transfers_csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            stamp(&t.at),
+            if t.sent { "sent" } else { "received" },
+            t.peer.map(|ip| ip.to_string()).unwrap_or_default(),
+            field(&t.name),
+            t.size,
+            field(&t.outcome),
+        ));
+    }
+
+    std::fs::create_dir_all(dir)?;
+    let peers_path = dir.join(format!("peers_{}.csv", suffix));
+    let transfers_path = dir.join(format!("transfers_{}.csv", suffix));
+    std::fs::write(&peers_path, redactor.apply(&peers_csv))?;
+    std::fs::write(&transfers_path, redactor.apply(&transfers_csv))?;
+    Ok((peers_path, transfers_path))
+}
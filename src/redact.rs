@@ -0,0 +1,135 @@
+// Redaction pass for exports shared outside the team (/export, /exportchat, /exportlog with
+// "redact"): IP addresses and the chosen names become placeholders. Within one export the same
+// address or name always gets the same placeholder, so "ip-1 sent peer-2 a file" still reads.
+
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::{contacts, message_pipeline, peers};
+
+pub struct Redactor {
+    on: bool,
+    names: Vec<(String, String)>, // lowercase name, placeholder. Longest first so "Sam B" wins over "Sam"
+    ips: BTreeMap<String, String>,
+}
+
+impl Redactor {
+    /// Passes everything through, for a plain export
+    pub fn off() -> Self {
+        Self { on: false, names: Vec::new(), ips: BTreeMap::new() }
+    }
+
+    /// IPs and `names`, every name we know when it's empty (contacts, peers on the LAN, ours)
+    pub fn new(names: &[String]) -> Self {
+        let mut names: Vec<String> = if names.is_empty() {
+            contacts::list().into_iter().map(|c| c.nickname).chain(peers::names()).chain([message_pipeline::nickname()]).collect()
+        } else {
+            names.to_vec()
+        };
+        names.retain(|n| !n.trim().is_empty());
+        let mut names: Vec<String> = names.into_iter().map(|n| n.trim().to_lowercase()).collect();
+        names.sort_by(|a, b| b.chars().count().cmp(&a.chars().count()).then(a.cmp(b)));
+        names.dedup();
+        let names = names.into_iter().enumerate().map(|(i, n)| (n, format!("peer-{}", i + 1))).collect();
+        Self { on: true, names, ips: BTreeMap::new() }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    pub fn apply(&mut self, text: &str) -> String {
+        if !self.on {
+            return text.to_string();
+        }
+        let text = self.replace_ips(text);
+        self.replace_names(&text)
+    }
+
+    fn placeholder(&mut self, ip: &str) -> String {
+        let next = self.ips.len() + 1;
+        self.ips.entry(ip.to_string()).or_insert_with(|| format!("ip-{}", next)).clone()
+    }
+
+    // runs of hex digits, dots and colons that start a word and parse as an address.
+    // "10.0.0.5:3001" keeps its port, a trailing "." or ":" is the sentence
+    fn replace_ips(&mut self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut i = 0;
+        while i < chars.len() {
+            let starts_word = i == 0 || !(chars[i - 1].is_alphanumeric() || chars[i - 1] == '.' || chars[i - 1] == ':');
+            let is_part = |c: char| c.is_ascii_hexdigit() || c == '.' || c == ':';
+            if !starts_word || !is_part(chars[i]) {
+                out.push(chars[i]);
+                i += 1;
+                continue;
+            }
+            let end = (i..chars.len()).find(|&j| !is_part(chars[j])).unwrap_or(chars.len());
+            // the run has to end the word too, "10.0.0.5abc" isn't an address
+            if chars.get(end).is_some_and(|c| c.is_alphanumeric()) {
+                out.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            let run: String = chars[i..end].iter().collect();
+            let trimmed = run.trim_end_matches(['.', ':']);
+            let ip_len = if is_ip(trimmed) {
+                trimmed.len()
+            } else {
+                match trimmed.split_once(':') {
+                    Some((host, port)) if host.parse::<Ipv4Addr>().is_ok() && port.parse::<u16>().is_ok() => host.len(),
+                    _ => 0,
+                }
+            };
+            if ip_len == 0 {
+                out.push_str(&run);
+            } else {
+                let placeholder = self.placeholder(&run[..ip_len]);
+                out.push_str(&placeholder);
+                out.push_str(&run[ip_len..]);
+            }
+            i = end;
+        }
+        out
+    }
+
+    // whole words only, case doesn't matter
+    fn replace_names(&self, text: &str) -> String {
+        let mut out = text.to_string();
+        for (name, placeholder) in &self.names {
+            out = replace_word(&out, name, placeholder);
+        }
+        out
+    }
+}
+
+fn is_ip(s: &str) -> bool {
+    // "::" on its own is more often C++ than an address
+    s.parse::<Ipv4Addr>().is_ok() || (s.matches(':').count() >= 2 && s != "::" && s.parse::<Ipv6Addr>().is_ok())
+}
+
+fn replace_word(text: &str, lower_name: &str, placeholder: &str) -> String {
+    let lower = text.to_lowercase();
+    // lowercasing can change byte lengths (rare scripts), don't risk cutting a char in half
+    if lower.len() != text.len() {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (at, _) in lower.match_indices(lower_name) {
+        if at < last {
+            continue;
+        }
+        let end = at + lower_name.len();
+        let before_ok = text[..at].chars().next_back().is_none_or(|c| !c.is_alphanumeric());
+        let after_ok = text[end..].chars().next().is_none_or(|c| !c.is_alphanumeric());
+        if before_ok && after_ok {
+            out.push_str(&text[last..at]);
+            out.push_str(placeholder);
+            last = end;
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
@@ -0,0 +1,111 @@
+// Downloads that were still running when the app closed, kept in downloads.json next to the
+// config. On the next start each one whose .part is still there comes back as a row in the
+// files panel with ⟳, a click asks the sender for the rest (FOFR resume). The rest is forgotten.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_transfer_protocol::{self, FileOffer};
+use crate::main_helpers::get_config_path;
+use crate::transfers;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingDownload {
+    pub offer_id: String, // hex
+    pub sender: String,   // label when it started, see peers::label
+    pub sender_addr: SocketAddr,
+    pub offer: FileOffer,
+    pub save_path: PathBuf,
+    pub bytes: u64, // in the .part when last written down
+}
+
+impl PendingDownload {
+    pub fn part_path(&self) -> PathBuf {
+        file_transfer_protocol::resume_part_path(&self.save_path, &self.offer.offer_id)
+    }
+}
+
+static PENDING: OnceLock<Mutex<Vec<PendingDownload>>> = OnceLock::new();
+
+fn pending_path() -> PathBuf {
+    get_config_path().with_file_name("downloads.json")
+}
+
+fn store() -> &'static Mutex<Vec<PendingDownload>> {
+    PENDING.get_or_init(|| {
+        let list = File::open(pending_path())
+            .ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .unwrap_or_default();
+        Mutex::new(list)
+    })
+}
+
+fn save(list: &[PendingDownload]) {
+    if let Ok(file) = File::create(pending_path()) {
+        let _ = serde_json::to_writer_pretty(file, list);
+    }
+}
+
+fn part_len(entry: &PendingDownload) -> Option<u64> {
+    std::fs::metadata(entry.part_path()).ok().map(|m| m.len()).filter(|&len| len > 0)
+}
+
+/// A single-file download starts (folders start over, nothing to keep)
+pub fn started(sender_addr: SocketAddr, sender: &str, offer: &FileOffer, save_path: PathBuf) {
+    let offer_id = file_transfer_protocol::offer_id_to_hex(&offer.offer_id);
+    let mut list = store().lock().unwrap();
+    list.retain(|e| e.offer_id != offer_id);
+    let mut entry = PendingDownload { offer_id, sender: sender.to_string(), sender_addr, offer: offer.clone(), save_path, bytes: 0 };
+    entry.bytes = part_len(&entry).unwrap_or(0);
+    list.push(entry);
+    save(&list);
+}
+
+/// The connection dropped, the .part stays for another try
+pub fn interrupted(id_hex: &str) {
+    let mut list = store().lock().unwrap();
+    if let Some(entry) = list.iter_mut().find(|e| e.offer_id == id_hex) {
+        entry.bytes = part_len(entry).unwrap_or(0);
+    }
+    save(&list);
+}
+
+/// Done, cancelled or broken beyond resuming
+pub fn finished(id_hex: &str) {
+    let mut list = store().lock().unwrap();
+    let before = list.len();
+    list.retain(|e| e.offer_id != id_hex);
+    if list.len() != before {
+        save(&list);
+    }
+}
+
+/// At startup: downloads left behind that still have their .part, with its size now
+pub fn orphans() -> Vec<(PendingDownload, u64)> {
+    let mut list = store().lock().unwrap();
+    let found: Vec<(PendingDownload, u64)> = list.iter().filter_map(|e| Some((e.clone(), part_len(e)?))).collect();
+    if found.len() != list.len() {
+        list.retain(|e| found.iter().any(|(f, _)| f.offer_id == e.offer_id));
+        save(&list);
+    }
+    found
+}
+
+/// The files panel was cleared: drop every paused download and its .part, returns how many.
+/// Running downloads are left alone, cancelling one removes its .part anyway.
+pub fn discard_paused() -> usize {
+    let mut list = store().lock().unwrap();
+    let (paused, running): (Vec<_>, Vec<_>) = list.drain(..).partition(|e| !transfers::is_running(&e.offer_id));
+    for entry in &paused {
+        let _ = std::fs::remove_file(entry.part_path());
+    }
+    *list = running;
+    save(&list);
+    paused.len()
+}
@@ -0,0 +1,78 @@
+// Local automation rules from config.rules. When a trigger fires, every enabled rule for it
+// runs; today that's "a new peer appeared" -> a message sent to that peer alone, like the
+// guest Wi-Fi password or where the printer is (/welcome). Rate limited, so a room full of
+// laptops waking up at once doesn't turn us into a spammer.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::classes::{AutomationRule, Config, RuleTrigger};
+use crate::contacts::{self, Trust};
+
+// the same peer is welcomed again at most this often, it may come and go all day
+const PER_PEER_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+// messages across all peers per minute
+const MAX_PER_MINUTE: usize = 5;
+
+static RULES: Mutex<Vec<AutomationRule>> = Mutex::new(Vec::new());
+static LAST_SENT: Mutex<Option<HashMap<IpAddr, Instant>>> = Mutex::new(None);
+static RECENT: Mutex<VecDeque<Instant>> = Mutex::new(VecDeque::new());
+
+/// Config.rules, the next peer or message is matched against the new list
+pub fn configure(cfg: &Config) {
+    *RULES.lock().unwrap() = cfg.rules.clone();
+}
+
+/// Messages for a peer that just appeared, empty when no rule applies or a limit was hit
+pub fn peer_joined(ip: IpAddr, name: &str) -> Vec<String> {
+    let trust = contacts::trust_for_ip(ip);
+    if trust == Trust::Blocked {
+        return Vec::new();
+    }
+    let messages: Vec<String> = RULES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|r| r.enabled && r.trigger == RuleTrigger::PeerJoined && !r.message.trim().is_empty())
+        .filter(|r| !r.trusted_only || trust == Trust::Trusted)
+        .map(|r| r.message.replace("{name}", name))
+        .collect();
+    if messages.is_empty() {
+        return messages;
+    }
+
+    let mut last = LAST_SENT.lock().unwrap();
+    let last = last.get_or_insert_with(HashMap::new);
+    if last.get(&ip).is_some_and(|at| at.elapsed() < PER_PEER_INTERVAL) {
+        return Vec::new();
+    }
+
+    let mut recent = RECENT.lock().unwrap();
+    while recent.front().is_some_and(|at| at.elapsed() >= Duration::from_secs(60)) {
+        recent.pop_front();
+    }
+    if recent.len() + messages.len() > MAX_PER_MINUTE {
+        crate::diagnostics::log("rules", format!("welcome for {} skipped, more than {} messages a minute", ip, MAX_PER_MINUTE));
+        return Vec::new();
+    }
+    recent.extend(messages.iter().map(|_| Instant::now()));
+    last.insert(ip, Instant::now());
+    messages
+}
+
+/// "/welcome" status line
+pub fn summary(cfg: &Config) -> String {
+    let welcome: Vec<&str> = cfg
+        .rules
+        .iter()
+        .filter(|r| r.enabled && r.trigger == RuleTrigger::PeerJoined)
+        .map(|r| r.message.as_str())
+        .collect();
+    if welcome.is_empty() {
+        "🤖 No welcome message (/welcome Hi {name}, Wi-Fi: guest / hunter2)".into()
+    } else {
+        format!("🤖 New peers get: {}", welcome.join(" | "))
+    }
+}
@@ -0,0 +1,108 @@
+// "📸 Screenshot" in the files panel: our window steps aside, the screen it was on is captured and
+// shown in a popup where a region can be dragged out. The picked part is saved as PNG in the temp
+// offer dir and offered like a pasted image, to the channel members in secure mode. Only in builds
+// with the screenshot feature (on by default).
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use image::RgbaImage;
+use slint::winit_030::WinitWindowAccessor;
+use slint::{ComponentHandle, Image, Rgba8Pixel, SharedPixelBuffer};
+
+use crate::{file_transfer_protocol, notify, AppWindow};
+
+// long enough for the compositor to take the minimized window off the screen
+const STEP_ASIDE: Duration = Duration::from_millis(400);
+
+// the last capture, until it's shared or the next one replaces it
+static CAPTURED: Mutex<Option<RgbaImage>> = Mutex::new(None);
+
+#[cfg(feature = "screenshot")]
+mod imp {
+    use image::RgbaImage;
+
+    pub const AVAILABLE: bool = true;
+
+    /// The monitor with (x, y) on it, the primary one when no monitor has it
+    pub fn capture(x: i32, y: i32) -> Result<RgbaImage, String> {
+        let monitor = match xcap::Monitor::from_point(x, y) {
+            Ok(m) => m,
+            Err(_) => xcap::Monitor::all()
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .find(|m| m.is_primary().unwrap_or(false))
+                .ok_or("No screen to capture")?,
+        };
+        monitor.capture_image().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(feature = "screenshot"))]
+mod imp {
+    use image::RgbaImage;
+
+    pub const AVAILABLE: bool = false;
+
+    pub fn capture(_x: i32, _y: i32) -> Result<RgbaImage, String> {
+        Err("This build can't take screenshots (--features screenshot)".into())
+    }
+}
+
+/// Capture the screen the window is on and open the region popup
+pub fn take(app: &AppWindow) {
+    if !imp::AVAILABLE {
+        app.invoke_show_temp_message("📸 This build can't take screenshots".into());
+        return;
+    }
+    // a point inside the window picks the monitor
+    let pos = app.window().position();
+    let (x, y) = (pos.x + 20, pos.y + 20);
+    app.window().with_winit_window(|w| w.set_minimized(true));
+
+    let weak = app.as_weak();
+    std::thread::spawn(move || {
+        std::thread::sleep(STEP_ASIDE);
+        let captured = imp::capture(x, y);
+        let _ = slint::invoke_from_event_loop(move || {
+            let Some(app) = weak.upgrade() else { return; };
+            notify::focus_window(&app);
+            match captured {
+                Ok(image) => {
+                    let buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(image.as_raw(), image.width(), image.height());
+                    app.set_screenshot_preview(Image::from_rgba8(buffer));
+                    *CAPTURED.lock().unwrap() = Some(image);
+                    app.invoke_show_screenshot_popup();
+                }
+                Err(e) => app.invoke_show_temp_message(format!("❌ Screenshot failed: {}", e).into()),
+            }
+        });
+    });
+}
+
+/// The captured region, fractions of the screen (0..1), written as PNG. The whole screen when
+/// the region is too small to be one
+pub fn save(x: f32, y: f32, w: f32, h: f32) -> io::Result<PathBuf> {
+    let Some(image) = CAPTURED.lock().unwrap().take() else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "No screenshot to share"));
+    };
+    let (iw, ih) = (image.width() as f32, image.height() as f32);
+    let left = (x.clamp(0.0, 1.0) * iw) as u32;
+    let top = (y.clamp(0.0, 1.0) * ih) as u32;
+    let width = ((w.clamp(0.0, 1.0) * iw) as u32).min(image.width() - left);
+    let height = ((h.clamp(0.0, 1.0) * ih) as u32).min(image.height() - top);
+    let image = if width < 8 || height < 8 {
+        image
+    } else {
+        image::imageops::crop_imm(&image, left, top, width, height).to_image()
+    };
+    file_transfer_protocol::save_screenshot(&image)
+}
+
+/// Closing the popup without sharing
+pub fn discard(app: &AppWindow) {
+    *CAPTURED.lock().unwrap() = None;
+    app.set_screenshot_preview(Image::default());
+}
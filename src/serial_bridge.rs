@@ -0,0 +1,169 @@
+// Lab gear that only has a UART joins the chat through a serial port (/serial, Config.serial_bridge):
+// every public channel message goes out as "sender: text\r\n", every line the device writes is
+// sent to the public channel as ours. Secure channels never reach the wire, and device lines
+// starting with / are dropped so it can't run commands. Only in builds with --features serial-bridge.
+
+use std::sync::Mutex;
+
+use crate::classes::{Config, SerialBridge};
+use crate::{diagnostics, main_helpers};
+
+pub const DEFAULT_BAUD: u32 = 115_200;
+const MAX_LINE: usize = 1000;
+const ECHO_MEMORY: usize = 16;
+
+// lines the device sent us, their confirmed row must not be written back to it
+static FROM_DEVICE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[cfg(feature = "serial-bridge")]
+mod imp {
+    use std::io::{ErrorKind, Read, Write};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use serialport::SerialPort;
+
+    use crate::classes::SerialBridge;
+    use crate::diagnostics;
+
+    pub const AVAILABLE: bool = true;
+
+    struct Open {
+        bridge: SerialBridge,
+        writer: Box<dyn SerialPort>,
+        stop: Arc<AtomicBool>,
+    }
+
+    static OPEN: Mutex<Option<Open>> = Mutex::new(None);
+
+    pub fn open(bridge: Option<&SerialBridge>) {
+        let mut open = OPEN.lock().unwrap();
+        if open.as_ref().map(|o| &o.bridge) == bridge {
+            return;
+        }
+        if let Some(old) = open.take() {
+            old.stop.store(true, Ordering::Relaxed);
+        }
+        let Some(bridge) = bridge else { return; };
+
+        let port = serialport::new(&bridge.port, bridge.baud).timeout(Duration::from_millis(200)).open();
+        let (reader, writer) = match port.and_then(|p| p.try_clone().map(|w| (p, w))) {
+            Ok(pair) => pair,
+            Err(e) => {
+                diagnostics::log("serial", format!("opening {} failed: {}", bridge.port, e));
+                return;
+            }
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let port_name = bridge.port.clone();
+        let stopped = Arc::clone(&stop);
+        std::thread::spawn(move || read_lines(reader, &port_name, &stopped));
+        *open = Some(Open { bridge: bridge.clone(), writer, stop });
+    }
+
+    // until stopped or the device goes away
+    fn read_lines(mut reader: Box<dyn SerialPort>, port_name: &str, stop: &AtomicBool) {
+        let mut buf = [0u8; 256];
+        let mut line = Vec::new();
+        while !stop.load(Ordering::Relaxed) {
+            let n = match reader.read(&mut buf) {
+                Ok(n) => n,
+                Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::Interrupted) => continue,
+                Err(e) => {
+                    diagnostics::log("serial", format!("reading {} failed: {}", port_name, e));
+                    return;
+                }
+            };
+            for &b in &buf[..n] {
+                if b == b'\n' || b == b'\r' {
+                    super::from_device(&String::from_utf8_lossy(&line));
+                    line.clear();
+                } else if line.len() < super::MAX_LINE {
+                    line.push(b);
+                }
+            }
+        }
+    }
+
+    pub fn write(line: &str) {
+        let mut open = OPEN.lock().unwrap();
+        let Some(o) = open.as_mut() else { return; };
+        if let Err(e) = o.writer.write_all(line.as_bytes()).and_then(|()| o.writer.flush()) {
+            diagnostics::log("serial", format!("writing {} failed: {}", o.bridge.port, e));
+        }
+    }
+}
+
+#[cfg(not(feature = "serial-bridge"))]
+mod imp {
+    use crate::classes::SerialBridge;
+    use crate::diagnostics;
+
+    pub const AVAILABLE: bool = false;
+
+    pub fn open(bridge: Option<&SerialBridge>) {
+        if let Some(bridge) = bridge {
+            diagnostics::log("serial", format!("{} configured but this build has no serial bridge", bridge.port));
+        }
+    }
+
+    pub fn write(_line: &str) {}
+}
+
+pub use imp::AVAILABLE;
+
+/// Mirror the config: open, switch or close the port
+pub fn configure(cfg: &Config) {
+    imp::open(cfg.serial_bridge.as_ref());
+}
+
+/// "/serial" without arguments
+pub fn summary(cfg: &Config) -> String {
+    match (&cfg.serial_bridge, AVAILABLE) {
+        (_, false) => "🔌 This build has no serial bridge (--features serial-bridge)".to_string(),
+        (Some(SerialBridge { port, baud }), true) => format!("🔌 Public channel bridged to {} at {} baud", port, baud),
+        (None, true) => "🔌 Serial bridge off".to_string(),
+    }
+}
+
+/// A public channel message, written to the device one line each
+pub fn mirror(sender: &str, text: &str) {
+    if sender == main_helpers::own_sender() {
+        let mut from_device = FROM_DEVICE.lock().unwrap();
+        if let Some(i) = from_device.iter().position(|l| l == text) {
+            from_device.remove(i);
+            return;
+        }
+    }
+    let name = sender.parse().map_or_else(|_| sender.to_string(), |ip| crate::peers::label(ip, None));
+    let out: String = text.lines().map(|l| format!("{}: {}\r\n", name, l)).collect();
+    imp::write(&out);
+}
+
+// a line from the device: send it like typed into the public channel, the draft stays
+fn from_device(line: &str) {
+    let line = line.trim().to_string();
+    if line.is_empty() || line.starts_with('/') {
+        return;
+    }
+    let Some(weak) = main_helpers::app_handle() else { return; };
+    let _ = slint::invoke_from_event_loop(move || {
+        let Some(app) = weak.upgrade() else { return; };
+        if crate::secure_channel_code::get_active_channel().is_some() {
+            diagnostics::log("serial", "line dropped, not in the public channel");
+            return;
+        }
+        {
+            let mut from_device = FROM_DEVICE.lock().unwrap();
+            from_device.push(line.clone());
+            if from_device.len() > ECHO_MEMORY {
+                from_device.remove(0);
+            }
+        }
+        let draft = app.get_input_text();
+        app.set_input_text(line.into());
+        app.invoke_send_clicked();
+        app.set_input_text(draft);
+    });
+}
@@ -0,0 +1,80 @@
+// What goes into a session bundle: the channel, the contact book and the settings. The file
+// envelope is lanchgo_core::session_bundle.
+
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::classes::Config;
+use crate::contacts::{self, Contact};
+use crate::secure_channel_code::Channel;
+
+pub use lanchgo_core::session_bundle::*;
+
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    created: i64, // unix seconds
+    channel: Option<BundledChannel>,
+    contacts: Vec<Contact>,
+    config: Config,
+}
+
+/// What a bundle brought along, the caller applies the config and joins the channel
+pub struct Imported {
+    pub created: i64,
+    pub channel: Option<Channel>,
+    pub contacts_added: usize,
+    pub config: Config,
+}
+
+/// Write the active channel (if any), the contacts and `config` to `path`
+pub fn export(path: &Path, passphrase: &str, config: &Config) -> io::Result<()> {
+    let bundle = Bundle {
+        created: chrono::Local::now().timestamp(),
+        channel: BundledChannel::active(),
+        contacts: contacts::list(),
+        config: config.clone(),
+    };
+    let plain = serde_json::to_vec(&bundle).map_err(io::Error::other)?;
+    std::fs::write(path, seal(passphrase, plain)?)
+}
+
+/// Open a bundle and merge its contacts in. Config and channel are handed back untouched.
+pub fn import(path: &Path, passphrase: &str) -> io::Result<Imported> {
+    let mut plain = open(&std::fs::read(path)?, passphrase)?;
+    let bundle: Result<Bundle, _> = serde_json::from_slice(&plain);
+    plain.zeroize();
+    let bundle = bundle.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "damaged bundle"))?;
+
+    let channel = bundle.channel.map(BundledChannel::into_channel).transpose()?;
+    Ok(Imported {
+        created: bundle.created,
+        channel,
+        contacts_added: contacts::merge(bundle.contacts),
+        config: bundle.config,
+    })
+}
+
+/// The imported settings, keeping what belongs to this machine: adapter, ports, folders, serial port,
+/// window layout and device name
+pub fn merge_config(imported: Config, local: &Config) -> Config {
+    Config {
+        version: local.version.clone(),
+        schema: local.schema,
+        selected_interface: local.selected_interface.clone(),
+        last_broadcast: local.last_broadcast.clone(),
+        last_gateway: local.last_gateway.clone(),
+        save_to_folder: local.save_to_folder.clone(),
+        port: local.port,
+        ui_scale: local.ui_scale,
+        files_panel_width: local.files_panel_width,
+        device_name: local.device_name.clone(),
+        network_fingerprint: local.network_fingerprint.clone(),
+        file_server_port: local.file_server_port,
+        serial_bridge: local.serial_bridge.clone(),
+        port_sharing: local.port_sharing,
+        ..imported
+    }
+}
@@ -0,0 +1,87 @@
+// SettingsStore: the one place that changes Config. Every update is saved right away and
+// pushed to the modules that keep their own copy of a setting (message pipeline, alert
+// sounds, auto-download, chat length, transfer timeouts), so nothing needs a restart.
+
+use std::sync::{Arc, Mutex};
+
+use slint::ComponentHandle;
+
+use crate::classes::Config;
+use crate::main_helpers::{self, save_config};
+use crate::{auto_download, channel_idle, clip_sync, discovery, download_queue, file_transfer_protocol, message_pipeline, pairing, phone_push, rate_limit, rules, secure_channel_code, serial_bridge, status_endpoint, transcript, AppWindow, Theme};
+
+/// Chat rows kept in the view, the settings page offers these
+pub const CHAT_ROW_CHOICES: [u32; 3] = [10, 50, 200];
+
+/// Downloads running at once, the settings page offers these
+pub const PARALLEL_DOWNLOAD_CHOICES: [u32; 4] = [1, 2, 3, 5];
+
+#[derive(Clone)]
+pub struct SettingsStore {
+    config: Arc<Mutex<Config>>,
+}
+
+impl SettingsStore {
+    pub fn new(config: Config) -> Self {
+        apply_runtime(&config);
+        Self { config: Arc::new(Mutex::new(config)) }
+    }
+
+    /// The shared Config for code that still locks it itself (download paths, interface switching)
+    pub fn shared(&self) -> Arc<Mutex<Config>> {
+        Arc::clone(&self.config)
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&Config) -> R) -> R {
+        f(&self.config.lock().unwrap())
+    }
+
+    /// Change, save and apply in one go
+    pub fn update<R>(&self, f: impl FnOnce(&mut Config) -> R) -> R {
+        let mut cfg = self.config.lock().unwrap();
+        let out = f(&mut cfg);
+        save_config(&cfg);
+        apply_runtime(&cfg);
+        out
+    }
+
+    /// Mirror everything the settings page shows into the window
+    pub fn apply_to_ui(&self, app: &AppWindow) {
+        let cfg = self.config.lock().unwrap();
+        app.set_download_folder(cfg.save_to_folder.clone().into());
+        app.set_ping_sound(cfg.ping_sound);
+        app.set_nutella_sound(cfg.nutella_sound);
+        app.set_notifications(cfg.notifications);
+        app.set_mentions_only(cfg.mentions_only);
+        app.set_public_alerts(cfg.public_alerts);
+        app.set_secure_alerts(cfg.secure_alerts);
+        app.set_settings_theme(cfg.theme.clone().into());
+        app.set_settings_chat_rows(cfg.chat_rows as i32);
+        app.set_settings_parallel_downloads(cfg.max_parallel_downloads as i32);
+        app.global::<Theme>().set_midnight(cfg.theme == "midnight");
+        if let Some(scale) = cfg.ui_scale {
+            app.set_global_scale(scale);
+        }
+    }
+}
+
+fn apply_runtime(cfg: &Config) {
+    message_pipeline::configure(cfg);
+    main_helpers::apply_alert_prefs(cfg);
+    main_helpers::set_port_sharing(cfg);
+    main_helpers::set_chat_row_limit(cfg.chat_rows);
+    auto_download::configure(cfg);
+    transcript::configure(cfg);
+    file_transfer_protocol::configure(cfg);
+    rate_limit::set_limits(&cfg.rate_limit);
+    channel_idle::configure(cfg);
+    download_queue::configure(cfg);
+    phone_push::configure(cfg);
+    pairing::set_devices(&cfg.paired_devices, &cfg.nickname);
+    discovery::configure(cfg);
+    rules::configure(cfg);
+    status_endpoint::configure(cfg);
+    serial_bridge::configure(cfg);
+    clip_sync::configure(cfg);
+    secure_channel_code::configure(cfg);
+}
@@ -0,0 +1,291 @@
+// Shared snippets: configs, logs or code too long for a chat message. The chat only carries a
+// small SNIP announcement, receivers fetch the text from our file server with FSNP + id(16) and
+// get u32 len (LE) + UTF-8 back (len 0 = gone), so the UDP size limit doesn't apply. The chat
+// shows it as a code block with the first lines, "Show all", a copy button and basic
+// highlighting. Public channel only, in a secure channel it goes as a sealed file instead.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use slint::{Model, ModelRc, VecModel};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::{diagnostics, file_transfer_protocol, history, main_helpers, mute, net_runtime, notify, tray, AppWindow, ChatMessageItem, CodeLine, CodeToken};
+
+pub const SNIP_MAGIC: &[u8; 4] = b"SNIP";
+pub const FSNP_MAGIC: &[u8; 4] = b"FSNP";
+pub const MAX_BYTES: usize = 512 * 1024;
+const KEEP: usize = 32; // snippets we still serve, the oldest is dropped first
+const COLLAPSED_LINES: usize = 8;
+const MAX_SHOWN_LINES: usize = 400; // expanded, copy has the rest
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// token kinds, index into Theme.code_colors
+const PLAIN: i32 = 0;
+const KEYWORD: i32 = 1;
+const STRING: i32 = 2;
+const COMMENT: i32 = 3;
+const NUMBER: i32 = 4;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def", "do", "elif", "else", "enum", "export",
+    "extern", "false", "fi", "fn", "for", "from", "func", "function", "if", "impl", "import", "in", "interface", "let", "loop",
+    "match", "mod", "mut", "new", "nil", "None", "null", "package", "pub", "return", "self", "static", "struct", "switch", "then",
+    "this", "throw", "trait", "true", "True", "False", "try", "type", "use", "var", "void", "while", "with", "yield",
+];
+
+/// What goes out over UDP, the text itself stays on our file server
+#[derive(Serialize, Deserialize)]
+pub struct Announce {
+    pub id: [u8; 16],
+    pub tcp_port: u16,
+    pub room: String,
+    pub lang: String,
+    pub lines: u32,
+}
+
+static SHARED: Mutex<VecDeque<([u8; 16], Arc<String>)>> = Mutex::new(VecDeque::new());
+
+/// Keep `text` to be served and build its SNIP packet, an empty `lang` is guessed
+pub fn share(text: &str, lang: &str, room: &str) -> io::Result<Vec<u8>> {
+    if text.len() > MAX_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("snippets are limited to {}", file_transfer_protocol::human_size(MAX_BYTES as u64))));
+    }
+    let lang = if lang.is_empty() { guess_lang(text) } else { lang };
+    let id: [u8; 16] = *uuid::Uuid::new_v4().as_bytes();
+    {
+        let mut shared = SHARED.lock().unwrap();
+        shared.push_back((id, Arc::new(text.to_string())));
+        while shared.len() > KEEP {
+            shared.pop_front();
+        }
+    }
+    let announce = Announce { id, tcp_port: file_transfer_protocol::tcp_port(), room: room.to_string(), lang: lang.to_string(), lines: text.lines().count() as u32 };
+    let mut packet = Vec::from(SNIP_MAGIC as &[u8]);
+    packet.extend(bincode::serde::encode_to_vec(&announce, bincode::config::standard()).map_err(io::Error::other)?);
+    Ok(packet)
+}
+
+pub fn parse(bytes: &[u8]) -> Option<Announce> {
+    let payload = bytes.strip_prefix(SNIP_MAGIC)?;
+    bincode::serde::decode_from_slice(payload, bincode::config::standard()).ok().map(|(a, _)| a)
+}
+
+/// Sender side, a peer asked for the text of one of our snippets
+pub async fn serve(mut stream: TcpStream, read_limit: Duration, write_limit: Duration) -> io::Result<()> {
+    let mut request = [0u8; 20];
+    tokio::time::timeout(read_limit, stream.read_exact(&mut request))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "FSNP timed out"))??;
+    let id: [u8; 16] = request[4..].try_into().unwrap();
+    let text = SHARED.lock().unwrap().iter().find(|(s, _)| *s == id).map(|(_, t)| Arc::clone(t));
+    let text = text.as_deref().map_or("", String::as_str);
+
+    let mut reply = Vec::with_capacity(4 + text.len());
+    reply.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    reply.extend_from_slice(text.as_bytes());
+    tokio::time::timeout(write_limit, stream.write_all(&reply))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "FSNP timed out"))?
+}
+
+async fn fetch_text(addr: SocketAddr, id: [u8; 16]) -> io::Result<String> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut request = Vec::from(FSNP_MAGIC as &[u8]);
+    request.extend_from_slice(&id);
+    stream.write_all(&request).await?;
+
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len == 0 || len > MAX_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "snippet gone or too large"));
+    }
+    let mut text = vec![0u8; len];
+    stream.read_exact(&mut text).await?;
+    String::from_utf8(text).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "snippet isn't UTF-8"))
+}
+
+/// Receiver side: a snippet was announced, fetch it and add it to the chat
+pub fn fetch(ui_weak: slint::Weak<AppWindow>, sender: SocketAddr, announce: Announce) {
+    if mute::is_muted(sender.ip()) {
+        return;
+    }
+    let mut addr = sender;
+    addr.set_port(announce.tcp_port);
+    net_runtime::spawn(async move {
+        let fetched = tokio::time::timeout(FETCH_TIMEOUT, fetch_text(addr, announce.id))
+            .await
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")));
+        let text = match fetched {
+            Ok(text) => text,
+            Err(e) => {
+                diagnostics::log("snippet", format!("fetch from {} failed: {}", addr, e));
+                return;
+            }
+        };
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = ui_weak.upgrade() {
+                show(&app, sender.ip(), &announce.lang, &text);
+            }
+        });
+    });
+}
+
+fn show(app: &AppWindow, peer: IpAddr, lang: &str, text: &str) {
+    let sender = peer.to_string();
+    let mut row = row(&sender, text, lang);
+    row.ts = history::append(&sender, text, "public").to_string().into();
+    tray::note_incoming();
+    notify::message(&sender, &format!("🧩 {} snippet, {} lines", row.snippet_lang, row.snippet_lines), false);
+    push(app, row);
+}
+
+/// Add a snippet row to the chat, the oldest rows make room
+pub fn push(app: &AppWindow, row: ChatMessageItem) {
+    let model_rc = app.get_messages();
+    let Some(vec) = model_rc.as_any().downcast_ref::<VecModel<ChatMessageItem>>() else { return; };
+    vec.push(row);
+    while vec.row_count() > main_helpers::chat_row_limit() {
+        vec.remove(0);
+    }
+}
+
+/// A collapsed snippet row, `sender` as for a normal message
+pub fn row(sender: &str, text: &str, lang: &str) -> ChatMessageItem {
+    let lang = if lang.is_empty() { guess_lang(text) } else { lang };
+    ChatMessageItem {
+        sender: sender.into(),
+        color_index: main_helpers::sender_color_index(sender),
+        snippet: highlight(text, lang, COLLAPSED_LINES),
+        snippet_lang: lang.into(),
+        snippet_lines: text.lines().count().max(1) as i32,
+        snippet_open: false,
+        ..main_helpers::system_row(text.to_string())
+    }
+}
+
+/// "Show all" / "Collapse" on a snippet row
+pub fn toggle(row: &mut ChatMessageItem) {
+    row.snippet_open = !row.snippet_open;
+    let limit = if row.snippet_open { MAX_SHOWN_LINES } else { COLLAPSED_LINES };
+    row.snippet = highlight(&row.text, &row.snippet_lang, limit);
+}
+
+/// Best guess at what the text is, "text" when nothing fits
+pub fn guess_lang(text: &str) -> &'static str {
+    let start = text.trim_start();
+    if (start.starts_with('{') || start.starts_with('[')) && serde_json::from_str::<serde_json::Value>(text).is_ok() {
+        "json"
+    } else if start.starts_with("#!") && start.lines().next().is_some_and(|l| l.ends_with("sh")) {
+        "shell"
+    } else if text.contains("fn ") && (text.contains("let ") || text.contains("->")) {
+        "rust"
+    } else if text.contains("#include") {
+        "c"
+    } else if text.contains("def ") && text.contains("):") {
+        "python"
+    } else if text.contains("function ") || text.contains("=> ") || text.contains("const ") {
+        "javascript"
+    } else if text.lines().any(|l| l.starts_with('[') && l.trim_end().ends_with(']')) && text.contains(" = ") {
+        "toml"
+    } else {
+        "text"
+    }
+}
+
+// line comment marker of a language, None = no comments
+fn comment_marker(lang: &str) -> Option<&'static str> {
+    match lang {
+        "python" | "shell" | "toml" | "yaml" | "ini" | "ruby" | "perl" => Some("#"),
+        "sql" | "lua" => Some("--"),
+        "json" | "text" => None,
+        _ => Some("//"),
+    }
+}
+
+// the first `limit` lines as colored runs, every line gets at least one token so it keeps its height
+fn highlight(text: &str, lang: &str, limit: usize) -> ModelRc<CodeLine> {
+    let comment = comment_marker(lang);
+    let keywords = lang != "text" && lang != "json";
+    let lines: Vec<CodeLine> = text
+        .lines()
+        .take(limit)
+        .map(|line| {
+            let line = line.replace('\t', "    ");
+            let tokens = if lang == "text" { vec![(PLAIN, line)] } else { tokenize(&line, comment, keywords) };
+            let tokens: Vec<CodeToken> = tokens.into_iter().map(|(kind, text)| CodeToken { text: text.into(), kind }).collect();
+            CodeLine { tokens: ModelRc::new(VecModel::from(tokens)) }
+        })
+        .collect();
+    ModelRc::new(VecModel::from(lines))
+}
+
+// a longer line only gets its start highlighted, the rest stays plain
+const MAX_HIGHLIGHT_CHARS: usize = 2000;
+
+fn tokenize(line: &str, comment: Option<&str>, keywords: bool) -> Vec<(i32, String)> {
+    let (line, tail) = match line.char_indices().nth(MAX_HIGHLIGHT_CHARS) {
+        Some((cut, _)) => line.split_at(cut),
+        None => (line, ""),
+    };
+    let mut tokens: Vec<(i32, String)> = Vec::new();
+    let mut add = |kind: i32, text: &str| match tokens.last_mut() {
+        Some((last, run)) if *last == kind && kind == PLAIN => run.push_str(text),
+        _ => tokens.push((kind, text.to_string())),
+    };
+    let mut i = 0; // byte index, always on a char boundary
+    while let Some(c) = line[i..].chars().next() {
+        let rest = &line[i..];
+        if comment.is_some_and(|m| rest.starts_with(m)) {
+            add(COMMENT, rest);
+            break;
+        }
+        if c == '"' || c == '\'' {
+            // an apostrophe with nothing to close it (Rust lifetimes, "don't") is plain text
+            let mut prev = c;
+            let close = rest.char_indices().skip(1).find(|&(_, ch)| {
+                let closes = ch == c && prev != '\\';
+                prev = ch;
+                closes
+            });
+            if let Some((j, _)) = close {
+                let end = i + j + c.len_utf8();
+                add(STRING, &line[i..end]);
+                i = end;
+                continue;
+            }
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let end = rest
+                .char_indices()
+                .find(|&(_, ch)| !(ch.is_alphanumeric() || ch == '_' || (c.is_ascii_digit() && ch == '.')))
+                .map_or(line.len(), |(j, _)| i + j);
+            let word = &line[i..end];
+            let kind = if c.is_ascii_digit() {
+                NUMBER
+            } else if keywords && KEYWORDS.contains(&word) {
+                KEYWORD
+            } else {
+                PLAIN
+            };
+            add(kind, word);
+            i = end;
+            continue;
+        }
+        add(PLAIN, &rest[..c.len_utf8()]);
+        i += c.len_utf8();
+    }
+    if !tail.is_empty() {
+        add(PLAIN, tail);
+    }
+    if tokens.is_empty() {
+        tokens.push((PLAIN, String::new()));
+    }
+    tokens
+}
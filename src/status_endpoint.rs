@@ -0,0 +1,109 @@
+// Opt-in monitoring endpoint for unattended instances (a kiosk showing announcements):
+// GET /health answers 200 "ok" while we're receiving, 503 with the reason otherwise, and
+// GET /metrics has the numbers in Prometheus text format. Listens on 127.0.0.1 only, on
+// Config.status_port (/status), so a scraper on the same machine or a tunnel is needed.
+
+use std::fmt::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use tokio::sync::oneshot;
+
+use crate::classes::Config;
+use crate::{diagnostics, download_queue, main_helpers, net_runtime, peers, tcp_file_server, udp_receiver};
+
+struct Running {
+    port: u16,
+    shutdown: oneshot::Sender<()>,
+}
+
+static RUNNING: Mutex<Option<Running>> = Mutex::new(None);
+static STARTED: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Mirror the config: start, move or stop the endpoint
+pub fn configure(cfg: &Config) {
+    STARTED.lock().unwrap().get_or_insert_with(Instant::now);
+    let mut running = RUNNING.lock().unwrap();
+    if running.as_ref().map(|r| r.port) == cfg.status_port {
+        return;
+    }
+    if let Some(old) = running.take() {
+        let _ = old.shutdown.send(());
+    }
+    let Some(port) = cfg.status_port else { return; };
+
+    // bind here so a busy port shows up in the log right away
+    let listener = match std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, port)).and_then(|l| l.set_nonblocking(true).map(|()| l)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            diagnostics::log("status", format!("bind on 127.0.0.1:{} failed: {}", port, e));
+            return;
+        }
+    };
+    let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+    *running = Some(Running { port, shutdown });
+
+    net_runtime::spawn(async move {
+        let Ok(listener) = tokio::net::TcpListener::from_std(listener) else { return; };
+        let router = Router::new().route("/health", get(health)).route("/metrics", get(metrics));
+        let served = axum::serve(listener, router)
+            .with_graceful_shutdown(async move {
+                let _ = shutdown_rx.await;
+            })
+            .await;
+        if let Err(e) = served {
+            diagnostics::log("status", format!("server error: {}", e));
+        }
+    });
+}
+
+/// "/status" without arguments
+pub fn summary(cfg: &Config) -> String {
+    match cfg.status_port {
+        Some(port) => format!("📈 Status endpoint on http://{}/health and /metrics", SocketAddr::from((Ipv4Addr::LOCALHOST, port))),
+        None => "📈 Status endpoint off".to_string(),
+    }
+}
+
+// None while healthy, else what's wrong
+fn problem() -> Option<String> {
+    main_helpers::receive_error().map(|e| format!("not receiving: {}", e))
+}
+
+async fn health() -> impl IntoResponse {
+    match problem() {
+        None => (StatusCode::OK, "ok\n".to_string()),
+        Some(reason) => (StatusCode::SERVICE_UNAVAILABLE, format!("{}\n", reason)),
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+}
+
+async fn metrics() -> impl IntoResponse {
+    let mut out = String::new();
+    let uptime = STARTED.lock().unwrap().map_or(0, |at| at.elapsed().as_secs());
+    let (downloads, waiting) = download_queue::depth();
+    metric(&mut out, "lanchgo_up", "gauge", "The instance answers", 1);
+    metric(&mut out, "lanchgo_uptime_seconds", "counter", "Seconds since start", uptime);
+    metric(&mut out, "lanchgo_receiver_up", "gauge", "1 while UDP receiving works", u8::from(main_helpers::receive_error().is_none()));
+    metric(&mut out, "lanchgo_file_server_up", "gauge", "1 when the TCP file server is listening", u8::from(main_helpers::file_server_error().is_none()));
+    let last_packet = udp_receiver::last_packet_age().map_or(-1, |age| age.as_secs() as i64);
+    metric(&mut out, "lanchgo_last_packet_age_seconds", "gauge", "Seconds since the last packet from a peer, -1 = none yet", last_packet);
+    metric(&mut out, "lanchgo_peers", "gauge", "Peers seen on the LAN", peers::count());
+    metric(&mut out, "lanchgo_uploads_active", "gauge", "File server connections being served", tcp_file_server::active_transfers());
+    metric(&mut out, "lanchgo_downloads_active", "gauge", "Downloads running", downloads);
+    metric(&mut out, "lanchgo_download_queue_depth", "gauge", "Downloads waiting for a slot", waiting);
+
+    let _ = writeln!(out, "# HELP lanchgo_errors_total Errors written to diagnostics.log, by area\n# TYPE lanchgo_errors_total counter");
+    for (area, count) in diagnostics::counts() {
+        let _ = writeln!(out, "lanchgo_errors_total{{area=\"{}\"}} {}", area.replace(['\\', '"'], "_"), count);
+    }
+    (StatusCode::OK, [(header::CONTENT_TYPE, HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"))], out)
+}
@@ -0,0 +1,141 @@
+// End-to-end driving without a display, only built with `--features test-hooks`.
+//
+// LANCHGO_HEADLESS=1 renders into Slint's testing backend instead of a window.
+// LANCHGO_TEST_SCRIPT=<file> runs one step per line on a background thread, then exits with
+// 0 when every expect matched or 1 at the first one that didn't:
+//
+//   packet <text>          UDP packet with this text to our own port over loopback, so it goes
+//   packet-hex <hex>       through the real receiver exactly like one from a peer
+//   input <text>           type into the chat input and press Send (commands work too)
+//   expect-chat <text>     a chat row containing <text> shows up within EXPECT_TIMEOUT
+//   expect-offer <text>    same for a row in the files panel
+//   expect-toast <text>    same for the temporary status message
+//   wait <ms>
+//   quit
+//
+// The functions below are the same steps for code that drives the app itself.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use slint::Model;
+
+use crate::{main_helpers, AppWindow};
+
+const EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Call before the first window is created
+pub fn init_headless_if_requested() {
+    if std::env::var_os("LANCHGO_HEADLESS").is_some() {
+        i_slint_backend_testing::init_integration_test_with_system_time();
+    }
+}
+
+/// Run `f` on the UI thread and hand its result back, None once the window is gone
+pub fn on_ui<T: Send + 'static>(f: impl FnOnce(&AppWindow) -> T + Send + 'static) -> Option<T> {
+    let weak = main_helpers::app_handle()?;
+    let (tx, rx) = mpsc::channel();
+    slint::invoke_from_event_loop(move || {
+        if let Some(app) = weak.upgrade() {
+            let _ = tx.send(f(&app));
+        }
+    })
+    .ok()?;
+    rx.recv_timeout(EXPECT_TIMEOUT).ok()
+}
+
+/// Deliver `bytes` to our receiver as if a peer on 127.0.0.1 had sent them
+pub fn inject_packet(port: u16, bytes: &[u8]) -> std::io::Result<()> {
+    let sock = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    sock.send_to(bytes, SocketAddr::from((Ipv4Addr::LOCALHOST, port)))?;
+    Ok(())
+}
+
+/// "sender: text" for every row in the chat view
+pub fn chat_rows(app: &AppWindow) -> Vec<String> {
+    app.get_messages().iter().map(|m| format!("{}: {}", m.sender, m.text)).collect()
+}
+
+/// "sender: name (size) progress" for every row in the files panel
+pub fn file_offer_rows(app: &AppWindow) -> Vec<String> {
+    app.get_file_offer()
+        .iter()
+        .map(|o| format!("{}: {} ({}) {}", o.sender, o.name, o.size_text, o.progress_text))
+        .collect()
+}
+
+/// Type `text` and press Send
+pub fn send_input(app: &AppWindow, text: &str) {
+    app.set_input_text(text.into());
+    app.invoke_send_clicked();
+}
+
+// polls `read` on the UI thread until one of its lines contains `needle`
+fn expect(needle: &str, read: fn(&AppWindow) -> Vec<String>) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < EXPECT_TIMEOUT {
+        if on_ui(read).is_some_and(|lines| lines.iter().any(|l| l.contains(needle))) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+fn hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// Start the LANCHGO_TEST_SCRIPT runner if one is set, `udp_port` tells where we listen
+pub fn start_script(udp_port: impl Fn() -> u16 + Send + 'static) {
+    let Some(path) = std::env::var_os("LANCHGO_TEST_SCRIPT") else { return; };
+    std::thread::spawn(move || {
+        let script = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[test] can't read script: {}", e);
+                std::process::exit(1);
+            }
+        };
+        // let the window and the receiver come up
+        std::thread::sleep(Duration::from_millis(500));
+
+        for (n, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (step, arg) = line.split_once(' ').unwrap_or((line, ""));
+            let ok = match step {
+                "packet" => inject_packet(udp_port(), arg.as_bytes()).is_ok(),
+                "packet-hex" => hex_bytes(arg).is_some_and(|bytes| inject_packet(udp_port(), &bytes).is_ok()),
+                "input" => {
+                    let text = arg.to_string();
+                    on_ui(move |app| send_input(app, &text)).is_some()
+                }
+                "expect-chat" => expect(arg, chat_rows),
+                "expect-offer" => expect(arg, file_offer_rows),
+                "expect-toast" => expect(arg, |app| vec![app.get_temp_message().to_string()]),
+                "wait" => {
+                    std::thread::sleep(Duration::from_millis(arg.parse().unwrap_or(0)));
+                    true
+                }
+                "quit" => std::process::exit(0),
+                _ => {
+                    eprintln!("[test] line {}: unknown step {}", n + 1, step);
+                    false
+                }
+            };
+            if !ok {
+                eprintln!("[test] line {} failed: {}", n + 1, line);
+                std::process::exit(1);
+            }
+            println!("[test] ok: {}", line);
+        }
+        std::process::exit(0);
+    });
+}
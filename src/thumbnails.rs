@@ -0,0 +1,121 @@
+// Small previews for image offers, shown in the files panel instead of just a name.
+// The receiver asks the sender's file server with FTHB + offer_id(16) and gets u32 len (LE) +
+// PNG back, len 0 = no preview (not an image, a channel offer, unreadable). Builds without
+// FTHB take it for a mobile request and hang up, the row just keeps its plain look.
+
+use std::io::{self, Cursor};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use image::ImageFormat;
+use slint::{Image, Model, Rgba8Pixel, SharedPixelBuffer, VecModel};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::file_transfer_protocol::{FileOffer, LocalFileOffer, OfferKind, OfferRegistry};
+use crate::{channel_files, net_runtime, peers, AppWindow, FileOfferItem};
+
+pub const FTHB_MAGIC: &[u8; 4] = b"FTHB";
+const THUMB_SIZE: u32 = 64; // px, longest side
+const MAX_THUMB_BYTES: usize = 64 * 1024;
+const MAX_SOURCE_BYTES: u64 = 20 * 1024 * 1024; // not worth decoding just for a preview
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "gif", "bmp", "webp", "ico"];
+
+pub fn is_image_name(name: &str) -> bool {
+    name.rsplit_once('.')
+        .is_some_and(|(_, ext)| IMAGE_EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+// the PNG for one of our offers, empty when there's nothing to show
+fn make(local: &LocalFileOffer) -> Vec<u8> {
+    if !matches!(local.kind, OfferKind::SingleFile) || !is_image_name(&local.name) || local.size > MAX_SOURCE_BYTES {
+        return Vec::new();
+    }
+    let Ok(img) = image::open(&local.path) else { return Vec::new(); };
+    let mut png = Vec::new();
+    if img.thumbnail(THUMB_SIZE, THUMB_SIZE).write_to(&mut Cursor::new(&mut png), ImageFormat::Png).is_err() || png.len() > MAX_THUMB_BYTES {
+        return Vec::new();
+    }
+    png
+}
+
+/// Sender side, a peer asked for the preview of one of our offers
+pub async fn serve(mut stream: TcpStream, registry: Arc<Mutex<OfferRegistry>>, read_limit: Duration, write_limit: Duration) -> io::Result<()> {
+    let mut request = [0u8; 20];
+    tokio::time::timeout(read_limit, stream.read_exact(&mut request))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "FTHB timed out"))??;
+    let offer_id: [u8; 16] = request[4..].try_into().unwrap();
+
+    // channel offers stay between members, a preview would leak what they are
+    let local = if channel_files::is_channel_offer(&offer_id) { None } else { registry.lock().unwrap().get(&offer_id).cloned() };
+    let png = match local {
+        Some(local) => tokio::task::spawn_blocking(move || make(&local)).await.unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    let mut reply = Vec::with_capacity(4 + png.len());
+    reply.extend_from_slice(&(png.len() as u32).to_le_bytes());
+    reply.extend_from_slice(&png);
+    tokio::time::timeout(write_limit, stream.write_all(&reply))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "FTHB timed out"))?
+}
+
+async fn fetch(addr: SocketAddr, offer_id: [u8; 16]) -> io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut request = Vec::from(FTHB_MAGIC as &[u8]);
+    request.extend_from_slice(&offer_id);
+    stream.write_all(&request).await?;
+
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_THUMB_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "thumbnail too large"));
+    }
+    let mut png = vec![0u8; len];
+    stream.read_exact(&mut png).await?;
+    Ok(png)
+}
+
+fn decode(png: &[u8]) -> Option<SharedPixelBuffer<Rgba8Pixel>> {
+    let rgba = image::load_from_memory_with_format(png, ImageFormat::Png).ok()?.to_rgba8();
+    if rgba.width() > THUMB_SIZE || rgba.height() > THUMB_SIZE {
+        return None;
+    }
+    Some(SharedPixelBuffer::clone_from_slice(rgba.as_raw(), rgba.width(), rgba.height()))
+}
+
+fn set_row_thumbnail(app: &AppWindow, offer_id: &str, thumbnail: Image) {
+    let model_rc = app.get_file_offer();
+    if let Some(vec) = model_rc.as_any().downcast_ref::<VecModel<FileOfferItem>>() {
+        if let Some(i) = (0..vec.row_count()).find(|&i| vec.row_data(i).is_some_and(|row| row.offer_id.as_str() == offer_id)) {
+            let mut row = vec.row_data(i).unwrap();
+            row.thumbnail = thumbnail;
+            vec.set_row_data(i, row);
+        }
+    }
+}
+
+/// Receiver side: a peer's offer arrived, ask for its preview when it's an image
+pub fn request(ui_weak: slint::Weak<AppWindow>, sender: SocketAddr, offer: &FileOffer) {
+    if !matches!(offer.kind, OfferKind::SingleFile) || !is_image_name(&offer.name) || !peers::supports(sender.ip(), peers::CAP_THUMBNAILS) {
+        return;
+    }
+    let mut addr = sender;
+    addr.set_port(offer.tcp_port);
+    let offer_id = offer.offer_id;
+    net_runtime::spawn(async move {
+        let Ok(Ok(png)) = tokio::time::timeout(FETCH_TIMEOUT, fetch(addr, offer_id)).await else { return; };
+        let Some(pixels) = tokio::task::spawn_blocking(move || decode(&png)).await.ok().flatten() else { return; };
+        let id_hex = crate::file_transfer_protocol::offer_id_to_hex(&offer_id);
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = ui_weak.upgrade() {
+                set_row_thumbnail(&app, &id_hex, Image::from_rgba8(pixels));
+            }
+        });
+    });
+}
@@ -0,0 +1,236 @@
+// Signed transcripts for secure channels. Every member keeps the channel's chat lines,
+// when the host closes the channel (with /signlog on) it signs the transcript hash with its
+// own ed25519 key and sends the signature inside the channel. Each member saves its copy
+// with that signature under transcripts/, /verifylog <file> checks one later.
+//
+// The record carries the key it was signed with, so a valid signature alone says nothing.
+// A member only takes one from the address its channel announcement came from, and only once
+// the host there proved it holds that key (identity::prove). /verifylog wants the key to be
+// ours or in the contact book.
+
+use std::fs::File;
+use std::future::Future;
+use std::io::BufReader;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::classes::Config;
+use crate::contacts::{self, Trust};
+use crate::identity::{self, fingerprint};
+use crate::peers;
+use crate::main_helpers::get_config_path;
+use crate::secure_channel_code::{self, Channel};
+
+/// ENCM plaintext carrying the host's signature instead of a chat line
+pub const SIGNATURE_PREFIX: &str = "\u{1}TSIG";
+
+const SIGNING_CONTEXT: &[u8] = b"LanChGo transcript v1";
+
+static SIGN: AtomicBool = AtomicBool::new(false);
+// chat lines of the current channel, in arrival order
+static LINES: Mutex<Vec<TranscriptLine>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TranscriptLine {
+    pub nonce: String, // hex, unique per message
+    pub sender: String,
+    pub text: String,
+}
+
+/// What the host signs, hex everywhere so the saved file stays readable
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignedTranscript {
+    pub channel: String, // channel salt
+    pub messages: u64,
+    pub hash: String,
+    pub closed_at: i64, // unix seconds
+    pub host_key: String,
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TranscriptFile {
+    record: SignedTranscript,
+    messages: Vec<TranscriptLine>,
+}
+
+/// Config.sign_transcripts (/signlog), read when a hosted channel closes
+pub fn configure(cfg: &Config) {
+    SIGN.store(cfg.sign_transcripts, Ordering::Relaxed);
+}
+
+/// A chat line that arrived in the secure channel (our own come back over the broadcast too)
+pub fn record(sender: &str, nonce: &[u8; 12], text: &str) {
+    LINES.lock().unwrap().push(TranscriptLine { nonce: to_hex(nonce), sender: sender.to_string(), text: text.to_string() });
+}
+
+/// Forget the lines, the channel is gone
+pub fn clear() {
+    LINES.lock().unwrap().clear();
+}
+
+// Lines are hashed in nonce order, not arrival order, so members that got two messages
+// the other way round still end up with the same hash.
+fn transcript_hash(lines: &[TranscriptLine]) -> [u8; 32] {
+    let mut sorted: Vec<&TranscriptLine> = lines.iter().collect();
+    sorted.sort_by(|a, b| a.nonce.cmp(&b.nonce));
+
+    let mut hash = [0u8; 32];
+    for line in sorted {
+        let mut h = Sha256::new();
+        h.update(hash);
+        h.update(line.nonce.as_bytes());
+        h.update((line.sender.len() as u32).to_be_bytes());
+        h.update(line.sender.as_bytes());
+        h.update((line.text.len() as u32).to_be_bytes());
+        h.update(line.text.as_bytes());
+        hash = h.finalize().into();
+    }
+    hash
+}
+
+fn signed_bytes(channel: &str, messages: u64, hash: &str, closed_at: i64) -> Vec<u8> {
+    let mut bytes = Vec::from(SIGNING_CONTEXT);
+    bytes.extend_from_slice(channel.as_bytes());
+    bytes.extend_from_slice(&messages.to_be_bytes());
+    bytes.extend_from_slice(hash.as_bytes());
+    bytes.extend_from_slice(&closed_at.to_be_bytes());
+    bytes
+}
+
+// ===================== host side =====================
+
+/// Sign and save the transcript as the host, returns the ENCM packet telling the members.
+/// None with /signlog off or nothing said. Call before the channel is destroyed.
+pub fn sign_and_close(channel: &Channel) -> Option<Vec<u8>> {
+    if !SIGN.load(Ordering::Relaxed) {
+        return None;
+    }
+    let lines = LINES.lock().unwrap().clone();
+    if lines.is_empty() {
+        return None;
+    }
+    let key = identity::signing_key()?;
+
+    let channel_id = to_hex(&channel.salt);
+    let hash = to_hex(&transcript_hash(&lines));
+    let closed_at = chrono::Local::now().timestamp();
+    let signature = key.sign(&signed_bytes(&channel_id, lines.len() as u64, &hash, closed_at));
+    let record = SignedTranscript {
+        channel: channel_id,
+        messages: lines.len() as u64,
+        hash,
+        closed_at,
+        host_key: to_hex(key.verifying_key().as_bytes()),
+        signature: to_hex(&signature.to_bytes()),
+    };
+    let _ = save(&record, lines);
+
+    let json = serde_json::to_string(&record).ok()?;
+    let encrypted = secure_channel_code::encrypt_message(&channel.key, &format!("{}{}", SIGNATURE_PREFIX, json));
+    let payload = bincode::serde::encode_to_vec(&encrypted, bincode::config::standard()).ok()?;
+    let mut packet = Vec::from(b"ENCM" as &[u8]);
+    packet.extend_from_slice(&payload);
+    Some(packet)
+}
+
+// ===================== member side =====================
+
+/// The host's signature inside a decrypted ENCM message, None for ordinary chat text
+pub fn parse_signature(plain: &str) -> Option<SignedTranscript> {
+    serde_json::from_str(plain.strip_prefix(SIGNATURE_PREFIX)?).ok()
+}
+
+/// A signature `from` someone in the channel: pin it to the channel host and its proven key,
+/// check it against our own copy and save it. Resolves to the toast text. Our copy is taken
+/// right away, the channel may be gone by the time the host answered the proof.
+pub fn accept_signature(record: SignedTranscript, from: IpAddr) -> impl Future<Output = String> + Send + 'static {
+    let lines = LINES.lock().unwrap().clone();
+    let from_host = secure_channel_code::channel_host() == Some(from);
+    async move {
+        if !from_host {
+            return format!("⚠️ A transcript signature came from {}, not the channel host, ignored", from);
+        }
+        let fingerprint = fingerprint(&record.host_key).to_string();
+        if !identity::prove(from, peers::file_port(from), &record.host_key).await {
+            return format!("⚠️ The host couldn't prove it holds key {}, transcript not saved", fingerprint);
+        }
+        if check_signature(&record).is_err() {
+            return "⚠️ The host's transcript signature is invalid, not saved".to_string();
+        }
+        let ours = to_hex(&transcript_hash(&lines));
+        if ours != record.hash || lines.len() as u64 != record.messages {
+            return format!("⚠️ Host {} signed {} messages, your copy differs ({})", fingerprint, record.messages, lines.len());
+        }
+        match save(&record, lines) {
+            Some(path) => format!("🔏 Transcript signed by host {}, saved to {}", fingerprint, path.display()),
+            None => format!("🔏 Transcript signed by host {}, but it could not be saved", fingerprint),
+        }
+    }
+}
+
+// ===================== files =====================
+
+fn transcripts_dir() -> PathBuf {
+    get_config_path().with_file_name("transcripts")
+}
+
+fn save(record: &SignedTranscript, messages: Vec<TranscriptLine>) -> Option<PathBuf> {
+    let dir = transcripts_dir();
+    std::fs::create_dir_all(&dir).ok()?;
+    let stamp = chrono::Local::now().format("%Y-%m-%d_%H%M%S");
+    // host_key is checked hex by now, safe in a file name
+    let path = dir.join(format!("{}_{}.json", stamp, fingerprint(&record.host_key)));
+    let file = File::create(&path).ok()?;
+    serde_json::to_writer_pretty(file, &TranscriptFile { record: record.clone(), messages }).ok()?;
+    Some(path)
+}
+
+fn check_signature(record: &SignedTranscript) -> Result<(), String> {
+    let key: [u8; 32] = from_hex(&record.host_key).and_then(|k| k.try_into().ok()).ok_or("bad host key")?;
+    let signature: [u8; 64] = from_hex(&record.signature).and_then(|s| s.try_into().ok()).ok_or("bad signature")?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| "bad host key")?;
+    key.verify(
+        &signed_bytes(&record.channel, record.messages, &record.hash, record.closed_at),
+        &Signature::from_bytes(&signature),
+    )
+    .map_err(|_| "signature doesn't match".to_string())
+}
+
+/// "/verifylog file": is a saved transcript still what the host signed
+pub fn verify_file(path: &Path) -> Result<String, String> {
+    let file = File::open(path).map_err(|e| format!("can't open {}: {}", path.display(), e))?;
+    let saved: TranscriptFile = serde_json::from_reader(BufReader::new(file)).map_err(|_| "not a transcript file".to_string())?;
+    let key = &saved.record.host_key;
+    let signer = if *key == identity::public_hex() {
+        "you".to_string()
+    } else {
+        match contacts::get(key) {
+            Some(c) if c.trust == Trust::Blocked => return Err(format!("signed by {}, a blocked contact", c.nickname)),
+            Some(c) => format!("{} ({})", c.nickname, c.trust.as_str()),
+            None => return Err(format!("signed by {}, a key not in your contacts", fingerprint(key))),
+        }
+    };
+    check_signature(&saved.record)?;
+    if saved.messages.len() as u64 != saved.record.messages || to_hex(&transcript_hash(&saved.messages)) != saved.record.hash {
+        return Err("messages were changed after signing".to_string());
+    }
+    Ok(format!("{} messages, signed by host {} {}", saved.record.messages, signer, fingerprint(key)))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
@@ -0,0 +1,152 @@
+// Tray icon: closing the window hides it there, messages that arrive meanwhile put a
+// badge on the icon, clicking it brings the window back.
+// Windows only for now. tray-icon needs a GTK loop of its own on Linux, there the
+// window still closes the app like before.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use slint::ComponentHandle;
+
+use crate::AppWindow;
+
+static HIDDEN: AtomicBool = AtomicBool::new(false);
+static UNREAD: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::cell::RefCell;
+    use std::sync::OnceLock;
+
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+    use tray_icon::{Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+
+    use crate::AppWindow;
+
+    const ICON_PNG: &[u8] = include_bytes!("../ui/assets/LanChGo_icon.png");
+    const ICON_SIZE: u32 = 32;
+
+    // TrayIcon is not Send, it lives on the UI thread
+    thread_local! {
+        static TRAY: RefCell<Option<TrayIcon>> = const { RefCell::new(None) };
+    }
+
+    fn base_rgba() -> Option<&'static Vec<u8>> {
+        static RGBA: OnceLock<Option<Vec<u8>>> = OnceLock::new();
+        RGBA.get_or_init(|| {
+            let img = image::load_from_memory(ICON_PNG).ok()?;
+            Some(img.resize_exact(ICON_SIZE, ICON_SIZE, image::imageops::FilterType::Lanczos3).to_rgba8().into_raw())
+        })
+        .as_ref()
+    }
+
+    // red dot in the top right corner while something is unread
+    fn icon(badge: bool) -> Option<Icon> {
+        let mut rgba = base_rgba()?.clone();
+        if badge {
+            let size = ICON_SIZE as i32;
+            let (cx, cy, r) = (size - 8, 7, 7);
+            for y in 0..size {
+                for x in 0..size {
+                    if (x - cx) * (x - cx) + (y - cy) * (y - cy) <= r * r {
+                        let i = ((y * size + x) * 4) as usize;
+                        rgba[i..i + 4].copy_from_slice(&[0xe5, 0x39, 0x35, 0xff]);
+                    }
+                }
+            }
+        }
+        Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE).ok()
+    }
+
+    pub fn init(weak: slint::Weak<AppWindow>) -> bool {
+        let menu = Menu::new();
+        let open = MenuItem::new("Open LanChGo", true, None);
+        let quit = MenuItem::new("Quit", true, None);
+        if menu.append_items(&[&open, &quit]).is_err() {
+            return false;
+        }
+        let (open_id, quit_id) = (open.id().clone(), quit.id().clone());
+
+        let Some(icon) = icon(false) else { return false; };
+        let Ok(tray) = TrayIconBuilder::new()
+            .with_tooltip("LanChGo")
+            .with_icon(icon)
+            .with_menu(Box::new(menu))
+            .build()
+        else {
+            return false;
+        };
+        TRAY.with(|t| *t.borrow_mut() = Some(tray));
+
+        {
+            let weak = weak.clone();
+            TrayIconEvent::set_event_handler(Some(move |event| {
+                if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                    super::restore_later(weak.clone());
+                }
+            }));
+        }
+        MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+            if event.id == open_id {
+                super::restore_later(weak.clone());
+            } else if event.id == quit_id {
+                let _ = slint::invoke_from_event_loop(|| {
+                    let _ = slint::quit_event_loop();
+                });
+            }
+        }));
+        true
+    }
+
+    pub fn show_unread(count: usize) {
+        TRAY.with(|t| {
+            if let Some(tray) = t.borrow().as_ref() {
+                let _ = tray.set_icon(icon(count > 0));
+                let tip = if count == 0 { "LanChGo".to_string() } else { format!("LanChGo — {} unread", count) };
+                let _ = tray.set_tooltip(Some(tip));
+            }
+        });
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod imp {
+    pub fn init(_weak: slint::Weak<crate::AppWindow>) -> bool {
+        false
+    }
+    pub fn show_unread(_count: usize) {}
+}
+
+/// Create the tray icon, false when there is none (closing the window should quit then)
+pub fn init(app: &AppWindow) -> bool {
+    imp::init(app.as_weak())
+}
+
+/// The window was just closed into the tray
+pub fn set_hidden() {
+    HIDDEN.store(true, Ordering::Relaxed);
+}
+
+pub fn is_hidden() -> bool {
+    HIDDEN.load(Ordering::Relaxed)
+}
+
+/// Count a peer message while the window is in the tray
+pub fn note_incoming() {
+    if HIDDEN.load(Ordering::Relaxed) && !crate::main_helpers::channel_muted() {
+        let unread = UNREAD.fetch_add(1, Ordering::Relaxed) + 1;
+        imp::show_unread(unread);
+    }
+}
+
+/// Bring the window back (tray click, notification click). Callable from any thread,
+/// the window is only touched on the UI thread.
+pub fn restore_later(weak: slint::Weak<AppWindow>) {
+    let _ = slint::invoke_from_event_loop(move || {
+        let Some(app) = weak.upgrade() else { return; };
+        let _ = app.show();
+        crate::notify::focus_window(&app);
+        HIDDEN.store(false, Ordering::Relaxed);
+        UNREAD.store(0, Ordering::Relaxed);
+        imp::show_unread(0);
+    });
+}
@@ -1,314 +1,601 @@
+// What the packets mean: chat, channel handshakes, offers, pings. The sockets and the receive
+// loops are lanchgo_core::udp_receiver, this is the on_packet it runs for every packet.
+
 use crate::AppWindow;
+use crate::classes::SharedSocket;
 use crate::FileOfferItem;
+use crate::file_transfer_protocol::OfferRegistry;
 use crate::file_transfer_protocol::RemoteMobileOfferRegistry;
 use crate::file_transfer_protocol::RemoteWindowsOfferRegistry;
 use crate::main_helpers;
-use crate::phone_protocol;
 use crate::secure_channel_code;
-use bincode;
 use slint;
-use std::io;
-use std::net::UdpSocket;
-use std::sync::{ Arc, Mutex, atomic::{AtomicBool, Ordering}, };
-use std::thread::{self, JoinHandle};
+use std::net::IpAddr;
+use std::sync::{ Arc, Mutex, atomic::AtomicBool, };
+use tokio::task::JoinHandle;
+use crate::net_runtime;
 use crate::main_helpers::get_local_ipv4;
-//use crate::file_transfer_protocol; // optional (you call it via crate::file_transfer_protocol::... but this is still fine)
-//use crate::helpers::get_local_ipv4; // adjust path to wherever you moved get_local_ipv4()
+
+pub use lanchgo_core::udp_receiver::*;
+
+/// What on_packet needs besides the packet
+struct Dispatcher {
+    ui_weak: slint::Weak<AppWindow>,
+    channel_mode: Arc<Mutex<String>>,
+    current_room: Arc<Mutex<String>>,
+    remote_windows_offers: Arc<Mutex<RemoteWindowsOfferRegistry>>,
+    remote_mobile_offers: Arc<Mutex<RemoteMobileOfferRegistry>>,
+    offer_registry: Arc<Mutex<OfferRegistry>>,
+    announce_replies: Mutex<AnnounceReplies>,
+}
 
 pub fn start_udp_receiver( 
-    sock: Arc<UdpSocket>,
+    sock: Arc<SharedSocket>,
     running: Arc<AtomicBool>, 
     ui_weak: slint::Weak<AppWindow>, 
     channel_mode: Arc<Mutex<String>>, 
+    current_room: Arc<Mutex<String>>,
     remote_windows_offers: Arc<Mutex<RemoteWindowsOfferRegistry>>,
     remote_mobile_offers: Arc <Mutex<RemoteMobileOfferRegistry>>,
+    offer_registry: Arc<Mutex<OfferRegistry>>,
 ) -> JoinHandle<()> {
-    thread::spawn(move || {
-        let mut buf = [0u8; 2048];
-        let my_ip: Option<std::net::IpAddr> = get_local_ipv4().map(std::net::IpAddr::V4);
+    let events_weak = ui_weak.clone();
+    let dispatcher = Dispatcher {
+        ui_weak,
+        channel_mode,
+        current_room,
+        remote_windows_offers,
+        remote_mobile_offers,
+        offer_registry,
+        announce_replies: Mutex::new(AnnounceReplies::new()),
+    };
+    lanchgo_core::udp_receiver::start_udp_receiver(
+        sock,
+        running,
+        get_local_ipv4().map(IpAddr::V4),
+        move |packet| dispatch(&dispatcher, packet),
+        move |event| match event {
+            ReceiverEvent::Log(line) => crate::diagnostics::log("receiver", line),
+            ReceiverEvent::Down(error) => {
+                let weak = events_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_receiver_down(error.into());
+                    }
+                });
+            }
+            ReceiverEvent::Up => {
+                let weak = events_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        main_helpers::set_receive_error(&app, None);
+                    }
+                });
+            }
+        },
+    )
+}
 
-        while running.load(Ordering::Relaxed) {
-            match sock.recv_from(&mut buf) {
-                Ok((n, _from)) => {
-                    let msg_bytes = &buf[..n];
-                    let mode = {
-                        let cm = channel_mode.lock().unwrap();
-                        cm.clone()
-                    };
-                    
-                    // ─── Secure Channel Mode ──────────────────────────────────────────────
-                    if mode == "joined" || mode == "host" {
-                        // 🛰 Step 1: Handle announcements
-                        if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"ANCH" {
-                            if let Some(ip) = my_ip {
-                                if _from.ip() == ip {
-                                    continue;
-                                }
-                            }
-                            let payload = &msg_bytes[4..];
+/// A channel member offered a file: its row (🔒) in the files panel, once per offer
+fn show_channel_offer(ui_weak: &slint::Weak<AppWindow>, from: std::net::SocketAddr, offer: crate::file_transfer_protocol::FileOffer) {
+    if !crate::channel_files::register_remote(from, offer.clone()) {
+        return;
+    }
+    let weak = ui_weak.clone();
+    let id_hex = crate::file_transfer_protocol::offer_id_to_hex(&offer.offer_id);
+    let display_name = crate::file_transfer_protocol::truncate_name(&offer.name, 16);
+    let size_text = crate::file_transfer_protocol::human_size(offer.size);
+    let auto = crate::auto_download::wants(from.ip(), offer.size);
+    let sender = crate::peers::label(from.ip(), None);
+    crate::notify::file_offer(&sender, &offer.name, &size_text);
+    slint::invoke_from_event_loop(move || {
+        if let Some(app) = weak.upgrade() {
+            app.invoke_add_file_offer(FileOfferItem {
+                offer_id: id_hex.clone().into(),
+                name: format!("🔒 {}", display_name).into(),
+                size_text: size_text.into(),
+                is_downloading: false,
+                progress_text: "".into(),
+                is_mobile: false,
+                is_own: false,
+                sender: sender.into(),
+                collapsed: false,
+                thumbnail: Default::default(),
+                saved: false,
+                queued: 0,
+            });
+            if auto {
+                app.invoke_download_offer(id_hex.into());
+            }
+        }
+    })
+    .ok();
+}
 
-                            if secure_channel_code::store_announcement(payload) {
-                                continue; // Successfully handled as announcement
-                            }
-                        } else if msg_bytes.len() >= 5 && &msg_bytes[..5] == b"MANCH" {
-                            let payload = &msg_bytes[5..];
-                            if phone_protocol::store_announcement_phone(payload) {
-                                // ok
-                            } else {
-                                // failed
-                            }
-                            continue;
-                        }
-                        // 🔒 Step 2: Handle encrypted messages
-                        else if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"ENCM" {
-                            let payload = &msg_bytes[4..]; // Strip header
+/// Handle one packet, returns the replies for its sender
+fn dispatch(d: &Dispatcher, packet: Datagram<'_>) -> Vec<Vec<u8>> {
+    let Dispatcher { ui_weak, channel_mode, current_room, remote_windows_offers, remote_mobile_offers, offer_registry, announce_replies } = d;
+    let Datagram { bytes: msg_bytes, from, from_self, local_port } = packet;
+    let mut replies = Vec::new();
+    if !from_self {
+        crate::peers::note_heard(from.ip());
+    }
 
-                            if let Some(decrypted) =
-                                secure_channel_code::decrypt_message_from_bytes(
-                                    payload,
-                                )
-                            {
-                                let weak = ui_weak.clone();
-                                slint::invoke_from_event_loop(move || {
-                                    if let Some(app) = weak.upgrade() {
-                                        if decrypted.eq_ignore_ascii_case("ping") {
-                                            secure_channel_code::play_ping_sound();
-                                        } else if decrypted.to_ascii_lowercase().contains("nutella") {
-                                            main_helpers::play_nutella_sound();
-                                        }
-                                        if !decrypted.eq_ignore_ascii_case("/exit")
-                                            || !decrypted.eq_ignore_ascii_case("/clear")
-                                            || !decrypted.eq_ignore_ascii_case( "/disconnect")
-                                            || !decrypted.eq_ignore_ascii_case( "/clearfiles")
-                                            || !decrypted.eq_ignore_ascii_case( "/clearall")
-                                            || !decrypted.eq_ignore_ascii_case( "/webjoin")                
-                                            || !decrypted.eq_ignore_ascii_case( "/webstop")
-                                            || !decrypted.eq_ignore_ascii_case( "/restart")
-                                            || !decrypted.eq_ignore_ascii_case( "/downloads")
-                                        {
-                                            app.invoke_append_message(decrypted.into(),);
-                                        }
-                                    }
-                                })
-                                .ok();
-                            }
-                            continue; // Done with encrypted message
-                        } else if msg_bytes.len() >= 5 && &msg_bytes[..5] == b"MENCM" {
-                            if let Some(ip) = my_ip {
-                                if _from.ip() == ip {
-                                    continue;
-                                }
-                            }
-                            if msg_bytes.len() > 17 {
-                                let nonce = &msg_bytes[5..17];
-                                let ciphertext = &msg_bytes[17..];
-                                if let Some(channel) =
-                                    secure_channel_code::get_active_channel()
-                                {
-                                    let aes_key = &channel.key;
-                                    if let Some(plain) =
-                                        phone_protocol::decrypt_message_phone(
-                                            aes_key,
-                                            nonce,
-                                            ciphertext,
-                                        )
-                                    {
-                                        let weak = ui_weak.clone();
-                                        slint::invoke_from_event_loop(move || {
-                                            if let Some(app) = weak.upgrade() {
-                                                if plain.eq_ignore_ascii_case("ping") {
-                                                    secure_channel_code::play_ping_sound();
-                                                } else if plain.to_ascii_lowercase().contains("nutella") {
-                                                    main_helpers::play_nutella_sound();
-                                                }
-                                                if !plain.eq_ignore_ascii_case("/exit")
-                                                    && !plain.eq_ignore_ascii_case("/clear")
-                                                    && !plain.eq_ignore_ascii_case("/disconnect")
-                                                    && !plain.eq_ignore_ascii_case("/clearfiles")
-                                                    && !plain.eq_ignore_ascii_case("/clearall")
-                                                    && !plain.eq_ignore_ascii_case("/webjoin")
-                                                    && !plain.eq_ignore_ascii_case("/webstop")
-                                                    && !plain.eq_ignore_ascii_case("/restart")
-                                                    && !plain.eq_ignore_ascii_case("/downloads")
-                                                {
-                                                    app.invoke_append_message(
-                                                        plain.into(),
-                                                    );
-                                                }
-                                            }
-                                        })
-                                        .ok();
-                                    } else {
-                                        // decryption failed
-                                    }
-                                } else {
-                                    // no channel
-                                }
-                            } else {
-                                // too short
-                            }
-                            continue; // important: skip further processing
+    // /ping works in every mode, it's unicast and carries no chat text
+    if let Some((id, nickname)) = crate::ping::parse_request(msg_bytes) {
+        replies.push(crate::ping::reply_packet(id));
+        if crate::ping::allow_alert(from.ip()) {
+            if main_helpers::ping_sound_enabled() {
+                tokio::task::spawn_blocking(secure_channel_code::play_ping_sound);
+            }
+            let who = crate::peers::label(from.ip(), (!nickname.is_empty()).then_some(nickname));
+            let weak = ui_weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(app) = weak.upgrade() {
+                    app.invoke_append_attention(format!("🏓 {} pinged you", who).into());
+                }
+            });
+        }
+        return replies;
+    }
+    if let Some((who, rtt)) = crate::ping::take_reply(msg_bytes) {
+        let weak = ui_weak.clone();
+        let _ = slint::invoke_from_event_loop(move || {
+            if let Some(app) = weak.upgrade() {
+                app.invoke_append_message(format!("🏓 pong from {} ({}ms)", who, rtt.as_millis()).into());
+            }
+        });
+        return replies;
+    }
+
+    // the sender cleared its files panel, drop the offer it withdrew (any mode)
+    if let Some(offer_id) = crate::transfers::parse_withdraw(msg_bytes) {
+        let id_hex = crate::file_transfer_protocol::offer_id_to_hex(&offer_id);
+        let withdrawn = {
+            let mut reg = remote_windows_offers.lock().unwrap();
+            let from_offerer = reg.get(&id_hex).is_some_and(|(addr, _)| addr.ip() == from.ip());
+            from_offerer && reg.remove(&id_hex).is_some()
+        } || crate::channel_files::withdraw(&id_hex, from.ip());
+        if withdrawn && !from_self {
+            let weak = ui_weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(app) = weak.upgrade() {
+                    main_helpers::remove_file_offer_row(&app, &id_hex);
+                }
+            });
+        }
+        return replies;
+    }
+
+    // the hash of a file offer, only taken from whoever made the offer
+    if let Some((offer_id, digest)) = crate::file_transfer_protocol::parse_hash_packet(msg_bytes) {
+        let id_hex = crate::file_transfer_protocol::offer_id_to_hex(&offer_id);
+        let from_offerer = remote_windows_offers.lock().unwrap().get(&id_hex).is_some_and(|(addr, _)| addr.ip() == from.ip());
+        if from_offerer && !from_self {
+            crate::file_transfer_protocol::remember_hash(offer_id, digest);
+        }
+        return replies;
+    }
+
+    let mode = {
+        let cm = channel_mode.lock().unwrap();
+        cm.clone()
+    };
+    
+    // ─── Secure Channel Mode ──────────────────────────────────────────────
+    if mode == "joined" || mode == "host" {
+        // 🛰 Step 1: Handle announcements
+        if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"ANCH" {
+            if from_self {
+                return replies;
+            }
+            let payload = &msg_bytes[4..];
+
+            // a unicast REQA reply can reach us from a host on another port
+            if let Some(host_port) = secure_channel_code::announcement_port(payload) {
+                let ours = local_port;
+                if host_port != ours {
+                    let weak = ui_weak.clone();
+                    let host = from.ip().to_string();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(app) = weak.upgrade() {
+                            app.invoke_show_temp_message(format!("⚠️ Channel host {} uses UDP port {}, you are on {}", host, host_port, ours).into());
                         }
-                        // 🔁 Step 3: Handle REQA (request announcement)
-                        else if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"REQA" {
-                            if mode == "host" {
-                                if let Some(channel) =
-                                    secure_channel_code::get_active_channel()
-                                {
-                                    // Build and send ANCH packet (desktop)
-                                    let announce =
-                                        secure_channel_code::build_announcement(
-                                            &channel,
-                                        );
-                                    if let Ok(payload) =
-                                        bincode::serde::encode_to_vec(
-                                            &announce,
-                                            bincode::config::standard(),
-                                        )
-                                    {
-                                        let mut packet = Vec::from(b"ANCH");
-                                        packet.extend_from_slice(&payload);
-                                        let _ = sock.send_to(&packet, _from);
-                                    }
+                    });
+                }
+            }
+
+            if secure_channel_code::store_announcement(payload, from.ip()) {
+                return replies; // Successfully handled as announcement
+            }
+        } else if msg_bytes.len() >= 5 && &msg_bytes[..5] == b"MANCH" {
+            let payload = &msg_bytes[5..];
+            if phone_protocol::store_announcement_phone(payload) {
+                // ok
+            } else {
+                // failed
+            }
+            return replies;
+        }
+        // 💓 the host is still there
+        else if msg_bytes.starts_with(crate::heartbeat::EHBT_MAGIC) {
+            if mode == "joined" && !from_self && crate::heartbeat::accept(&msg_bytes[4..]) {
+                let weak = ui_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_append_message("✅ The channel host is back".into());
+                    }
+                });
+            }
+            return replies;
+        }
+        // 🔌 the host closed the channel
+        else if msg_bytes.starts_with(secure_channel_code::ECLS_MAGIC) {
+            if mode == "joined" && !from_self && secure_channel_code::is_close_packet(&msg_bytes[4..]) {
+                secure_channel_code::destroy_channel();
+                let weak = ui_weak.clone();
+                let channel_mode = Arc::clone(channel_mode);
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        main_helpers::force_switch_to_public(&app, &channel_mode);
+                        app.set_web_join_enabled(true);
+                        app.invoke_show_temp_message("🔌 The host closed the secure channel — back to public mode".into());
+                    }
+                });
+            }
+            return replies;
+        }
+        // 🔑 a joiner asking for the session key, only the host has it to give
+        else if msg_bytes.starts_with(secure_channel_code::EKXQ_MAGIC) {
+            if mode == "host" && !from_self {
+                if let Some(reply) = secure_channel_code::answer_key_request(&msg_bytes[4..]) {
+                    replies.push(reply);
+                }
+            }
+            return replies;
+        }
+        // 🔑 the host's answer, the channel is usable from here on
+        else if msg_bytes.starts_with(secure_channel_code::EKXR_MAGIC) {
+            if mode == "joined" && secure_channel_code::finish_key_exchange(&msg_bytes[4..]) {
+                let weak = ui_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        secure_channel_code::play_ping_sound();
+                        app.invoke_hide_connecting_popup();
+                        app.invoke_show_temp_message("✅ Joined secure channel successfully!".into());
+                    }
+                });
+            }
+            return replies;
+        }
+        // 📋 a member shared their clipboard
+        else if msg_bytes.starts_with(crate::clip_sync::ECLP_MAGIC) {
+            if !from_self {
+                crate::clip_sync::receive(ui_weak, from.ip(), &msg_bytes[4..]);
+            }
+            return replies;
+        }
+        // 🔒 a file offered to the whole channel, sealed like the chat
+        else if msg_bytes.starts_with(crate::channel_files::EFOF_MAGIC) {
+            if let Some(offer) = crate::channel_files::parse_offer_packet(msg_bytes) {
+                if !from_self {
+                    crate::channel_idle::touch();
+                    show_channel_offer(ui_weak, from, offer);
+                }
+            }
+            return replies;
+        }
+        // 🔒 Step 2: Handle encrypted messages
+        else if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"ENCM" {
+            let payload = &msg_bytes[4..]; // Strip header
 
-                                    // Build and send MANCH packet (mobile)
-                                    if let Ok(man_json) = phone_protocol::build_MANCH(
-                                        &channel,
-                                    ) {
-                                        let mut man_packet = Vec::from(b"MANCH");
-                                        man_packet
-                                            .extend_from_slice(man_json.as_bytes());
-                                        let _ = sock.send_to(&man_packet, _from);
-                                    }
-                                }
+            if let Some((decrypted, nonce)) =
+                secure_channel_code::decrypt_message_with_nonce(
+                    payload,
+                )
+            {
+                // the host closed the channel and signed what was said
+                // (the host saved its own copy when it signed)
+                if let Some(record) = crate::transcript::parse_signature(&decrypted) {
+                    if from_self {
+                        return replies;
+                    }
+                    let outcome = crate::transcript::accept_signature(record, from.ip());
+                    let weak = ui_weak.clone();
+                    net_runtime::spawn(async move {
+                        let outcome = outcome.await;
+                        slint::invoke_from_event_loop(move || {
+                            if let Some(app) = weak.upgrade() {
+                                app.invoke_show_temp_message(outcome.into());
+                            }
+                        })
+                        .ok();
+                    });
+                    return replies;
+                }
+                // a file offered to the whole channel by an older build, not a chat line
+                if let Some(offer) = crate::channel_files::parse_offer(&decrypted) {
+                    if !from_self {
+                        show_channel_offer(ui_weak, from, offer);
+                    }
+                    return replies;
+                }
+                let weak = ui_weak.clone();
+                let sender = from.ip().to_string();
+                crate::transcript::record(&sender, &nonce, &decrypted);
+                // our own line is signed like everyone's, but it's already on screen
+                if from_self {
+                    return replies;
+                }
+                crate::channel_idle::touch();
+                if mode == "host" {
+                    crate::reannounce::member_heard(from.ip());
+                }
+                let Some(decrypted) = crate::message_pipeline::inbound(from.ip(), true, decrypted) else {
+                    return replies;
+                };
+                crate::records::message_from(from.ip());
+                slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_append_peer_message(sender.into(), decrypted.into());
+                    }
+                })
+                .ok();
+            }
+            return replies; // Done with encrypted message
+        } else if msg_bytes.len() >= 5 && &msg_bytes[..5] == b"MENCM" {
+            if from_self {
+                return replies;
+            }
+            if msg_bytes.len() > 17 {
+                let nonce = &msg_bytes[5..17];
+                let ciphertext = &msg_bytes[17..];
+                if let Some(channel) =
+                    secure_channel_code::get_active_channel()
+                {
+                    // phones seal with the PIN key, see phone_protocol::build_MANCH
+                    let aes_key = &channel.auth_key;
+                    if let Some(plain) =
+                        phone_protocol::decrypt_message_phone(
+                            aes_key,
+                            nonce,
+                            ciphertext,
+                        )
+                    {
+                        crate::channel_idle::touch();
+                        let Some(plain) = crate::message_pipeline::inbound(from.ip(), true, plain) else {
+                            return replies;
+                        };
+                        crate::records::message_from(from.ip());
+                        let weak = ui_weak.clone();
+                        let sender = from.ip().to_string();
+                        slint::invoke_from_event_loop(move || {
+                            if let Some(app) = weak.upgrade() {
+                                app.invoke_append_peer_message(sender.into(), plain.into());
                             }
-                            continue;
-                        } else if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"FOFR" {
-                            // ignore FOFR in secure mode for now
-                            continue;
-                        } else if msg_bytes.len() >= 5 && &msg_bytes[..5] == b"MFOFT" {
-                            continue;
+                        })
+                        .ok();
+                    } else {
+                        // decryption failed
+                    }
+                } else {
+                    // no channel
+                }
+            } else {
+                // too short
+            }
+            return replies; // important: skip further processing
+        }
+        // 🔁 Step 3: Handle REQA (request announcement)
+        else if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"REQA" {
+            if mode == "host" && !from_self {
+                crate::reannounce::activity();
+                crate::channel_idle::touch();
+            }
+            if mode == "host" && announce_replies.lock().unwrap().allow(from.ip()) {
+                if let Some(channel) =
+                    secure_channel_code::get_active_channel()
+                {
+                    // ANCH for desktop, MANCH for mobile
+                    if let Some((anch, manch)) = announce_replies.lock().unwrap().packets(&channel, local_port) {
+                        replies.push(anch.to_vec());
+                        if let Some(manch) = manch {
+                            replies.push(manch.to_vec());
                         }
                     }
+                }
+            }
+            return replies;
+        } else if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"FOFR" {
+            // ignore FOFR in secure mode for now
+            return replies;
+        } else if msg_bytes.len() >= 5 && &msg_bytes[..5] == b"MFOFT" {
+            return replies;
+        }
+    }
 
-                    // ─── Public Mode ──────────────────────────────────────────────────────
-                    if mode == "public" {
-                        // 1) Special handling for FOFR
-                        if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"FOFT" {
-                            if let Some(offer) = crate::file_transfer_protocol::decode_foft(msg_bytes) {
-                                let id_hex =
-                                    crate::file_transfer_protocol::offer_id_to_hex(&offer.offer_id);
-                                let sender_ip = _from.ip();
+    // ─── Public Mode ──────────────────────────────────────────────────────
+    if mode == "public" {
+        // a peer saved one of our offers for later and wants it again
+        if let Some(offer_id) = crate::later::parse_request(msg_bytes) {
+            let packet = offer_registry
+                .lock()
+                .unwrap()
+                .get(&offer_id)
+                .and_then(|local| crate::file_transfer_protocol::reoffer_packet(offer_id, local).ok());
+            if let Some(packet) = packet {
+                replies.push(packet);
+            }
+            return replies;
+        }
 
-                                {
-                                    let mut reg = remote_windows_offers.lock().unwrap();
-                                    reg.insert(id_hex.clone(), (sender_ip, offer.clone()));
-                                }
+        // 🧩 a shared snippet, the text itself comes over TCP
+        if let Some(announce) = crate::snippets::parse(msg_bytes) {
+            if !from_self && announce.room == *current_room.lock().unwrap() {
+                crate::snippets::fetch(ui_weak.clone(), from, announce);
+            }
+            return replies;
+        }
 
-                                let weak = ui_weak.clone();
+        // 1) Special handling for FOFR
+        if msg_bytes.len() >= 4 && &msg_bytes[..4] == b"FOFT" {
+            if let Some(mut offer) = crate::file_transfer_protocol::decode_foft(msg_bytes) {
+                offer.expires_at = crate::file_transfer_protocol::foft_expiry(msg_bytes);
+                let id_hex =
+                    crate::file_transfer_protocol::offer_id_to_hex(&offer.offer_id);
+                // full address, an IPv6 link-local peer needs its scope id to connect back
+                let sender_addr = from;
 
-                                // ✅ truncate using helper
-                                let display_name =
-                                    crate::file_transfer_protocol::truncate_name(&offer.name, 16);
+                let is_new = {
+                    let mut reg = remote_windows_offers.lock().unwrap();
+                    reg.insert(id_hex.clone(), (sender_addr, offer.clone())).is_none()
+                };
+                // our own offers loop back too, never fetch / announce those
+                let from_peer = is_new && !from_self;
+                if let Some(digest) = crate::file_transfer_protocol::foft_hash(msg_bytes).filter(|_| !from_self) {
+                    crate::file_transfer_protocol::remember_hash(offer.offer_id, digest);
+                }
+                // the answer to a "download later" request starts right away
+                let auto = crate::later::take_requested(&id_hex)
+                    || (from_peer && crate::auto_download::wants(from.ip(), offer.size));
+                // a re-announcement only pushes the expiry back, the row is there already
+                if !is_new && !auto {
+                    return replies;
+                }
 
-                                let size_text =
-                                    crate::file_transfer_protocol::human_size(offer.size);
+                let weak = ui_weak.clone();
 
-                                slint::invoke_from_event_loop(move || {
-                                    if let Some(app) = weak.upgrade() {
-                                        let item = FileOfferItem {
-                                            offer_id: id_hex.into(),
-                                            name: display_name.into(),
-                                            size_text: size_text.into(),
-                                            is_downloading: false,
-                                            progress_text: "".into(),
-                                            is_mobile: false,
-                                        };
+                // ✅ truncate using helper
+                let mut display_name =
+                    crate::file_transfer_protocol::truncate_name(&offer.name, 16);
+                if matches!(offer.kind, crate::file_transfer_protocol::OfferKind::Folder) {
+                    display_name = format!("📁 {}", display_name);
+                }
 
-                                        app.invoke_add_file_offer(item);
-                                    }
-                                })
-                                .ok();
-                            }
+                let size_text =
+                    crate::file_transfer_protocol::human_size(offer.size);
 
-                            continue;
-                        }
+                let sender = if from_self {
+                    "You".to_string()
+                } else {
+                    crate::peers::label(from.ip(), crate::file_transfer_protocol::foft_sender(msg_bytes))
+                };
+                if from_peer {
+                    crate::notify::file_offer(&sender, &offer.name, &size_text);
+                    // its reply is queued after the row added below
+                    crate::thumbnails::request(ui_weak.clone(), sender_addr, &offer);
+                }
 
-                        if msg_bytes.len() >= 5 && &msg_bytes[..5] == b"MFOFT" {
-                            if let Some(ip) = my_ip {
-                                if _from.ip() == ip {
-                                    continue;
-                                }
-                            }
-                            let payload = &msg_bytes[5..];
-                            if let Some((offer, id_hex)) = crate::file_transfer_protocol::decode_mfoft(payload) {
-                                if remote_windows_offers.lock().unwrap().contains_key(&id_hex) {
-                                    continue;
-                                }
-                                let sender_ip = _from.ip();
+                slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        let item = FileOfferItem {
+                            offer_id: id_hex.clone().into(),
+                            name: display_name.into(),
+                            size_text: size_text.into(),
+                            is_downloading: false,
+                            progress_text: "".into(),
+                            is_mobile: false,
+                            is_own: false,
+                            sender: sender.into(),
+                            collapsed: false,
+                            thumbnail: Default::default(),
+                            saved: false,
+                            queued: 0,
+                        };
 
-                                let is_new = crate::file_transfer_protocol::register_remote_offer(
-                                    &remote_mobile_offers,
-                                    sender_ip,
-                                    id_hex.clone(),
-                                    offer.clone(),
-                                );
+                        app.invoke_add_file_offer(item);
+                        if auto {
+                            app.invoke_download_offer(id_hex.into());
+                        }
+                    }
+                })
+                .ok();
+            }
 
-                                if !is_new {
-                                    continue; // duplicate MFOFT, don't spam UI
-                                }
+            return replies;
+        }
 
-                                let weak = ui_weak.clone();
-                                let display_name = crate::file_transfer_protocol::truncate_name(&offer.name, 16);
-                                let size_text = crate::file_transfer_protocol::human_size(offer.size);
+        if msg_bytes.len() >= 5 && &msg_bytes[..5] == b"MFOFT" {
+            if from_self {
+                return replies;
+            }
+            let payload = &msg_bytes[5..];
+            if let Some((offer, id_hex)) = crate::file_transfer_protocol::decode_mfoft(payload) {
+                if remote_windows_offers.lock().unwrap().contains_key(&id_hex) {
+                    return replies;
+                }
+                let sender_addr = from;
 
-                                slint::invoke_from_event_loop(move || {
-                                    if let Some(app) = weak.upgrade() {
-                                        app.invoke_add_file_offer(FileOfferItem {
-                                            offer_id: id_hex.into(),
-                                            name: display_name.into(),
-                                            size_text: size_text.into(),
-                                            is_downloading: false,
-                                            progress_text: "".into(),
-                                            is_mobile: true,
-                                        });
-                                    }
-                                })
-                                .ok();
-                            }
+                let is_new = crate::file_transfer_protocol::register_remote_offer(
+                    remote_mobile_offers,
+                    sender_addr,
+                    id_hex.clone(),
+                    offer.clone(),
+                );
 
-                            continue;
-                        }
+                if !is_new {
+                    return replies; // duplicate MFOFT, don't spam UI
+                }
 
-                        // 2️⃣ Normal text messages
-                        if let Ok(msg) = String::from_utf8(msg_bytes.to_vec()) {
-                            if msg.eq_ignore_ascii_case("ping") {
-                                secure_channel_code::play_ping_sound();
-                            } else if msg.to_ascii_lowercase().contains("nutella") {
-                                main_helpers::play_nutella_sound();
-                            }
-                            if !msg.starts_with("/") && !msg.starts_with("MANCH") && !msg.starts_with("REQA") {
-                                let weak = ui_weak.clone();
-                                slint::invoke_from_event_loop(move || {
-                                    if let Some(app) = weak.upgrade() {
-                                        app.invoke_append_message(msg.into());
-                                    }
-                                })
-                                .ok();
-                            }
+                let weak = ui_weak.clone();
+                let display_name = crate::file_transfer_protocol::truncate_name(&offer.name, 16);
+                let size_text = crate::file_transfer_protocol::human_size(offer.size);
+                let auto = crate::auto_download::wants(from.ip(), offer.size);
+                let sender = format!("📱 {}", crate::peers::label(from.ip(), None));
+                crate::notify::file_offer(&sender, &offer.name, &size_text);
+
+                slint::invoke_from_event_loop(move || {
+                    if let Some(app) = weak.upgrade() {
+                        app.invoke_add_file_offer(FileOfferItem {
+                            offer_id: id_hex.clone().into(),
+                            name: display_name.into(),
+                            size_text: size_text.into(),
+                            is_downloading: false,
+                            progress_text: "".into(),
+                            is_mobile: true,
+                            is_own: false,
+                            sender: sender.into(),
+                            collapsed: false,
+                            thumbnail: Default::default(),
+                            saved: false,
+                            queued: 0,
+                        });
+                        if auto {
+                            app.invoke_download_offer(id_hex.into());
                         }
                     }
-                }
-                Err(e)
-                    if e.kind() == io::ErrorKind::WouldBlock
-                        || e.kind() == io::ErrorKind::TimedOut =>
-                {
-                    // Expected timeouts
-                }
-                Err(_e) => {
-                    break;
-                }
+                })
+                .ok();
             }
+
+            return replies;
         }
-    })
+
+        // 2️⃣ Normal text messages, only the room we are in (ours are shown when sent)
+        if from_self {
+            return replies;
+        }
+        let Some((room, text_bytes)) = crate::rooms::decode_text(msg_bytes) else {
+            return replies;
+        };
+        if room != *current_room.lock().unwrap() {
+            crate::rooms::note_seen(&room);
+            return replies;
+        }
+        if let Ok(msg) = String::from_utf8(text_bytes.to_vec()) {
+            let Some(msg) = crate::message_pipeline::inbound(from.ip(), false, msg) else {
+                return replies;
+            };
+            crate::records::message_from(from.ip());
+            let weak = ui_weak.clone();
+            let sender = from.ip().to_string();
+            slint::invoke_from_event_loop(move || {
+                if let Some(app) = weak.upgrade() {
+                    app.invoke_append_peer_message(sender.into(), msg.into());
+                }
+            })
+            .ok();
+        }
+    }
+    replies
 }